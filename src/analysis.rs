@@ -0,0 +1,368 @@
+use std::collections::hash_map::HashMap;
+use std::collections::hash_set::HashSet;
+use std::collections::VecDeque;
+
+use serde::{Deserialize, Serialize};
+
+use crate::state_graph::{next_pushed_states, Push, StateGraph, StateId};
+
+// A push to make and the state it leads to
+#[derive(Copy, Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct PushMove {
+  pub to: StateId,
+  pub push: Push,
+}
+
+impl StateGraph {
+  // The next push on a shortest path from `id` to a goal, `None` at a goal or
+  // when no goal can be reached. Ties go to the lowest state id.
+  pub fn hint(&self, id: &StateId) -> Option<PushMove> {
+    let dist = self.distances_to_goal();
+    let remaining = *dist.get(id)?;
+    if remaining == 0 {
+      return None;
+    }
+    let closer = |to: &StateId| dist.get(to) == Some(&(remaining - 1));
+    if self.is_pulled() {
+      // The edges of pulled graphs hold the pulls that found each state, so
+      // the pushes are worked out again from the board
+      let state = self.get_state(id)?;
+      return next_pushed_states(&state, self.dims()).into_iter()
+        .filter_map(|(next, push)| self.get_id(&next).map(|to| PushMove { to, push }))
+        .filter(|hint| closer(&hint.to))
+        .min_by_key(|hint| hint.to);
+    }
+    self.edges_from(id)
+      .filter(|(to, _)| closer(to))
+      .min_by_key(|(to, _)| *to)
+      .map(|(to, push)| PushMove { to, push: *push })
+  }
+  // States every run of pushes from `start` to a goal has to pass through,
+  // ordered from `start` onwards. `start` and the goals themselves are left
+  // out. Edges are followed the way pushes go, so on pulled graphs, whose
+  // root is the goal, `start` is where the player begins.
+  pub fn bottleneck_states(&self, start: &StateId) -> Vec<StateId> {
+    if !self.contains_id(start) {
+      return vec![];
+    }
+    // Number states reachable from `start` in postorder, with a virtual sink
+    // fed by every goal state numbered last
+    let order = postorder_from(self, start);
+    let index = order.iter()
+      .enumerate()
+      .map(|(idx, id)| (*id, idx))
+      .collect::<HashMap<StateId, usize>>();
+    let sink = order.len();
+    let mut preds = vec![vec![]; order.len() + 1];
+    for (idx, id) in order.iter().enumerate() {
+      for prev in self.pushed_from(id) {
+        if let Some(prev_idx) = index.get(prev) {
+          preds[idx].push(*prev_idx);
+        }
+      }
+      if self.is_goal(id) {
+        preds[sink].push(idx);
+      }
+    }
+    if preds[sink].is_empty() {
+      return vec![];
+    }
+    let idom = dominators(&preds, index[start], sink);
+    let mut bottlenecks = vec![];
+    let mut current = idom[sink].unwrap();
+    while current != index[start] {
+      let id = order[current];
+      if !self.is_goal(&id) {
+        bottlenecks.push(id);
+      }
+      current = idom[current].unwrap();
+    }
+    bottlenecks.reverse();
+    bottlenecks
+  }
+}
+
+// Counts saturate here rather than overflowing on densely connected graphs
+pub const MAX_SOLUTIONS: u64 = 1 << 48;
+
+// Number of distinct shortest paths from `root` to any of `goals`. Only edges
+// that bring a state one push closer to a goal are followed, whichever way
+// the graph stores them, which leaves a DAG the paths can be counted on.
+pub fn count_solutions(graph: &StateGraph, root: &StateId, goals: &[StateId]) -> u64 {
+  let mut dist = HashMap::new();
+  let mut order = vec![];
+  let mut queue = VecDeque::new();
+  for goal in goals.iter().filter(|goal| graph.contains_id(goal)) {
+    if dist.insert(*goal, 0).is_none() {
+      queue.push_back(*goal);
+    }
+  }
+  while let Some(next) = queue.pop_front() {
+    order.push(next);
+    if next == *root {
+      break;
+    }
+    let depth = dist[&next] + 1;
+    for prev in graph.pushed_from(&next) {
+      if !dist.contains_key(prev) {
+        dist.insert(*prev, depth);
+        queue.push_back(*prev);
+      }
+    }
+  }
+  if !dist.contains_key(root) {
+    return 0;
+  }
+  // States come out of the BFS closest first, so every state is counted
+  // after all the states it can step down to
+  let mut counts = HashMap::new();
+  for id in order {
+    let count = if dist[&id] == 0 {
+      1
+    } else {
+      graph.pushed_to(&id).iter()
+        .filter(|next| dist.get(next) == Some(&(dist[&id] - 1)))
+        .filter_map(|next| counts.get(next))
+        .fold(0u64, |total, count| total.saturating_add(*count).min(MAX_SOLUTIONS))
+    };
+    counts.insert(id, count);
+  }
+  counts[root]
+}
+
+fn postorder_from(graph: &StateGraph, root: &StateId) -> Vec<StateId> {
+  let mut order = vec![];
+  let mut visited = HashSet::new();
+  visited.insert(*root);
+  let mut stack = vec![(*root, 0)];
+  while let Some((id, next)) = stack.pop() {
+    if let Some(neighbor) = graph.pushed_to(&id).get(next).cloned() {
+      stack.push((id, next + 1));
+      if visited.insert(neighbor) {
+        stack.push((neighbor, 0));
+      }
+    } else {
+      order.push(id);
+    }
+  }
+  order
+}
+
+// Immediate dominators for nodes numbered in postorder, with `sink` numbered
+// after everything else (Cooper, Harvey & Kennedy)
+fn dominators(preds: &[Vec<usize>], root: usize, sink: usize) -> Vec<Option<usize>> {
+  let mut idom = vec![None; preds.len()];
+  idom[root] = Some(root);
+  let mut changed = true;
+  while changed {
+    changed = false;
+    // Reverse postorder, the sink comes after every goal it depends on
+    let nodes = (0..sink).rev().chain(std::iter::once(sink));
+    for node in nodes.filter(|node| *node != root) {
+      let mut new_idom = None;
+      for pred in &preds[node] {
+        if idom[*pred].is_none() {
+          continue;
+        }
+        new_idom = Some(match new_idom {
+          None => *pred,
+          Some(other) => intersect(&idom, *pred, other),
+        });
+      }
+      if new_idom.is_some() && idom[node] != new_idom {
+        idom[node] = new_idom;
+        changed = true;
+      }
+    }
+  }
+  idom
+}
+
+fn intersect(idom: &[Option<usize>], mut a: usize, mut b: usize) -> usize {
+  // Dominators sit later in postorder than the nodes they dominate
+  while a != b {
+    while a < b {
+      a = idom[a].unwrap();
+    }
+    while b < a {
+      b = idom[b].unwrap();
+    }
+  }
+  a
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::grid::{Dims, Grid};
+  use crate::level::Level;
+  use crate::cell::Cell;
+  use crate::direction::Direction;
+  use crate::state_graph::*;
+
+  fn corridor() -> StateGraph {
+    let grid = vec![
+      Cell::BoulderInHole, Cell::Unreachable, Cell::Unreachable, Cell::Unreachable,
+      Cell::Block, Cell::Block, Cell::Block, Cell::Unreachable,
+      Cell::Block, Cell::Block, Cell::Block, Cell::Unreachable,
+      Cell::Block, Cell::Block, Cell::Block, Cell::Unreachable,
+    ];
+    find_solvable_states(&Level::new(grid, Dims::square(4), 15))
+  }
+
+  // A 4x4 board solved in the corner, with a block the boulder has to go
+  // either side of
+  fn around_block() -> StateGraph {
+    let mut grid = vec![Cell::Unreachable; 16];
+    grid[0] = Cell::BoulderInHole;
+    grid[5] = Cell::Block;
+    find_solvable_states(&Level::new(grid, Dims::square(4), 15))
+  }
+
+  // The state of a pulled graph with the boulder at `boulder`
+  fn boulder_at(graph: &StateGraph, boulder: usize) -> StateId {
+    graph.states()
+      .find(|(_, state)| state[boulder].has_boulder())
+      .map(|(id, _)| id)
+      .unwrap()
+  }
+
+  #[test]
+  fn test_bottleneck_pulled() {
+    let graph = around_block();
+    // Left of the block the boulder can only come up the first column...
+    assert_eq!(graph.bottleneck_states(&boulder_at(&graph, 8)), vec![boulder_at(&graph, 4)]);
+    assert_eq!(graph.bottleneck_states(&boulder_at(&graph, 2)), vec![boulder_at(&graph, 1)]);
+    // ...but from below and right of it there are ways round either side
+    assert!(graph.bottleneck_states(&boulder_at(&graph, 10)).is_empty());
+    // Nothing lies between the solved board and itself
+    assert!(graph.bottleneck_states(&graph.root()).is_empty());
+    assert!(corridor().bottleneck_states(&corridor().root()).is_empty());
+  }
+
+  // A 3x3 board with a hole in the corner and a boulder at `boulder`
+  fn state(boulder: usize) -> Vec<Cell> {
+    let mut state = vec![Cell::Unreachable; 9];
+    state[0] = Cell::Hole;
+    state[boulder] = if boulder == 0 { Cell::BoulderInHole } else { Cell::Boulder };
+    state
+  }
+
+  fn push(boulder: usize) -> Push {
+    Push { boulder, dir: Direction::Left, tractor: 8 }
+  }
+
+  #[test]
+  fn test_bottleneck_chain() {
+    let mut graph = StateGraph::new(Grid::new(state(1), Dims::square(3)).unwrap());
+    let root = graph.root();
+    let a = graph.insert_state(state(2)).unwrap();
+    let b = graph.insert_state(state(4)).unwrap();
+    let c = graph.insert_state(state(5)).unwrap();
+    let d = graph.insert_state(state(6)).unwrap();
+    let goal = graph.insert_state(state(0)).unwrap();
+    // root -> a -> c -> d -> goal, root -> b -> c
+    graph.connect(&root, a, push(1));
+    graph.connect(&root, b, push(1));
+    graph.connect(&a, c, push(2));
+    graph.connect(&b, c, push(4));
+    graph.connect(&c, d, push(5));
+    graph.connect(&d, goal, push(6));
+    assert_eq!(graph.bottleneck_states(&root), vec![c, d]);
+    // A shortcut around `d` leaves `c` as the only bottleneck
+    graph.connect(&c, goal, push(5));
+    assert_eq!(graph.bottleneck_states(&root), vec![c]);
+    // A second goal that can be reached without `c` leaves none
+    let mut other = state(0);
+    other[8] = Cell::Reachable;
+    let other = graph.insert_state(other).unwrap();
+    graph.connect(&a, other, push(2));
+    assert_eq!(graph.bottleneck_states(&root), vec![]);
+  }
+
+  #[test]
+  fn test_hint() {
+    let mut graph = StateGraph::new(Grid::new(state(1), Dims::square(3)).unwrap());
+    let root = graph.root();
+    let a = graph.insert_state(state(2)).unwrap();
+    let b = graph.insert_state(state(4)).unwrap();
+    let c = graph.insert_state(state(5)).unwrap();
+    let goal = graph.insert_state(state(0)).unwrap();
+    graph.connect(&root, b, push(1));
+    graph.connect(&root, a, push(1));
+    graph.connect(&a, c, push(2));
+    graph.connect(&b, c, push(4));
+    graph.connect(&c, goal, push(5));
+    assert_eq!(graph.hint(&root), Some(PushMove { to: a, push: push(1) }));
+    assert_eq!(graph.hint(&c), Some(PushMove { to: goal, push: push(5) }));
+    assert_eq!(graph.hint(&goal), None);
+    // A dead end has no hint either
+    let stuck = graph.insert_state(state(8)).unwrap();
+    graph.connect(&root, stuck, push(1));
+    assert_eq!(graph.hint(&stuck), None);
+  }
+
+  #[test]
+  fn test_hint_pulled() {
+    let graph = corridor();
+    let dims = Dims::square(4);
+    for (id, _) in graph.states() {
+      // Following the hints from anywhere makes real pushes to the root
+      let mut current = id;
+      let mut pushes = 0;
+      while let Some(hint) = graph.hint(&current) {
+        let state = graph.get_state(&current).unwrap();
+        assert!(next_pushed_states(&state, dims).contains(&(graph.get_state(&hint.to).unwrap().to_vec(), hint.push)));
+        current = hint.to;
+        pushes += 1;
+      }
+      assert_eq!(current, graph.root());
+      assert_eq!(Some(pushes), graph.distances_to_goal().get(&id).cloned());
+    }
+  }
+
+  #[test]
+  fn test_count_solutions() {
+    let mut graph = StateGraph::new(Grid::new(state(1), Dims::square(3)).unwrap());
+    let root = graph.root();
+    let a = graph.insert_state(state(2)).unwrap();
+    let b = graph.insert_state(state(4)).unwrap();
+    let c = graph.insert_state(state(5)).unwrap();
+    let d = graph.insert_state(state(6)).unwrap();
+    let goal = graph.insert_state(state(0)).unwrap();
+    graph.connect(&root, a, push(1));
+    graph.connect(&root, b, push(1));
+    graph.connect(&a, c, push(2));
+    graph.connect(&b, c, push(4));
+    graph.connect(&c, d, push(5));
+    graph.connect(&d, goal, push(6));
+    assert_eq!(count_solutions(&graph, &root, &[goal]), 2);
+    assert_eq!(count_solutions(&graph, &c, &[goal]), 1);
+    assert_eq!(count_solutions(&graph, &goal, &[goal]), 1);
+    assert_eq!(count_solutions(&graph, &goal, &[root]), 0);
+    // A shortcut from `c` leaves the longer detours via `d` and `e` out
+    let e = graph.insert_state(state(7)).unwrap();
+    graph.connect(&c, e, push(5));
+    graph.connect(&e, goal, push(7));
+    graph.connect(&c, goal, push(5));
+    assert_eq!(count_solutions(&graph, &root, &[goal]), 2);
+    assert_eq!(count_solutions(&graph, &root, &[goal, e]), 4);
+    // A quicker way to `e` alone
+    graph.connect(&b, e, push(4));
+    assert_eq!(count_solutions(&graph, &root, &[e]), 1);
+  }
+
+  #[test]
+  fn test_count_solutions_pulled() {
+    let graph = around_block();
+    let dims = Dims::square(4);
+    for (id, state) in graph.states() {
+      // The same count as pushing from that board
+      let tractor = state.iter().position(|cell| *cell == Cell::Reachable).unwrap();
+      let pushed = find_pushed_states(&Level::new(state.to_vec(), dims, tractor));
+      let expected = count_solutions(&pushed, &pushed.root(), &pushed.goal_states());
+      assert_eq!(count_solutions(&graph, &id, &[graph.root()]), expected);
+    }
+    assert_eq!(count_solutions(&graph, &boulder_at(&graph, 10), &[graph.root()]), 2);
+  }
+}
@@ -0,0 +1,123 @@
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+use serde::Serialize;
+
+use crate::difficulty::push_graph_stats;
+use crate::formats::load_paths;
+use crate::level::Level;
+use crate::metrics::measure_difficulty;
+use crate::solver::solve;
+
+// Size, solution and difficulty of one level, as `stats` tabulates them.
+// Whatever couldn't be worked out is `None`.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct LevelReport {
+  pub level: String,
+  pub rows: usize,
+  pub cols: usize,
+  pub boulders: usize,
+  pub pushes: Option<usize>,
+  pub moves: Option<usize>,
+  pub states: Option<usize>,
+  pub branching: Option<f64>,
+  pub dead_fraction: Option<f64>,
+  pub score: Option<f64>,
+}
+
+pub fn analyze(name: &str, level: &Level) -> LevelReport {
+  let stats = push_graph_stats(level);
+  let solution = solve(level);
+  LevelReport {
+    level: name.to_string(),
+    rows: level.dims.rows,
+    cols: level.dims.cols,
+    boulders: level.grid.iter().filter(|cell| cell.has_boulder()).count(),
+    pushes: solution.as_ref().map(|solution| solution.len()),
+    moves: solution.as_ref().map(|solution| solution.moves.len()),
+    states: stats.as_ref().map(|stats| stats.states),
+    branching: stats.as_ref().map(|stats| stats.branching),
+    dead_fraction: stats.as_ref().map(|stats| stats.dead_fraction),
+    score: stats.and_then(|_| measure_difficulty(level)).map(|difficulty| difficulty.score),
+  }
+}
+
+// `analyze` for each level on `threads` threads, reports in the levels' order.
+// 0 threads uses every core.
+pub fn analyze_levels(levels: &[(String, Level)], threads: usize) -> Vec<LevelReport> {
+  let threads = match threads {
+    0 => thread::available_parallelism().map_or(1, |n| n.get()),
+    n => n,
+  }.min(levels.len()).max(1);
+  let next = AtomicUsize::new(0);
+  let reports = Mutex::new(vec![None; levels.len()]);
+  thread::scope(|scope| {
+    for _ in 0..threads {
+      scope.spawn(|| loop {
+        let idx = next.fetch_add(1, Ordering::Relaxed);
+        let (name, level) = match levels.get(idx) {
+          Some(level) => level,
+          None => break,
+        };
+        let report = analyze(name, level);
+        reports.lock().unwrap()[idx] = Some(report);
+      });
+    }
+  });
+  reports.into_inner().unwrap().into_iter().map(Option::unwrap).collect()
+}
+
+// What `analyze_all` found
+#[derive(Debug)]
+pub struct BatchReport {
+  pub reports: Vec<LevelReport>,
+  // As in `LoadedLevels`
+  pub skipped: Vec<(PathBuf, io::Error)>,
+}
+
+// `analyze_levels` for every level under `paths`, loaded as `load_paths`
+// does
+pub fn analyze_all<P: AsRef<Path>>(paths: &[P], threads: usize) -> io::Result<BatchReport> {
+  let loaded = load_paths(paths)?;
+  Ok(BatchReport { reports: analyze_levels(&loaded.levels, threads), skipped: loaded.skipped })
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use std::fs;
+  use crate::bench::corpus;
+  use crate::metrics::Corpus;
+
+  #[test]
+  fn test_analyze_levels() {
+    let levels = corpus().into_iter().map(|(name, level)| (name.to_string(), level)).collect::<Vec<_>>();
+    let reports = analyze_levels(&levels, 3);
+    assert_eq!(reports.len(), levels.len());
+    for ((name, level), report) in levels.iter().zip(&reports) {
+      assert_eq!(*report, analyze(name, level));
+    }
+    assert_eq!(analyze_levels(&levels[..1], 0), reports[..1].to_vec());
+    // The scores reported are the ones a corpus grades by
+    let scores = reports.iter().filter_map(|report| report.score).collect();
+    let levels = levels.into_iter().map(|(_, level)| level).collect::<Vec<Level>>();
+    assert_eq!(Corpus::from_levels(&levels), Corpus::new(scores));
+  }
+
+  #[test]
+  fn test_analyze_all() {
+    let dir = std::env::temp_dir().join("lvlgen_test_analyze_all");
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("a.txt"), "+---+\n|.*O|\n+---+\n").unwrap();
+    fs::write(dir.join("notes.txt"), [0xff]).unwrap();
+    let batch = analyze_all(&[&dir], 2).unwrap();
+    let loaded = load_paths(&[&dir]).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(batch.reports, analyze_levels(&loaded.levels, 1));
+    assert_eq!(batch.reports[0].pushes, Some(1));
+    assert_eq!(batch.skipped.len(), 1);
+  }
+}
@@ -0,0 +1,105 @@
+use std::fs;
+use std::time::{Duration, Instant};
+
+use crate::cell::Cell;
+use crate::formats::parse_level;
+use crate::grid::{fill_reachable_cells_unchecked, Dims};
+use crate::level::Level;
+use crate::solver::solve;
+use crate::state_graph::find_solvable_states;
+
+// Boards of growing size, from a handful of states to tens of thousands
+static CORPUS: &[(&str, &str)] = &[
+  ("t0", include_str!("../tests/t0")),
+  ("t6", include_str!("../tests/t6")),
+  ("t7", include_str!("../tests/t7")),
+  ("t5", include_str!("../tests/t5")),
+  ("t8", include_str!("../tests/t8")),
+];
+
+pub fn corpus() -> Vec<(&'static str, Level)> {
+  CORPUS.iter()
+    .map(|(name, text)| (*name, parse_level(text).expect("the built-in corpus parses")))
+    .collect()
+}
+
+// How long one level took to explore, and to solve when it isn't solved yet
+#[derive(Clone, Debug)]
+pub struct Timing {
+  pub states: usize,
+  pub edges: usize,
+  pub explore: Duration,
+  pub solve: Option<Duration>,
+}
+
+impl Timing {
+  pub fn states_per_second(&self) -> f64 {
+    self.states as f64 / self.explore.as_secs_f64().max(1e-9)
+  }
+}
+
+pub fn time_level(level: &Level) -> Timing {
+  let start = Instant::now();
+  let graph = find_solvable_states(level);
+  let explore = start.elapsed();
+  let solve = if level.grid.iter().any(|cell| cell.is_loose_boulder()) {
+    let start = Instant::now();
+    solve(level);
+    Some(start.elapsed())
+  } else {
+    None
+  };
+  Timing { states: graph.len(), edges: graph.edges().count(), explore, solve }
+}
+
+// A fixed 10x10 board of floor with blocks and boulders dotted about, for
+// timing the flood fill that runs for every explored state
+pub fn fill_board() -> (Vec<Cell>, Dims) {
+  let dims = Dims::square(10);
+  let grid = (0..dims.len()).map(|idx| match (idx * 7) % 11 {
+    0 if idx > 0 => Cell::Block,
+    5 => Cell::Boulder,
+    _ => Cell::Unreachable,
+  }).collect();
+  (grid, dims)
+}
+
+// Flood fills of `fill_board` per second, over `rounds` fills
+pub fn fill_rate(rounds: usize) -> f64 {
+  let (grid, dims) = fill_board();
+  let start = Instant::now();
+  for _ in 0..rounds {
+    let mut filled = grid.clone();
+    fill_reachable_cells_unchecked(0, &mut filled, dims);
+  }
+  rounds as f64 / start.elapsed().as_secs_f64().max(1e-9)
+}
+
+// The most memory the process has held so far in kB, where the OS says
+pub fn peak_memory_kb() -> Option<u64> {
+  let status = fs::read_to_string("/proc/self/status").ok()?;
+  let line = status.lines().find(|line| line.starts_with("VmHWM:"))?;
+  line.split_whitespace().nth(1)?.parse().ok()
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn test_time_level() {
+    let corpus = corpus();
+    assert_eq!(corpus.len(), CORPUS.len());
+    let timing = time_level(&corpus[0].1);
+    assert_eq!(timing.states, 4);
+    assert!(timing.solve.is_none());
+  }
+
+  #[test]
+  fn test_fill_board() {
+    let (mut grid, dims) = fill_board();
+    fill_reachable_cells_unchecked(0, &mut grid, dims);
+    assert!(grid.iter().filter(|cell| **cell == Cell::Reachable).count() > dims.len() / 2);
+    assert!(fill_rate(10) > 0.0);
+  }
+}
@@ -0,0 +1,137 @@
+use std::collections::HashSet;
+
+use rand::{seq::SliceRandom, Rng, SeedableRng};
+use rand_pcg::Pcg64;
+use serde::Serialize;
+
+use crate::cell::Cell;
+use crate::deadlock::{find_dead_squares, is_deadlocked, DeadSquares};
+use crate::grid::Dims;
+use crate::heuristics::MatchingBound;
+use crate::level::Level;
+use crate::play::Game;
+use crate::solver::walking_path;
+use crate::state_graph::{is_goal_state, next_pushed_states, Push};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct BotConfig {
+  pub runs: usize,
+  // Pushes looked ahead before each one, at least one
+  pub lookahead: usize,
+  // A run gives up after this many pushes
+  pub max_pushes: usize,
+  // Chance of any push that isn't an obvious deadlock in place of the best
+  // looking one
+  pub mistakes: f64,
+  pub seed: u64,
+}
+
+impl Default for BotConfig {
+  fn default() -> Self {
+    BotConfig { runs: 20, lookahead: 2, max_pushes: 100, mistakes: 0.1, seed: 0 }
+  }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Playability {
+  pub runs: usize,
+  pub solved: usize,
+  pub success_rate: f64,
+  // Over the solved runs, counting walking steps and pushes
+  pub mean_moves: Option<f64>,
+}
+
+// Plays the level `config.runs` times like a player who only sees a few
+// pushes ahead: each push is the one that looks best by `MatchingBound`
+// within the lookahead, ties broken at random. Pushes into deadlocks the
+// solvers rule out cheaply, or back to a board already seen, are never made.
+// The same config always plays the same way.
+pub fn simulate(level: &Level, config: &BotConfig) -> Playability {
+  let state = level.initial_state();
+  let dead = find_dead_squares(&state, level.dims);
+  let bound = MatchingBound::new(&state, level.dims);
+  let mut rng = Pcg64::seed_from_u64(config.seed);
+  let moves = (0..config.runs)
+    .filter_map(|_| play_once(level, config, &dead, &bound, &mut rng))
+    .collect::<Vec<usize>>();
+  Playability {
+    runs: config.runs,
+    solved: moves.len(),
+    success_rate: if config.runs == 0 { 0.0 } else { moves.len() as f64 / config.runs as f64 },
+    mean_moves: if moves.is_empty() { None } else { Some(moves.iter().sum::<usize>() as f64 / moves.len() as f64) },
+  }
+}
+
+// The moves of a run that solves the level
+fn play_once<R: Rng>(level: &Level, config: &BotConfig, dead: &DeadSquares, bound: &MatchingBound, rng: &mut R) -> Option<usize> {
+  let dims = level.dims;
+  let mut game = Game::new(level.clone());
+  let mut seen = HashSet::new();
+  seen.insert(game.state().to_vec());
+  for _ in 0..config.max_pushes {
+    if game.is_solved() {
+      break;
+    }
+    let state = game.state().to_vec();
+    let options = next_pushed_states(&state, dims).into_iter()
+      .filter(|(next, push)| !seen.contains(next) && !is_deadlocked(push, &state, dead, dims))
+      .collect::<Vec<(Vec<Cell>, Push)>>();
+    if options.is_empty() {
+      return None;
+    }
+    let (next, push) = if rng.gen_bool(config.mistakes.clamp(0.0, 1.0)) {
+      options.choose(rng)?
+    } else {
+      let depth = config.lookahead.max(1) - 1;
+      let scores = options.iter().map(|(next, _)| outlook(next, depth, dead, bound, dims)).collect::<Vec<usize>>();
+      let best = *scores.iter().min()?;
+      let ties = (0..options.len()).filter(|idx| scores[*idx] == best).collect::<Vec<usize>>();
+      &options[*ties.choose(rng)?]
+    };
+    let start = push.tractor_start(&state, dims)?;
+    for dir in walking_path(game.tractor(), start, &state, dims)? {
+      game.step(dir);
+    }
+    game.step(push.dir);
+    seen.insert(next.clone());
+  }
+  if game.is_solved() { Some(game.moves().len()) } else { None }
+}
+
+// The fewest pushes to a goal as the bot sees it: searched for `depth` more
+// pushes, then estimated
+fn outlook(state: &[Cell], depth: usize, dead: &DeadSquares, bound: &MatchingBound, dims: Dims) -> usize {
+  if is_goal_state(state) {
+    return 0;
+  }
+  if depth == 0 {
+    return bound.estimate(state).unwrap_or(usize::MAX);
+  }
+  next_pushed_states(state, dims).into_iter()
+    .filter(|(_, push)| !is_deadlocked(push, state, dead, dims))
+    .map(|(next, _)| outlook(&next, depth - 1, dead, bound, dims).saturating_add(1))
+    .min()
+    .unwrap_or(usize::MAX)
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn test_simulate() {
+    let grid = vec![
+      Cell::Unreachable, Cell::Boulder, Cell::Unreachable, Cell::Hole,
+      Cell::Unreachable, Cell::Unreachable, Cell::Unreachable, Cell::Unreachable,
+    ];
+    let level = Level::new(grid, Dims::new(2, 4), 4);
+    let careful = BotConfig { runs: 5, mistakes: 0.0, ..Default::default() };
+    let expected = Playability { runs: 5, solved: 5, success_rate: 1.0, mean_moves: Some(3.0) };
+    assert_eq!(simulate(&level, &careful), expected);
+    let sloppy = BotConfig { mistakes: 1.0, ..careful };
+    assert_eq!(simulate(&level, &sloppy), simulate(&level, &sloppy));
+    let mut stuck = level.clone();
+    stuck.grid.swap(0, 1);
+    assert_eq!(simulate(&stuck, &BotConfig::default()).success_rate, 0.0);
+  }
+}
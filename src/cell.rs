@@ -1,5 +1,7 @@
 use serde::{Deserialize, Serialize};
 
+use crate::direction::Direction;
+
 #[derive(Copy, Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
 pub enum Cell {
   Unreachable,
@@ -8,8 +10,47 @@ pub enum Cell {
   Hole,
   Block,
   Boulder,
+  // Part of the board's shape rather than a puzzle piece: generators and
+  // mutations never place, move or clear it
+  Wall,
+  // Boulders pushed onto ice slide on, and so does a tractor walking onto it.
+  // The tractor can't push from ice.
+  Ice,
+  BoulderOnIce,
+  // Floor that the tractor and boulders only cross heading one way. The
+  // tractor can't push from it either.
+  OneWay(Direction),
+  BoulderOnOneWay(Direction),
+  // A colored boulder only drops into a hole of its color, any other hole
+  // stops it like a block. It can't be pushed onto ice or one-way cells.
+  ColoredBoulder(Color),
+  ColoredHole(Color),
+  ColoredBoulderInHole(Color),
+  // Stepping into a portal carries on out of the other one with the same id,
+  // for the tractor and pushed boulders alike. Nothing ever stops on one.
+  Portal(u8),
+  // The tractor can cross cracked floor once, straight over, and it caves in
+  // behind it. Boulders never go onto either.
+  Cracked,
+  Collapsed,
+  // Only moves when pushed with a run-up, see `state_graph::RUN_UP`. It can't
+  // be pushed onto ice or one-way cells, nor pulled.
+  HeavyBoulder,
+  HeavyBoulderInHole,
+}
+
+#[derive(Copy, Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+pub enum Color {
+  Red,
+  Green,
+  Blue,
 }
 
+pub static COLORS: &[Color] = &[Color::Red, Color::Green, Color::Blue];
+
+// Up to four pairs of portals, drawn by id
+static PORTALS: &[char] = &['p', 'q', 'r', 's'];
+
 impl Cell {
   pub fn try_from_char(c: char) -> Option<Self> {
     Some(match c {
@@ -19,10 +60,35 @@ impl Cell {
       'O' => Cell::Hole,
       '#' => Cell::Block,
       '*' => Cell::Boulder,
+      'X' => Cell::Wall,
+      '~' => Cell::Ice,
+      '&' => Cell::BoulderOnIce,
+      '^' => Cell::OneWay(Direction::Up),
+      'v' => Cell::OneWay(Direction::Down),
+      '<' => Cell::OneWay(Direction::Left),
+      '>' => Cell::OneWay(Direction::Right),
+      'U' => Cell::BoulderOnOneWay(Direction::Up),
+      'D' => Cell::BoulderOnOneWay(Direction::Down),
+      'L' => Cell::BoulderOnOneWay(Direction::Left),
+      'R' => Cell::BoulderOnOneWay(Direction::Right),
+      'a' => Cell::ColoredBoulder(Color::Red),
+      'b' => Cell::ColoredBoulder(Color::Green),
+      'c' => Cell::ColoredBoulder(Color::Blue),
+      'A' => Cell::ColoredHole(Color::Red),
+      'B' => Cell::ColoredHole(Color::Green),
+      'C' => Cell::ColoredHole(Color::Blue),
+      '1' => Cell::ColoredBoulderInHole(Color::Red),
+      '2' => Cell::ColoredBoulderInHole(Color::Green),
+      '3' => Cell::ColoredBoulderInHole(Color::Blue),
+      '%' => Cell::Cracked,
+      '=' => Cell::Collapsed,
+      'H' => Cell::HeavyBoulder,
+      'h' => Cell::HeavyBoulderInHole,
+      c if PORTALS.contains(&c) => Cell::Portal(PORTALS.iter().position(|p| *p == c).unwrap() as u8),
       _ => return None,
     })
   }
-  pub fn to_char(&self) -> char {
+  pub fn to_char(self) -> char {
     match self {
       Cell::Unreachable => ' ',
       Cell::Reachable => '.',
@@ -30,7 +96,119 @@ impl Cell {
       Cell::Hole => 'O',
       Cell::Block => '#',
       Cell::Boulder => '*',
+      Cell::Wall => 'X',
+      Cell::Ice => '~',
+      Cell::BoulderOnIce => '&',
+      Cell::OneWay(Direction::Up) => '^',
+      Cell::OneWay(Direction::Down) => 'v',
+      Cell::OneWay(Direction::Left) => '<',
+      Cell::OneWay(Direction::Right) => '>',
+      Cell::BoulderOnOneWay(Direction::Up) => 'U',
+      Cell::BoulderOnOneWay(Direction::Down) => 'D',
+      Cell::BoulderOnOneWay(Direction::Left) => 'L',
+      Cell::BoulderOnOneWay(Direction::Right) => 'R',
+      Cell::ColoredBoulder(Color::Red) => 'a',
+      Cell::ColoredBoulder(Color::Green) => 'b',
+      Cell::ColoredBoulder(Color::Blue) => 'c',
+      Cell::ColoredHole(Color::Red) => 'A',
+      Cell::ColoredHole(Color::Green) => 'B',
+      Cell::ColoredHole(Color::Blue) => 'C',
+      Cell::ColoredBoulderInHole(Color::Red) => '1',
+      Cell::ColoredBoulderInHole(Color::Green) => '2',
+      Cell::ColoredBoulderInHole(Color::Blue) => '3',
+      Cell::Portal(id) => PORTALS[id as usize],
+      Cell::Cracked => '%',
+      Cell::Collapsed => '=',
+      Cell::HeavyBoulder => 'H',
+      Cell::HeavyBoulderInHole => 'h',
+    }
+  }
+  // A boulder that still has to reach a hole
+  pub fn is_loose_boulder(self) -> bool {
+    matches!(self, Cell::Boulder | Cell::BoulderOnIce | Cell::BoulderOnOneWay(_) | Cell::ColoredBoulder(_) | Cell::HeavyBoulder)
+  }
+  pub fn has_boulder(self) -> bool {
+    self.is_loose_boulder() || matches!(self, Cell::BoulderInHole | Cell::ColoredBoulderInHole(_) | Cell::HeavyBoulderInHole)
+  }
+  // A hole still waiting for a boulder
+  pub fn is_open_hole(self) -> bool {
+    matches!(self, Cell::Hole | Cell::ColoredHole(_))
+  }
+  pub fn is_hole(self) -> bool {
+    matches!(self, Cell::Hole | Cell::BoulderInHole | Cell::ColoredHole(_) | Cell::ColoredBoulderInHole(_) | Cell::HeavyBoulderInHole)
+  }
+  pub fn is_heavy(self) -> bool {
+    matches!(self, Cell::HeavyBoulder | Cell::HeavyBoulderInHole)
+  }
+  // The color of a boulder or hole, `None` for plain ones
+  pub fn color(self) -> Option<Color> {
+    match self {
+      Cell::ColoredBoulder(color) | Cell::ColoredHole(color) | Cell::ColoredBoulderInHole(color) => Some(color),
+      _ => None,
+    }
+  }
+  // Whether the boulder held by `boulder` may be pushed onto the cell
+  pub fn accepts(self, boulder: Cell) -> bool {
+    match self {
+      Cell::Unreachable | Cell::Reachable => true,
+      Cell::Hole => boulder.color().is_none(),
+      Cell::Ice | Cell::OneWay(_) => boulder.color().is_none() && !boulder.is_heavy(),
+      Cell::ColoredHole(hole) => boulder.color() == Some(hole),
+      _ => false,
+    }
+  }
+  // The cell with a boulder moved onto it
+  pub fn with_boulder(self) -> Self {
+    match self {
+      Cell::Unreachable | Cell::Reachable => Cell::Boulder,
+      Cell::Hole => Cell::BoulderInHole,
+      Cell::Ice => Cell::BoulderOnIce,
+      Cell::OneWay(dir) => Cell::BoulderOnOneWay(dir),
+      other => other,
+    }
+  }
+  // Like `with_boulder`, for a boulder of `color`
+  pub fn with_boulder_of(self, color: Option<Color>) -> Self {
+    match (self, color) {
+      (cell, None) => cell.with_boulder(),
+      (Cell::Unreachable | Cell::Reachable, Some(color)) => Cell::ColoredBoulder(color),
+      (Cell::ColoredHole(hole), Some(color)) if hole == color => Cell::ColoredBoulderInHole(color),
+      (other, _) => other,
+    }
+  }
+  // The cell with the boulder held by `boulder` moved onto it
+  pub fn with_boulder_like(self, boulder: Cell) -> Self {
+    match (self, boulder.is_heavy()) {
+      (Cell::Unreachable | Cell::Reachable, true) => Cell::HeavyBoulder,
+      (Cell::Hole, true) => Cell::HeavyBoulderInHole,
+      (cell, _) => cell.with_boulder_of(boulder.color()),
     }
   }
+  // The cell once its boulder moves off
+  pub fn without_boulder(self) -> Self {
+    match self {
+      Cell::Boulder => Cell::Unreachable,
+      Cell::BoulderInHole => Cell::Hole,
+      Cell::BoulderOnIce => Cell::Ice,
+      Cell::BoulderOnOneWay(dir) => Cell::OneWay(dir),
+      Cell::ColoredBoulder(_) => Cell::Unreachable,
+      Cell::ColoredBoulderInHole(color) => Cell::ColoredHole(color),
+      Cell::HeavyBoulder => Cell::Unreachable,
+      Cell::HeavyBoulderInHole => Cell::Hole,
+      other => other,
+    }
+  }
+  // Whether anything may move into or out of the cell heading `dir`
+  pub fn allows(self, dir: Direction) -> bool {
+    match self {
+      Cell::OneWay(way) | Cell::BoulderOnOneWay(way) => way == dir,
+      _ => true,
+    }
+  }
+  // Ice and one-way cells make walking one way only, so a tractor region
+  // can't always be walked back across
+  pub fn is_directed(self) -> bool {
+    matches!(self, Cell::Ice | Cell::BoulderOnIce | Cell::OneWay(_) | Cell::BoulderOnOneWay(_))
+  }
 }
 
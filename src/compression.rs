@@ -0,0 +1,85 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+// Every zstd frame starts with these, which is how compressed files are told
+// apart on load. None of the plain formats can start with them.
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+pub fn is_compressed(buf: &[u8]) -> bool {
+  buf.starts_with(ZSTD_MAGIC)
+}
+
+#[cfg(feature = "compression")]
+pub fn compress(buf: &[u8]) -> io::Result<Vec<u8>> {
+  zstd::encode_all(buf, 0)
+}
+
+// Without the feature nothing can be compressed
+#[cfg(not(feature = "compression"))]
+pub fn compress(_: &[u8]) -> io::Result<Vec<u8>> {
+  Err(unsupported())
+}
+
+// Undoes `compress`, passing anything that isn't compressed through as is
+pub fn decompress(buf: Vec<u8>) -> io::Result<Vec<u8>> {
+  if !is_compressed(&buf) {
+    return Ok(buf);
+  }
+  #[cfg(feature = "compression")]
+  return zstd::decode_all(buf.as_slice());
+  #[cfg(not(feature = "compression"))]
+  return Err(unsupported());
+}
+
+#[cfg(not(feature = "compression"))]
+fn unsupported() -> io::Error {
+  io::Error::new(io::ErrorKind::Unsupported, "built without the `compression` feature")
+}
+
+// Reads a file written by `write`, compressed or not
+pub fn read(path: &Path) -> io::Result<Vec<u8>> {
+  decompress(fs::read(path)?)
+}
+
+pub fn read_to_string(path: &Path) -> io::Result<String> {
+  String::from_utf8(read(path)?).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+pub fn write(path: &Path, buf: &[u8], compressed: bool) -> io::Result<()> {
+  if compressed {
+    fs::write(path, compress(buf)?)
+  } else {
+    fs::write(path, buf)
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn test_plain_passes_through() {
+    let text = b"#####\n#@$.#\n#####\n".to_vec();
+    assert!(!is_compressed(&text));
+    assert_eq!(decompress(text.clone()).unwrap(), text);
+  }
+
+  #[cfg(feature = "compression")]
+  #[test]
+  fn test_round_trip() {
+    let buf = vec![7u8; 4096];
+    let compressed = compress(&buf).unwrap();
+    assert!(is_compressed(&compressed));
+    assert!(compressed.len() < buf.len());
+    assert_eq!(decompress(compressed).unwrap(), buf);
+  }
+
+  #[cfg(not(feature = "compression"))]
+  #[test]
+  fn test_needs_feature() {
+    assert_eq!(compress(b"").unwrap_err().kind(), io::ErrorKind::Unsupported);
+    let compressed = [ZSTD_MAGIC, &[0]].concat();
+    assert_eq!(decompress(compressed).unwrap_err().kind(), io::ErrorKind::Unsupported);
+  }
+}
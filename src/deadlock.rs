@@ -0,0 +1,267 @@
+use std::collections::{HashMap, VecDeque};
+
+use crate::cell::{Cell, Color};
+use crate::direction::{Direction, DIRECTIONS};
+use crate::grid::{step, Dims};
+use crate::state_graph::*;
+
+// Cells a loose boulder can never be pushed from into a hole of its color,
+// even with every other boulder out of the way, kept for each color of
+// boulder on the board
+#[derive(Clone, Debug)]
+pub struct DeadSquares {
+  by_color: HashMap<Option<Color>, Vec<bool>>,
+}
+
+impl DeadSquares {
+  pub fn is_dead(&self, idx: usize, boulder: Cell) -> bool {
+    self.by_color.get(&boulder.color()).is_some_and(|dead| dead[idx])
+  }
+}
+
+// Only the blocks and holes of `grid` matter, and which colors its boulders
+// come in
+pub fn find_dead_squares(grid: &[Cell], dims: Dims) -> DeadSquares {
+  let mut by_color = HashMap::new();
+  for cell in grid.iter().filter(|cell| cell.has_boulder()) {
+    by_color.entry(cell.color()).or_insert_with(|| {
+      let holes = (0..grid.len())
+        .filter(|idx| grid[*idx].is_hole() && grid[*idx].color() == cell.color())
+        .collect::<Vec<usize>>();
+      push_distances_to(&holes, grid, dims).iter()
+        .enumerate()
+        .map(|(idx, dist)| dist.is_none() && !is_solid(grid[idx]))
+        .collect()
+    });
+  }
+  DeadSquares { by_color }
+}
+
+// Fewest pushes that take a lone boulder from each cell into some hole,
+// ignoring every other boulder. Holes are 0, blocks and dead squares `None`.
+pub fn push_distances(grid: &[Cell], dims: Dims) -> Vec<Option<usize>> {
+  let holes = (0..grid.len()).filter(|idx| grid[*idx].is_hole()).collect::<Vec<usize>>();
+  push_distances_to(&holes, grid, dims)
+}
+
+// Like `push_distances`, but only into one of `targets`. A boulder can't
+// cross any hole of `grid` on the way, it would drop in.
+pub fn push_distances_to(targets: &[usize], grid: &[Cell], dims: Dims) -> Vec<Option<usize>> {
+  let is_floor = |idx: usize| !is_solid(grid[idx]) && !grid[idx].is_hole();
+  let mut dist = vec![None; grid.len()];
+  for target in targets {
+    dist[*target] = Some(0);
+  }
+  let mut queue = targets.iter().cloned().collect::<VecDeque<usize>>();
+  // Walk pushes backwards: a boulder on `from` pushed towards `dir` lands on
+  // `to`, with the tractor standing on `behind`
+  while let Some(to) = queue.pop_front() {
+    for dir in DIRECTIONS {
+      let from = match step(to, dir.opposite(), grid, dims) {
+        Some(from) => from,
+        None => continue,
+      };
+      let behind = match step(from, dir.opposite(), grid, dims) {
+        Some(behind) => behind,
+        None => continue,
+      };
+      if dist[from].is_none() && is_floor(from) && is_floor(behind) {
+        dist[from] = dist[to].map(|d| d + 1);
+        queue.push_back(from);
+      }
+    }
+  }
+  dist
+}
+
+// Boulders never go onto cracked floor, so it is as good as a wall to them
+fn is_solid(cell: Cell) -> bool {
+  matches!(cell, Cell::Block | Cell::Wall | Cell::Cracked | Cell::Collapsed)
+}
+
+// Where the boulder `push` moved ended up in `state`, past any ice it slid
+// across
+fn landing(push: &Push, state: &[Cell], dims: Dims) -> Option<usize> {
+  let mut idx = step(push.boulder, push.dir, state, dims)?;
+  while state[idx] == Cell::Ice {
+    idx = step(idx, push.dir, state, dims)?;
+  }
+  Some(idx)
+}
+
+// Whether `push` left its boulder loose on a dead square of `state`
+pub fn pushed_onto_dead_square(push: &Push, state: &[Cell], dead: &DeadSquares, dims: Dims) -> bool {
+  match landing(push, state, dims) {
+    Some(idx) => state[idx].is_loose_boulder() && dead.is_dead(idx, state[idx]),
+    None => false,
+  }
+}
+
+// A loose boulder is frozen when it can be pushed along neither axis. Blocks,
+// the board edge, boulders already in holes, and other frozen boulders all
+// pin it in place.
+pub fn is_frozen(idx: usize, state: &[Cell], dead: &DeadSquares, dims: Dims) -> bool {
+  is_frozen_with(idx, state, dead, dims, &mut vec![])
+}
+
+fn is_frozen_with(idx: usize, state: &[Cell], dead: &DeadSquares, dims: Dims, pinned: &mut Vec<usize>) -> bool {
+  pinned.push(idx);
+  let boulder = state[idx];
+  let frozen = [(Direction::Left, Direction::Right), (Direction::Up, Direction::Down)]
+    .iter()
+    .all(|(a, b)| {
+      let a = side(a.apply(idx, dims), boulder, state, dead, dims, pinned);
+      let b = side(b.apply(idx, dims), boulder, state, dead, dims, pinned);
+      let movable = a.standable() && b.enterable() || b.standable() && a.enterable();
+      !movable
+    });
+  pinned.pop();
+  frozen
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum Side {
+  Wall,
+  Hole,
+  Floor { dead: bool },
+}
+
+impl Side {
+  fn standable(self) -> bool {
+    matches!(self, Side::Floor { .. })
+  }
+  fn enterable(self) -> bool {
+    self == Side::Hole || self == Side::Floor { dead: false }
+  }
+}
+
+// What sits next to `boulder`. A loose neighbor counts as floor unless it
+// is frozen itself, with the boulders in `pinned` held fixed, and a hole of
+// another color as a wall.
+fn side(idx: Option<usize>, boulder: Cell, state: &[Cell], dead: &DeadSquares, dims: Dims, pinned: &mut Vec<usize>) -> Side {
+  let idx = match idx {
+    Some(idx) => idx,
+    None => return Side::Wall,
+  };
+  if pinned.contains(&idx) {
+    return Side::Wall;
+  }
+  match state[idx] {
+    cell if is_solid(cell) => Side::Wall,
+    Cell::BoulderInHole | Cell::ColoredBoulderInHole(_) => Side::Wall,
+    // Where a portal leads isn't worked out, so it never pins anything
+    Cell::Portal(_) => Side::Floor { dead: false },
+    cell if cell.is_open_hole() && cell.accepts(boulder) => Side::Hole,
+    cell if cell.is_open_hole() => Side::Wall,
+    cell if cell.is_loose_boulder() && is_frozen_with(idx, state, dead, dims, pinned) => Side::Wall,
+    _ => Side::Floor { dead: dead.is_dead(idx, boulder) },
+  }
+}
+
+// Whether `push` left a loose boulder frozen off its hole, either the one it
+// moved or one next to where it stopped
+pub fn pushed_into_freeze(push: &Push, state: &[Cell], dead: &DeadSquares, dims: Dims) -> bool {
+  let moved = match landing(push, state, dims) {
+    Some(moved) => moved,
+    None => return false,
+  };
+  let nearby = DIRECTIONS.iter().filter_map(|dir| dir.apply(moved, dims));
+  std::iter::once(moved).chain(nearby)
+    .any(|idx| state[idx].is_loose_boulder() && is_frozen(idx, state, dead, dims))
+}
+
+// Either kind of deadlock the solvers can rule out cheaply
+pub fn is_deadlocked(push: &Push, state: &[Cell], dead: &DeadSquares, dims: Dims) -> bool {
+  pushed_onto_dead_square(push, state, dead, dims) || pushed_into_freeze(push, state, dead, dims)
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::cell::COLORS;
+
+  // The same dead squares for every color
+  impl From<Vec<bool>> for DeadSquares {
+    fn from(dead: Vec<bool>) -> Self {
+      let by_color = std::iter::once(None).chain(COLORS.iter().map(|color| Some(*color)))
+        .map(|color| (color, dead.clone()))
+        .collect();
+      DeadSquares { by_color }
+    }
+  }
+
+  #[test]
+  fn test_dead_squares() {
+    let grid = vec![
+      Cell::Unreachable, Cell::Unreachable, Cell::Unreachable, Cell::Unreachable,
+      Cell::Unreachable, Cell::Unreachable, Cell::Hole, Cell::Unreachable,
+      Cell::Unreachable, Cell::Unreachable, Cell::Unreachable, Cell::Unreachable,
+      Cell::Block, Cell::Unreachable, Cell::Unreachable, Cell::Unreachable,
+    ];
+    let mut with_boulder = grid.clone();
+    with_boulder[5] = Cell::Boulder;
+    let dead = find_dead_squares(&with_boulder, Dims::square(4));
+    let dead = (0..16).map(|idx| dead.is_dead(idx, Cell::Boulder)).collect::<Vec<bool>>();
+    assert_eq!(dead, vec![
+      true, true, true, true,
+      true, false, false, true,
+      true, false, false, true,
+      false, true, true, true,
+    ]);
+    let dist = push_distances(&grid, Dims::square(4));
+    assert_eq!(dist[6], Some(0));
+    assert_eq!(dist[9], Some(2));
+    assert_eq!(dist[12], None);
+    assert_eq!(dist[0], None);
+  }
+
+  #[test]
+  fn test_dead_squares_by_color() {
+    // Each boulder can only be pushed away from the hole of its color
+    let grid = vec![
+      Cell::ColoredHole(Color::Red), Cell::ColoredBoulder(Color::Red),
+      Cell::ColoredBoulder(Color::Green), Cell::ColoredHole(Color::Green),
+    ];
+    let dead = find_dead_squares(&grid, Dims::new(1, 4));
+    assert!(!dead.is_dead(1, Cell::ColoredBoulder(Color::Red)));
+    assert!(dead.is_dead(2, Cell::ColoredBoulder(Color::Red)));
+    assert!(dead.is_dead(1, Cell::ColoredBoulder(Color::Green)));
+    assert!(!dead.is_dead(2, Cell::ColoredBoulder(Color::Green)));
+  }
+
+  #[test]
+  fn test_frozen_pair_along_wall() {
+    // Two boulders side by side against the top wall, holes elsewhere
+    let state = vec![
+      Cell::Unreachable, Cell::Boulder, Cell::Boulder, Cell::Unreachable,
+      Cell::Unreachable, Cell::Unreachable, Cell::Unreachable, Cell::Unreachable,
+      Cell::Unreachable, Cell::Hole, Cell::Hole, Cell::Unreachable,
+      Cell::Unreachable, Cell::Unreachable, Cell::Unreachable, Cell::Unreachable,
+    ];
+    let dead = DeadSquares::from(vec![false; 16]);
+    assert!(is_frozen(1, &state, &dead, Dims::square(4)));
+    assert!(is_frozen(2, &state, &dead, Dims::square(4)));
+    // Apart, each can still slide along the wall
+    let mut apart = state.clone();
+    apart[2] = Cell::Unreachable;
+    apart[3] = Cell::Boulder;
+    assert!(!is_frozen(1, &apart, &dead, Dims::square(4)));
+  }
+
+  #[test]
+  fn test_frozen_square() {
+    let state = vec![
+      Cell::Unreachable, Cell::Unreachable, Cell::Unreachable, Cell::Unreachable,
+      Cell::Unreachable, Cell::Boulder, Cell::Boulder, Cell::Unreachable,
+      Cell::Unreachable, Cell::Boulder, Cell::Boulder, Cell::Unreachable,
+      Cell::Hole, Cell::Hole, Cell::Hole, Cell::Hole,
+    ];
+    let dead = DeadSquares::from(vec![false; 16]);
+    assert!(is_frozen(5, &state, &dead, Dims::square(4)));
+    let push = Push { boulder: 6, dir: Direction::Down, tractor: 6 };
+    assert!(pushed_into_freeze(&push, &state, &dead, Dims::square(4)));
+    let mut open = state.clone();
+    open[6] = Cell::Unreachable;
+    assert!(!is_frozen(5, &open, &dead, Dims::square(4)));
+  }
+}
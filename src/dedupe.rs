@@ -0,0 +1,136 @@
+use std::collections::HashMap;
+
+use crate::level::Level;
+use crate::metrics::box_lines_of;
+use crate::solver::solve;
+use crate::symmetry::{canonical_level, TRANSFORMS};
+
+// A hash of the canonical form, the same for levels that only differ by a
+// rotation, reflection or shift. FNV-1a keeps it stable between runs, so it
+// can be stored alongside a collection.
+pub fn fingerprint(level: &Level) -> u64 {
+  let canonical = canonical_level(level);
+  let mut hash = 0xcbf2_9ce4_8422_2325u64;
+  let dims = [canonical.dims.rows as u64, canonical.dims.cols as u64];
+  let bytes = dims.iter().flat_map(|n| n.to_le_bytes().to_vec())
+    .chain(canonical.grid.iter().flat_map(|cell| cell.to_char().to_string().into_bytes()));
+  for byte in bytes {
+    hash ^= byte as u64;
+    hash = hash.wrapping_mul(0x0100_0000_01b3);
+  }
+  hash
+}
+
+// For each level, the index of the first earlier one it duplicates.
+// Fingerprints only pick out candidates, the canonical forms decide.
+pub fn find_duplicates(levels: &[Level]) -> Vec<Option<usize>> {
+  let mut seen: HashMap<u64, Vec<(usize, Level)>> = HashMap::new();
+  let mut duplicates = vec![];
+  for (idx, level) in levels.iter().enumerate() {
+    let canonical = canonical_level(level);
+    let bucket = seen.entry(fingerprint(&canonical)).or_default();
+    let original = bucket.iter().find(|(_, other)| *other == canonical).map(|(first, _)| *first);
+    if original.is_none() {
+      bucket.push((idx, canonical));
+    }
+    duplicates.push(original);
+  }
+  duplicates
+}
+
+// A level solved and put in canonical form once, to be compared with many
+// others by `similarity_of`
+#[derive(Clone, Debug)]
+pub struct Prepared {
+  canonical: Level,
+  shape: Option<SolutionShape>,
+}
+
+// What `similarity` compares of a solution
+#[derive(Clone, Debug)]
+struct SolutionShape {
+  pushes: usize,
+  lines: usize,
+  turns: usize,
+}
+
+impl Prepared {
+  pub fn new(level: &Level) -> Self {
+    let shape = solve(level).map(|solution| {
+      let lines = box_lines_of(&solution);
+      SolutionShape { pushes: solution.len(), lines: lines.lines, turns: lines.direction_changes.iter().sum() }
+    });
+    Prepared { canonical: canonical_level(level), shape }
+  }
+}
+
+// How alike two levels are, from 0 to 1: the mean of how many cells their
+// canonical forms share, lined up the best way, and how close their solutions
+// come in pushes, lines of pushes and turns. Duplicates score 1.
+pub fn similarity(a: &Level, b: &Level) -> f32 {
+  similarity_of(&Prepared::new(a), &Prepared::new(b))
+}
+
+// `similarity` without solving either level again
+pub fn similarity_of(a: &Prepared, b: &Prepared) -> f32 {
+  let structure = match (&a.shape, &b.shape) {
+    (Some(x), Some(y)) => solution_likeness(x, y),
+    (None, None) => 1.0,
+    _ => 0.0,
+  };
+  let (a, b) = (&a.canonical, &b.canonical);
+  let overlap = TRANSFORMS.iter()
+    .map(|transform| transform.apply_level(b))
+    .filter(|turned| turned.dims == a.dims)
+    .map(|turned| a.grid.iter().zip(&turned.grid).filter(|(x, y)| x == y).count() as f32 / a.grid.len().max(1) as f32)
+    .fold(0.0, f32::max);
+  (overlap + structure) / 2.0
+}
+
+fn solution_likeness(a: &SolutionShape, b: &SolutionShape) -> f32 {
+  let ratio = |x: usize, y: usize| if x == y { 1.0 } else { x.min(y) as f32 / x.max(y) as f32 };
+  (ratio(a.pushes, b.pushes) + ratio(a.lines, b.lines) + ratio(a.turns, b.turns)) / 3.0
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::cell::Cell;
+  use crate::grid::Dims;
+  use crate::symmetry::Transform;
+
+  #[test]
+  fn test_find_duplicates() {
+    let level = Level::new(vec![
+      Cell::Unreachable, Cell::Boulder, Cell::Hole,
+      Cell::Unreachable, Cell::Unreachable, Cell::Block,
+    ], Dims::new(2, 3), 0);
+    let mut other = level.clone();
+    other.grid.swap(4, 5);
+    let levels = vec![
+      level.clone(),
+      other.clone(),
+      Transform::Rotate90.apply_level(&level),
+      Level { tractor: 4, ..level.clone() },
+      Transform::FlipVertical.apply_level(&other),
+    ];
+    assert_eq!(find_duplicates(&levels), vec![None, None, Some(0), Some(0), Some(1)]);
+    assert_eq!(fingerprint(&levels[2]), fingerprint(&level));
+    assert_ne!(fingerprint(&other), fingerprint(&level));
+  }
+
+  #[test]
+  fn test_similarity() {
+    let level = Level::new(vec![Cell::Unreachable, Cell::Boulder, Cell::Unreachable, Cell::Hole], Dims::new(1, 4), 0);
+    assert_eq!(similarity(&level, &Transform::FlipHorizontal.apply_level(&level)), 1.0);
+    // Half the cells match, and the solution is one push right instead of two
+    let mut moved = level.clone();
+    moved.grid.swap(1, 2);
+    assert!((similarity(&level, &moved) - (0.5 + 5.0 / 6.0) / 2.0).abs() < 1e-6);
+    let stuck = Level::new(vec![Cell::Boulder, Cell::Unreachable, Cell::Unreachable, Cell::Hole], Dims::new(1, 4), 1);
+    assert!(similarity(&level, &stuck) < 0.5);
+    let prepared = [&level, &moved, &stuck].iter().map(|level| Prepared::new(level)).collect::<Vec<Prepared>>();
+    assert_eq!(similarity_of(&prepared[0], &prepared[1]), similarity(&level, &moved));
+    assert_eq!(similarity_of(&prepared[2], &prepared[0]), similarity(&stuck, &level));
+  }
+}
@@ -0,0 +1,186 @@
+use std::collections::hash_map::HashMap;
+use std::collections::VecDeque;
+
+use crate::level::Level;
+use crate::packed::*;
+use crate::state_graph::{is_goal_state, next_pushed_states};
+
+// Levels with more states than this reachable by pushing aren't measured
+pub const MAX_MEASURED_STATES: usize = 200_000;
+
+// How a level plays out, taken from every state reachable by pushing
+#[derive(Clone, Debug, PartialEq)]
+pub struct LevelMetrics {
+  // Fewest pushes to solve
+  pub pushes: usize,
+  // Mean number of pushes on offer in the unsolved states
+  pub branching: f64,
+  // Share of reachable states from which the level can't be solved any more
+  pub dead_fraction: f64,
+}
+
+impl LevelMetrics {
+  // One number to rank levels by, longer solutions with more ways to go
+  // wrong score higher
+  pub fn score(&self) -> f64 {
+    self.pushes as f64 + self.branching + 10.0 * self.dead_fraction
+  }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum DifficultyRange {
+  Easy,
+  Medium,
+  Hard,
+  // Scores from `min` up to but not including `max`
+  Between { min: f64, max: f64 },
+}
+
+impl DifficultyRange {
+  pub fn bounds(self) -> (f64, f64) {
+    match self {
+      DifficultyRange::Easy => (0.0, 8.0),
+      DifficultyRange::Medium => (8.0, 16.0),
+      DifficultyRange::Hard => (16.0, f64::INFINITY),
+      DifficultyRange::Between { min, max } => (min, max),
+    }
+  }
+  pub fn contains(self, metrics: &LevelMetrics) -> bool {
+    let (min, max) = self.bounds();
+    let score = metrics.score();
+    min <= score && score < max
+  }
+}
+
+// Counts over every state reachable by pushing, whether or not the level can
+// be solved
+#[derive(Clone, Debug, PartialEq)]
+pub struct PushGraphStats {
+  pub states: usize,
+  // Pushes on offer, summed over the unsolved states
+  pub edges: usize,
+  pub goals: usize,
+  // Fewest pushes to solve, `None` if no goal can be reached
+  pub pushes: Option<usize>,
+  pub branching: f64,
+  pub dead_fraction: f64,
+}
+
+impl PushGraphStats {
+  pub fn metrics(&self) -> Option<LevelMetrics> {
+    Some(LevelMetrics { pushes: self.pushes?, branching: self.branching, dead_fraction: self.dead_fraction })
+  }
+}
+
+// `None` for unsolvable levels and ones too big to measure
+pub fn measure(level: &Level) -> Option<LevelMetrics> {
+  push_graph_stats(level)?.metrics()
+}
+
+// `None` for levels too big to measure
+pub fn push_graph_stats(level: &Level) -> Option<PushGraphStats> {
+  let start = level.initial_state();
+  let layout = Layout::from_state(&start, level.dims);
+  let mut index = HashMap::new();
+  let mut states = vec![start];
+  let mut successors: Vec<Vec<usize>> = vec![];
+  index.insert(layout.encode(&states[0]), 0);
+  let mut current = 0;
+  while current < states.len() {
+    let mut next_ids = vec![];
+    if !is_goal_state(&states[current]) {
+      for (next, _) in next_pushed_states(&states[current], level.dims) {
+        let packed = layout.encode(&next);
+        let id = match index.get(&packed) {
+          Some(id) => *id,
+          None => {
+            if states.len() == MAX_MEASURED_STATES {
+              return None;
+            }
+            index.insert(packed, states.len());
+            states.push(next);
+            states.len() - 1
+          },
+        };
+        next_ids.push(id);
+      }
+    }
+    successors.push(next_ids);
+    current += 1;
+  }
+  // Walk back from the goals to find how far each state is from one
+  let mut predecessors = vec![vec![]; states.len()];
+  for (id, next_ids) in successors.iter().enumerate() {
+    for next in next_ids {
+      predecessors[*next].push(id);
+    }
+  }
+  let mut dist = vec![None; states.len()];
+  let mut queue = VecDeque::new();
+  for (id, state) in states.iter().enumerate() {
+    if is_goal_state(state) {
+      dist[id] = Some(0);
+      queue.push_back(id);
+    }
+  }
+  while let Some(id) = queue.pop_front() {
+    for prev in &predecessors[id] {
+      if dist[*prev].is_none() {
+        dist[*prev] = dist[id].map(|d| d + 1);
+        queue.push_back(*prev);
+      }
+    }
+  }
+  let goals = states.iter().filter(|state| is_goal_state(state)).count();
+  let unsolved = states.len() - goals;
+  let offered = successors.iter().map(|next_ids| next_ids.len()).sum::<usize>();
+  let dead = dist.iter().filter(|d| d.is_none()).count();
+  Some(PushGraphStats {
+    states: states.len(),
+    edges: offered,
+    goals,
+    pushes: dist[0],
+    branching: if unsolved == 0 { 0.0 } else { offered as f64 / unsolved as f64 },
+    dead_fraction: dead as f64 / states.len() as f64,
+  })
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::grid::Dims;
+  use crate::cell::Cell;
+
+  #[test]
+  fn test_measure() {
+    let grid = vec![
+      Cell::Hole, Cell::Unreachable, Cell::Unreachable, Cell::Unreachable,
+      Cell::Block, Cell::Block, Cell::Boulder, Cell::Unreachable,
+      Cell::Block, Cell::Block, Cell::Unreachable, Cell::Unreachable,
+      Cell::Block, Cell::Block, Cell::Unreachable, Cell::Unreachable,
+    ];
+    let level = Level::new(grid, Dims::square(4), 15);
+    let metrics = measure(&level).unwrap();
+    assert_eq!(push_graph_stats(&level).unwrap().metrics(), Some(metrics.clone()));
+    assert_eq!(metrics.pushes, 3);
+    assert!(metrics.branching > 0.0);
+    // Pushing the boulder down or right strands it
+    assert!(metrics.dead_fraction > 0.0 && metrics.dead_fraction < 1.0);
+    assert!(DifficultyRange::Between { min: 0.0, max: f64::INFINITY }.contains(&metrics));
+  }
+
+  #[test]
+  fn test_unsolvable_not_measured() {
+    let grid = vec![
+      Cell::Hole, Cell::Block, Cell::Unreachable,
+      Cell::Block, Cell::Boulder, Cell::Unreachable,
+      Cell::Unreachable, Cell::Unreachable, Cell::Unreachable,
+    ];
+    assert_eq!(measure(&Level::new(grid.clone(), Dims::square(3), 8)), None);
+    let stats = push_graph_stats(&Level::new(grid, Dims::square(3), 8)).unwrap();
+    assert_eq!(stats.pushes, None);
+    assert_eq!(stats.goals, 0);
+    assert_eq!(stats.dead_fraction, 1.0);
+    assert_eq!(stats.edges, stats.states - 1);
+  }
+}
@@ -0,0 +1,69 @@
+use serde::{Deserialize, Serialize};
+
+use crate::grid::Dims;
+
+// The ways the tractor walks and boulders get pushed. Serializes by name, so
+// a list of pushes or moves can be written out and read back.
+#[derive(Copy, Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub enum Direction {
+  Up,
+  Down,
+  Left,
+  Right,
+}
+
+pub static DIRECTIONS: &[Direction] = &[Direction::Up, Direction::Down, Direction::Left, Direction::Right];
+
+impl Direction {
+  // In the order of `DIRECTIONS`
+  pub fn all() -> impl Iterator<Item = Direction> {
+    DIRECTIONS.iter().cloned()
+  }
+  pub fn opposite(self) -> Self {
+    match self {
+      Direction::Up => Direction::Down,
+      Direction::Down => Direction::Up,
+      Direction::Left => Direction::Right,
+      Direction::Right => Direction::Left,
+    }
+  }
+  // Rows and columns moved by one step, rows counting down the board
+  pub fn delta(self) -> (isize, isize) {
+    match self {
+      Direction::Up => (-1, 0),
+      Direction::Down => (1, 0),
+      Direction::Left => (0, -1),
+      Direction::Right => (0, 1),
+    }
+  }
+  // The cell one step from `idx`, unless that's off the board
+  pub fn apply(self, idx: usize, dims: Dims) -> Option<usize> {
+    let (rows, cols) = self.delta();
+    let row = dims.row(idx).checked_add_signed(rows).filter(|row| *row < dims.rows)?;
+    let col = dims.col(idx).checked_add_signed(cols).filter(|col| *col < dims.cols)?;
+    Some(dims.index(row, col))
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn test_apply() {
+    // Steps mustn't wrap from the end of one row onto the start of the next
+    let dims = Dims::new(2, 4);
+    assert_eq!(Direction::Right.apply(3, dims), None);
+    assert_eq!(Direction::Down.apply(3, dims), Some(7));
+    assert_eq!(Direction::Down.apply(5, dims), None);
+    assert_eq!(Direction::Left.apply(4, dims), None);
+    for dir in Direction::all() {
+      let (rows, cols) = dir.delta();
+      assert_eq!(dir.opposite().delta(), (-rows, -cols));
+      assert_eq!(dir.apply(1, dims).and_then(|idx| dir.opposite().apply(idx, dims)), dir.apply(1, dims).map(|_| 1));
+    }
+    let json = serde_json::to_string(&[Direction::Up, Direction::Left]).unwrap();
+    assert_eq!(json, r#"["Up","Left"]"#);
+    assert_eq!(serde_json::from_str::<Vec<Direction>>(&json).unwrap(), vec![Direction::Up, Direction::Left]);
+  }
+}
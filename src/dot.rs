@@ -0,0 +1,90 @@
+use std::fmt::Write;
+
+use crate::state_graph::{StateGraph, StateId};
+
+#[derive(Clone, Debug, Default)]
+pub struct DotOptions {
+  // Draw each state as a mini grid instead of just its id
+  pub grids: bool,
+  // Label each edge with the boulder that moved and its direction
+  pub pushes: bool,
+}
+
+impl StateGraph {
+  // GraphViz output, e.g. for `dot -Tsvg`. The root is drawn bold and goal
+  // states with a double border.
+  pub fn to_dot(&self, opts: &DotOptions) -> String {
+    let mut out = String::new();
+    writeln!(out, "digraph states {{").unwrap();
+    writeln!(out, "  node [shape=box fontname=\"monospace\"];").unwrap();
+    let mut states = self.states().collect::<Vec<_>>();
+    states.sort_by_key(|(id, _)| *id);
+    for (id, state) in &states {
+      let mut label = format!("{}", id);
+      if opts.grids {
+        label.push_str("\\l");
+        for row in state.chunks(self.dims().cols) {
+          let row = row.iter().map(|cell| cell.to_char()).collect::<String>();
+          label.push_str(&escape(&format!("|{}|", row)));
+          label.push_str("\\l");
+        }
+      }
+      let mut attrs = format!("label=\"{}\"", label);
+      if *id == self.root() {
+        attrs.push_str(" style=bold");
+      }
+      if self.is_goal(id) {
+        attrs.push_str(" peripheries=2");
+      }
+      writeln!(out, "  {} [{}];", node_name(id), attrs).unwrap();
+    }
+    let mut edges = self.edges().collect::<Vec<_>>();
+    edges.sort();
+    for (from, to) in edges {
+      write!(out, "  {} -> {}", node_name(&from), node_name(&to)).unwrap();
+      match self.get_edge(&from, &to) {
+        Some(push) if opts.pushes => {
+          writeln!(out, " [label=\"{} {:?}\"];", push.boulder, push.dir).unwrap()
+        },
+        _ => writeln!(out, ";").unwrap(),
+      }
+    }
+    writeln!(out, "}}").unwrap();
+    out
+  }
+}
+
+fn node_name(id: &StateId) -> String {
+  format!("s{}", id.index())
+}
+
+fn escape(text: &str) -> String {
+  text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::grid::Dims;
+  use crate::level::Level;
+  use crate::cell::Cell;
+  use crate::state_graph::find_solvable_states;
+
+  #[test]
+  fn test_to_dot() {
+    let grid = vec![
+      Cell::BoulderInHole, Cell::Unreachable, Cell::Unreachable,
+      Cell::Block, Cell::Block, Cell::Unreachable,
+      Cell::Block, Cell::Block, Cell::Unreachable,
+    ];
+    let graph = find_solvable_states(&Level::new(grid, Dims::square(3), 8));
+    let plain = graph.to_dot(&DotOptions::default());
+    assert!(plain.starts_with("digraph states {\n"));
+    assert!(plain.contains("  s0 [label=\"#0\" style=bold peripheries=2];\n"));
+    assert!(plain.contains("  s0 -> s1;\n"));
+    let opts = DotOptions { grids: true, pushes: true };
+    let labeled = graph.to_dot(&opts);
+    assert!(labeled.contains("label=\"#1\\l|O*.|\\l|##.|\\l|##.|\\l\""));
+    assert!(labeled.contains("  s0 -> s1 [label=\"0 Right\"];\n"));
+  }
+}
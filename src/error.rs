@@ -0,0 +1,40 @@
+use std::io;
+
+use thiserror::Error;
+
+use crate::grid::Dims;
+use crate::state_graph::StateId;
+use crate::validate::Problem;
+
+// Misuse of the board and graph APIs that would otherwise panic. Each has an
+// `_unchecked` variant that panics instead, for hot paths whose input is
+// known to be good.
+#[derive(Clone, Debug, Eq, Error, PartialEq)]
+pub enum Error {
+  #[error("cell {idx} is off a board of {len} cells")]
+  OutOfBounds { idx: usize, len: usize },
+  #[error("({row}, {col}) is off a {}x{} board", dims.rows, dims.cols)]
+  OffBoard { row: usize, col: usize, dims: Dims },
+  #[error("{cells} cells don't make a {}x{} board", dims.rows, dims.cols)]
+  DimsMismatch { cells: usize, dims: Dims },
+  #[error("the tractor can't stand on cell {0}")]
+  NotFloor(usize),
+  #[error("the state is already in the graph as {0:?}")]
+  DuplicateState(StateId),
+  #[error("the state isn't in the graph")]
+  UnknownState,
+  #[error("the state store already holds states")]
+  StoreNotEmpty,
+  #[error("the level has no tractor")]
+  NoTractor,
+  #[error("only the default rules explore more than one tractor")]
+  RulesNeedOneTractor,
+  #[error("the level isn't sound: {}", .0.iter().map(ToString::to_string).collect::<Vec<_>>().join(", "))]
+  InvalidLevel(Vec<Problem>),
+}
+
+impl From<Error> for io::Error {
+  fn from(err: Error) -> Self {
+    io::Error::new(io::ErrorKind::InvalidInput, err)
+  }
+}
@@ -0,0 +1,199 @@
+use rand::{seq::SliceRandom, Rng, SeedableRng};
+use rand_pcg::Pcg64;
+
+use crate::cell::Cell;
+use crate::difficulty::{measure, LevelMetrics};
+use crate::generator::{generate_by_pulling, GenConfig};
+use crate::level::Level;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct EvolveConfig {
+  pub population: usize,
+  pub generations: usize,
+  // The fittest levels carried over unchanged into each generation
+  pub elite: usize,
+  // Chance a child splices two parents rather than copying one
+  pub crossover_rate: f64,
+}
+
+impl Default for EvolveConfig {
+  fn default() -> Self {
+    EvolveConfig { population: 20, generations: 10, elite: 2, crossover_rate: 0.5 }
+  }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Mutation {
+  // A block moves to an empty cell
+  MoveBlock,
+  // A new boulder and a new hole for it
+  AddBoulder,
+  // A hole moves to an empty cell
+  SwapHole,
+}
+
+static MUTATIONS: &[Mutation] = &[Mutation::MoveBlock, Mutation::AddBoulder, Mutation::SwapHole];
+
+// Breeds levels, starting from ones generated by pulling with `gen`. Each
+// child comes from tournament selection, maybe crossover, then one mutation,
+// and only solvable children are kept. Returns the fittest level seen with
+// its fitness.
+pub fn evolve<F>(gen: &GenConfig, config: &EvolveConfig, fitness: F) -> Option<(Level, f64)>
+where
+  F: Fn(&LevelMetrics) -> f64,
+{
+  let mut rng = Pcg64::seed_from_u64(gen.seed);
+  let evaluate = |level: Level| measure(&level).map(|metrics| (level, fitness(&metrics)));
+  let longest = 4 * gen.size * gen.boulders.max(1);
+  let mut population = vec![];
+  for _ in 0..config.population * 10 {
+    if population.len() == config.population {
+      break;
+    }
+    let pulls = rng.gen_range(1..=longest);
+    let single = GenConfig { seed: rng.gen(), max_attempts: 1, ..gen.clone() };
+    if let Some(scored) = generate_by_pulling(&single, pulls).and_then(evaluate) {
+      population.push(scored);
+    }
+  }
+  for _ in 0..config.generations {
+    if population.is_empty() {
+      break;
+    }
+    population.sort_by(|a, b| b.1.total_cmp(&a.1));
+    let mut next = population.iter().take(config.elite).cloned().collect::<Vec<_>>();
+    for _ in 0..config.population * 20 {
+      if next.len() >= config.population {
+        break;
+      }
+      let mut child = tournament(&population, &mut rng).clone();
+      if rng.gen_bool(config.crossover_rate) {
+        child = crossover(&child, tournament(&population, &mut rng), &mut rng);
+      }
+      mutate(&mut child, *MUTATIONS.choose(&mut rng).unwrap(), &mut rng);
+      if let Some(scored) = evaluate(child) {
+        next.push(scored);
+      }
+    }
+    population = next;
+  }
+  population.into_iter().max_by(|a, b| a.1.total_cmp(&b.1))
+}
+
+// The fitter of two random levels
+fn tournament<'a, R: Rng>(population: &'a [(Level, f64)], rng: &mut R) -> &'a Level {
+  let a = population.choose(rng).unwrap();
+  let b = population.choose(rng).unwrap();
+  if a.1 >= b.1 { &a.0 } else { &b.0 }
+}
+
+// Empty cells other than the tractor's
+fn empty_cells(level: &Level) -> Vec<usize> {
+  (0..level.grid.len())
+    .filter(|idx| *idx != level.tractor && level.grid[*idx] == Cell::Unreachable)
+    .collect()
+}
+
+pub fn mutate<R: Rng>(level: &mut Level, mutation: Mutation, rng: &mut R) {
+  let empty = empty_cells(level);
+  let find = |cells: &[Cell]| {
+    (0..level.grid.len()).filter(|idx| cells.contains(&level.grid[*idx])).collect::<Vec<usize>>()
+  };
+  match mutation {
+    Mutation::MoveBlock => {
+      let blocks = find(&[Cell::Block]);
+      if let (Some(from), Some(to)) = (blocks.choose(rng), empty.choose(rng)) {
+        level.grid.swap(*from, *to);
+      }
+    },
+    Mutation::AddBoulder => {
+      let picked = empty.choose_multiple(rng, 2).cloned().collect::<Vec<usize>>();
+      if let [boulder, hole] = picked[..] {
+        level.grid[boulder] = Cell::Boulder;
+        level.grid[hole] = Cell::Hole;
+      }
+    },
+    Mutation::SwapHole => {
+      let holes = find(&[Cell::Hole]);
+      if let (Some(from), Some(to)) = (holes.choose(rng), empty.choose(rng)) {
+        level.grid.swap(*from, *to);
+      }
+    },
+  }
+}
+
+// `a` with a random rectangle of cells copied over from `b`. The tractor stays
+// where it was in `a`, children that don't add up fail to measure and are
+// dropped.
+pub fn crossover<R: Rng>(a: &Level, b: &Level, rng: &mut R) -> Level {
+  let dims = a.dims;
+  let (top, bottom) = span(dims.rows, rng);
+  let (left, right) = span(dims.cols, rng);
+  let mut child = a.clone();
+  for row in top..bottom {
+    for col in left..right {
+      let idx = dims.index(row, col);
+      if idx != a.tractor {
+        child.grid[idx] = b.grid[idx];
+      }
+    }
+  }
+  child
+}
+
+fn span<R: Rng>(len: usize, rng: &mut R) -> (usize, usize) {
+  let start = rng.gen_range(0..len);
+  (start, rng.gen_range(start + 1..=len))
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::grid::Dims;
+
+  fn count(level: &Level, cell: Cell) -> usize {
+    level.grid.iter().filter(|c| **c == cell).count()
+  }
+
+  #[test]
+  fn test_mutations_keep_counts_balanced() {
+    let mut rng = Pcg64::seed_from_u64(1);
+    let mut grid = vec![Cell::Unreachable; 16];
+    grid[0] = Cell::Hole;
+    grid[5] = Cell::Boulder;
+    grid[10] = Cell::Block;
+    let mut level = Level::new(grid, Dims::square(4), 15);
+    for _ in 0..20 {
+      for mutation in MUTATIONS {
+        mutate(&mut level, *mutation, &mut rng);
+        assert_eq!(count(&level, Cell::Hole), count(&level, Cell::Boulder));
+        assert_eq!(count(&level, Cell::Block), 1);
+        assert_eq!(level.grid[15], Cell::Unreachable);
+      }
+    }
+  }
+
+  #[test]
+  fn test_crossover_keeps_tractor_cell() {
+    let mut rng = Pcg64::seed_from_u64(2);
+    let a = Level::new(vec![Cell::Unreachable; 9], Dims::square(3), 4);
+    let b = Level::new(vec![Cell::Block; 9], Dims::square(3), 0);
+    for _ in 0..10 {
+      let child = crossover(&a, &b, &mut rng);
+      assert_eq!(child.grid[4], Cell::Unreachable);
+      assert!(count(&child, Cell::Block) >= 1);
+    }
+  }
+
+  #[test]
+  fn test_evolve_never_gets_worse() {
+    let gen = GenConfig { size: 4, boulders: 1, wall_density: 0.125, seed: 5, ..Default::default() };
+    let fitness = |metrics: &LevelMetrics| metrics.pushes as f64;
+    let start = EvolveConfig { population: 6, generations: 0, ..Default::default() };
+    let evolved = EvolveConfig { generations: 4, ..start.clone() };
+    let (_, before) = evolve(&gen, &start, fitness).unwrap();
+    let (level, after) = evolve(&gen, &evolved, fitness).unwrap();
+    assert!(after >= before);
+    assert_eq!(measure(&level).unwrap().pushes as f64, after);
+  }
+}
@@ -4,40 +4,42 @@ use rand::prelude::*;
 use serde::{Deserialize, Serialize};
 
 use crate::Cell;
-use crate::state_graph::StateGraph;
+use crate::grid::Dims;
+use crate::state_graph::{StateGraph, StateId};
 use crate::shortest_path::*;
 
 #[derive(Deserialize, Serialize)]
 pub struct StateGraphExplorer {
   graph: StateGraph,
-  dist: Vec<Vec<usize>>,
+  dist: Vec<Vec<StateId>>,
   shortest: ShortestGraph,
-  size: usize,
-  visited: HashSet<usize>,
-  saved: Vec<usize>,
-  history: Vec<usize>,
+  dims: Dims,
+  visited: HashSet<StateId>,
+  saved: Vec<StateId>,
+  history: Vec<StateId>,
 }
 
 impl StateGraphExplorer {
-  pub fn new(graph: StateGraph, size: usize) -> Self {
-    let shortest = graph.build_shortest_path_from(&0);
+  pub fn new(graph: StateGraph, dims: Dims) -> Self {
+    let root = graph.root();
+    let shortest = graph.build_shortest_path_from(&root);
     let dist = shortest.build_dist();
     StateGraphExplorer {
       graph,
       dist,
       shortest,
-      size,
+      dims,
       visited: {
         let mut visited = HashSet::new();
-        visited.insert(0);
+        visited.insert(root);
         visited
       },
       saved: vec![],
-      history: vec![0],
+      history: vec![root],
     }
   }
   // Node
-  pub fn jump_to_node(&mut self, id: usize) -> bool {
+  pub fn jump_to_node(&mut self, id: StateId) -> bool {
     if !self.graph.contains_id(&id) {
       return false;
     }
@@ -46,7 +48,7 @@ impl StateGraphExplorer {
     true
   }
   pub fn jump_to_random_node(&mut self) {
-    assert!(self.jump_to_node(rand::thread_rng().gen_range(0..self.graph.len())));
+    assert!(self.jump_to_node(StateId::new(rand::thread_rng().gen_range(0..self.graph.len()))));
   }
   pub fn jump_to_random_node_with_depth(&mut self, depth: usize) -> bool {
     if let Some(states) = self.dist.get(depth) {
@@ -56,12 +58,10 @@ impl StateGraphExplorer {
     }
     false
   }
-  pub fn save_node(&mut self, id: usize) -> bool {
-    if self.graph.contains_id(&id) {
-      if self.saved.iter().all(|i| *i != id) {
-        self.saved.push(id);
-        return true;
-      }
+  pub fn save_node(&mut self, id: StateId) -> bool {
+    if self.graph.contains_id(&id) && self.saved.iter().all(|i| *i != id) {
+      self.saved.push(id);
+      return true;
     }
     false
   }
@@ -70,18 +70,13 @@ impl StateGraphExplorer {
       self.history.pop();
       return true;
     }
-    return false;
+    false
   }
   // Getters
-  pub fn get_neighbor_id(&self, idx: &usize) -> Option<usize> {
-    if let Some(id) = self.history.last() {
-      if let Some(neighbors) = self.graph.get_neighbors(id) {
-        return neighbors.get(*idx).cloned()
-      }
-    }
-    None
+  pub fn get_neighbor_id(&self, idx: &usize) -> Option<StateId> {
+    self.history.last().and_then(|id| self.graph.neighbors(id).nth(*idx))
   }
-  pub fn get_saved_id(&self, idx: &usize) -> Option<usize> {
+  pub fn get_saved_id(&self, idx: &usize) -> Option<StateId> {
     self.saved.get(*idx).cloned()
   }
   pub fn get_longest_path(&self) -> usize {
@@ -92,13 +87,13 @@ impl StateGraphExplorer {
     if let Some(id) = self.history.last() {
       if let Some(state) = self.graph.get_state(id) {
         print!("+");
-        for _ in 0..self.size {
+        for _ in 0..self.dims.cols {
           print!("-");
         }
         println!("+");
         let mut tractor = true;
         for (idx, cell) in state.iter().enumerate() {
-          let col = idx % self.size;
+          let col = idx % self.dims.cols;
           if col == 0 {
             print!("|");
           }
@@ -109,17 +104,17 @@ impl StateGraphExplorer {
             } else {
               print!("{}", Cell::Unreachable.to_char());
             }
-          } else if cell == &Cell::BoulderInHole {
+          } else if cell.has_boulder() && !cell.is_loose_boulder() {
             print!("{}", Cell::Block.to_char());
           } else {
             print!("{}", cell.to_char());
           }
-          if col == self.size - 1 {
+          if col == self.dims.cols - 1 {
             println!("|");
           }
         }
         print!("+");
-        for _ in 0..self.size {
+        for _ in 0..self.dims.cols {
           print!("-");
         }
         println!("+");
@@ -147,7 +142,8 @@ impl StateGraphExplorer {
   }
   pub fn print_path_to_root(&self) {
     if let Some(id) = self.history.last() {
-      if let Some(path) = self.shortest.path(id) {
+      if let Some(mut path) = self.graph.bidirectional_shortest_path(&self.graph.root(), id) {
+        path.reverse();
         for (idx, id) in path.iter().enumerate() {
           self.print_neighbor_state(id, idx);
         }
@@ -164,31 +160,29 @@ impl StateGraphExplorer {
       self.print_neighbor_state(id, idx);
     }
   }
-  fn print_node(&self, id: &usize) {
+  fn print_node(&self, id: &StateId) {
     println!("Current Node:");
     self.print_current_state(id);
     println!("Neighbors:");
-    if let Some(neighbors) = self.graph.get_neighbors(id) {
-      for (idx, neighbor) in neighbors.iter().enumerate() {
-        self.print_neighbor_state(neighbor, idx);
-      }
+    for (idx, neighbor) in self.graph.neighbors(id).enumerate() {
+      self.print_neighbor_state(&neighbor, idx);
     }
   }
-  fn print_neighbor_state(&self, id: &usize, idx: usize) {
+  fn print_neighbor_state(&self, id: &StateId, idx: usize) {
     if let Some(state) = self.graph.get_state(id) {
       print!("-  +");
-      for _ in 0..(self.size) {
+      for _ in 0..(self.dims.cols) {
         print!("-");
       }
-      println!("+ [{}] #{}", idx, id);
+      println!("+ [{}] {}", idx, id);
       for (idx, cell) in state.iter().enumerate() {
-        let row = idx / self.size;
-        let col = idx % self.size;
+        let row = idx / self.dims.cols;
+        let col = idx % self.dims.cols;
         if col == 0 {
           print!("   |");
         }
         print!("{}", cell.to_char());
-        if col == self.size - 1 {
+        if col == self.dims.cols - 1 {
           print!("|");
           if row == 0 {
             if let Some(depth) = self.shortest.depth(id) {
@@ -214,28 +208,28 @@ impl StateGraphExplorer {
         }
       }
       print!("   +");
-      for _ in 0..(self.size) {
+      for _ in 0..(self.dims.cols) {
         print!("-");
       }
       println!("+");
     }
   }
-  fn print_current_state(&self, id: &usize) {
+  fn print_current_state(&self, id: &StateId) {
     if let Some(state) = self.graph.get_state(id) {
       print!("+");
-      for _ in 0..(self.size) {
+      for _ in 0..(self.dims.cols) {
         print!("-");
       }
       print!("+ ");
-      println!("#{}", id);
+      println!("{}", id);
       for (idx, cell) in state.iter().enumerate() {
-        let row = idx / self.size;
-        let col = idx % self.size;
+        let row = idx / self.dims.cols;
+        let col = idx % self.dims.cols;
         if col == 0 {
           print!("|");
         }
         print!("{}", cell.to_char());
-        if col == self.size - 1 {
+        if col == self.dims.cols - 1 {
           print!("|");
           if row == 0 {
             if let Some(depth) = self.shortest.depth(id) {
@@ -261,7 +255,7 @@ impl StateGraphExplorer {
         }
       }
       print!("+");
-      for _ in 0..(self.size) {
+      for _ in 0..(self.dims.cols) {
         print!("-");
       }
       println!("+");
@@ -0,0 +1,162 @@
+use crate::cell::Cell;
+use crate::direction::DIRECTIONS;
+use crate::grid::Dims;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum RegionKind {
+  // Floor where a 2x2 block of it fits
+  Room,
+  // Floor one cell wide
+  Corridor,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Region {
+  pub kind: RegionKind,
+  // In increasing order
+  pub cells: Vec<usize>,
+}
+
+// The shape of a board's floor, everything but blocks, walls and collapsed
+// cells, whatever is standing on it. Neighbouring cells of one kind make up
+// a region.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FloorPlan {
+  // Ordered by their first cell
+  pub regions: Vec<Region>,
+  // The region of each cell, `None` off the floor
+  pub labels: Vec<Option<usize>>,
+  // Floor cells that would split the floor in two if they were blocked, in
+  // increasing order
+  pub choke_points: Vec<usize>,
+}
+
+impl FloorPlan {
+  pub fn region_of(&self, idx: usize) -> Option<&Region> {
+    self.labels.get(idx).cloned().flatten().map(|label| &self.regions[label])
+  }
+  pub fn rooms(&self) -> usize {
+    self.count(RegionKind::Room)
+  }
+  pub fn corridors(&self) -> usize {
+    self.count(RegionKind::Corridor)
+  }
+  fn count(&self, kind: RegionKind) -> usize {
+    self.regions.iter().filter(|region| region.kind == kind).count()
+  }
+}
+
+fn is_floor(cell: Cell) -> bool {
+  !matches!(cell, Cell::Block | Cell::Wall | Cell::Collapsed)
+}
+
+pub fn floor_plan(grid: &[Cell], dims: Dims) -> FloorPlan {
+  let floor = grid.iter().map(|cell| is_floor(*cell)).collect::<Vec<bool>>();
+  let mut kinds = floor.iter().map(|floor| floor.then_some(RegionKind::Corridor)).collect::<Vec<_>>();
+  for row in 1..dims.rows {
+    for col in 1..dims.cols {
+      let square = [dims.index(row - 1, col - 1), dims.index(row - 1, col), dims.index(row, col - 1), dims.index(row, col)];
+      if square.iter().all(|idx| floor[*idx]) {
+        for idx in square {
+          kinds[idx] = Some(RegionKind::Room);
+        }
+      }
+    }
+  }
+  let mut regions = vec![];
+  let mut labels = vec![None; grid.len()];
+  for start in 0..grid.len() {
+    let kind = match kinds[start] {
+      Some(kind) if labels[start].is_none() => kind,
+      _ => continue,
+    };
+    labels[start] = Some(regions.len());
+    let mut cells = vec![start];
+    let mut stack = vec![start];
+    while let Some(current) = stack.pop() {
+      for next in DIRECTIONS.iter().filter_map(|dir| dir.apply(current, dims)) {
+        if kinds[next] == Some(kind) && labels[next].is_none() {
+          labels[next] = Some(regions.len());
+          cells.push(next);
+          stack.push(next);
+        }
+      }
+    }
+    cells.sort_unstable();
+    regions.push(Region { kind, cells });
+  }
+  FloorPlan { regions, labels, choke_points: cut_cells(&floor, dims) }
+}
+
+// The articulation points of the floor, found by a depth-first search that
+// tracks the earliest cell each subtree reaches back to
+fn cut_cells(floor: &[bool], dims: Dims) -> Vec<usize> {
+  let mut found = vec![usize::MAX; floor.len()];
+  let mut low = vec![0; floor.len()];
+  let mut cut = vec![false; floor.len()];
+  let mut time = 0;
+  for root in (0..floor.len()).filter(|idx| floor[*idx]) {
+    if found[root] != usize::MAX {
+      continue;
+    }
+    found[root] = time;
+    low[root] = time;
+    time += 1;
+    let mut children = 0;
+    // Each cell on the search path, its parent, and the next direction to try
+    let mut stack = vec![(root, None::<usize>, 0)];
+    while let Some((current, parent, next)) = stack.last().cloned() {
+      if next == DIRECTIONS.len() {
+        stack.pop();
+        if let Some(parent) = parent {
+          low[parent] = low[parent].min(low[current]);
+          if parent != root && low[current] >= found[parent] {
+            cut[parent] = true;
+          }
+        }
+        continue;
+      }
+      stack.last_mut().unwrap().2 += 1;
+      let neighbor = match DIRECTIONS[next].apply(current, dims).filter(|idx| floor[*idx]) {
+        Some(neighbor) => neighbor,
+        None => continue,
+      };
+      if found[neighbor] == usize::MAX {
+        found[neighbor] = time;
+        low[neighbor] = time;
+        time += 1;
+        if current == root {
+          children += 1;
+        }
+        stack.push((neighbor, Some(current), 0));
+      } else if Some(neighbor) != parent {
+        low[current] = low[current].min(found[neighbor]);
+      }
+    }
+    if children > 1 {
+      cut[root] = true;
+    }
+  }
+  (0..floor.len()).filter(|idx| cut[*idx]).collect()
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn test_floor_plan() {
+    // Two rooms joined by a corridor under a wall, and a dead end off the
+    // right room
+    let grid = "..X..#\n......".lines()
+      .flat_map(|line| line.chars().map(|c| Cell::try_from_char(c).unwrap()))
+      .collect::<Vec<Cell>>();
+    let plan = floor_plan(&grid, Dims::new(2, 6));
+    assert_eq!((plan.rooms(), plan.corridors()), (2, 2));
+    assert_eq!(plan.region_of(8), Some(&Region { kind: RegionKind::Corridor, cells: vec![8] }));
+    assert_eq!(plan.region_of(11).unwrap().cells, vec![11]);
+    assert_eq!(plan.region_of(0).unwrap().cells, vec![0, 1, 6, 7]);
+    assert_eq!(plan.region_of(2), None);
+    assert_eq!(plan.choke_points, vec![7, 8, 9, 10]);
+  }
+}
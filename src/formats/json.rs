@@ -0,0 +1,107 @@
+use serde::{Deserialize, Serialize};
+
+use crate::cell::Cell;
+use crate::grid::Dims;
+use crate::level::{Level, Metadata};
+
+// Bumped whenever a change would stop older readers understanding a file
+pub const VERSION: u32 = 1;
+
+// The interchange form of a level for other tools, kept apart from the types
+// it's built from so their serde can change freely. Each row of `cells` is a
+// string of the usual cell characters, with the tractor's start given by
+// `tractor` rather than drawn.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct JsonLevel {
+  pub version: u32,
+  pub rows: usize,
+  pub cols: usize,
+  pub cells: Vec<String>,
+  pub tractor: Position,
+  #[serde(default)]
+  pub metadata: Metadata,
+}
+
+#[derive(Copy, Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct Position {
+  pub row: usize,
+  pub col: usize,
+}
+
+impl JsonLevel {
+  pub fn new(level: &Level) -> Self {
+    let cells = level.grid.chunks(level.dims.cols)
+      .map(|row| row.iter()
+        .map(|cell| if *cell == Cell::Reachable { Cell::Unreachable } else { *cell })
+        .map(Cell::to_char)
+        .collect())
+      .collect();
+    let tractor = Position { row: level.dims.row(level.tractor), col: level.dims.col(level.tractor) };
+    let metadata = level.metadata.clone();
+    JsonLevel { version: VERSION, rows: level.dims.rows, cols: level.dims.cols, cells, tractor, metadata }
+  }
+  pub fn to_level(&self) -> Result<Level, String> {
+    if self.version > VERSION {
+      return Err(format!("version {} is newer than {}", self.version, VERSION));
+    }
+    if self.cells.len() != self.rows {
+      return Err(format!("{} rows, expected {}", self.cells.len(), self.rows));
+    }
+    let mut grid = vec![];
+    for row in &self.cells {
+      if row.chars().count() != self.cols {
+        return Err(format!("row `{}` is not {} cells wide", row, self.cols));
+      }
+      for c in row.chars() {
+        grid.push(Cell::try_from_char(c).ok_or_else(|| format!("unrecognized character `{}`", c))?);
+      }
+    }
+    let dims = Dims::new(self.rows, self.cols);
+    if dims.is_empty() {
+      return Err("empty board".into());
+    }
+    if self.tractor.row >= self.rows || self.tractor.col >= self.cols {
+      return Err("tractor off the board".into());
+    }
+    Ok(Level::new(grid, dims, dims.index(self.tractor.row, self.tractor.col)).with_metadata(self.metadata.clone()))
+  }
+}
+
+pub fn parse(text: &str) -> Result<Level, String> {
+  let json: JsonLevel = serde_json::from_str(text).map_err(|err| err.to_string())?;
+  json.to_level()
+}
+
+pub fn to_string(level: &Level) -> String {
+  serde_json::to_string_pretty(&JsonLevel::new(level)).unwrap()
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn test_round_trip() {
+    let grid = vec![
+      Cell::Unreachable, Cell::Boulder, Cell::Hole,
+      Cell::Wall, Cell::Ice, Cell::BoulderInHole,
+    ];
+    let metadata = Metadata { title: Some("Two rows".into()), seed: Some(7), ..Metadata::default() };
+    let level = Level::new(grid, Dims::new(2, 3), 0).with_metadata(metadata);
+    let text = to_string(&level);
+    assert_eq!(parse(&text), Ok(level));
+  }
+
+  #[test]
+  fn test_schema() {
+    let text = r#"{"version":1,"rows":1,"cols":3,"cells":[" *O"],"tractor":{"row":0,"col":0}}"#;
+    let level = parse(text).unwrap();
+    assert_eq!(level.grid, vec![Cell::Unreachable, Cell::Boulder, Cell::Hole]);
+    assert_eq!(level.metadata, Metadata::default());
+    let written = serde_json::to_string(&JsonLevel::new(&level)).unwrap();
+    assert_eq!(written, text.replace("}}", "},\"metadata\":{}}"));
+    assert!(parse(&text.replace("\"version\":1", "\"version\":2")).is_err());
+    assert!(parse(&text.replace(" *O", " *")).is_err());
+    assert!(parse(&text.replace("\"col\":0", "\"col\":3")).is_err());
+  }
+}
@@ -0,0 +1,242 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::compression;
+use crate::level::Level;
+use crate::render::text::level_to_string;
+use crate::template::Template;
+
+// Level formats shared with other tools
+pub mod json;
+pub mod rle;
+pub mod tiled;
+pub mod xsb;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Format {
+  // The format the generator writes
+  Boxed,
+  Xsb,
+  Rle,
+  Json,
+  Tmx,
+}
+
+pub static FORMAT_NAMES: &[&str] = &["boxed", "xsb", "rle", "json", "tmx"];
+
+impl Format {
+  pub fn from_name(name: &str) -> Option<Self> {
+    Some(match name {
+      "boxed" => Format::Boxed,
+      "xsb" => Format::Xsb,
+      "rle" => Format::Rle,
+      "json" => Format::Json,
+      "tmx" => Format::Tmx,
+      _ => return None,
+    })
+  }
+  pub fn extension(self) -> &'static str {
+    match self {
+      Format::Boxed => "txt",
+      Format::Xsb => "xsb",
+      Format::Rle => "rle",
+      Format::Json => "json",
+      Format::Tmx => "tmx",
+    }
+  }
+  // Whether one file can hold many levels
+  pub fn holds_collections(self) -> bool {
+    self != Format::Tmx
+  }
+  pub fn write(self, level: &Level) -> Result<String, String> {
+    match self {
+      Format::Boxed => Ok(level_to_string(level)),
+      Format::Xsb => xsb::to_string(level),
+      Format::Rle => rle::to_string(level).map(|rle| rle + "\n"),
+      Format::Json => Ok(json::to_string(level) + "\n"),
+      Format::Tmx => Ok(tiled::to_tmx(level, &tiled::TiledOptions::default())),
+    }
+  }
+  // Several levels in one file the way `load_collection` reads them back:
+  // xsb levels a blank line apart, RLE one per line and JSON as an array
+  pub fn write_collection(self, levels: &[Level]) -> Result<String, String> {
+    match self {
+      Format::Json => {
+        let levels = levels.iter().map(json::JsonLevel::new).collect::<Vec<_>>();
+        Ok(serde_json::to_string_pretty(&levels).unwrap() + "\n")
+      },
+      Format::Tmx if levels.len() != 1 => Err("a TMX map holds a single level".into()),
+      Format::Xsb => Ok(levels.iter().map(xsb::to_string).collect::<Result<Vec<String>, String>>()?.join("\n")),
+      _ => Ok(levels.iter().map(|level| self.write(level)).collect::<Result<Vec<String>, String>>()?.concat()),
+    }
+  }
+}
+
+// Reads a level in any format this crate knows, told apart by how it starts:
+// JSON, TMX, the boxed text format, one line of RLE, or xsb
+pub fn parse_level(text: &str) -> Result<Level, String> {
+  let trimmed = text.trim();
+  if trimmed.starts_with('{') {
+    json::parse(text)
+  } else if trimmed.starts_with('<') {
+    tiled::parse_tmx(text, &tiled::TileIds::default())
+  } else if trimmed.starts_with('+') || trimmed.starts_with('|') {
+    parse_boxed(text)
+  } else if !trimmed.contains('\n') && trimmed.contains('|') {
+    rle::parse(trimmed)
+  } else {
+    xsb::parse(text)
+  }
+}
+
+// The boxed format the generator writes, `.` marking the tractor
+fn parse_boxed(text: &str) -> Result<Level, String> {
+  let template = Template::parse(text)?;
+  if !template.wildcards().is_empty() {
+    return Err("a level can't have `?` cells".into());
+  }
+  let tractor = template.tractor.ok_or("no tractor")?;
+  Ok(Level::new(template.cells.into_iter().flatten().collect(), template.dims, tractor))
+}
+
+// Like `parse_level` for a file, compressed or not
+pub fn load_level(path: &Path) -> io::Result<Level> {
+  let text = compression::read_to_string(path)?;
+  parse_level(&text).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+// Every level of a collection: boxed or xsb levels one after the other, RLE
+// one per line or a JSON array. Anything else holds a single level.
+pub fn parse_collection(text: &str) -> Result<Vec<Level>, String> {
+  let trimmed = text.trim();
+  let lines = trimmed.lines().map(str::trim).filter(|line| !line.is_empty()).collect::<Vec<&str>>();
+  if trimmed.starts_with('+') {
+    let mut levels = vec![];
+    let mut rows = vec![];
+    for line in lines {
+      rows.push(line);
+      // A box is closed by its second border
+      if rows.len() > 1 && line.starts_with('+') {
+        levels.push(parse_boxed(&rows.join("\n"))?);
+        rows.clear();
+      }
+    }
+    if !rows.is_empty() {
+      return Err("the last level isn't closed".into());
+    }
+    Ok(levels)
+  } else if trimmed.starts_with('[') {
+    let levels: Vec<json::JsonLevel> = serde_json::from_str(text).map_err(|err| err.to_string())?;
+    levels.iter().map(json::JsonLevel::to_level).collect()
+  } else if lines.len() > 1 && lines.iter().all(|line| line.contains('|') && !line.starts_with(&['+', '|'][..])) {
+    lines.iter().map(|line| rle::parse(line)).collect()
+  } else if !trimmed.starts_with(&['{', '<'][..]) && !trimmed.contains('|') {
+    // Only the boxed and RLE formats use `|`
+    xsb::parse_collection(text)
+  } else {
+    parse_level(text).map(|level| vec![level])
+  }
+}
+
+pub fn load_collection(path: &Path) -> io::Result<Vec<Level>> {
+  let text = compression::read_to_string(path)?;
+  parse_collection(&text).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+// What `load_paths` found
+#[derive(Debug)]
+pub struct LoadedLevels {
+  pub levels: Vec<(String, Level)>,
+  // Files in directories that hold no level, with why
+  pub skipped: Vec<(PathBuf, io::Error)>,
+}
+
+// Every level under `paths`, named after its file and, in collections, its
+// place there. Files in a directory that hold no level are skipped, and
+// returned for the caller to warn about.
+pub fn load_paths<P: AsRef<Path>>(paths: &[P]) -> io::Result<LoadedLevels> {
+  let mut levels = vec![];
+  let mut skipped = vec![];
+  for path in paths.iter().map(AsRef::as_ref) {
+    let files = if path.is_dir() {
+      let mut files = fs::read_dir(path)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<io::Result<Vec<PathBuf>>>()?;
+      files.retain(|file| file.is_file());
+      files.sort();
+      files
+    } else {
+      vec![path.to_path_buf()]
+    };
+    for file in files {
+      let collection = match load_collection(&file) {
+        Ok(collection) => collection,
+        Err(err) if path.is_dir() => {
+          skipped.push((file, err));
+          continue;
+        },
+        Err(err) => return Err(err),
+      };
+      let count = collection.len();
+      for (n, level) in collection.into_iter().enumerate() {
+        let name = if count == 1 {
+          file.display().to_string()
+        } else {
+          format!("{}#{}", file.display(), n + 1)
+        };
+        levels.push((name, level));
+      }
+    }
+  }
+  Ok(LoadedLevels { levels, skipped })
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::cell::Cell;
+  use crate::grid::Dims;
+
+  #[test]
+  fn test_parse_level() {
+    let boxed = parse_level("+---+\n|.*O|\n+---+\n").unwrap();
+    assert_eq!(boxed, Level::new(vec![Cell::Unreachable, Cell::Boulder, Cell::Hole], Dims::new(1, 3), 0));
+    assert_eq!(parse_level("#####\n#@$.#\n#####\n").unwrap(), boxed);
+    assert_eq!(parse_level("5#|#@$.#|5#\n").unwrap(), boxed);
+    assert_eq!(parse_level(&json::to_string(&boxed)).unwrap(), boxed);
+    assert!(parse_level("+---+\n|.*?|\n+---+\n").is_err());
+    assert!(parse_level("+---+\n| *O|\n+---+\n").is_err());
+  }
+
+  #[test]
+  fn test_collections() {
+    let levels = vec![
+      Level::new(vec![Cell::Unreachable, Cell::Boulder, Cell::Hole], Dims::new(1, 3), 0),
+      Level::new(vec![Cell::Hole, Cell::Boulder, Cell::Unreachable, Cell::Unreachable], Dims::new(1, 4), 3),
+    ];
+    for name in FORMAT_NAMES {
+      let format = Format::from_name(name).unwrap();
+      if format.holds_collections() {
+        let text = format.write_collection(&levels).unwrap();
+        assert_eq!(parse_collection(&text).unwrap(), levels, "{}", name);
+      }
+      assert_eq!(parse_level(&format.write(&levels[1]).unwrap()).unwrap(), levels[1], "{}", name);
+    }
+    assert!(Format::Tmx.write_collection(&levels).is_err());
+  }
+
+  #[test]
+  fn test_load_paths() {
+    let dir = std::env::temp_dir().join("lvlgen_test_load_paths");
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("a.txt"), "+---+\n|.*O|\n+---+\n").unwrap();
+    fs::write(dir.join("notes.txt"), [0xff]).unwrap();
+    let loaded = load_paths(&[&dir]).unwrap();
+    assert_eq!(loaded.levels.len(), 1);
+    assert_eq!(loaded.skipped.len(), 1);
+    assert_eq!(loaded.skipped[0].0, dir.join("notes.txt"));
+    assert!(load_paths(&[dir.join("notes.txt")]).is_err());
+    fs::remove_dir_all(&dir).unwrap();
+  }
+}
@@ -0,0 +1,109 @@
+use crate::formats::xsb;
+use crate::level::Level;
+
+// The run-length encoded form of the xsb text that some collections use: a
+// count before a cell repeats it, `|` ends a row, and floor is written `-`
+// so the whole level fits on one line, as in `5#|#@$.#|5#`.
+pub fn parse(text: &str) -> Result<Level, String> {
+  xsb::parse(&expand(text.trim())?)
+}
+
+// Wider than any board is meant to be, so that a stray count can't expand
+// into gigabytes
+const MAX_WIDTH: usize = 1000;
+
+fn expand(text: &str) -> Result<String, String> {
+  let too_wide = || format!("row wider than {} cells", MAX_WIDTH);
+  let mut expanded = String::new();
+  let mut count = None;
+  let mut width = 0;
+  for c in text.chars() {
+    match c {
+      '0'..='9' => {
+        let digit = c.to_digit(10).unwrap() as usize;
+        let run = count.unwrap_or(0usize).checked_mul(10).and_then(|run| run.checked_add(digit));
+        count = Some(run.filter(|run| *run <= MAX_WIDTH).ok_or_else(too_wide)?);
+      },
+      '|' | '\n' if count.is_some() => return Err(format!("count before `{}`", c)),
+      '|' | '\n' => {
+        expanded.push('\n');
+        width = 0;
+      },
+      c => {
+        let run = count.take().unwrap_or(1);
+        width += run;
+        if width > MAX_WIDTH {
+          return Err(too_wide());
+        }
+        for _ in 0..run {
+          expanded.push(c);
+        }
+      },
+    }
+  }
+  match count {
+    Some(_) => Err("count at the end".into()),
+    None => Ok(expanded),
+  }
+}
+
+pub fn to_string(level: &Level) -> Result<String, String> {
+  let text = xsb::to_string(level)?;
+  let rows = text.lines()
+    .map(|line| compress(&line.replace(' ', "-")))
+    .collect::<Vec<String>>();
+  Ok(rows.join("|"))
+}
+
+fn compress(row: &str) -> String {
+  let mut compressed = String::new();
+  let mut chars = row.chars().peekable();
+  while let Some(c) = chars.next() {
+    let mut run = 1;
+    while chars.peek() == Some(&c) {
+      chars.next();
+      run += 1;
+    }
+    if run > 1 {
+      compressed.push_str(&run.to_string());
+    }
+    compressed.push(c);
+  }
+  compressed
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::cell::Cell;
+  use crate::grid::Dims;
+
+  #[test]
+  fn test_parse() {
+    let level = parse("5#|#@$.#|5#").unwrap();
+    assert_eq!(level.dims, Dims::new(1, 3));
+    assert_eq!(level.grid, vec![Cell::Unreachable, Cell::Boulder, Cell::Hole]);
+    assert_eq!(level.tractor, 0);
+    assert_eq!(parse("7#|#@2-$.#|7#").unwrap().dims, Dims::new(1, 5));
+    assert!(parse("5#|#@$.#|5").is_err());
+    assert!(parse("5#|2|#@$.#").is_err());
+    assert!(parse("99999999999999999999#|#@$.#|5#").is_err());
+    assert!(parse("1000#|#@$.#|5#").is_ok());
+    assert!(parse("800#200-#|#@$.#|5#").is_err());
+  }
+
+  #[test]
+  fn test_round_trip() {
+    let grid = vec![
+      Cell::Unreachable, Cell::Unreachable, Cell::Boulder, Cell::Hole,
+      Cell::Block, Cell::Unreachable, Cell::Boulder, Cell::Hole,
+    ];
+    let level = Level::new(grid, Dims::new(2, 4), 0);
+    let text = to_string(&level).unwrap();
+    assert_eq!(text, "6#|#@-$.#|2#-$.#|6#");
+    let parsed = parse(&text).unwrap();
+    assert_eq!(to_string(&parsed).unwrap(), text);
+    assert_eq!(parsed.tractor, level.tractor);
+    assert_eq!(parsed.dims, level.dims);
+  }
+}
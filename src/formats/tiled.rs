@@ -0,0 +1,344 @@
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::cell::{Cell, COLORS};
+use crate::grid::Dims;
+use crate::level::Level;
+
+// Global tile ids drawn for each kind of cell, 0 leaving a tile empty as in
+// Tiled. Colored holes and boulders use the plain ids, with the color kept as
+// an object property on boulders.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(default)]
+pub struct TileIds {
+  pub floor: u32,
+  pub wall: u32,
+  pub block: u32,
+  pub hole: u32,
+  pub ice: u32,
+  pub one_way: u32,
+  pub portal: u32,
+  pub cracked: u32,
+  pub collapsed: u32,
+  pub boulder: u32,
+  pub heavy_boulder: u32,
+  pub tractor: u32,
+}
+
+impl Default for TileIds {
+  fn default() -> Self {
+    TileIds {
+      floor: 1,
+      wall: 2,
+      block: 3,
+      hole: 4,
+      ice: 5,
+      one_way: 6,
+      portal: 7,
+      cracked: 8,
+      collapsed: 9,
+      boulder: 10,
+      heavy_boulder: 11,
+      tractor: 12,
+    }
+  }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(default)]
+pub struct TiledOptions {
+  pub tile_width: u32,
+  pub tile_height: u32,
+  // The external tileset the ids refer to, starting at id 1
+  pub tileset: String,
+  pub ids: TileIds,
+}
+
+impl Default for TiledOptions {
+  fn default() -> Self {
+    TiledOptions { tile_width: 32, tile_height: 32, tileset: "lvlgen.tsx".into(), ids: TileIds::default() }
+  }
+}
+
+// A boulder or the tractor spawn, placed on the object layer
+struct Piece {
+  kind: &'static str,
+  gid: u32,
+  idx: usize,
+  properties: Vec<(&'static str, String)>,
+}
+
+// What the board layer draws under whatever stands on the cell
+fn tile(cell: Cell, ids: &TileIds) -> u32 {
+  match cell.without_boulder() {
+    Cell::Wall => ids.wall,
+    Cell::Block => ids.block,
+    Cell::Hole | Cell::ColoredHole(_) => ids.hole,
+    Cell::Ice => ids.ice,
+    Cell::OneWay(_) => ids.one_way,
+    Cell::Portal(_) => ids.portal,
+    Cell::Cracked => ids.cracked,
+    Cell::Collapsed => ids.collapsed,
+    _ => ids.floor,
+  }
+}
+
+fn pieces(level: &Level, ids: &TileIds) -> Vec<Piece> {
+  let mut pieces = vec![];
+  for (idx, cell) in level.grid.iter().enumerate().filter(|(_, cell)| cell.has_boulder()) {
+    let mut properties = vec![];
+    if let Some(color) = cell.color() {
+      properties.push(("color", format!("{:?}", color).to_lowercase()));
+    }
+    let (kind, gid) = if cell.is_heavy() {
+      ("heavy_boulder", ids.heavy_boulder)
+    } else {
+      ("boulder", ids.boulder)
+    };
+    pieces.push(Piece { kind, gid, idx, properties });
+  }
+  pieces.push(Piece { kind: "tractor", gid: ids.tractor, idx: level.tractor, properties: vec![] });
+  pieces
+}
+
+// A Tiled JSON map (`.tmj`) with a `board` tile layer holding the cells and a
+// `pieces` object layer holding the boulders and the tractor spawn
+pub fn to_json(level: &Level, options: &TiledOptions) -> String {
+  let (width, height) = (options.tile_width, options.tile_height);
+  let data = level.grid.iter().map(|cell| tile(*cell, &options.ids)).collect::<Vec<u32>>();
+  let pieces = pieces(level, &options.ids);
+  let objects = pieces.iter().enumerate().map(|(n, piece)| json!({
+    "id": n + 1,
+    "name": "",
+    "type": piece.kind,
+    "gid": piece.gid,
+    "x": level.dims.col(piece.idx) as u32 * width,
+    // Tile objects hang up from their bottom edge
+    "y": (level.dims.row(piece.idx) as u32 + 1) * height,
+    "width": width,
+    "height": height,
+    "rotation": 0,
+    "visible": true,
+    "properties": piece.properties.iter()
+      .map(|(name, value)| json!({ "name": name, "type": "string", "value": value }))
+      .collect::<Vec<_>>(),
+  })).collect::<Vec<_>>();
+  let map = json!({
+    "type": "map",
+    "version": "1.10",
+    "orientation": "orthogonal",
+    "renderorder": "right-down",
+    "infinite": false,
+    "width": level.dims.cols,
+    "height": level.dims.rows,
+    "tilewidth": width,
+    "tileheight": height,
+    "nextlayerid": 3,
+    "nextobjectid": pieces.len() + 1,
+    "tilesets": [{ "firstgid": 1, "source": options.tileset }],
+    "layers": [
+      {
+        "id": 1,
+        "name": "board",
+        "type": "tilelayer",
+        "x": 0,
+        "y": 0,
+        "width": level.dims.cols,
+        "height": level.dims.rows,
+        "opacity": 1,
+        "visible": true,
+        "data": data,
+      },
+      {
+        "id": 2,
+        "name": "pieces",
+        "type": "objectgroup",
+        "draworder": "topdown",
+        "x": 0,
+        "y": 0,
+        "opacity": 1,
+        "visible": true,
+        "objects": objects,
+      },
+    ],
+  });
+  serde_json::to_string_pretty(&map).unwrap()
+}
+
+// The same map as `to_json` in Tiled's XML format (`.tmx`)
+pub fn to_tmx(level: &Level, options: &TiledOptions) -> String {
+  let (width, height) = (options.tile_width, options.tile_height);
+  let (cols, rows) = (level.dims.cols, level.dims.rows);
+  let pieces = pieces(level, &options.ids);
+  let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+  xml += &format!(
+    "<map version=\"1.10\" orientation=\"orthogonal\" renderorder=\"right-down\" width=\"{}\" height=\"{}\" \
+     tilewidth=\"{}\" tileheight=\"{}\" infinite=\"0\" nextlayerid=\"3\" nextobjectid=\"{}\">\n",
+    cols, rows, width, height, pieces.len() + 1);
+  xml += &format!(" <tileset firstgid=\"1\" source=\"{}\"/>\n", escape(&options.tileset));
+  xml += &format!(" <layer id=\"1\" name=\"board\" width=\"{}\" height=\"{}\">\n", cols, rows);
+  xml += "  <data encoding=\"csv\">\n";
+  let lines = level.grid.chunks(cols)
+    .map(|row| row.iter().map(|cell| tile(*cell, &options.ids).to_string()).collect::<Vec<String>>().join(","))
+    .collect::<Vec<String>>();
+  xml += &lines.join(",\n");
+  xml += "\n  </data>\n </layer>\n";
+  xml += " <objectgroup id=\"2\" name=\"pieces\">\n";
+  for (n, piece) in pieces.iter().enumerate() {
+    let x = level.dims.col(piece.idx) as u32 * width;
+    let y = (level.dims.row(piece.idx) as u32 + 1) * height;
+    let object = format!("  <object id=\"{}\" type=\"{}\" gid=\"{}\" x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\"",
+      n + 1, piece.kind, piece.gid, x, y, width, height);
+    if piece.properties.is_empty() {
+      xml += &format!("{}/>\n", object);
+      continue;
+    }
+    xml += &format!("{}>\n   <properties>\n", object);
+    for (name, value) in &piece.properties {
+      xml += &format!("    <property name=\"{}\" value=\"{}\"/>\n", name, escape(value));
+    }
+    xml += "   </properties>\n  </object>\n";
+  }
+  xml += " </objectgroup>\n</map>\n";
+  xml
+}
+
+fn escape(text: &str) -> String {
+  text.replace('&', "&amp;").replace('"', "&quot;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+fn unescape(text: &str) -> String {
+  text.replace("&quot;", "\"").replace("&lt;", "<").replace("&gt;", ">").replace("&amp;", "&")
+}
+
+// The value of attribute `name` in the tag `tag` starts with
+fn attr(tag: &str, name: &str) -> Option<String> {
+  let tag = format!(" {}", &tag[..tag.find('>').unwrap_or(tag.len())]);
+  let start = tag.find(&format!(" {}=\"", name))? + name.len() + 3;
+  let len = tag[start..].find('"')?;
+  Some(unescape(&tag[start..start + len]))
+}
+
+fn number_attr(tag: &str, name: &str) -> Result<u32, String> {
+  let value = attr(tag, name).ok_or_else(|| format!("no `{}` attribute", name))?;
+  value.parse().map_err(|_| format!("`{}` is not a number: `{}`", name, value))
+}
+
+// Reads a map as `to_tmx` writes it, with the board as CSV. One-way cells and
+// portals can't be read back, since their tiles don't say which way or which
+// pair, and all holes come back plain.
+pub fn parse_tmx(text: &str, ids: &TileIds) -> Result<Level, String> {
+  let map = &text[text.find("<map").ok_or("no <map>")?..];
+  let dims = Dims::new(number_attr(map, "height")? as usize, number_attr(map, "width")? as usize);
+  let (width, height) = (number_attr(map, "tilewidth")?, number_attr(map, "tileheight")?);
+  if width == 0 || height == 0 {
+    return Err(format!("{}x{} tiles", width, height));
+  }
+  let data = &map[map.find("<data").ok_or("no <data>")?..];
+  if attr(data, "encoding").as_deref() != Some("csv") {
+    return Err("only CSV tile data is supported".into());
+  }
+  let start = data.find('>').ok_or("unterminated <data>")? + 1;
+  let csv = data.get(start..data.find("</data>").ok_or("no </data>")?).ok_or("unterminated <data>")?;
+  let mut grid = vec![];
+  for gid in csv.split(',').map(str::trim).filter(|gid| !gid.is_empty()) {
+    let gid: u32 = gid.parse().map_err(|_| format!("not a tile id `{}`", gid))?;
+    grid.push(match gid {
+      gid if gid == ids.floor => Cell::Unreachable,
+      gid if gid == ids.wall || gid == 0 => Cell::Wall,
+      gid if gid == ids.block => Cell::Block,
+      gid if gid == ids.hole => Cell::Hole,
+      gid if gid == ids.ice => Cell::Ice,
+      gid if gid == ids.cracked => Cell::Cracked,
+      gid if gid == ids.collapsed => Cell::Collapsed,
+      gid => return Err(format!("can't read tile id {} back", gid)),
+    });
+  }
+  if grid.len() != dims.len() || dims.is_empty() {
+    return Err(format!("{} tiles for a {}x{} map", grid.len(), dims.cols, dims.rows));
+  }
+  let mut tractor = None;
+  for object in map.split("<object ").skip(1) {
+    let object = &object[..object.find("</object>").or_else(|| object.find("/>")).unwrap_or(object.len())];
+    let (x, y) = (number_attr(object, "x")?, number_attr(object, "y")?);
+    let (col, row) = ((x / width) as usize, (y / height) as usize);
+    if row == 0 || row > dims.rows || col >= dims.cols {
+      return Err(format!("object at {},{} is off the map", x, y));
+    }
+    let idx = dims.index(row - 1, col);
+    let color = object.split("<property ")
+      .skip(1)
+      .find(|property| attr(property, "name").as_deref() == Some("color"))
+      .and_then(|property| attr(property, "value"))
+      .map(|value| COLORS.iter().cloned().find(|color| format!("{:?}", color).to_lowercase() == value)
+        .ok_or_else(|| format!("unknown color `{}`", value)))
+      .transpose()?;
+    match attr(object, "type").as_deref() {
+      Some("tractor") => tractor = Some(idx),
+      Some("boulder") => grid[idx] = grid[idx].with_boulder_of(color),
+      Some("heavy_boulder") => grid[idx] = grid[idx].with_boulder_like(Cell::HeavyBoulder),
+      _ => (),
+    }
+  }
+  Ok(Level::new(grid, dims, tractor.ok_or("no tractor object")?))
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::cell::Color;
+
+  fn level() -> Level {
+    let grid = vec![
+      Cell::Unreachable, Cell::ColoredBoulder(Color::Red), Cell::ColoredHole(Color::Red),
+      Cell::Wall, Cell::BoulderInHole, Cell::Ice,
+    ];
+    Level::new(grid, Dims::new(2, 3), 0)
+  }
+
+  #[test]
+  fn test_to_json() {
+    let options = TiledOptions { tile_width: 16, ids: TileIds { wall: 20, ..TileIds::default() }, ..TiledOptions::default() };
+    let map: serde_json::Value = serde_json::from_str(&to_json(&level(), &options)).unwrap();
+    assert_eq!(map["width"], 3);
+    assert_eq!(map["layers"][0]["data"], json!([1, 1, 4, 20, 4, 5]));
+    let objects = map["layers"][1]["objects"].as_array().unwrap();
+    assert_eq!(objects.len(), 3);
+    assert_eq!(objects[0]["type"], "boulder");
+    assert_eq!(objects[0]["x"], 16);
+    assert_eq!(objects[0]["y"], 32);
+    assert_eq!(objects[0]["properties"][0]["value"], "red");
+    assert_eq!(objects[2]["type"], "tractor");
+    assert_eq!(objects[2]["gid"], 12);
+  }
+
+  #[test]
+  fn test_to_tmx() {
+    let tmx = to_tmx(&level(), &TiledOptions::default());
+    assert!(tmx.contains("<data encoding=\"csv\">\n1,1,4,\n2,4,5\n  </data>"));
+    assert_eq!(tmx.matches("<object ").count(), 3);
+    assert!(tmx.contains("<property name=\"color\" value=\"red\"/>"));
+    assert!(tmx.contains("type=\"tractor\" gid=\"12\" x=\"0\" y=\"32\""));
+  }
+
+  #[test]
+  fn test_parse_tmx() {
+    let mut level = level();
+    // Colored holes come back plain
+    level.grid[2] = Cell::Hole;
+    level.grid[1] = Cell::Boulder;
+    let ids = TileIds { hole: 40, ..TileIds::default() };
+    let options = TiledOptions { ids: ids.clone(), ..TiledOptions::default() };
+    assert_eq!(parse_tmx(&to_tmx(&level, &options), &ids), Ok(level.clone()));
+    level.grid[1] = Cell::ColoredBoulder(Color::Blue);
+    assert_eq!(parse_tmx(&to_tmx(&level, &options), &ids).unwrap().grid[1], Cell::ColoredBoulder(Color::Blue));
+    let tmx = to_tmx(&level, &options);
+    let flat = TiledOptions { tile_height: 0, ids: ids.clone(), ..TiledOptions::default() };
+    assert!(parse_tmx(&to_tmx(&level, &flat), &ids).is_err());
+    let data = tmx.find("<data").unwrap();
+    assert!(parse_tmx(&tmx[..data + 20], &ids).is_err());
+    assert!(parse_tmx(&format!("{}</data>", &tmx[..data + 20]), &ids).is_err());
+    level.grid[0] = Cell::Portal(0);
+    assert!(parse_tmx(&to_tmx(&level, &options), &ids).is_err());
+  }
+}
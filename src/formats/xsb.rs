@@ -0,0 +1,213 @@
+use crate::cell::Cell;
+use crate::direction::DIRECTIONS;
+use crate::grid::Dims;
+use crate::level::Level;
+
+// The plain text format most Sokoban collections use: `#` walls, `$` boxes,
+// `.` goals, `*` boxes on goals, `@` the player and `+` the player on a goal.
+// Floor is a space, or `-` and `_` where spaces would get lost.
+fn cell_from_char(c: char) -> Option<(Cell, bool)> {
+  Some(match c {
+    '#' => (Cell::Wall, false),
+    '$' => (Cell::Boulder, false),
+    '.' => (Cell::Hole, false),
+    '*' => (Cell::BoulderInHole, false),
+    '@' => (Cell::Unreachable, true),
+    '+' => (Cell::Hole, true),
+    ' ' | '-' | '_' => (Cell::Unreachable, false),
+    _ => return None,
+  })
+}
+
+fn is_board_line(line: &str) -> bool {
+  line.contains('#') && line.chars().all(|c| cell_from_char(c).is_some())
+}
+
+// Parses a single level. Rows and columns along the edges holding nothing but
+// walls are dropped, since the edge of a board already stops the tractor.
+pub fn parse(text: &str) -> Result<Level, String> {
+  let lines = text.lines()
+    .map(|line| line.trim_end_matches('\r'))
+    .skip_while(|line| line.trim().is_empty())
+    .take_while(|line| !line.trim().is_empty())
+    .collect::<Vec<&str>>();
+  if let Some(line) = lines.iter().find(|line| !is_board_line(line)) {
+    return Err(format!("not a board row `{}`", line));
+  }
+  parse_rows(&lines)
+}
+
+// Every level of a collection file, in order. Titles, comments and anything
+// else that isn't a board row separate the levels.
+pub fn parse_collection(text: &str) -> Result<Vec<Level>, String> {
+  let mut levels = vec![];
+  let mut rows = vec![];
+  for line in text.lines().map(|line| line.trim_end_matches('\r')) {
+    if is_board_line(line) {
+      rows.push(line);
+    } else if !rows.is_empty() {
+      levels.push(parse_rows(&rows)?);
+      rows.clear();
+    }
+  }
+  if !rows.is_empty() {
+    levels.push(parse_rows(&rows)?);
+  }
+  Ok(levels)
+}
+
+fn parse_rows(lines: &[&str]) -> Result<Level, String> {
+  let cols = lines.iter().map(|line| line.chars().count()).max().unwrap_or(0);
+  if cols == 0 {
+    return Err("empty board".into());
+  }
+  let mut grid = vec![];
+  let mut tractor = None;
+  for line in lines {
+    for c in line.chars().chain(std::iter::repeat(' ')).take(cols) {
+      match cell_from_char(c) {
+        Some((_, true)) if tractor.is_some() => return Err("more than one player".into()),
+        Some((cell, is_player)) => {
+          if is_player {
+            tractor = Some(grid.len());
+          }
+          grid.push(cell);
+        },
+        None => return Err(format!("unrecognized character `{}`", c)),
+      }
+    }
+  }
+  let tractor = tractor.ok_or("no player")?;
+  Ok(trim_walls(Level::new(grid, Dims::new(lines.len(), cols), tractor)))
+}
+
+// Bare floor the player can't get to from inside the walls becomes wall too.
+// Goals and boxes out of reach are kept, for `validate` to point out.
+fn trim_walls(mut level: Level) -> Level {
+  let dims = level.dims;
+  let mut inside = vec![false; level.grid.len()];
+  inside[level.tractor] = true;
+  let mut stack = vec![level.tractor];
+  while let Some(idx) = stack.pop() {
+    for next in DIRECTIONS.iter().filter_map(|dir| dir.apply(idx, dims)) {
+      if !inside[next] && level.grid[next] != Cell::Wall {
+        inside[next] = true;
+        stack.push(next);
+      }
+    }
+  }
+  for (cell, inside) in level.grid.iter_mut().zip(&inside) {
+    if !inside && *cell == Cell::Unreachable {
+      *cell = Cell::Wall;
+    }
+  }
+  let is_wall = |row: usize, col: usize| level.grid[dims.index(row, col)] == Cell::Wall;
+  let (mut top, mut bottom, mut left, mut right) = (0, dims.rows, 0, dims.cols);
+  while top < bottom && (left..right).all(|col| is_wall(top, col)) {
+    top += 1;
+  }
+  while bottom > top && (left..right).all(|col| is_wall(bottom - 1, col)) {
+    bottom -= 1;
+  }
+  while left < right && (top..bottom).all(|row| is_wall(row, left)) {
+    left += 1;
+  }
+  while right > left && (top..bottom).all(|row| is_wall(row, right - 1)) {
+    right -= 1;
+  }
+  let trimmed = Dims::new(bottom - top, right - left);
+  let grid = (top..bottom)
+    .flat_map(|row| (left..right).map(move |col| (row, col)))
+    .map(|(row, col)| level.grid[dims.index(row, col)])
+    .collect();
+  let tractor = trimmed.index(dims.row(level.tractor) - top, dims.col(level.tractor) - left);
+  Level::new(grid, trimmed, tractor)
+}
+
+// The level inside a border of walls, one row per line with trailing floor
+// left off. Fails on cells plain Sokoban has no equivalent for.
+pub fn to_string(level: &Level) -> Result<String, String> {
+  let border = "#".repeat(level.dims.cols + 2);
+  let mut text = format!("{}\n", border);
+  for (row, cells) in level.grid.chunks(level.dims.cols).enumerate() {
+    let mut line = String::from("#");
+    for (col, cell) in cells.iter().enumerate() {
+      let is_tractor = level.dims.index(row, col) == level.tractor;
+      line.push(match (cell, is_tractor) {
+        (Cell::Unreachable | Cell::Reachable, false) => ' ',
+        (Cell::Unreachable | Cell::Reachable, true) => '@',
+        (Cell::Hole, true) => '+',
+        (Cell::Hole, false) => '.',
+        (Cell::Boulder, false) => '$',
+        (Cell::BoulderInHole, false) => '*',
+        (Cell::Block | Cell::Wall, false) => '#',
+        (cell, _) => return Err(format!("no Sokoban equivalent for `{}`", cell.to_char())),
+      });
+    }
+    line.push('#');
+    text.push_str(&line);
+    text.push('\n');
+  }
+  text.push_str(&border);
+  text.push('\n');
+  Ok(text)
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn test_parse() {
+    let level = parse(concat!(
+      "  #####\n",
+      "###   #\n",
+      "#.@$  #\n",
+      "### $.#\n",
+      "  #*  #\n",
+      "  #####\n",
+    )).unwrap();
+    assert_eq!(level.dims, Dims::new(4, 5));
+    assert_eq!(level.tractor, level.dims.index(1, 1));
+    assert_eq!(level.grid[level.dims.index(1, 0)], Cell::Hole);
+    assert_eq!(level.grid[level.dims.index(1, 2)], Cell::Boulder);
+    assert_eq!(level.grid[level.dims.index(3, 2)], Cell::BoulderInHole);
+    assert_eq!(level.grid[level.dims.index(0, 3)], Cell::Unreachable);
+    assert_eq!(level.grid[level.dims.index(3, 0)], Cell::Wall);
+    assert!(parse("#@$.#\n#@  #\n").is_err());
+    assert!(parse("# $.#\n").is_err());
+    assert!(parse("#@$x#\n").is_err());
+    // A goal walled off from the player stays, and makes the level invalid
+    let level = parse("#######\n#@$.#.#\n#######\n").unwrap();
+    assert_eq!(level.dims, Dims::new(1, 5));
+    assert_eq!(level.grid[4], Cell::Hole);
+    assert!(!level.validate().is_empty());
+  }
+
+  #[test]
+  fn test_round_trip() {
+    let grid = vec![
+      Cell::Unreachable, Cell::Boulder, Cell::Hole,
+      Cell::Block, Cell::Unreachable, Cell::BoulderInHole,
+    ];
+    let level = Level::new(grid, Dims::new(2, 3), 2);
+    let text = to_string(&level).unwrap();
+    assert_eq!(text, "#####\n# $+#\n## *#\n#####\n");
+    let parsed = parse(&text).unwrap();
+    assert_eq!(parsed.tractor, level.tractor);
+    assert_eq!(parsed.grid[3], Cell::Wall);
+    let mut ice = level.clone();
+    ice.grid[0] = Cell::Ice;
+    assert!(to_string(&ice).is_err());
+  }
+
+  #[test]
+  fn test_parse_collection() {
+    let text = "; Two levels\n\n####\n#@$.#\n####\nTitle: Second\n#####\n#+$ #\n# * #\n#####\n";
+    let levels = parse_collection(text).unwrap();
+    assert_eq!(levels.len(), 2);
+    assert_eq!(levels[0].dims, Dims::new(1, 3));
+    assert_eq!(levels[1].dims, Dims::new(2, 3));
+    assert_eq!(levels[1].grid[levels[1].tractor], Cell::Hole);
+  }
+}
@@ -1,16 +1,390 @@
-use rand::{self, seq::{IteratorRandom, SliceRandom}, Rng};
+use rand::{self, seq::{IteratorRandom, SliceRandom}, Rng, SeedableRng};
+use rand_pcg::Pcg64;
+use serde::{Deserialize, Serialize};
+
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::Path;
 
 use crate::cell::Cell;
+use crate::dedupe::{similarity_of, Prepared};
+use crate::difficulty::{measure, DifficultyRange};
+use crate::floor_plan::floor_plan;
 use crate::grid::*;
+use crate::level::Level;
+use crate::solver::{count_distinct_solutions, solve, solve_with};
+use crate::state_graph::{find_solvable_states, next_pulled_states};
+use crate::template::Template;
+use crate::transposition::{state_key, Outcome, TranspositionTable};
+
+// What to generate, shared by every strategy. Missing fields keep their
+// defaults when read from a file.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(default)]
+pub struct GenConfig {
+  pub size: usize,
+  // Boulders placed, with as many holes
+  pub boulders: usize,
+  // How many of those boulders are heavy
+  pub heavy: usize,
+  // Share of the open cells turned into blocks
+  pub wall_density: f64,
+  // Accepted levels take at least `min_pushes` to solve, and at most
+  // `max_pushes` if set
+  pub min_pushes: usize,
+  pub max_pushes: Option<usize>,
+  // Mirror blocks left to right
+  pub symmetry: bool,
+  // Only accept levels with one essentially different shortest solution
+  pub unique_solution: bool,
+  pub seed: u64,
+  // Random boards tried before giving up
+  pub max_attempts: usize,
+  // States whose outcome is remembered from one attempt to the next. Random
+  // boards rarely share states, so this is off by default.
+  pub transpositions: usize,
+  // Only accept boards whose floor has this many rooms and corridors, see
+  // `floor_plan`
+  pub rooms: Option<usize>,
+  pub corridors: Option<usize>,
+  // Curriculum levels more similar than this to an earlier one are skipped,
+  // see `dedupe::similarity`
+  pub max_similarity: Option<f32>,
+}
+
+impl Default for GenConfig {
+  fn default() -> Self {
+    GenConfig {
+      size: 6,
+      boulders: 2,
+      heavy: 0,
+      wall_density: 1.0 / 6.0,
+      min_pushes: 1,
+      max_pushes: None,
+      symmetry: false,
+      unique_solution: false,
+      seed: 0,
+      max_attempts: 1000,
+      transpositions: 0,
+      rooms: None,
+      corridors: None,
+      max_similarity: None,
+    }
+  }
+}
+
+impl GenConfig {
+  // TOML for `.toml` files, JSON otherwise
+  pub fn load(path: &Path) -> io::Result<Self> {
+    let text = fs::read_to_string(path)?;
+    if path.extension().is_some_and(|ext| ext == "toml") {
+      toml::from_str(&text).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    } else {
+      serde_json::from_str(&text).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+  }
+  // Generated boards are square
+  pub fn dims(&self) -> Dims {
+    Dims::square(self.size)
+  }
+  // Blocks for a board with `cells` open cells
+  pub fn blocks_for(&self, cells: usize) -> usize {
+    ((self.wall_density * cells as f64).round() as usize).min(cells)
+  }
+  pub fn accepts(&self, pushes: usize) -> bool {
+    pushes >= self.min_pushes && self.max_pushes.is_none_or(|max| pushes <= max)
+  }
+  // Whether a generated level meets every constraint
+  pub fn accepts_level(&self, level: &Level) -> bool {
+    self.accepts_level_with(level, &mut TranspositionTable::new(0))
+  }
+  // Reusing and adding to what `table` knows from earlier levels
+  pub fn accepts_level_with(&self, level: &Level, table: &mut TranspositionTable) -> bool {
+    self.accepts_floor(level)
+      && fewest_pushes_with(level, table).is_some_and(|pushes| self.accepts(pushes))
+      && (!self.unique_solution || count_distinct_solutions(level, 2) == 1)
+  }
+  fn accepts_floor(&self, level: &Level) -> bool {
+    if self.rooms.is_none() && self.corridors.is_none() {
+      return true;
+    }
+    let plan = floor_plan(&level.grid, level.dims);
+    self.rooms.is_none_or(|rooms| plan.rooms() == rooms)
+      && self.corridors.is_none_or(|corridors| plan.corridors() == corridors)
+  }
+  // The boulder to place `nth` of `config.boulders`, heavy ones first
+  fn boulder(&self, nth: usize) -> Cell {
+    if nth < self.heavy { Cell::HeavyBoulder } else { Cell::Boulder }
+  }
+  // The same config under another seed, tried only once
+  fn single(&self, seed: u64) -> Self {
+    GenConfig { seed, max_attempts: 1, ..self.clone() }
+  }
+}
+
+// Puts `config.blocks_for` blocks on an empty square board, mirrored if the
+// config asks for symmetry
+fn scatter_blocks<R: Rng>(config: &GenConfig, grid: &mut [Cell], rng: &mut R) {
+  let size = config.size;
+  let wanted = config.blocks_for(grid.len());
+  let mut order = (0..grid.len())
+    .filter(|idx| !config.symmetry || idx % size < size.div_ceil(2))
+    .collect::<Vec<usize>>();
+  order.shuffle(rng);
+  let mut placed = 0;
+  for idx in order {
+    if placed >= wanted {
+      break;
+    }
+    grid[idx] = Cell::Block;
+    placed += 1;
+    let mirror = idx - idx % size + (size - 1 - idx % size);
+    if config.symmetry && mirror != idx {
+      grid[mirror] = Cell::Block;
+      placed += 1;
+    }
+  }
+}
+
+// Scatters blocks, holes, boulders and the tractor at random until a board
+// turns up that the config accepts. The same config always gives the same
+// level.
+pub fn generate(config: &GenConfig) -> Option<Level> {
+  let cells = config.dims().len();
+  let mut rng = Pcg64::seed_from_u64(config.seed);
+  let mut table = TranspositionTable::new(config.transpositions);
+  for _ in 0..config.max_attempts {
+    let mut grid = vec![Cell::Unreachable; cells];
+    scatter_blocks(config, &mut grid, &mut rng);
+    let mut order = (0..cells).filter(|idx| grid[*idx] != Cell::Block).collect::<Vec<usize>>();
+    if order.len() <= 2 * config.boulders {
+      return None;
+    }
+    order.shuffle(&mut rng);
+    let (holes, rest) = order.split_at(config.boulders);
+    let (boulders, rest) = rest.split_at(config.boulders);
+    for idx in holes {
+      grid[*idx] = Cell::Hole;
+    }
+    for (nth, idx) in boulders.iter().enumerate() {
+      grid[*idx] = config.boulder(nth);
+    }
+    let level = Level::new(grid, config.dims(), rest[0]);
+    if config.accepts_level_with(&level, &mut table) {
+      return Some(level);
+    }
+  }
+  None
+}
+
+// Plays backwards from a solved board: blocks and filled holes are scattered
+// at random, then `pulls` random pulls drag boulders out. Every level found
+// this way is solvable in at most `pulls` pushes. Walks the config doesn't
+// accept are thrown away.
+pub fn generate_by_pulling(config: &GenConfig, pulls: usize) -> Option<Level> {
+  let cells = config.dims().len();
+  let mut rng = Pcg64::seed_from_u64(config.seed);
+  let mut table = TranspositionTable::new(config.transpositions);
+  for _ in 0..config.max_attempts {
+    let mut grid = vec![Cell::Unreachable; cells];
+    scatter_blocks(config, &mut grid, &mut rng);
+    let mut order = (0..cells).filter(|idx| grid[*idx] != Cell::Block).collect::<Vec<usize>>();
+    if order.len() <= config.boulders {
+      return None;
+    }
+    order.shuffle(&mut rng);
+    let (holes, rest) = order.split_at(config.boulders);
+    for (nth, idx) in holes.iter().enumerate() {
+      grid[*idx] = Cell::Hole.with_boulder_like(config.boulder(nth));
+    }
+    let mut tractor = rest[0];
+    fill_reachable_cells_unchecked(tractor, &mut grid, config.dims());
+    // Prefer pulls to states the walk hasn't been in yet, so it doesn't just
+    // undo itself
+    let mut visited = vec![grid.clone()];
+    for _ in 0..pulls {
+      let next = next_pulled_states(&grid, config.dims());
+      let fresh = next.iter().filter(|(state, _)| !visited.contains(state)).collect::<Vec<_>>();
+      let (state, push) = match fresh.choose(&mut rng).cloned().or_else(|| next.choose(&mut rng)) {
+        Some(pulled) => pulled.clone(),
+        None => break,
+      };
+      visited.push(state.clone());
+      grid = state;
+      tractor = push.tractor;
+    }
+    for cell in &mut grid {
+      if *cell == Cell::Reachable {
+        *cell = Cell::Unreachable;
+      }
+    }
+    let level = Level::new(grid, config.dims(), tractor);
+    if config.accepts_level_with(&level, &mut table) {
+      return Some(level);
+    }
+  }
+  None
+}
+
+// Keeps generating by pulling until a level scores inside `range`, trying up
+// to `max_attempts` random walks of varying length
+pub fn generate_with_difficulty(config: &GenConfig, range: DifficultyRange) -> Option<Level> {
+  let mut rng = Pcg64::seed_from_u64(config.seed);
+  (0..config.max_attempts).find_map(|_| walk_with_difficulty(config, range, &mut rng))
+}
+
+// One random walk of up to four pulls per cell and boulder, kept if the
+// level it leaves scores inside `range`
+fn walk_with_difficulty<R: Rng>(config: &GenConfig, range: DifficultyRange, rng: &mut R) -> Option<Level> {
+  let longest = 4 * config.size * config.boulders.max(1);
+  let pulls = rng.gen_range(1..=longest);
+  let level = generate_by_pulling(&config.single(rng.gen()), pulls)?;
+  measure(&level).filter(|metrics| range.contains(metrics)).map(|_| level)
+}
+
+// An ordered pack of `n` levels for a campaign. The score range from `start`
+// to `end` is cut into `n` equal bands tried in order, so scores strictly
+// increase, and levels too close to an earlier one are skipped, or too
+// similar if the config limits that. Each band gets `max_attempts` random
+// walks. `None` if some band can't be filled.
+pub fn generate_curriculum(config: &GenConfig, n: usize, start: f64, end: f64) -> Option<Vec<Level>> {
+  let mut rng = Pcg64::seed_from_u64(config.seed);
+  let step = (end - start) / n as f64;
+  let mut pack: Vec<Level> = vec![];
+  // The pack solved once for `max_similarity`
+  let mut prepared = vec![];
+  for band in 0..n {
+    let min = start + band as f64 * step;
+    let range = DifficultyRange::Between { min, max: min + step };
+    let (level, candidate) = (0..config.max_attempts)
+      .filter_map(|_| walk_with_difficulty(config, range, &mut rng))
+      .filter(|level| pack.iter().all(|other| !is_near_duplicate(level, other)))
+      .map(|level| {
+        let candidate = config.max_similarity.map(|_| Prepared::new(&level));
+        (level, candidate)
+      })
+      .find(|(_, candidate)| match (candidate, config.max_similarity) {
+        (Some(candidate), Some(max)) => prepared.iter().all(|other| similarity_of(candidate, other) <= max),
+        _ => true,
+      })?;
+    pack.push(level);
+    prepared.extend(candidate);
+  }
+  Some(pack)
+}
+
+// Levels that differ in at most two cells, or only by where the tractor starts
+pub fn is_near_duplicate(a: &Level, b: &Level) -> bool {
+  a.dims == b.dims
+    && a.grid.iter().zip(&b.grid).filter(|(x, y)| x != y).count() <= 2
+}
+
+// Fills the wildcards of `template` at random: `boulders` holes and as many
+// boulders, blocks at `wall_density` of the wildcards, the tractor if the
+// template doesn't place it, and floor everywhere else. The template's own
+// size wins over the config's, and symmetry isn't applied.
+pub fn generate_from_template(template: &Template, config: &GenConfig) -> Option<Level> {
+  let wildcards = template.wildcards();
+  let tractor_needed = if template.tractor.is_some() { 0 } else { 1 };
+  let blocks = config.blocks_for(wildcards.len());
+  if blocks + 2 * config.boulders + tractor_needed > wildcards.len() {
+    return None;
+  }
+  let mut rng = Pcg64::seed_from_u64(config.seed);
+  let mut table = TranspositionTable::new(config.transpositions);
+  for _ in 0..config.max_attempts {
+    let mut grid = template.cells.iter()
+      .map(|cell| cell.unwrap_or(Cell::Unreachable))
+      .collect::<Vec<Cell>>();
+    let mut order = wildcards.clone();
+    order.shuffle(&mut rng);
+    let (block_cells, rest) = order.split_at(blocks);
+    let (holes, rest) = rest.split_at(config.boulders);
+    let (boulders, rest) = rest.split_at(config.boulders);
+    for idx in block_cells {
+      grid[*idx] = Cell::Block;
+    }
+    for idx in holes {
+      grid[*idx] = Cell::Hole;
+    }
+    for (nth, idx) in boulders.iter().enumerate() {
+      grid[*idx] = config.boulder(nth);
+    }
+    let tractor = template.tractor.unwrap_or_else(|| rest[0]);
+    let level = Level::new(grid, template.dims, tractor);
+    if config.accepts_level_with(&level, &mut table) {
+      return Some(level);
+    }
+  }
+  None
+}
+
+// Pulls every boulder back out of the solved board, once for each region the
+// tractor could finish in, and looks for the level's start among the results.
+// Which hole each heavy boulder ends up in, or which cracked floor caves in,
+// isn't known up front, so those levels are solved forwards instead.
+pub fn fewest_pushes(level: &Level) -> Option<usize> {
+  if level.grid.iter().any(|cell| cell.is_heavy() || *cell == Cell::Cracked) {
+    return solve(level).map(|solution| solution.len());
+  }
+  pulled_fewest_pushes(level)
+}
+
+// Like `fewest_pushes`, looking the level up in `table` first and recording
+// the answer there
+pub fn fewest_pushes_with(level: &Level, table: &mut TranspositionTable) -> Option<usize> {
+  let key = state_key(&level.initial_state(), level.dims);
+  match table.get(key) {
+    Some(Outcome::Solved(pushes)) => return Some(pushes),
+    Some(Outcome::Dead) => return None,
+    None => (),
+  }
+  if level.grid.iter().any(|cell| cell.is_heavy() || *cell == Cell::Cracked) {
+    return solve_with(level, table).map(|solution| solution.len());
+  }
+  let pushes = pulled_fewest_pushes(level);
+  table.insert(key, pushes.map_or(Outcome::Dead, Outcome::Solved));
+  pushes
+}
+
+fn pulled_fewest_pushes(level: &Level) -> Option<usize> {
+  let start = level.initial_state();
+  let solved = level.grid.iter()
+    .map(|cell| match cell.without_boulder() {
+      hole if hole.is_open_hole() => hole.with_boulder_of(hole.color()),
+      Cell::Reachable => Cell::Unreachable,
+      floor => floor,
+    })
+    .collect::<Vec<Cell>>();
+  let mut seen = vec![false; solved.len()];
+  let mut best: Option<usize> = None;
+  for tractor in 0..solved.len() {
+    if solved[tractor] != Cell::Unreachable || seen[tractor] {
+      continue;
+    }
+    for idx in find_reachable_empty_cells_unchecked(tractor, &solved, level.dims) {
+      seen[idx] = true;
+    }
+    let graph = find_solvable_states(&Level::new(solved.clone(), level.dims, tractor));
+    let pushes = graph.get_id(&start)
+      .and_then(|id| graph.bidirectional_shortest_path(&graph.root(), &id))
+      .map(|path| path.len() - 1);
+    if let Some(pushes) = pushes {
+      best = Some(best.map_or(pushes, |best| best.min(pushes)));
+    }
+  }
+  best
+}
 
 pub fn generate_level<T: Rng>(size: &usize, rng: &mut T) -> Vec<Cell> {
   let mut grid = vec![Cell::Unreachable; size * size];
   // 1. Pick random number of blocks to place
-  let n_blocks: usize = rng.gen_range(0..size * size * 1 / 2);
+  let n_blocks: usize = rng.gen_range(0..size * size / 2);
   // 2. Place blocks randomly:
   //   A. Fill grid with blocks and unreachable
-  for i in 0..n_blocks {
-    grid[i] = Cell::Block;
+  for cell in grid.iter_mut().take(n_blocks) {
+    *cell = Cell::Block;
   }
   //   B. Shuffle
   grid.shuffle(rng);
@@ -22,7 +396,7 @@ pub fn generate_level<T: Rng>(size: &usize, rng: &mut T) -> Vec<Cell> {
     }
   }
   let tractor = *tractor_candidates.choose(rng).unwrap();
-  let reachable = find_reachable_empty_cells(tractor, &grid, *size);
+  let reachable = find_reachable_empty_cells_unchecked(tractor, &grid, Dims::square(*size));
   let mut empty_cells = 0;
   // 4. Fill unreachable cells
   for (idx, cell) in grid.iter_mut().enumerate() {
@@ -49,17 +423,23 @@ pub fn generate_level<T: Rng>(size: &usize, rng: &mut T) -> Vec<Cell> {
   // 6. For each hole:
   for _ in 0..n_holes {
     // A. Let candidates be all reachable cells
-    let mut candidates = find_reachable_empty_cells(tractor, &grid, *size);
+    let mut candidates = find_reachable_empty_cells_unchecked(tractor, &grid, Dims::square(*size));
     candidates.remove(&tractor);
     // B. while there are candidates:
     let mut sorted_candidates = candidates.iter().cloned().collect::<Vec<usize>>();
     sorted_candidates.sort();
-    while let Some(candidate) = sorted_candidates.iter().choose(rng).cloned() {
+    // A candidate that's been ruled out can be drawn again and ruled out the
+    // same way, so drawing stops once all of them have been
+    let mut ruled_out = HashSet::new();
+    while ruled_out.len() < sorted_candidates.len() {
+      let candidate = sorted_candidates.iter().choose(rng).cloned().unwrap();
       // 1. Place hole at random candidate
       assert!(grid[candidate] == Cell::Unreachable);
       grid[candidate] = Cell::BoulderInHole; 
-      let new_reachable = find_reachable_empty_cells(tractor, &grid, *size);
+      let new_reachable = find_reachable_empty_cells_unchecked(tractor, &grid, Dims::square(*size));
       let mut all_holes_reachable = true;
+      // Only the first hole placed is checked, every way out of the loop breaks
+      #[allow(clippy::never_loop)]
       for hole in &holes {
         let row = hole / size;
         let col = hole % size;
@@ -94,10 +474,8 @@ pub fn generate_level<T: Rng>(size: &usize, rng: &mut T) -> Vec<Cell> {
       if all_holes_reachable {
         // i.  Fill unreachable cells
         for (idx, cell) in grid.iter_mut().enumerate() {
-          if *cell == Cell::Unreachable {
-            if !new_reachable.contains(&idx) {
-              *cell = Cell::Block;
-            }
+          if *cell == Cell::Unreachable && !new_reachable.contains(&idx) {
+            *cell = Cell::Block;
           }
         }
         holes.push(candidate);
@@ -106,10 +484,170 @@ pub fn generate_level<T: Rng>(size: &usize, rng: &mut T) -> Vec<Cell> {
       }
       // 3. remove the hole from the grid
       grid[candidate] = Cell::Unreachable;
-      // 4. remove the cell from the candidates
-      candidates.remove(&candidate);
+      // 4. rule the cell out
+      ruled_out.insert(candidate);
     }
   }
   grid[tractor] = Cell::Reachable;
   grid
 }
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::dedupe::similarity;
+  use crate::solver::solve;
+
+  #[test]
+  fn test_generate() {
+    let config = GenConfig {
+      size: 4, boulders: 1, wall_density: 3.0 / 16.0, min_pushes: 2, seed: 7, max_attempts: 200,
+      ..Default::default()
+    };
+    let level = generate(&config).unwrap();
+    assert_eq!(generate(&config), Some(level.clone()));
+    assert_eq!(generate(&GenConfig { transpositions: 1 << 10, ..config.clone() }), Some(level.clone()));
+    assert_eq!(level.grid.iter().filter(|cell| **cell == Cell::Block).count(), 3);
+    let solution = solve(&level).unwrap();
+    assert!(solution.len() >= 2);
+    assert_eq!(fewest_pushes(&level), Some(solution.len()));
+  }
+
+  #[test]
+  fn test_generate_heavy() {
+    let config = GenConfig {
+      size: 5, boulders: 2, heavy: 1, wall_density: 0.1, seed: 5, max_attempts: 100,
+      ..Default::default()
+    };
+    let level = generate_by_pulling(&config, 6).unwrap();
+    assert_eq!(level.grid.iter().filter(|cell| cell.is_heavy()).count(), 1);
+    assert!(solve(&level).is_some());
+    let level = generate(&config).unwrap();
+    assert_eq!(level.grid.iter().filter(|cell| **cell == Cell::HeavyBoulder).count(), 1);
+  }
+
+  #[test]
+  fn test_generate_by_pulling() {
+    let config = GenConfig {
+      size: 5, boulders: 2, wall_density: 0.16, min_pushes: 3, seed: 3, max_attempts: 100,
+      ..Default::default()
+    };
+    let level = generate_by_pulling(&config, 8).unwrap();
+    assert_eq!(generate_by_pulling(&config, 8), Some(level.clone()));
+    let holes = level.grid.iter()
+      .filter(|cell| **cell == Cell::Hole || **cell == Cell::BoulderInHole)
+      .count();
+    assert_eq!(holes, 2);
+    let pushes = solve(&level).unwrap().len();
+    assert!((3..=8).contains(&pushes));
+  }
+
+  #[test]
+  fn test_generate_with_difficulty() {
+    let config = GenConfig { size: 5, wall_density: 0.16, seed: 11, max_attempts: 200, ..Default::default() };
+    for range in &[DifficultyRange::Easy, DifficultyRange::Medium] {
+      let level = generate_with_difficulty(&config, *range).unwrap();
+      assert!(range.contains(&measure(&level).unwrap()));
+    }
+  }
+
+  #[test]
+  fn test_generate_from_template() {
+    let template = Template::parse("+----+\n|####|\n|????|\n|????|\n|#??#|\n+----+\n").unwrap();
+    let config = GenConfig { boulders: 1, wall_density: 0.1, seed: 9, ..Default::default() };
+    let level = generate_from_template(&template, &config).unwrap();
+    assert_eq!(level.dims, Dims::square(4));
+    for idx in [0, 1, 2, 3, 12, 15] {
+      assert_eq!(level.grid[idx], Cell::Block);
+    }
+    assert_eq!(level.grid.iter().filter(|cell| **cell == Cell::Block).count(), 7);
+    assert!(solve(&level).is_some());
+    let pinned = Template::parse("|???|\n|.??|\n|???|\n").unwrap();
+    let config = GenConfig { wall_density: 0.0, seed: 0, ..config };
+    assert_eq!(generate_from_template(&pinned, &config).unwrap().tractor, 3);
+  }
+
+  #[test]
+  fn test_generate_impossible() {
+    let config = GenConfig { size: 3, boulders: 2, wall_density: 6.0 / 9.0, ..Default::default() };
+    assert_eq!(generate(&config), None);
+  }
+
+  #[test]
+  fn test_push_limits_and_symmetry() {
+    let config = GenConfig {
+      size: 5, wall_density: 0.2, min_pushes: 2, max_pushes: Some(3), symmetry: true, seed: 4,
+      ..Default::default()
+    };
+    let level = generate(&config).unwrap();
+    assert!((2..=3).contains(&solve(&level).unwrap().len()));
+    for row in level.grid.chunks(5) {
+      for col in 0..5 {
+        assert_eq!(row[col] == Cell::Block, row[4 - col] == Cell::Block);
+      }
+    }
+  }
+
+  #[test]
+  fn test_generate_curriculum() {
+    let config = GenConfig { size: 5, seed: 8, max_attempts: 50, ..Default::default() };
+    let pack = generate_curriculum(&config, 3, 2.0, 14.0).unwrap();
+    assert_eq!(pack.len(), 3);
+    let scores = pack.iter().map(|level| measure(level).unwrap().score()).collect::<Vec<f64>>();
+    assert!(scores.windows(2).all(|pair| pair[0] < pair[1]));
+    assert!(scores[0] >= 2.0 && scores[2] < 14.0);
+    for (idx, level) in pack.iter().enumerate() {
+      assert!(pack[..idx].iter().all(|other| !is_near_duplicate(level, other)));
+    }
+    let config = GenConfig { max_similarity: Some(0.7), ..config };
+    let pack = generate_curriculum(&config, 3, 2.0, 14.0).unwrap();
+    for (idx, level) in pack.iter().enumerate() {
+      assert!(pack[..idx].iter().all(|other| similarity(level, other) <= 0.7));
+    }
+    // No small level scores this high, so the first band gives up after its
+    // own attempts
+    let started = std::time::Instant::now();
+    assert_eq!(generate_curriculum(&config, 3, 1000.0, 1003.0), None);
+    assert!(started.elapsed() < std::time::Duration::from_secs(10));
+  }
+
+  #[test]
+  fn test_generate_level() {
+    // On these seeds every cell left for a hole would cut off the first one
+    for seed in [127, 197, 213] {
+      let grid = generate_level(&3, &mut Pcg64::seed_from_u64(seed));
+      assert_eq!(grid.iter().filter(|cell| **cell == Cell::Reachable).count(), 1);
+    }
+  }
+
+  #[test]
+  fn test_unique_solution() {
+    let config = GenConfig { size: 5, unique_solution: true, seed: 6, ..Default::default() };
+    let level = generate(&config).unwrap();
+    assert_eq!(count_distinct_solutions(&level, 5), 1);
+    assert!(config.accepts_level(&level));
+  }
+
+  #[test]
+  fn test_floor_constraints() {
+    let config = GenConfig { size: 5, wall_density: 0.2, rooms: Some(1), corridors: Some(2), seed: 2, ..Default::default() };
+    let plan = floor_plan(&generate(&config).unwrap().grid, config.dims());
+    assert_eq!((plan.rooms(), plan.corridors()), (1, 2));
+  }
+
+  #[test]
+  fn test_load_config() {
+    let dir = std::env::temp_dir();
+    let json = dir.join("lvlgen_test_config.json");
+    fs::write(&json, r#"{ "size": 8, "boulders": 4, "max_pushes": 20 }"#).unwrap();
+    let toml = dir.join("lvlgen_test_config.toml");
+    fs::write(&toml, "size = 8\nboulders = 4\nmax_pushes = 20\n").unwrap();
+    let expected = GenConfig { size: 8, boulders: 4, max_pushes: Some(20), ..Default::default() };
+    assert_eq!(GenConfig::load(&json).unwrap(), expected);
+    assert_eq!(GenConfig::load(&toml).unwrap(), expected);
+    fs::write(&json, "{ \"size\": \"big\" }").unwrap();
+    assert_eq!(GenConfig::load(&json).unwrap_err().kind(), io::ErrorKind::InvalidData);
+    fs::remove_file(&json).unwrap();
+    fs::remove_file(&toml).unwrap();
+  }
+}
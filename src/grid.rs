@@ -1,72 +1,551 @@
+use serde::{Deserialize, Serialize};
+
 use crate::cell::Cell;
+use crate::error::Error;
+use crate::direction::{Direction, DIRECTIONS};
 
-use std::collections::hash_map::HashMap;
 use std::collections::hash_set::HashSet;
+use std::collections::VecDeque;
+use std::ops::{Deref, DerefMut, Index, IndexMut};
+
+// The shape of a board, cells are stored row by row
+#[derive(Copy, Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct Dims {
+  pub rows: usize,
+  pub cols: usize,
+}
 
-pub fn fill_reachable_cells(from: usize, grid: &mut Vec<Cell>, size: usize) {
-  for idx in find_reachable_empty_cells(from, grid, size) {
-    assert!(grid[idx] == Cell::Unreachable);
-    grid[idx] = Cell::Reachable;
+impl Dims {
+  pub fn new(rows: usize, cols: usize) -> Self {
+    Dims { rows, cols }
+  }
+  pub fn square(size: usize) -> Self {
+    Dims { rows: size, cols: size }
+  }
+  pub fn len(self) -> usize {
+    self.rows * self.cols
+  }
+  pub fn is_empty(self) -> bool {
+    self.len() == 0
+  }
+  pub fn is_square(self) -> bool {
+    self.rows == self.cols
+  }
+  pub fn row(self, idx: usize) -> usize {
+    idx / self.cols
+  }
+  pub fn col(self, idx: usize) -> usize {
+    idx % self.cols
+  }
+  pub fn index(self, row: usize, col: usize) -> usize {
+    to_index(row, col, self.cols)
   }
 }
 
-pub fn find_reachable_empty_cells(from: usize, grid: &Vec<Cell>, width: usize) -> HashSet<usize> {
-  walk_graph_from(from, &grid_to_movement_graph(grid, width))
+// A board's cells along with its shape, which always agree. Cells can be
+// indexed by `(row, col)` or by their index in row order, and the grid
+// derefs to its cells for the functions taking a slice and `Dims`.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Grid {
+  cells: Vec<Cell>,
+  rows: usize,
+  cols: usize,
 }
 
-fn walk_graph_from(from: usize, graph: &HashMap<usize, Vec<usize>>) -> HashSet<usize> {
-  let mut visited = HashSet::new();
-  visited.insert(from);
+impl Grid {
+  pub fn new(cells: Vec<Cell>, dims: Dims) -> Result<Self, Error> {
+    if cells.len() != dims.len() {
+      return Err(Error::DimsMismatch { cells: cells.len(), dims });
+    }
+    Ok(Grid { cells, rows: dims.rows, cols: dims.cols })
+  }
+  pub fn new_unchecked(cells: Vec<Cell>, dims: Dims) -> Self {
+    Self::new(cells, dims).unwrap()
+  }
+  // Every cell `cell`
+  pub fn filled(dims: Dims, cell: Cell) -> Self {
+    Grid { cells: vec![cell; dims.len()], rows: dims.rows, cols: dims.cols }
+  }
+  pub fn rows(&self) -> usize {
+    self.rows
+  }
+  pub fn cols(&self) -> usize {
+    self.cols
+  }
+  pub fn dims(&self) -> Dims {
+    Dims::new(self.rows, self.cols)
+  }
+  pub fn cells(&self) -> &[Cell] {
+    &self.cells
+  }
+  pub fn into_cells(self) -> Vec<Cell> {
+    self.cells
+  }
+  // The index of `(row, col)` in row order, if it's on the board
+  pub fn index_of(&self, row: usize, col: usize) -> Option<usize> {
+    if row < self.rows && col < self.cols {
+      Some(self.dims().index(row, col))
+    } else {
+      None
+    }
+  }
+  pub fn get(&self, row: usize, col: usize) -> Option<Cell> {
+    self.index_of(row, col).map(|idx| self.cells[idx])
+  }
+  pub fn set(&mut self, row: usize, col: usize, cell: Cell) -> Result<(), Error> {
+    let idx = self.index_of(row, col).ok_or(Error::OffBoard { row, col, dims: self.dims() })?;
+    self.cells[idx] = cell;
+    Ok(())
+  }
+}
+
+impl Deref for Grid {
+  type Target = [Cell];
+
+  fn deref(&self) -> &[Cell] {
+    &self.cells
+  }
+}
+
+impl DerefMut for Grid {
+  fn deref_mut(&mut self) -> &mut [Cell] {
+    &mut self.cells
+  }
+}
+
+// Panics off the board, like `get(row, col).unwrap()`
+impl Index<(usize, usize)> for Grid {
+  type Output = Cell;
+
+  fn index(&self, (row, col): (usize, usize)) -> &Cell {
+    let idx = self.index_of(row, col).unwrap_or_else(|| panic!("({}, {}) is off a {}x{} board", row, col, self.rows, self.cols));
+    &self.cells[idx]
+  }
+}
+
+impl IndexMut<(usize, usize)> for Grid {
+  fn index_mut(&mut self, (row, col): (usize, usize)) -> &mut Cell {
+    let idx = self.index_of(row, col).unwrap_or_else(|| panic!("({}, {}) is off a {}x{} board", row, col, self.rows, self.cols));
+    &mut self.cells[idx]
+  }
+}
+
+// Indexing with `(row, col)` keeps plain indices from reaching the slice
+// through `Deref`, so they get their own impls
+impl Index<usize> for Grid {
+  type Output = Cell;
+
+  fn index(&self, idx: usize) -> &Cell {
+    &self.cells[idx]
+  }
+}
+
+impl IndexMut<usize> for Grid {
+  fn index_mut(&mut self, idx: usize) -> &mut Cell {
+    &mut self.cells[idx]
+  }
+}
+
+// Marks the tractor's region around `from` `Reachable`, `from` being a cell
+// of floor or a passage
+pub fn fill_reachable_cells(from: usize, grid: &mut Grid) -> Result<(), Error> {
+  match grid.cells.get(from) {
+    None => return Err(Error::OutOfBounds { idx: from, len: grid.len() }),
+    Some(cell) if *cell != Cell::Unreachable && !is_passage(*cell) => return Err(Error::NotFloor(from)),
+    _ => (),
+  }
+  let dims = grid.dims();
+  fill_reachable_cells_unchecked(from, grid, dims);
+  Ok(())
+}
+
+pub fn fill_reachable_cells_unchecked(from: usize, grid: &mut [Cell], dims: Dims) {
+  let reached = walk_from(from, grid, dims);
+  for (cell, reached) in grid.iter_mut().zip(reached) {
+    if reached && !is_passage(*cell) {
+      assert!(*cell == Cell::Unreachable);
+      *cell = Cell::Reachable;
+    }
+  }
+}
+
+pub fn find_reachable_empty_cells(from: usize, grid: &Grid) -> HashSet<usize> {
+  find_reachable_empty_cells_unchecked(from, grid, grid.dims())
+}
+
+pub fn find_reachable_empty_cells_unchecked(from: usize, grid: &[Cell], dims: Dims) -> HashSet<usize> {
+  let reached = walk_from(from, grid, dims);
+  (0..grid.len()).filter(|idx| reached[*idx] && !is_passage(grid[*idx])).collect()
+}
+
+// Marks every cell the tractor can walk to from `from`, passages included
+fn walk_from(from: usize, grid: &[Cell], dims: Dims) -> Vec<bool> {
+  let mut reached = vec![false; grid.len()];
+  reached[from] = true;
   let mut stack = vec![from];
   while let Some(current) = stack.pop() {
-    for neighbor in &graph[&current] {
-      if !visited.contains(neighbor) {
-        visited.insert(*neighbor);
-        stack.push(*neighbor);
+    for dir in DIRECTIONS {
+      if let Some(next) = slide(current, *dir, grid, dims) {
+        if !reached[next] && (grid[next] == Cell::Unreachable || is_passage(grid[next])) {
+          reached[next] = true;
+          stack.push(next);
+        }
       }
     }
   }
-  visited
+  reached
 }
 
-fn grid_to_movement_graph(grid: &Vec<Cell>, board_size: usize) -> HashMap<usize, Vec<usize>> {
-  let mut graph = HashMap::new();
-  for idx in 0..grid.len() {
-    if grid[idx] != Cell::Unreachable {
-      continue;
+// Ice and one-way cells the tractor can stand on, but never pushes from, so
+// they are left out of its region
+fn is_passage(cell: Cell) -> bool {
+  matches!(cell, Cell::Ice | Cell::OneWay(_))
+}
+
+// Where the tractor stops stepping from `from` towards `dir`: the next cell,
+// or further across ice until it steps onto floor or something stops it.
+// One-way cells only let it through heading their way.
+pub fn slide(from: usize, dir: Direction, grid: &[Cell], dims: Dims) -> Option<usize> {
+  if !grid[from].allows(dir) {
+    return None;
+  }
+  let walkable = |idx: &usize| {
+    (matches!(grid[*idx], Cell::Unreachable | Cell::Reachable) || is_passage(grid[*idx])) && grid[*idx].allows(dir)
+  };
+  let mut current = step(from, dir, grid, dims).filter(walkable)?;
+  while grid[current] == Cell::Ice {
+    match step(current, dir, grid, dims).filter(walkable) {
+      Some(next) => current = next,
+      None => break,
     }
-    let mut edges = vec![];
-    let row = idx / board_size;
-    let col = idx % board_size;
-    if row != 0 {
-      let up = to_index(row - 1, col, board_size);
-      if grid[up] == Cell::Unreachable {
-        edges.push(up);
+  }
+  Some(current)
+}
+
+// Like `Direction::apply`, but stepping into a portal comes out past its partner,
+// still heading `dir`. Unpaired portals, and portals right behind another,
+// can't be stepped into.
+pub fn step(from: usize, dir: Direction, grid: &[Cell], dims: Dims) -> Option<usize> {
+  let next = dir.apply(from, dims)?;
+  let id = match grid[next] {
+    Cell::Portal(id) => id,
+    _ => return Some(next),
+  };
+  let partner = (0..grid.len()).find(|idx| *idx != next && grid[*idx] == Cell::Portal(id))?;
+  dir.apply(partner, dims).filter(|idx| !matches!(grid[*idx], Cell::Portal(_)))
+}
+
+pub fn to_index(row: usize, col: usize, width: usize) -> usize {
+  row * width + col
+}
+
+// Steps the tractor needs from `from` to every `Reachable` cell of `grid`, a
+// slide across ice counting as one and one-way cells only crossed their way
+pub fn walking_distances(from: usize, grid: &Grid) -> Vec<Option<usize>> {
+  walking_distances_unchecked(from, grid, grid.dims())
+}
+
+pub fn walking_distances_unchecked(from: usize, grid: &[Cell], dims: Dims) -> Vec<Option<usize>> {
+  let mut dist = vec![None; grid.len()];
+  dist[from] = Some(0);
+  let mut queue = VecDeque::new();
+  queue.push_back(from);
+  while let Some(current) = queue.pop_front() {
+    for idx in DIRECTIONS.iter().filter_map(|dir| slide(current, *dir, grid, dims)) {
+      if (grid[idx] == Cell::Reachable || is_passage(grid[idx])) && dist[idx].is_none() {
+        dist[idx] = Some(dist[current].unwrap() + 1);
+        queue.push_back(idx);
       }
     }
-    if row != board_size - 1 {
-      let down = to_index(row + 1, col, board_size);
-      if grid[down] == Cell::Unreachable {
-        edges.push(down);
+  }
+  dist
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use std::collections::hash_map::HashMap;
+
+  // What `walk_from` walks without building it. Passages are nodes too, the
+  // tractor may come to rest on one, but only floor counts as empty cells.
+  // Edges only go the way they can be walked.
+  fn grid_to_movement_graph(grid: &[Cell], dims: Dims) -> HashMap<usize, Vec<usize>> {
+    let mut graph = HashMap::new();
+    for idx in 0..grid.len() {
+      if grid[idx] != Cell::Unreachable && !is_passage(grid[idx]) {
+        continue;
       }
+      let edges = DIRECTIONS.iter()
+        .filter_map(|dir| slide(idx, *dir, grid, dims))
+        .filter(|next| grid[*next] == Cell::Unreachable || is_passage(grid[*next]))
+        .collect();
+      graph.insert(idx, edges);
     }
-    if col != 0 {
-      let left = to_index(row, col - 1, board_size);
-      if grid[left] == Cell::Unreachable {
-        edges.push(left);
+    graph
+  }
+
+  fn walk_graph_from(from: usize, graph: &HashMap<usize, Vec<usize>>) -> HashSet<usize> {
+    let mut visited = HashSet::new();
+    visited.insert(from);
+    let mut stack = vec![from];
+    while let Some(current) = stack.pop() {
+      for neighbor in &graph[&current] {
+        if !visited.contains(neighbor) {
+          visited.insert(*neighbor);
+          stack.push(*neighbor);
+        }
       }
     }
-    if col != board_size - 1 {
-      let right = to_index(row, col + 1, board_size);
-      if grid[right] == Cell::Unreachable {
-        edges.push(right);
-      }
+    visited
+  }
+
+  #[test]
+  fn test_walk_movement_graph() {
+    let grid = vec![
+      Cell::Hole, Cell::Unreachable, Cell::Unreachable, Cell::Hole,
+      Cell::Unreachable, Cell::Boulder, Cell::Unreachable, Cell::Boulder,
+      Cell::Boulder, Cell::Unreachable, Cell::Unreachable, Cell::Unreachable,
+      Cell::Hole, Cell::Unreachable, Cell::Boulder, Cell::Hole,
+    ];
+    let graph = grid_to_movement_graph(&grid, Dims::square(4));
+    let reachable = walk_graph_from(1, &graph);
+    assert_eq!(reachable.len(), 7);
+    assert!(reachable.contains(&1));
+    assert!(reachable.contains(&2));
+    assert!(reachable.contains(&6));
+    assert!(reachable.contains(&9));
+    assert!(reachable.contains(&10));
+    assert!(reachable.contains(&11));
+    assert!(reachable.contains(&13));
+  }
+
+  #[test]
+  fn test_walk_movement_graph_2() {
+    let grid = vec![
+      Cell::Hole, Cell::Unreachable, Cell::Unreachable, Cell::BoulderInHole,
+      Cell::Boulder, Cell::Unreachable, Cell::Unreachable, Cell::Unreachable,
+      Cell::Unreachable, Cell::Unreachable, Cell::Unreachable, Cell::Unreachable,
+      Cell::BoulderInHole, Cell::Unreachable, Cell::Unreachable, Cell::BoulderInHole,
+    ];
+    let graph = grid_to_movement_graph(&grid, Dims::square(4));
+    let reachable = walk_graph_from(8, &graph);
+    assert_eq!(reachable.len(), 11);
+    assert!(reachable.contains(&1));
+    assert!(reachable.contains(&2));
+    assert!(reachable.contains(&5));
+    assert!(reachable.contains(&6));
+    assert!(reachable.contains(&7));
+    assert!(reachable.contains(&8));
+    assert!(reachable.contains(&9));
+    assert!(reachable.contains(&10));
+    assert!(reachable.contains(&11));
+    assert!(reachable.contains(&13));
+    assert!(reachable.contains(&14));
+  }
+
+  #[test]
+  fn test_build_movement_graph() {
+    let grid = vec![
+      Cell::Hole, Cell::Unreachable, Cell::Unreachable, Cell::Hole,
+      Cell::Unreachable, Cell::Boulder, Cell::Unreachable, Cell::Boulder,
+      Cell::Boulder, Cell::Unreachable, Cell::Unreachable, Cell::Unreachable,
+      Cell::Hole, Cell::Unreachable, Cell::Boulder, Cell::Hole,
+    ];
+
+    let graph = grid_to_movement_graph(&grid, Dims::square(4));
+    assert_eq!(graph.len(), 8);
+
+    assert!(!graph.contains_key(&0));
+
+    assert!(graph.contains_key(&1));
+    assert_eq!(graph[&1].len(), 1);
+    assert_eq!(graph[&1][0], 2);
+
+    assert!(graph.contains_key(&2));
+    assert_eq!(graph[&2].len(), 2);
+    assert_eq!(graph[&2][0], 6);
+    assert_eq!(graph[&2][1], 1);
+
+    assert!(!graph.contains_key(&3));
+
+    assert!(graph.contains_key(&4));
+    assert_eq!(graph[&4].len(), 0);
+
+    assert!(!graph.contains_key(&5));
+
+    assert!(graph.contains_key(&6));
+    assert_eq!(graph[&6].len(), 2);
+    assert_eq!(graph[&6][0], 2);
+    assert_eq!(graph[&6][1], 10);
+
+    assert!(!graph.contains_key(&7));
+
+    assert!(!graph.contains_key(&8));
+
+    assert!(graph.contains_key(&9));
+    assert_eq!(graph[&9].len(), 2);
+    assert_eq!(graph[&9][0], 13);
+    assert_eq!(graph[&9][1], 10);
+
+    assert!(graph.contains_key(&10));
+    assert_eq!(graph[&10].len(), 3);
+    assert_eq!(graph[&10][0], 6);
+    assert_eq!(graph[&10][1], 9);
+    assert_eq!(graph[&10][2], 11);
+
+    assert!(graph.contains_key(&11));
+    assert_eq!(graph[&11].len(), 1);
+    assert_eq!(graph[&11][0], 10);
+
+    assert!(!graph.contains_key(&12));
+
+    assert!(graph.contains_key(&13));
+    assert_eq!(graph[&13].len(), 1);
+    assert_eq!(graph[&13][0], 9);
+
+    assert!(!graph.contains_key(&14));
+
+    assert!(!graph.contains_key(&15));
+  }
+
+  #[test]
+  fn test_walk_matches_movement_graph() {
+    let grid = vec![
+      Cell::Unreachable, Cell::Ice, Cell::Ice, Cell::Unreachable,
+      Cell::Block, Cell::Unreachable, Cell::OneWay(Direction::Up), Cell::Boulder,
+      Cell::Portal(0), Cell::Unreachable, Cell::Block, Cell::Portal(0),
+      Cell::Unreachable, Cell::Hole, Cell::Unreachable, Cell::Unreachable,
+    ];
+    let dims = Dims::square(4);
+    let graph = grid_to_movement_graph(&grid, dims);
+    for from in graph.keys() {
+      let reached = walk_from(*from, &grid, dims);
+      assert_eq!((0..grid.len()).filter(|idx| reached[*idx]).collect::<HashSet<usize>>(), walk_graph_from(*from, &graph));
     }
-    graph.insert(idx, edges);
   }
-  graph
-}
 
-pub fn to_index(row: usize, col: usize, width: usize) -> usize {
-  row * width + col
+  #[test]
+  fn test_fill_stops_at_walls() {
+    let mut grid = Grid::new(vec![
+      Cell::Unreachable, Cell::Wall, Cell::Unreachable,
+      Cell::Unreachable, Cell::Wall, Cell::Unreachable,
+      Cell::Unreachable, Cell::Wall, Cell::Unreachable,
+    ], Dims::square(3)).unwrap();
+    fill_reachable_cells(0, &mut grid).unwrap();
+    assert_eq!(grid.cells(), [
+      Cell::Reachable, Cell::Wall, Cell::Unreachable,
+      Cell::Reachable, Cell::Wall, Cell::Unreachable,
+      Cell::Reachable, Cell::Wall, Cell::Unreachable,
+    ]);
+  }
+
+  #[test]
+  fn test_fill_rejects_bad_input() {
+    let cells = vec![Cell::Unreachable, Cell::Wall, Cell::Unreachable];
+    assert_eq!(Grid::new(cells.clone(), Dims::square(2)), Err(Error::DimsMismatch { cells: 3, dims: Dims::square(2) }));
+    let mut grid = Grid::new(cells.clone(), Dims::new(1, 3)).unwrap();
+    assert_eq!(fill_reachable_cells(1, &mut grid), Err(Error::NotFloor(1)));
+    assert_eq!(fill_reachable_cells(3, &mut grid), Err(Error::OutOfBounds { idx: 3, len: 3 }));
+    assert_eq!(grid.cells(), cells.as_slice());
+  }
+
+  #[test]
+  fn test_sliding_on_ice() {
+    // Stepping right from 0 slides all the way to 3 without stopping on the
+    // ice, so 5 below it is out of reach. From 5 everything else is.
+    let dims = Dims::new(2, 4);
+    let grid = Grid::new(vec![
+      Cell::Unreachable, Cell::Ice, Cell::Ice, Cell::Unreachable,
+      Cell::Block, Cell::Unreachable, Cell::Block, Cell::Block,
+    ], dims).unwrap();
+    let graph = grid_to_movement_graph(&grid, dims);
+    assert_eq!(graph[&0], vec![3]);
+    assert_eq!(graph[&5], vec![1]);
+    assert_eq!(graph[&1], vec![5, 0, 3]);
+    assert_eq!(find_reachable_empty_cells(0, &grid), [0, 3].iter().cloned().collect());
+    assert_eq!(find_reachable_empty_cells(5, &grid), [0, 3, 5].iter().cloned().collect());
+    let mut filled = grid.clone();
+    fill_reachable_cells(5, &mut filled).unwrap();
+    let dist = walking_distances(5, &filled);
+    assert_eq!(dist[1], Some(1));
+    assert_eq!(dist[0], Some(2));
+    assert_eq!(dist[3], Some(2));
+    assert_eq!(dist[2], None);
+  }
+
+  #[test]
+  fn test_one_way_edges() {
+    let dims = Dims::new(1, 3);
+    let grid = Grid::new(vec![
+      Cell::Unreachable, Cell::OneWay(Direction::Right), Cell::Unreachable,
+    ], dims).unwrap();
+    let graph = grid_to_movement_graph(&grid, dims);
+    assert_eq!(graph[&0], vec![1]);
+    assert_eq!(graph[&1], vec![2]);
+    assert!(graph[&2].is_empty());
+    assert_eq!(find_reachable_empty_cells(0, &grid), [0, 2].iter().cloned().collect());
+    assert_eq!(find_reachable_empty_cells(2, &grid), [2].iter().cloned().collect());
+  }
+
+  #[test]
+  fn test_portals_join_regions() {
+    // The block column splits the board, only the portals connect its sides
+    let dims = Dims::new(2, 5);
+    let grid = Grid::new(vec![
+      Cell::Unreachable, Cell::Portal(0), Cell::Block, Cell::Unreachable, Cell::Unreachable,
+      Cell::Unreachable, Cell::Unreachable, Cell::Block, Cell::Portal(0), Cell::Unreachable,
+    ], dims).unwrap();
+    assert_eq!(step(0, Direction::Right, &grid, dims), Some(9));
+    assert_eq!(step(9, Direction::Left, &grid, dims), Some(0));
+    assert_eq!(step(6, Direction::Up, &grid, dims), Some(3));
+    let reachable = find_reachable_empty_cells(5, &grid);
+    assert_eq!(reachable, [0, 3, 4, 5, 6, 9].iter().cloned().collect());
+    let mut filled = grid.clone();
+    fill_reachable_cells(5, &mut filled).unwrap();
+    assert_eq!(walking_distances(5, &filled)[4], Some(3));
+  }
+
+  #[test]
+  fn test_walking_distances() {
+    let mut grid = Grid::new(vec![
+      Cell::Unreachable, Cell::Unreachable, Cell::Unreachable,
+      Cell::Block, Cell::Block, Cell::Unreachable,
+      Cell::Hole, Cell::Unreachable, Cell::Unreachable,
+    ], Dims::square(3)).unwrap();
+    fill_reachable_cells(0, &mut grid).unwrap();
+    let dist = walking_distances(0, &grid);
+    assert_eq!(dist, vec![
+      Some(0), Some(1), Some(2),
+      None, None, Some(3),
+      None, Some(5), Some(4),
+    ]);
+  }
+
+  #[test]
+  fn test_walking_distances_rectangular() {
+    let dims = Dims::new(2, 5);
+    let mut grid = Grid::new(vec![
+      Cell::Unreachable, Cell::Block, Cell::Unreachable, Cell::Unreachable, Cell::Unreachable,
+      Cell::Unreachable, Cell::Unreachable, Cell::Unreachable, Cell::Block, Cell::Unreachable,
+    ], dims).unwrap();
+    fill_reachable_cells(0, &mut grid).unwrap();
+    let dist = walking_distances(0, &grid);
+    assert_eq!(dist, vec![
+      Some(0), None, Some(4), Some(5), Some(6),
+      Some(1), Some(2), Some(3), None, Some(7),
+    ]);
+  }
+
+  #[test]
+  fn test_grid_indexing() {
+    let mut grid = Grid::filled(Dims::new(2, 3), Cell::Unreachable);
+    assert_eq!((grid.rows(), grid.cols()), (2, 3));
+    grid.set(1, 2, Cell::Hole).unwrap();
+    grid[(0, 1)] = Cell::Wall;
+    assert_eq!(grid[5], Cell::Hole);
+    assert_eq!(grid.get(0, 1), Some(Cell::Wall));
+    assert_eq!(grid.get(0, 3), None);
+    assert_eq!(grid.set(2, 0, Cell::Wall), Err(Error::OffBoard { row: 2, col: 0, dims: Dims::new(2, 3) }));
+    assert_eq!(grid.iter().filter(|cell| **cell == Cell::Unreachable).count(), 4);
+  }
 }
@@ -0,0 +1,175 @@
+use crate::cell::Cell;
+use crate::deadlock::push_distances_to;
+use crate::grid::Dims;
+
+// Lower bound on the pushes left in `grid`: every loose boulder has to end up
+// in a different empty hole of its color, so the cheapest such pairing by push
+// distance can't be beaten. `None` when no pairing exists at all.
+pub fn matching_lower_bound(grid: &[Cell], dims: Dims) -> Option<usize> {
+  MatchingBound::new(grid, dims).estimate(grid)
+}
+
+// Push distances into each hole of a board, worked out once so many states of
+// the same level can be estimated cheaply
+pub struct MatchingBound {
+  holes: Vec<usize>,
+  distances: Vec<Vec<Option<usize>>>,
+}
+
+impl MatchingBound {
+  pub fn new(grid: &[Cell], dims: Dims) -> Self {
+    let holes = (0..grid.len())
+      .filter(|idx| grid[*idx].is_hole())
+      .collect::<Vec<usize>>();
+    // One push can slide a boulder any distance across ice, so there every
+    // boulder off its hole only counts for one
+    let icy = grid.iter().any(|cell| *cell == Cell::Ice || *cell == Cell::BoulderOnIce);
+    let distances = holes.iter()
+      .map(|hole| {
+        let dist = push_distances_to(&[*hole], grid, dims);
+        if icy { dist.iter().map(|d| d.map(|d| d.min(1))).collect() } else { dist }
+      })
+      .collect();
+    MatchingBound { holes, distances }
+  }
+  pub fn estimate(&self, state: &[Cell]) -> Option<usize> {
+    let boulders = (0..state.len())
+      .filter(|idx| state[*idx].is_loose_boulder())
+      .collect::<Vec<usize>>();
+    let open = (0..self.holes.len())
+      .filter(|h| state[self.holes[*h]].is_open_hole())
+      .collect::<Vec<usize>>();
+    if boulders.len() != open.len() {
+      return None;
+    }
+    let costs = boulders.iter()
+      .map(|boulder| {
+        open.iter()
+          .map(|h| self.distances[*h][*boulder].filter(|_| state[self.holes[*h]].accepts(state[*boulder])))
+          .collect()
+      })
+      .collect::<Vec<Vec<Option<usize>>>>();
+    min_cost_assignment(&costs)
+  }
+}
+
+// Hungarian algorithm on a square cost matrix, `None` entries can't be paired
+fn min_cost_assignment(costs: &[Vec<Option<usize>>]) -> Option<usize> {
+  let n = costs.len();
+  // Anything dearer than every real pairing combined stands in for `None`
+  let missing = costs.iter().flatten().flatten().sum::<usize>() as i64 + 1;
+  let cost = |row: usize, col: usize| costs[row - 1][col - 1].map_or(missing, |c| c as i64);
+  // Potentials and matching are 1-based, column 0 is a sentinel
+  let mut u = vec![0i64; n + 1];
+  let mut v = vec![0i64; n + 1];
+  let mut matched = vec![0usize; n + 1];
+  let mut way = vec![0usize; n + 1];
+  for row in 1..=n {
+    matched[0] = row;
+    let mut col = 0;
+    let mut min = vec![i64::MAX; n + 1];
+    let mut used = vec![false; n + 1];
+    loop {
+      used[col] = true;
+      let current = matched[col];
+      let mut delta = i64::MAX;
+      let mut next = 0;
+      for other in 1..=n {
+        if used[other] {
+          continue;
+        }
+        let reduced = cost(current, other) - u[current] - v[other];
+        if reduced < min[other] {
+          min[other] = reduced;
+          way[other] = col;
+        }
+        if min[other] < delta {
+          delta = min[other];
+          next = other;
+        }
+      }
+      for other in 0..=n {
+        if used[other] {
+          u[matched[other]] += delta;
+          v[other] -= delta;
+        } else {
+          min[other] -= delta;
+        }
+      }
+      col = next;
+      if matched[col] == 0 {
+        break;
+      }
+    }
+    // Flip the augmenting path back to the sentinel
+    while col != 0 {
+      let prev = way[col];
+      matched[col] = matched[prev];
+      col = prev;
+    }
+  }
+  let mut total = 0;
+  for col in 1..=n {
+    total += costs[matched[col] - 1][col - 1]?;
+  }
+  Some(total)
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::cell::Color;
+  use crate::deadlock::push_distances;
+
+  #[test]
+  fn test_matching_beats_nearest_hole() {
+    // Both boulders sit closest to the left hole, but only one can have it
+    let grid = vec![
+      Cell::Unreachable, Cell::Unreachable, Cell::Unreachable, Cell::Unreachable, Cell::Unreachable,
+      Cell::Unreachable, Cell::Hole, Cell::Boulder, Cell::Unreachable, Cell::Unreachable,
+      Cell::Unreachable, Cell::Unreachable, Cell::Boulder, Cell::Unreachable, Cell::Unreachable,
+      Cell::Unreachable, Cell::Unreachable, Cell::Unreachable, Cell::Unreachable, Cell::Unreachable,
+      Cell::Unreachable, Cell::Unreachable, Cell::Unreachable, Cell::Hole, Cell::Unreachable,
+    ];
+    let nearest = push_distances(&grid, Dims::square(5));
+    assert_eq!(nearest[7].unwrap() + nearest[12].unwrap(), 3);
+    assert_eq!(matching_lower_bound(&grid, Dims::square(5)), Some(4));
+  }
+
+  #[test]
+  fn test_matching_by_color() {
+    let mut grid = vec![
+      Cell::ColoredHole(Color::Red), Cell::Unreachable, Cell::ColoredBoulder(Color::Green),
+      Cell::ColoredBoulder(Color::Red), Cell::Unreachable, Cell::ColoredHole(Color::Green),
+    ];
+    assert_eq!(matching_lower_bound(&grid, Dims::new(1, 6)), Some(6));
+    grid.swap(2, 3);
+    assert_eq!(matching_lower_bound(&grid, Dims::new(1, 6)), Some(4));
+  }
+
+  #[test]
+  fn test_assignment() {
+    let costs = vec![
+      vec![Some(4), Some(1), Some(3)],
+      vec![Some(2), Some(0), Some(5)],
+      vec![Some(3), Some(2), Some(2)],
+    ];
+    assert_eq!(min_cost_assignment(&costs), Some(5));
+    let blocked = vec![
+      vec![Some(1), None],
+      vec![Some(1), None],
+    ];
+    assert_eq!(min_cost_assignment(&blocked), None);
+    assert_eq!(min_cost_assignment(&[]), Some(0));
+  }
+
+  #[test]
+  fn test_counts_must_match() {
+    let grid = vec![
+      Cell::Hole, Cell::Unreachable, Cell::Unreachable,
+      Cell::Unreachable, Cell::Unreachable, Cell::Unreachable,
+      Cell::Unreachable, Cell::Unreachable, Cell::Hole,
+    ];
+    assert_eq!(matching_lower_bound(&grid, Dims::square(3)), None);
+  }
+}
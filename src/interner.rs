@@ -0,0 +1,207 @@
+use std::borrow::Cow;
+use std::hash::{Hash, Hasher};
+
+use hashbrown::HashTable;
+use rustc_hash::FxHasher;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+// Stores each distinct value once, at the position it was first interned at,
+// which serves as its handle. The lookup table only holds handles and keys,
+// so no value is kept twice and the arena is only read to tell values with
+// the same key apart. Removed values leave a gap behind and handles are
+// never reused.
+#[derive(Clone, Debug)]
+pub struct Interner<T, A = Vec<Option<T>>> {
+  arena: A,
+  index: HashTable<(u64, usize)>,
+  // Hashes values for the lookup table
+  key: fn(&T) -> u64,
+}
+
+// Where an `Interner` keeps its values, by handle. Handles are handed out in
+// push order.
+pub trait Arena<T: Clone> {
+  fn push(&mut self, value: T);
+  fn get(&self, handle: usize) -> Option<Cow<'_, T>>;
+  fn take(&mut self, handle: usize) -> Option<T>;
+  // Counting gaps
+  fn len(&self) -> usize;
+  fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+}
+
+impl<T: Clone> Arena<T> for Vec<Option<T>> {
+  fn push(&mut self, value: T) {
+    Vec::push(self, Some(value));
+  }
+  fn get(&self, handle: usize) -> Option<Cow<'_, T>> {
+    self.as_slice().get(handle).and_then(Option::as_ref).map(Cow::Borrowed)
+  }
+  fn take(&mut self, handle: usize) -> Option<T> {
+    self.get_mut(handle)?.take()
+  }
+  fn len(&self) -> usize {
+    Vec::len(self)
+  }
+}
+
+impl<T: Clone, A: Arena<T> + ?Sized> Arena<T> for Box<A> {
+  fn push(&mut self, value: T) {
+    (**self).push(value)
+  }
+  fn get(&self, handle: usize) -> Option<Cow<'_, T>> {
+    (**self).get(handle)
+  }
+  fn take(&mut self, handle: usize) -> Option<T> {
+    (**self).take(handle)
+  }
+  fn len(&self) -> usize {
+    (**self).len()
+  }
+}
+
+// Values here are bit-packed states, a few machine words each, which FxHash
+// hashes much faster than the default SipHash. Nothing hashed
+// comes from outside, so its weakness against crafted keys doesn't matter.
+fn hash_of<T: Hash>(value: &T) -> u64 {
+  let mut hasher = FxHasher::default();
+  value.hash(&mut hasher);
+  hasher.finish()
+}
+
+impl<T: Hash + Eq + Clone> Interner<T> {
+  pub fn new() -> Self {
+    Self::with_key(hash_of)
+  }
+}
+
+impl<T: Eq + Clone> Interner<T> {
+  // Looks values up by `key` rather than their `Hash`, so that callers who
+  // already know a value's key can `find` it without building the value
+  pub fn with_key(key: fn(&T) -> u64) -> Self {
+    Self::from_arena(vec![], key)
+  }
+}
+
+impl<T: Eq + Clone, A: Arena<T>> Interner<T, A> {
+  // Takes the arena as is, gaps and all. Equal values are kept at the first
+  // of their handles only.
+  pub fn from_arena(mut arena: A, key: fn(&T) -> u64) -> Self {
+    let mut index = HashTable::new();
+    for handle in 0..arena.len() {
+      let value = match arena.get(handle) {
+        Some(value) => value.into_owned(),
+        None => continue,
+      };
+      let hash = key(&value);
+      let seen = index.find(hash, |(other, seen): &(u64, usize)| {
+        *other == hash && arena.get(*seen).is_some_and(|seen| *seen == value)
+      });
+      if seen.is_some() {
+        arena.take(handle);
+      } else {
+        index.insert_unique(hash, (hash, handle), |(hash, _)| *hash);
+      }
+    }
+    Interner { arena, index, key }
+  }
+  pub fn get(&self, value: &T) -> Option<usize> {
+    self.find((self.key)(value), |other| other == value)
+  }
+  // The value with key `key` that `matches` accepts. Other values with the
+  // same key may be put to `matches` too, so it has to tell them apart in
+  // full.
+  pub fn find<F: Fn(&T) -> bool>(&self, key: u64, matches: F) -> Option<usize> {
+    self.index.find(key, |(other, handle)| *other == key && self.arena.get(*handle).is_some_and(|value| matches(&value)))
+      .map(|(_, handle)| *handle)
+  }
+  // The handle of `value`, interning it first if it's new
+  pub fn intern(&mut self, value: T) -> usize {
+    match self.get(&value) {
+      Some(handle) => handle,
+      None => self.push(value),
+    }
+  }
+  fn push(&mut self, value: T) -> usize {
+    let handle = self.arena.len();
+    let key = (self.key)(&value);
+    self.index.insert_unique(key, (key, handle), |(key, _)| *key);
+    self.arena.push(value);
+    handle
+  }
+  pub fn resolve(&self, handle: usize) -> Option<Cow<'_, T>> {
+    self.arena.get(handle)
+  }
+  pub fn remove(&mut self, handle: usize) -> Option<T> {
+    let value = self.arena.take(handle)?;
+    let key = (self.key)(&value);
+    if let Ok(entry) = self.index.find_entry(key, |(_, other)| *other == handle) {
+      entry.remove();
+    }
+    Some(value)
+  }
+  pub fn len(&self) -> usize {
+    self.index.len()
+  }
+  pub fn is_empty(&self) -> bool {
+    self.index.is_empty()
+  }
+  // The handle the next new value will get
+  pub fn next_handle(&self) -> usize {
+    self.arena.len()
+  }
+  // In handle order
+  pub fn iter(&self) -> impl Iterator<Item = (usize, Cow<'_, T>)> {
+    (0..self.arena.len()).filter_map(move |handle| self.arena.get(handle).map(|value| (handle, value)))
+  }
+}
+
+impl<T: Hash + Eq + Clone> Default for Interner<T> {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl<T: Hash + Eq + Clone> From<Vec<Option<T>>> for Interner<T> {
+  fn from(arena: Vec<Option<T>>) -> Self {
+    Self::from_arena(arena, hash_of)
+  }
+}
+
+// Gaps included, whatever the arena
+impl<T: Serialize + Clone, A: Arena<T>> Serialize for Interner<T, A> {
+  fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq((0..self.arena.len()).map(|handle| self.arena.get(handle)))
+  }
+}
+
+impl<'de, T: Deserialize<'de> + Hash + Eq + Clone> Deserialize<'de> for Interner<T> {
+  fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+    Vec::<Option<T>>::deserialize(deserializer).map(Interner::from)
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn test_intern() {
+    let mut interner = Interner::new();
+    assert_eq!(interner.intern("a".to_string()), 0);
+    assert_eq!(interner.intern("b".to_string()), 1);
+    assert_eq!(interner.intern("a".to_string()), 0);
+    assert_eq!(interner.len(), 2);
+    assert_eq!(interner.remove(0), Some("a".to_string()));
+    assert_eq!(interner.get(&"a".to_string()), None);
+    assert_eq!(interner.intern("a".to_string()), 2);
+    assert_eq!(interner.iter().map(|(handle, _)| handle).collect::<Vec<usize>>(), vec![1, 2]);
+    let copy = Interner::from(vec![None, Some("b".to_string()), Some("a".to_string())]);
+    assert_eq!(copy.get(&"a".to_string()), Some(2));
+    assert_eq!(copy.next_handle(), 3);
+    let by_length: Interner<String> = Interner::from_arena(vec![Some("ab".into()), Some("c".into())], |s| s.len() as u64);
+    assert_eq!(by_length.find(2, |s| s.starts_with('a')), Some(0));
+    assert_eq!(by_length.find(2, |s| s.starts_with('x')), None);
+  }
+}
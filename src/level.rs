@@ -0,0 +1,140 @@
+use serde::{Deserialize, Serialize};
+
+use crate::cell::Cell;
+use crate::error::Error;
+use crate::grid::{Dims, Grid};
+use crate::validate::{validate, Problem};
+
+// A puzzle as handed to the solver: the board, its shape, where the
+// tractor starts, and whatever is known about it
+#[derive(Clone, Debug, PartialEq)]
+pub struct Level {
+  pub grid: Vec<Cell>,
+  pub dims: Dims,
+  pub tractor: usize,
+  pub metadata: Metadata,
+}
+
+// Anything known about where a level came from, all of it optional
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct Metadata {
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub title: Option<String>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub author: Option<String>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub seed: Option<u64>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub pushes: Option<usize>,
+}
+
+impl Level {
+  // With no metadata
+  pub fn new(grid: Vec<Cell>, dims: Dims, tractor: usize) -> Self {
+    Level { grid, dims, tractor, metadata: Metadata::default() }
+  }
+  pub fn with_metadata(self, metadata: Metadata) -> Self {
+    Level { metadata, ..self }
+  }
+  pub fn board(&self) -> Grid {
+    Grid::new_unchecked(self.grid.clone(), self.dims)
+  }
+  // Everything wrong with the level that would trip up exploring it, empty
+  // for a sound one. See `validate::Problem`.
+  pub fn validate(&self) -> Vec<Problem> {
+    validate(self)
+  }
+  // The board with the tractor's region marked `Reachable`
+  pub fn initial_state(&self) -> Vec<Cell> {
+    let mut state = self.grid.clone();
+    for cell in &mut state {
+      if *cell == Cell::Reachable {
+        *cell = Cell::Unreachable;
+      }
+    }
+    crate::grid::fill_reachable_cells_unchecked(self.tractor, &mut state, self.dims);
+    state
+  }
+}
+
+// Lays a level out piece by piece on an empty floor, for levels written in
+// code:
+//
+//   GridBuilder::new(3, 3).wall(1, 1).boulder(0, 1).hole(0, 2).tractor(2, 0).build()
+//
+// Placing a piece over another replaces it, and a second tractor moves the
+// first. Nothing is checked until `build`.
+#[derive(Clone, Debug)]
+pub struct GridBuilder {
+  grid: Grid,
+  tractor: Option<(usize, usize)>,
+  // The first piece placed off the board
+  error: Option<Error>,
+}
+
+impl GridBuilder {
+  pub fn new(rows: usize, cols: usize) -> Self {
+    GridBuilder { grid: Grid::filled(Dims::new(rows, cols), Cell::Unreachable), tractor: None, error: None }
+  }
+  pub fn cell(mut self, row: usize, col: usize, cell: Cell) -> Self {
+    if let Err(err) = self.grid.set(row, col, cell) {
+      self.error.get_or_insert(err);
+    }
+    self
+  }
+  pub fn wall(self, row: usize, col: usize) -> Self {
+    self.cell(row, col, Cell::Wall)
+  }
+  pub fn block(self, row: usize, col: usize) -> Self {
+    self.cell(row, col, Cell::Block)
+  }
+  pub fn boulder(self, row: usize, col: usize) -> Self {
+    self.cell(row, col, Cell::Boulder)
+  }
+  pub fn hole(self, row: usize, col: usize) -> Self {
+    self.cell(row, col, Cell::Hole)
+  }
+  pub fn ice(self, row: usize, col: usize) -> Self {
+    self.cell(row, col, Cell::Ice)
+  }
+  pub fn tractor(mut self, row: usize, col: usize) -> Self {
+    self.tractor = Some((row, col));
+    self
+  }
+  // Fails on pieces off the board, a missing tractor, or any `Problem` that
+  // `validate` finds
+  pub fn build(self) -> Result<Level, Error> {
+    if let Some(err) = self.error {
+      return Err(err);
+    }
+    let (row, col) = self.tractor.ok_or(Error::NoTractor)?;
+    let dims = self.grid.dims();
+    let tractor = self.grid.index_of(row, col).ok_or(Error::OffBoard { row, col, dims })?;
+    let level = Level::new(self.grid.into_cells(), dims, tractor);
+    let problems = level.validate();
+    if !problems.is_empty() {
+      return Err(Error::InvalidLevel(problems));
+    }
+    Ok(level)
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::validate::Problem;
+
+  #[test]
+  fn test_grid_builder() {
+    let level = GridBuilder::new(2, 3).boulder(0, 1).hole(0, 2).wall(1, 2).tractor(0, 0).build().unwrap();
+    assert_eq!(level, Level::new(vec![
+      Cell::Unreachable, Cell::Boulder, Cell::Hole,
+      Cell::Unreachable, Cell::Unreachable, Cell::Wall,
+    ], Dims::new(2, 3), 0));
+    assert_eq!(GridBuilder::new(2, 3).build(), Err(Error::NoTractor));
+    assert_eq!(GridBuilder::new(2, 3).wall(2, 0).tractor(0, 0).build(), Err(Error::OffBoard { row: 2, col: 0, dims: Dims::new(2, 3) }));
+    assert_eq!(GridBuilder::new(1, 2).boulder(0, 1).tractor(0, 0).build(), Err(Error::InvalidLevel(vec![
+      Problem::CountMismatch { color: None, boulders: 1, holes: 0 },
+    ])));
+  }
+}
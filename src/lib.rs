@@ -0,0 +1,42 @@
+pub mod analysis;
+pub mod batch;
+pub mod bench;
+pub mod bot;
+pub mod cell;
+pub mod compression;
+pub mod deadlock;
+pub mod dedupe;
+pub mod difficulty;
+pub mod direction;
+pub mod dot;
+pub mod error;
+pub mod evolve;
+pub mod explorer;
+pub mod floor_plan;
+pub mod formats;
+pub mod generator;
+pub mod grid;
+pub mod heuristics;
+pub mod interner;
+pub mod level;
+pub mod metrics;
+pub mod packed;
+pub mod play;
+pub mod proto;
+pub mod pruner;
+pub mod refine;
+pub mod render;
+pub mod rpc;
+pub mod shortest_path;
+pub mod solver;
+pub mod state_graph;
+pub mod storage;
+pub mod symmetry;
+pub mod template;
+pub mod transposition;
+pub mod validate;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+pub mod zobrist;
+
+pub use cell::Cell;
@@ -1,37 +1,142 @@
-use std::fs::File;
-use std::io::{self, Read, Stdin, Stdout, Write};
+use std::fs::{self, File};
+use std::io::{self, Stdin, Stdout, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 use clap::{Arg, App, SubCommand};
+use crossterm::{cursor, execute, queue};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind};
+use crossterm::style::Print;
+use crossterm::terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+use indicatif::{ProgressBar, ProgressStyle};
 use rand::{self, Rng, SeedableRng};
 use rand_pcg::Pcg64;
-use rmp_serde;
 
-mod cell;
-mod explorer;
-mod generator;
-mod grid;
-mod shortest_path;
-mod state_graph;
-
-use cell::Cell;
-use explorer::*;
-use generator::*;
-use crate::state_graph::find_solvable_states;
+use lvlgen::Cell;
+use lvlgen::batch::analyze_all;
+use lvlgen::bench;
+use lvlgen::bot::{simulate, BotConfig};
+use lvlgen::compression;
+use lvlgen::dedupe::{find_duplicates, similarity_of, Prepared};
+use lvlgen::difficulty::{measure, push_graph_stats, MAX_MEASURED_STATES};
+use lvlgen::direction::Direction;
+use lvlgen::dot::DotOptions;
+use lvlgen::explorer::*;
+use lvlgen::formats::{load_level, load_paths, parse_level, Format, FORMAT_NAMES};
+use lvlgen::grid::Dims;
+use lvlgen::generator::*;
+use lvlgen::level::Level;
+use lvlgen::metrics::{measure_difficulty, trickiness};
+use lvlgen::solver::solve_or_explain;
+use lvlgen::play::Game;
+#[cfg(feature = "png")]
+use lvlgen::render::render_level_png;
+#[cfg(feature = "gif")]
+use lvlgen::render::render_solution_gif;
+use lvlgen::render::{playback_levels, Style};
+use lvlgen::render::svg::render_level_svg;
+use lvlgen::render::text::{grid_to_string, level_to_string, level_to_string_with, Glyphs};
+use lvlgen::rpc::serve;
+use lvlgen::state_graph::{find_solvable_states, find_solvable_states_in, ExploreOptions, Progress, StateGraph, StateId};
+use lvlgen::storage::{DiskStore, StateStore};
+use lvlgen::validate::Problem;
 
 fn main() -> io::Result<()> {
   let matches = App::new("lvlgen")
+    .subcommand(SubCommand::with_name("dot")
+      .arg(Arg::with_name("file")
+        .required(true)
+        .index(1))
+      .arg(Arg::with_name("grids")
+        .long("--grids")
+        .short("-g"))
+      .arg(Arg::with_name("pushes")
+        .long("--pushes")
+        .short("-p"))
+      .arg(Arg::with_name("quiet")
+        .long("--quiet")
+        .short("-q")))
+    .subcommand(SubCommand::with_name("bench")
+      .about("Times exploring and solving the built-in levels, or those under the paths given")
+      .arg(Arg::with_name("paths")
+        .multiple(true)
+        .index(1))
+      .arg(Arg::with_name("format")
+        .takes_value(true)
+        .possible_values(&["text", "json"])
+        .default_value("text")
+        .long("--format")
+        .short("-f")))
+    .subcommand(SubCommand::with_name("convert")
+      .about("Rewrites levels or collections in another format")
+      .arg(Arg::with_name("paths")
+        .required(true)
+        .multiple(true)
+        .index(1))
+      .arg(Arg::with_name("to")
+        .takes_value(true)
+        .required(true)
+        .possible_values(FORMAT_NAMES)
+        .long("--to")
+        .short("-t"))
+      .arg(Arg::with_name("out")
+        .takes_value(true)
+        .long("--out")
+        .short("-o")))
+    .subcommand(SubCommand::with_name("dedupe")
+      .about("Drops levels that repeat another up to rotation, reflection or shifting")
+      .arg(Arg::with_name("paths")
+        .required(true)
+        .multiple(true)
+        .index(1))
+      .arg(Arg::with_name("similar")
+        .help("Also reports kept levels at least this similar to an earlier one, from 0 to 1")
+        .takes_value(true)
+        .long("--similar")
+        .short("-s"))
+      .arg(Arg::with_name("out")
+        .takes_value(true)
+        .long("--out")
+        .short("-o")))
     .subcommand(SubCommand::with_name("explore")
       .arg(Arg::with_name("file")
         .required(true)
-        .index(1)))
+        .index(1))
+      .arg(Arg::with_name("quiet")
+        .long("--quiet")
+        .short("-q"))
+      .arg(Arg::with_name("disk")
+        .help("Keeps states in a scratch file under this directory")
+        .takes_value(true)
+        .long("--disk")))
     .subcommand(SubCommand::with_name("generate")
       .arg(Arg::with_name("size")
-        .required(true)
+        .required_unless_one(&["size_flag", "config"])
         .index(1))
+      .arg(Arg::with_name("size_flag")
+        .takes_value(true)
+        .conflicts_with("size")
+        .long("--size"))
       .arg(Arg::with_name("seed")
         .takes_value(true)
         .long("--seed")
-        .short("-s")))
+        .short("-s"))
+      .arg(Arg::with_name("count")
+        .takes_value(true)
+        .long("--count")
+        .short("-n"))
+      .arg(Arg::with_name("boulders")
+        .takes_value(true)
+        .long("--boulders")
+        .short("-b"))
+      .arg(Arg::with_name("out")
+        .takes_value(true)
+        .long("--out")
+        .short("-o"))
+      .arg(Arg::with_name("config")
+        .takes_value(true)
+        .long("--config")
+        .short("-c")))
     .subcommand(SubCommand::with_name("search")
       .arg(Arg::with_name("size")
         .required(true)
@@ -49,21 +154,205 @@ fn main() -> io::Result<()> {
       .arg(Arg::with_name("file")
         .required(true)
         .index(1)))
+    .subcommand(SubCommand::with_name("analyze")
+      .about("Reports on every state a level can be pushed into")
+      .arg(Arg::with_name("file")
+        .required(true)
+        .index(1))
+      .arg(Arg::with_name("format")
+        .takes_value(true)
+        .possible_values(&["text", "json"])
+        .default_value("text")
+        .long("--format")
+        .short("-f")))
+    .subcommand(SubCommand::with_name("serve")
+      .about("Answers JSON-RPC requests for generate, solve, analyze and hint, one per line")
+      .arg(Arg::with_name("stdio")
+        .help("Reads requests from stdin and writes responses to stdout")
+        .required(true)
+        .long("--stdio")))
+    .subcommand(SubCommand::with_name("simulate")
+      .about("Estimates how playable a level is from bots that only look a few pushes ahead")
+      .arg(Arg::with_name("file")
+        .required(true)
+        .index(1))
+      .arg(Arg::with_name("runs")
+        .takes_value(true)
+        .long("--runs")
+        .short("-n"))
+      .arg(Arg::with_name("lookahead")
+        .takes_value(true)
+        .long("--lookahead")
+        .short("-l"))
+      .arg(Arg::with_name("seed")
+        .takes_value(true)
+        .long("--seed")
+        .short("-s"))
+      .arg(Arg::with_name("format")
+        .takes_value(true)
+        .possible_values(&["text", "json"])
+        .default_value("text")
+        .long("--format")
+        .short("-f")))
+    .subcommand(SubCommand::with_name("validate")
+      .about("Checks a level for structural problems, exiting with 1 if it has any")
+      .arg(Arg::with_name("file")
+        .required(true)
+        .index(1))
+      .arg(Arg::with_name("format")
+        .takes_value(true)
+        .possible_values(&["text", "json"])
+        .default_value("text")
+        .long("--format")
+        .short("-f")))
+    .subcommand(SubCommand::with_name("play")
+      .about("Plays a level in the terminal: arrow keys move, u undoes, h hints, q quits")
+      .arg(Arg::with_name("file")
+        .required(true)
+        .index(1)))
+    .subcommand(SubCommand::with_name("render")
+      .about("Draws a level, or every step of a solution to it")
+      .arg(Arg::with_name("file")
+        .required(true)
+        .index(1))
+      .arg(Arg::with_name("format")
+        .takes_value(true)
+        .possible_values(&["ascii", "svg", "png", "gif"])
+        .default_value("ascii")
+        .long("--format")
+        .short("-f"))
+      .arg(Arg::with_name("out")
+        .takes_value(true)
+        .required_ifs(&[("format", "png"), ("format", "gif")])
+        .long("--out")
+        .short("-o"))
+      .arg(Arg::with_name("solution")
+        .takes_value(true)
+        .long("--solution")
+        .short("-s")))
+    .subcommand(SubCommand::with_name("stats")
+      .about("Tabulates size, solution and difficulty for every level under the paths given")
+      .arg(Arg::with_name("paths")
+        .required(true)
+        .multiple(true)
+        .index(1))
+      .arg(Arg::with_name("format")
+        .takes_value(true)
+        .possible_values(&["csv", "json"])
+        .default_value("csv")
+        .long("--format")
+        .short("-f"))
+      .arg(Arg::with_name("threads")
+        .takes_value(true)
+        .default_value("0")
+        .long("--threads")
+        .short("-j")))
+    .subcommand(SubCommand::with_name("solve")
+      .about("Solves a level, exiting with 1 if it has no solution")
+      .arg(Arg::with_name("file")
+        .required(true)
+        .index(1))
+      .arg(Arg::with_name("format")
+        .takes_value(true)
+        .possible_values(&["text", "json"])
+        .default_value("text")
+        .long("--format")
+        .short("-f")))
     .get_matches();
-  if let Some(matches) = matches.subcommand_matches("explore") {
+  if let Some(matches) = matches.subcommand_matches("dot") {
+    let file = matches.value_of("file").unwrap();
+    let opts = DotOptions {
+      grids: matches.is_present("grids"),
+      pushes: matches.is_present("pushes"),
+    };
+    do_dot(file, &opts, matches.is_present("quiet"))?;
+  } else if let Some(matches) = matches.subcommand_matches("bench") {
+    let paths = matches.values_of("paths").map_or(vec![], |paths| paths.collect::<Vec<&str>>());
+    do_bench(&paths, matches.value_of("format") == Some("json"))?;
+  } else if let Some(matches) = matches.subcommand_matches("convert") {
+    let paths = matches.values_of("paths").unwrap().collect::<Vec<&str>>();
+    let format = Format::from_name(matches.value_of("to").unwrap()).unwrap();
+    do_convert(&paths, format, matches.value_of("out"))?;
+  } else if let Some(matches) = matches.subcommand_matches("dedupe") {
+    let paths = matches.values_of("paths").unwrap().collect::<Vec<&str>>();
+    let similar = match matches.value_of("similar") {
+      Some(similar) => Some(similar.parse::<f32>()
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?),
+      None => None,
+    };
+    do_dedupe(&paths, matches.value_of("out"), similar)?;
+  } else if let Some(matches) = matches.subcommand_matches("explore") {
     let file = matches.value_of("file").unwrap();
-    do_explore(file)?;
+    do_explore(file, matches.is_present("quiet"), matches.value_of("disk"))?;
   } else if let Some(matches) = matches.subcommand_matches("generate") {
-    let size: usize = matches.value_of("size").unwrap()
+    let mut config = match matches.value_of("config") {
+      Some(path) => GenConfig::load(Path::new(path))?,
+      None => GenConfig { seed: rand::thread_rng().gen(), ..GenConfig::default() },
+    };
+    if let Some(size) = matches.value_of("size").or_else(|| matches.value_of("size_flag")) {
+      config.size = size.parse()
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+    }
+    if let Some(boulders) = matches.value_of("boulders") {
+      config.boulders = boulders.parse()
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+    }
+    if let Some(seed) = matches.value_of("seed") {
+      config.seed = seed.parse()
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+    }
+    if let Some(count) = matches.value_of("count") {
+      let count: usize = count.parse()
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+      do_generate_batch(&config, count, matches.value_of("out"))?;
+    } else if matches.is_present("config") || matches.is_present("boulders") {
+      do_generate_with(&config);
+    } else {
+      do_generate(config.size, config.seed);
+    }
+  } else if let Some(matches) = matches.subcommand_matches("analyze") {
+    let file = matches.value_of("file").unwrap();
+    do_analyze(file, matches.value_of("format") == Some("json"))?;
+  } else if matches.subcommand_matches("serve").is_some() {
+    serve(io::stdin().lock(), io::stdout().lock())?;
+  } else if let Some(matches) = matches.subcommand_matches("simulate") {
+    let mut config = BotConfig::default();
+    if let Some(runs) = matches.value_of("runs") {
+      config.runs = runs.parse()
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+    }
+    if let Some(lookahead) = matches.value_of("lookahead") {
+      config.lookahead = lookahead.parse()
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+    }
+    if let Some(seed) = matches.value_of("seed") {
+      config.seed = seed.parse()
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+    }
+    do_simulate(matches.value_of("file").unwrap(), &config, matches.value_of("format") == Some("json"))?;
+  } else if let Some(matches) = matches.subcommand_matches("validate") {
+    let file = matches.value_of("file").unwrap();
+    if !do_validate(file, matches.value_of("format") == Some("json"))? {
+      std::process::exit(1);
+    }
+  } else if let Some(matches) = matches.subcommand_matches("play") {
+    let file = matches.value_of("file").unwrap();
+    do_play(file)?;
+  } else if let Some(matches) = matches.subcommand_matches("render") {
+    let file = matches.value_of("file").unwrap();
+    let format = matches.value_of("format").unwrap();
+    do_render(file, format, matches.value_of("out"), matches.value_of("solution"))?;
+  } else if let Some(matches) = matches.subcommand_matches("stats") {
+    let paths = matches.values_of("paths").unwrap().collect::<Vec<&str>>();
+    let threads: usize = matches.value_of("threads").unwrap()
       .parse()
       .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
-    let seed: u64 = match matches.value_of("seed").map(|arg|
-      arg.parse().map_err(|err|
-        io::Error::new(io::ErrorKind::InvalidInput, err))) {
-      Some(seed) => seed?,
-      None => rand::thread_rng().gen(),
-    };
-    do_generate(size, seed);
+    do_stats(&paths, threads, matches.value_of("format") == Some("json"))?;
+  } else if let Some(matches) = matches.subcommand_matches("solve") {
+    let file = matches.value_of("file").unwrap();
+    if !do_solve(file, matches.value_of("format") == Some("json"))? {
+      std::process::exit(1);
+    }
   } else if let Some(matches) = matches.subcommand_matches("restore") {
     let file = matches.value_of("file").unwrap();
     do_restore(file)?;
@@ -85,23 +374,410 @@ fn main() -> io::Result<()> {
   Ok(())
 }
 
-fn do_explore(file: &str) -> io::Result<()> {
-  let mut fin = File::open(file)?;
-  let (tractor, grid) = read_game_grid(&mut fin)?;
-  let size = match guess_size(grid.len()) {
-    Some(size) => size,
+// Every level under `paths`, warning about the files that were skipped
+fn load_levels(paths: &[&str]) -> io::Result<Vec<(String, Level)>> {
+  let loaded = load_paths(paths)?;
+  warn_skipped(&loaded.skipped);
+  Ok(loaded.levels)
+}
+
+fn warn_skipped(skipped: &[(PathBuf, io::Error)]) {
+  for (file, err) in skipped {
+    eprintln!("skipping {}: {}", file.display(), err);
+  }
+}
+
+// Enough for the fill timing to settle, a fraction of a second
+const FILL_ROUNDS: usize = 100_000;
+
+fn do_bench(paths: &[&str], json: bool) -> io::Result<()> {
+  let levels = if paths.is_empty() {
+    bench::corpus().into_iter().map(|(name, level)| (name.to_string(), level)).collect()
+  } else {
+    load_levels(paths)?
+  };
+  if !json {
+    println!("{:<24} {:>8} {:>8} {:>10} {:>12} {:>10}", "level", "states", "edges", "explore", "states/s", "solve");
+  }
+  let mut rows = vec![];
+  let (mut states, mut elapsed) = (0, 0.0);
+  for (name, level) in &levels {
+    let timing = bench::time_level(level);
+    states += timing.states;
+    elapsed += timing.explore.as_secs_f64();
+    let solve = timing.solve.map(|solve| solve.as_secs_f64());
+    if json {
+      rows.push(serde_json::json!({
+        "level": name,
+        "states": timing.states,
+        "edges": timing.edges,
+        "explore_secs": timing.explore.as_secs_f64(),
+        "states_per_sec": timing.states_per_second(),
+        "solve_secs": solve,
+      }));
+    } else {
+      println!("{:<24} {:>8} {:>8} {:>9.3}s {:>12.0} {:>10}", name, timing.states, timing.edges,
+        timing.explore.as_secs_f64(), timing.states_per_second(),
+        solve.map_or("-".to_string(), |solve| format!("{:.3}s", solve)));
+    }
+  }
+  let rate = states as f64 / elapsed.max(1e-9);
+  let fills = bench::fill_rate(FILL_ROUNDS);
+  let peak = bench::peak_memory_kb();
+  if json {
+    println!("{}", serde_json::json!({
+      "levels": rows, "states_per_sec": rate, "fills_per_sec": fills, "peak_memory_kb": peak,
+    }));
+  } else {
+    println!("{} states in {:.3}s, {:.0} states/s", states, elapsed, rate);
+    println!("{:.0} flood fills/s on a 10x10 board", fills);
+    if let Some(peak) = peak {
+      println!("peak memory {} kB", peak);
+    }
+  }
+  Ok(())
+}
+
+// Levels go out as one collection, except in formats holding a single level,
+// where each gets a numbered file of its own
+fn do_convert(paths: &[&str], format: Format, out: Option<&str>) -> io::Result<()> {
+  let levels = load_levels(paths)?.into_iter().map(|(_, level)| level).collect::<Vec<Level>>();
+  let invalid = |err| io::Error::new(io::ErrorKind::InvalidData, err);
+  let out = match out {
+    Some(out) => Path::new(out),
     None => {
-      println!("bad grid size `{}`", grid.len());
+      print!("{}", format.write_collection(&levels).map_err(invalid)?);
       return Ok(());
+    },
+  };
+  if format.holds_collections() {
+    return fs::write(out, format.write_collection(&levels).map_err(invalid)?);
+  }
+  for (n, level) in levels.iter().enumerate() {
+    fs::write(numbered_path(out, n, levels.len()), format.write(level).map_err(invalid)?)?;
+  }
+  Ok(())
+}
+
+fn do_dedupe(paths: &[&str], out: Option<&str>, similar: Option<f32>) -> io::Result<()> {
+  let levels = load_levels(paths)?;
+  let duplicates = find_duplicates(&levels.iter().map(|(_, level)| level.clone()).collect::<Vec<Level>>());
+  if let Some(dir) = out {
+    fs::create_dir_all(dir)?;
+  }
+  let mut kept = 0;
+  // Each level is solved once, however many it's compared with
+  let mut earlier: Vec<(&str, Prepared)> = vec![];
+  for ((name, level), duplicate) in levels.iter().zip(&duplicates) {
+    if let Some(original) = duplicate {
+      eprintln!("{} repeats {}", name, levels[*original].0);
+      continue;
+    }
+    if let Some(threshold) = similar {
+      let prepared = Prepared::new(level);
+      for (other, score) in earlier.iter().map(|(other, first)| (other, similarity_of(&prepared, first))) {
+        if score >= threshold {
+          eprintln!("{} resembles {} ({:.2})", name, other, score);
+        }
+      }
+      earlier.push((name, prepared));
     }
+    let text = level_to_string(level);
+    match out {
+      Some(dir) => fs::write(Path::new(dir).join(format!("level_{:03}.txt", kept)), text)?,
+      None => print!("{}", text),
+    }
+    kept += 1;
+  }
+  eprintln!("kept {} of {} levels", kept, levels.len());
+  Ok(())
+}
+
+// Explores with a spinner on stderr counting states, unless `quiet`
+fn explore_level(level: Level, quiet: bool, store: Box<dyn StateStore>) -> io::Result<StateGraph> {
+  if quiet {
+    return Ok(find_solvable_states_in(&level, &ExploreOptions::default(), store)?);
+  }
+  let bar = ProgressBar::new_spinner();
+  bar.set_style(ProgressStyle::with_template("{spinner} [{elapsed_precise}] {msg}").unwrap());
+  let spinner = bar.clone();
+  let options = ExploreOptions {
+    progress: Some(Arc::new(move |progress: &Progress| {
+      spinner.set_message(format!("{} expanded, {} queued, {} states",
+        progress.expanded, progress.frontier, progress.states));
+      spinner.tick();
+    })),
+    ..Default::default()
+  };
+  let found = find_solvable_states_in(&level, &options, store);
+  bar.finish_and_clear();
+  Ok(found?)
+}
+
+fn do_explore(file: &str, quiet: bool, disk: Option<&str>) -> io::Result<()> {
+  let level = load_level(Path::new(file))?;
+  let dims = level.dims;
+  let store: Box<dyn StateStore> = match disk {
+    Some(dir) => Box::new(DiskStore::in_dir(Path::new(dir))?),
+    None => Box::new(vec![]),
   };
-  let found = find_solvable_states(tractor, grid, size);
+  let found = explore_level(level, quiet, store)?;
   println!("Found {} states", found.len());
-  let explorer = StateGraphExplorer::new(found, size);
+  let explorer = StateGraphExplorer::new(found, dims);
   explorer.print_dist();
   run_shell(explorer)
 }
 
+fn do_dot(file: &str, opts: &DotOptions, quiet: bool) -> io::Result<()> {
+  let found = explore_level(load_level(Path::new(file))?, quiet, Box::new(vec![]))?;
+  print!("{}", found.to_dot(opts));
+  Ok(())
+}
+
+fn do_analyze(file: &str, json: bool) -> io::Result<()> {
+  let level = load_level(Path::new(file))?;
+  let stats = push_graph_stats(&level)
+    .ok_or_else(|| io::Error::other(format!("more than {} states", MAX_MEASURED_STATES)))?;
+  let score = measure_difficulty(&level).map(|difficulty| difficulty.score);
+  let trickiness = stats.pushes.and_then(|_| trickiness(&level));
+  if json {
+    println!("{}", serde_json::json!({
+      "states": stats.states,
+      "edges": stats.edges,
+      "goals": stats.goals,
+      "pushes": stats.pushes,
+      "branching": stats.branching,
+      "dead_fraction": stats.dead_fraction,
+      "score": score,
+      "trickiness": trickiness,
+    }));
+    return Ok(());
+  }
+  let or_dash = |value: Option<String>| value.unwrap_or_else(|| "-".into());
+  println!("states:     {}", stats.states);
+  println!("edges:      {}", stats.edges);
+  println!("goals:      {}", stats.goals);
+  println!("pushes:     {}", or_dash(stats.pushes.map(|pushes| pushes.to_string())));
+  println!("branching:  {:.2}", stats.branching);
+  println!("dead:       {:.0}%", 100.0 * stats.dead_fraction);
+  println!("score:      {}", or_dash(score.map(|score| format!("{:.1}", score))));
+  println!("trickiness: {}", or_dash(trickiness.map(|trickiness| trickiness.to_string())));
+  Ok(())
+}
+
+fn do_simulate(file: &str, config: &BotConfig, json: bool) -> io::Result<()> {
+  let playability = simulate(&load_level(Path::new(file))?, config);
+  if json {
+    println!("{}", serde_json::to_string(&playability)?);
+    return Ok(());
+  }
+  println!("solved: {} of {} ({:.0}%)", playability.solved, playability.runs, 100.0 * playability.success_rate);
+  println!("moves:  {}", playability.mean_moves.map_or_else(|| "-".into(), |moves| format!("{:.1}", moves)));
+  Ok(())
+}
+
+// Prints every problem found and returns whether there were none
+fn do_validate(file: &str, json: bool) -> io::Result<bool> {
+  let text = compression::read_to_string(Path::new(file))?;
+  let problems = match parse_level(&text) {
+    Ok(level) => level.validate(),
+    Err(message) => vec![Problem::Unparseable { message }],
+  };
+  if json {
+    println!("{}", serde_json::json!({ "valid": problems.is_empty(), "problems": problems }));
+  } else if problems.is_empty() {
+    println!("ok");
+  } else {
+    for problem in &problems {
+      println!("{}", problem);
+    }
+  }
+  Ok(problems.is_empty())
+}
+
+fn do_play(file: &str) -> io::Result<()> {
+  let mut game = Game::new(load_level(Path::new(file))?);
+  let mut stdout = io::stdout();
+  terminal::enable_raw_mode()?;
+  execute!(stdout, EnterAlternateScreen, cursor::Hide)?;
+  let played = play_loop(&mut game, &mut stdout);
+  execute!(stdout, cursor::Show, LeaveAlternateScreen)?;
+  terminal::disable_raw_mode()?;
+  played?;
+  println!("{} moves, {} pushes: {}", game.moves().len(), game.pushes(), game.moves());
+  Ok(())
+}
+
+fn play_loop(game: &mut Game, stdout: &mut Stdout) -> io::Result<()> {
+  let glyphs = Glyphs::unicode();
+  let mut message = String::new();
+  loop {
+    let board = level_to_string_with(&game.current(), &glyphs);
+    let status = if game.is_solved() { "solved!" } else { message.as_str() };
+    queue!(stdout, Clear(ClearType::All), cursor::MoveTo(0, 0))?;
+    for line in board.lines().chain([
+      format!("{} moves, {} pushes", game.moves().len(), game.pushes()).as_str(),
+      status,
+      "arrows move, u undo, h hint, q quit",
+    ].iter().cloned()) {
+      queue!(stdout, Print(line), cursor::MoveToNextLine(1))?;
+    }
+    stdout.flush()?;
+    let code = match event::read()? {
+      Event::Key(KeyEvent { code, kind: KeyEventKind::Press, .. }) => code,
+      _ => continue,
+    };
+    message.clear();
+    let dir = match code {
+      KeyCode::Up => Direction::Up,
+      KeyCode::Down => Direction::Down,
+      KeyCode::Left => Direction::Left,
+      KeyCode::Right => Direction::Right,
+      KeyCode::Char('u') => {
+        if !game.undo() {
+          message.push_str("nothing to undo");
+        }
+        continue;
+      },
+      KeyCode::Char('h') => {
+        let dims = game.current().dims;
+        message = match game.hint() {
+          Some(push) => format!("push ({}, {}) {:?}", dims.row(push.boulder), dims.col(push.boulder), push.dir),
+          None if game.is_solved() => String::new(),
+          None => "no way to solve from here, try undoing".into(),
+        };
+        continue;
+      },
+      KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+      _ => continue,
+    };
+    if !game.step(dir) {
+      message.push_str("blocked");
+    }
+  }
+}
+
+// A solution file holds a move string, or the JSON `solve --format json`
+// prints. With several frames to write, each file gets its number added.
+fn do_render(file: &str, format: &str, out: Option<&str>, solution: Option<&str>) -> io::Result<()> {
+  let level = load_level(Path::new(file))?;
+  let game = match solution {
+    Some(path) => {
+      let text = fs::read_to_string(path)?;
+      let moves = match serde_json::from_str::<serde_json::Value>(&text) {
+        Ok(json) => json["moves"].as_str().unwrap_or_default().to_string(),
+        Err(_) => text,
+      };
+      Game::replay(level.clone(), &moves).map_err(|at| io::Error::new(io::ErrorKind::InvalidData,
+        format!("move {} of the solution can't be made", at + 1)))?
+    },
+    None => Game::new(level.clone()),
+  };
+  let solution = game.solution();
+  let frames = playback_levels(&level, &solution);
+  let style = Style::default();
+  let rendered: Vec<Vec<u8>> = match format {
+    "ascii" => frames.iter().map(|frame| level_to_string(frame).into_bytes()).collect(),
+    "svg" => frames.iter().map(|frame| render_level_svg(frame, &style).into_bytes()).collect(),
+    #[cfg(feature = "png")]
+    "png" => frames.iter().map(|frame| render_level_png(frame, &style)).collect(),
+    #[cfg(feature = "gif")]
+    "gif" => vec![render_solution_gif(&level, &solution, &style, 50)],
+    other => return Err(io::Error::new(io::ErrorKind::Unsupported,
+      format!("built without the `{}` feature", other))),
+  };
+  let out = match out {
+    Some(out) => Path::new(out),
+    None => {
+      for frame in rendered {
+        io::stdout().write_all(&frame)?;
+      }
+      return Ok(());
+    },
+  };
+  let count = rendered.len();
+  for (n, frame) in rendered.into_iter().enumerate() {
+    fs::write(numbered_path(out, n, count), frame)?;
+  }
+  Ok(())
+}
+
+// Where to write file `n` of `count`, numbered before the extension when
+// there is more than one
+fn numbered_path(out: &Path, n: usize, count: usize) -> PathBuf {
+  if count == 1 {
+    return out.to_path_buf();
+  }
+  let stem = out.file_stem().unwrap_or_default().to_string_lossy();
+  let name = match out.extension() {
+    Some(ext) => format!("{}_{:03}.{}", stem, n, ext.to_string_lossy()),
+    None => format!("{}_{:03}", stem, n),
+  };
+  out.with_file_name(name)
+}
+
+// Prints the solution and returns whether there was one
+fn do_solve(file: &str, json: bool) -> io::Result<bool> {
+  let level = load_level(Path::new(file))?;
+  let result = solve_or_explain(&level);
+  if json {
+    let out = match &result {
+      Ok(solution) => serde_json::json!({
+        "solvable": true,
+        "pushes": solution.len(),
+        "moves": solution.moves,
+        "steps": solution.pushes.iter().map(|push| serde_json::json!({
+          "row": level.dims.row(push.boulder),
+          "col": level.dims.col(push.boulder),
+          "dir": push.dir,
+        })).collect::<Vec<_>>(),
+      }),
+      Err(reason) => serde_json::json!({ "solvable": false, "reason": reason.to_string() }),
+    };
+    println!("{}", out);
+  } else {
+    match &result {
+      Ok(solution) => {
+        println!("solvable in {} pushes, {} moves", solution.len(), solution.moves.len());
+        println!("moves: {}", solution.moves);
+        for (n, push) in solution.pushes.iter().enumerate() {
+          let (row, col) = (level.dims.row(push.boulder), level.dims.col(push.boulder));
+          println!("{:>4}. ({}, {}) {:?}", n + 1, row, col, push.dir);
+        }
+      },
+      Err(reason) => println!("unsolvable: {}", reason),
+    }
+  }
+  Ok(result.is_ok())
+}
+
+// One row per level. Levels too big to measure still get a solution, with
+// the columns taken from the push graph left empty.
+// Levels are analyzed on `threads` threads, all cores if 0
+fn do_stats(paths: &[&str], threads: usize, json: bool) -> io::Result<()> {
+  let batch = analyze_all(paths, threads)?;
+  warn_skipped(&batch.skipped);
+  let rows = batch.reports.iter()
+    .map(|report| serde_json::to_value(report).unwrap())
+    .collect::<Vec<_>>();
+  if json {
+    println!("{}", serde_json::Value::Array(rows));
+    return Ok(());
+  }
+  let columns = ["level", "rows", "cols", "boulders", "pushes", "moves", "states", "branching", "dead_fraction", "score"];
+  println!("{}", columns.join(","));
+  for row in rows {
+    let fields = columns.iter().map(|column| match &row[column] {
+      serde_json::Value::Null => String::new(),
+      serde_json::Value::String(text) if text.contains(&[',', '"', '\n'][..]) => format!("\"{}\"", text.replace('"', "\"\"")),
+      serde_json::Value::String(text) => text.clone(),
+      value => value.to_string(),
+    }).collect::<Vec<String>>();
+    println!("{}", fields.join(","));
+  }
+  Ok(())
+}
+
 fn do_generate(size: usize, seed: u64) {
   println!("seed = {}", seed);
   let mut rng = Pcg64::seed_from_u64(seed);
@@ -109,9 +785,65 @@ fn do_generate(size: usize, seed: u64) {
   print_state(&level, size);
 }
 
+// One level meeting every constraint in `config`, where plain `do_generate`
+// knows only the size
+fn do_generate_with(config: &GenConfig) {
+  println!("seed = {}", config.seed);
+  match generate(config) {
+    Some(level) => print!("{}", level_to_string(&level)),
+    None => eprintln!("gave up after {} attempts", config.max_attempts),
+  }
+}
+
+fn do_generate_batch(config: &GenConfig, count: usize, out: Option<&str>) -> io::Result<()> {
+  println!("seed = {}", config.seed);
+  let mut rng = Pcg64::seed_from_u64(config.seed);
+  let (size, boulders) = (config.size, config.boulders);
+  if let Some(dir) = out {
+    fs::create_dir_all(dir)?;
+  }
+  let mut rows = vec![];
+  let mut attempts = 0;
+  while rows.len() < count {
+    attempts += 1;
+    if attempts > 1000 * count {
+      eprintln!("gave up after {} attempts", attempts - 1);
+      break;
+    }
+    let pulls = rng.gen_range(1..=4 * size * boulders.max(1));
+    let single = GenConfig { seed: rng.gen(), max_attempts: 1, ..config.clone() };
+    let level = match generate_by_pulling(&single, pulls) {
+      Some(level) => level,
+      None => continue,
+    };
+    let text = level_to_string(&level);
+    let name = match out {
+      Some(dir) => {
+        let path = Path::new(dir).join(format!("level_{:03}.txt", rows.len()));
+        fs::write(&path, &text)?;
+        path.display().to_string()
+      },
+      None => {
+        print!("{}", text);
+        format!("#{}", rows.len())
+      },
+    };
+    rows.push((name, measure(&level).zip(measure_difficulty(&level))));
+  }
+  println!("{:<24} {:>6} {:>9} {:>6} {:>6}", "level", "pushes", "branching", "dead", "score");
+  for (name, measured) in rows {
+    match measured {
+      Some((m, difficulty)) => println!("{:<24} {:>6} {:>9.2} {:>5.0}% {:>6.1}",
+        name, m.pushes, m.branching, 100.0 * m.dead_fraction, difficulty.score),
+      None => println!("{:<24} {:>6} {:>9} {:>6} {:>6}", name, "-", "-", "-", "-"),
+    }
+  }
+  Ok(())
+}
+
 fn do_restore(file: &str) -> io::Result<()> {
-  let fin = File::open(file)?;
-  let explorer = rmp_serde::decode::from_read(fin).unwrap();
+  let buf = compression::read(Path::new(file))?;
+  let explorer = rmp_serde::decode::from_read_ref(&buf).unwrap();
   run_shell(explorer)
 }
 
@@ -130,9 +862,9 @@ fn do_search(size: usize, threshold: usize, seed: u64) -> io::Result<()> {
       }
     }
     print_state(&level, size);
-    let found = find_solvable_states(tractor, level, size);
+    let found = find_solvable_states(&Level::new(level, Dims::square(size), tractor));
     println!("Found {} states", found.len());
-    let explorer = StateGraphExplorer::new(found, size);
+    let explorer = StateGraphExplorer::new(found, Dims::square(size));
     let longest = explorer.get_longest_path();
     println!("{} ", longest);
     if longest >= threshold {
@@ -199,7 +931,7 @@ fn run_shell(mut explorer: StateGraphExplorer) -> io::Result<()> {
                   if explorer.save_node(id) {
                     break;
                   }
-                  println!("already saved node '#{}'", id);
+                  println!("already saved node '{}'", id);
                 },
                 Err(msg) => {
                   println!("{}", msg);
@@ -247,7 +979,7 @@ fn run_shell(mut explorer: StateGraphExplorer) -> io::Result<()> {
                   print_current = true;
                   break;
                 }
-                println!("no node '#{}'", id);
+                println!("no node '{}'", id);
               },
               Err(msg) => {
                 println!("{}", msg);
@@ -260,10 +992,12 @@ fn run_shell(mut explorer: StateGraphExplorer) -> io::Result<()> {
   }
 }
 
-fn parse_node_ref(explorer: &StateGraphExplorer, input: &str) -> Result<usize, String> {
+fn parse_node_ref(explorer: &StateGraphExplorer, input: &str) -> Result<StateId, String> {
   if input.starts_with("#") {
     if let Some(rest) = input.get(1..) {
-      rest.parse::<usize>().map_err(|_| format!("'{}' not a number", rest))
+      rest.parse::<usize>()
+        .map(StateId::new)
+        .map_err(|_| format!("'{}' not a number", rest))
     } else {
       Err("Missing id after '#'".into())
     }
@@ -298,54 +1032,6 @@ fn read_next_line(stdin: &mut Stdin, stdout: &mut Stdout) -> io::Result<String>
   Ok(line.trim().into())
 }
 
-fn read_game_grid<T: Read>(input: &mut T) -> io::Result<(usize, Vec<Cell>)> {
-  let mut buffer = String::new();
-  input.read_to_string(&mut buffer)?;
-  let mut tractor = None;
-  let mut grid = vec![];
-  for c in buffer.chars().skip_while(|c| *c != '+') {
-    if let Some(cell) = Cell::try_from_char(c) {
-      if cell == Cell::Reachable {
-        tractor = Some(grid.len());
-        grid.push(Cell::Unreachable);
-      } else {
-        grid.push(cell);
-      }
-    } else if !(c == '\n' || c == '+' || c == '-' || c== '|') {
-      panic!("unrecognized character `{}`", c);
-    }
-  }
-  Ok((tractor.unwrap(), grid))
-}
-
-fn guess_size(n: usize) -> Option<usize> {
-  for i in 3..n/2 {
-    if i * i == n {
-      return Some(i);
-    }
-  }
-  None
-}
-
-fn print_state(state: &Vec<Cell>, size: usize) {
-  print!("+");
-  for _ in 0..size {
-    print!("-");
-  }
-  println!("+");
-  for (idx, cell) in state.iter().enumerate() {
-    let col = idx % size;
-    if col == 0 {
-      print!("|");
-    }
-    print!("{}", cell.to_char());
-    if col == size - 1 {
-      println!("|");
-    }
-  }
-  print!("+");
-  for _ in 0..size {
-    print!("-");
-  }
-  println!("+");
+fn print_state(state: &[Cell], size: usize) {
+  print!("{}", grid_to_string(state, Dims::square(size)));
 }
@@ -0,0 +1,478 @@
+use std::collections::hash_map::HashMap;
+use std::collections::hash_set::HashSet;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::cell::Cell;
+use crate::difficulty::MAX_MEASURED_STATES;
+use crate::grid::{slide, Dims};
+use crate::heuristics::MatchingBound;
+use crate::level::Level;
+use crate::solver::{solve, solve_by_moves, walking_path, Solution};
+use crate::state_graph::{find_pushed_states, find_pushed_states_up_to, Push, StateGraph, StateId};
+
+// How much each part of a `DifficultyScore` counts towards its `score`
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DifficultyWeights {
+  pub pushes: f64,
+  pub counter_intuitive: f64,
+  pub branching: f64,
+  pub dead_density: f64,
+  pub trap_density: f64,
+}
+
+impl Default for DifficultyWeights {
+  fn default() -> Self {
+    DifficultyWeights { pushes: 1.0, counter_intuitive: 2.0, branching: 1.0, dead_density: 10.0, trap_density: 10.0 }
+  }
+}
+
+// How hard a level is along its shortest solution, with `score` weighing the
+// parts up so levels can be ranked by it
+#[derive(Clone, Debug, PartialEq)]
+pub struct DifficultyScore {
+  // Fewest pushes to solve
+  pub pushes: usize,
+  // Pushes on the solution that leave the boulders further from the holes
+  // by `MatchingBound`
+  pub counter_intuitive: usize,
+  // Mean number of pushes on offer in the unsolved states on the solution
+  // and one push off it
+  pub branching: f64,
+  // `TrapMetrics::dead_fraction` and `TrapMetrics::trap_density` from the
+  // level
+  pub dead_density: f64,
+  pub trap_density: f64,
+  pub score: f64,
+}
+
+// How a graph explored by pushing lets the player go wrong, counting from
+// one of its states
+#[derive(Clone, Debug, PartialEq)]
+pub struct TrapMetrics {
+  // Share of the states no goal can be reached from
+  pub dead_fraction: f64,
+  // Share of the pushes out of unsolved states that can still reach a goal
+  // which lead into a dead state
+  pub trap_density: f64,
+  // Mean number of pushes from the start up to and including each push into
+  // a dead state, taking the fewest pushes to reach where it is made. `None`
+  // without any.
+  pub trap_depth: Option<f64>,
+}
+
+// The shortest solution from the level is looked up in `graph`, which needs
+// to hold its initial state. Dead states only show up in a graph explored
+// by pushing, such as `find_pushed_states`, and pulled graphs give `None`
+// like levels that aren't in the graph or can't be solved in it.
+pub fn difficulty(level: &Level, graph: &StateGraph) -> Option<DifficultyScore> {
+  difficulty_with(level, graph, &DifficultyWeights::default())
+}
+
+// `difficulty` over every state reachable by pushing. This is the score
+// `analyze` and `stats` report. `None` for levels that can't be solved or
+// have more than `MAX_MEASURED_STATES`.
+pub fn measure_difficulty(level: &Level) -> Option<DifficultyScore> {
+  difficulty(level, &find_pushed_states_up_to(level, MAX_MEASURED_STATES)?)
+}
+
+pub fn difficulty_with(level: &Level, graph: &StateGraph, weights: &DifficultyWeights) -> Option<DifficultyScore> {
+  if graph.is_pulled() {
+    return None;
+  }
+  let start = graph.get_id(&level.initial_state())?;
+  let dist = graph.distances_to_goal();
+  let path = solution_path(graph, &dist, &start)?;
+  let (states, _) = graph.resolve_path(&path)?;
+  let counter_intuitive = counter_intuitive_pushes(&states, level.dims).len();
+  let near = path.iter()
+    .flat_map(|id| std::iter::once(*id).chain(graph.neighbors(id)))
+    .filter(|id| dist.get(id) != Some(&0))
+    .collect::<HashSet<StateId>>();
+  let branching = if near.is_empty() {
+    0.0
+  } else {
+    near.iter().map(|id| graph.out_degree(id)).sum::<usize>() as f64 / near.len() as f64
+  };
+  let traps = traps_from(graph, &start, &dist);
+  let pushes = path.len() - 1;
+  let score = weights.pushes * pushes as f64
+    + weights.counter_intuitive * counter_intuitive as f64
+    + weights.branching * branching
+    + weights.dead_density * traps.dead_fraction
+    + weights.trap_density * traps.trap_density;
+  Some(DifficultyScore {
+    pushes,
+    counter_intuitive,
+    branching,
+    dead_density: traps.dead_fraction,
+    trap_density: traps.trap_density,
+    score,
+  })
+}
+
+// Difficulty scores of a set of levels, for placing other scores among
+// them. Saved alongside a level pack, levels keep their grades however the
+// pack grows.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct Corpus {
+  // Sorted
+  scores: Vec<f64>,
+}
+
+// From the easiest fifth of a corpus to the hardest
+static GRADES: &[char] = &['E', 'D', 'C', 'B', 'A'];
+
+impl Corpus {
+  pub fn new(mut scores: Vec<f64>) -> Self {
+    scores.retain(|score| !score.is_nan());
+    scores.sort_by(f64::total_cmp);
+    Corpus { scores }
+  }
+  // Scored by `measure_difficulty`, leaving out levels it gives no score
+  pub fn from_levels(levels: &[Level]) -> Self {
+    Self::new(levels.iter().filter_map(measure_difficulty).map(|difficulty| difficulty.score).collect())
+  }
+  pub fn load(path: &Path) -> io::Result<Self> {
+    let corpus: Corpus = serde_json::from_str(&fs::read_to_string(path)?)
+      .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    Ok(Self::new(corpus.scores))
+  }
+  pub fn save(&self, path: &Path) -> io::Result<()> {
+    fs::write(path, serde_json::to_string_pretty(self)?)
+  }
+  pub fn len(&self) -> usize {
+    self.scores.len()
+  }
+  pub fn is_empty(&self) -> bool {
+    self.scores.is_empty()
+  }
+  // Percentage of the corpus scoring below `score`, counting ties as half
+  // below, 50 in an empty corpus
+  pub fn percentile(&self, score: f64) -> f64 {
+    if self.scores.is_empty() {
+      return 50.0;
+    }
+    let below = self.scores.partition_point(|other| *other < score);
+    let up_to = self.scores.partition_point(|other| *other <= score);
+    100.0 * (below + up_to) as f64 / (2 * self.scores.len()) as f64
+  }
+  // A for scores in the hardest fifth of the corpus, down to E
+  pub fn grade(&self, score: f64) -> char {
+    GRADES[((self.percentile(score) / 20.0) as usize).min(GRADES.len() - 1)]
+  }
+}
+
+// Only dead states show up as traps, so `graph` has to be explored by
+// pushing, such as by `find_pushed_states`, and pruned graphs have none
+pub fn trap_metrics(graph: &StateGraph, from: &StateId) -> TrapMetrics {
+  traps_from(graph, from, &graph.distances_to_goal())
+}
+
+fn traps_from(graph: &StateGraph, from: &StateId, dist: &HashMap<StateId, usize>) -> TrapMetrics {
+  let depths = graph.build_shortest_path_from(from);
+  let mut offered = 0;
+  let mut trap_depths = vec![];
+  for (id, _) in dist.iter().filter(|(_, remaining)| **remaining > 0) {
+    let depth = match depths.depth(id) {
+      Some(depth) => depth,
+      None => continue,
+    };
+    for next in graph.neighbors(id) {
+      offered += 1;
+      if !dist.contains_key(&next) {
+        trap_depths.push(depth + 1);
+      }
+    }
+  }
+  TrapMetrics {
+    dead_fraction: if graph.is_empty() { 0.0 } else { (graph.len() - dist.len()) as f64 / graph.len() as f64 },
+    trap_density: if offered == 0 { 0.0 } else { trap_depths.len() as f64 / offered as f64 },
+    trap_depth: if trap_depths.is_empty() {
+      None
+    } else {
+      Some(trap_depths.iter().sum::<usize>() as f64 / trap_depths.len() as f64)
+    },
+  }
+}
+
+// The classic Sokoban measures of how a solution moves the boulders about
+#[derive(Clone, Debug, PartialEq)]
+pub struct BoxLines {
+  // Runs of pushes of one boulder the same way, each as long as it goes
+  pub lines: usize,
+  // How often the pushes of each boulder turn, the boulders numbered in cell
+  // order on the first board
+  pub direction_changes: Vec<usize>,
+}
+
+// Boulders are followed from push to push by where each one lands. Crossing
+// cracked floor moves no boulder, so it counts for nothing.
+pub fn box_lines_of(solution: &Solution) -> BoxLines {
+  let mut boulders = match solution.states.first() {
+    Some(first) => (0..first.len()).filter(|idx| first[*idx].has_boulder()).collect::<Vec<usize>>(),
+    None => vec![],
+  };
+  let mut last_dirs = vec![None; boulders.len()];
+  let mut direction_changes = vec![0; boulders.len()];
+  let mut lines = 0;
+  let mut last_pushed = None;
+  for (push, states) in solution.pushes.iter().zip(solution.states.windows(2)) {
+    if push.is_crossing(&states[0]) {
+      continue;
+    }
+    let pushed = match boulders.iter().position(|idx| *idx == push.boulder) {
+      Some(pushed) => pushed,
+      None => continue,
+    };
+    if let Some(landed) = (0..states[1].len()).find(|idx| states[1][*idx].has_boulder() && !states[0][*idx].has_boulder()) {
+      boulders[pushed] = landed;
+    }
+    if last_dirs[pushed].is_some_and(|dir| dir != push.dir) {
+      direction_changes[pushed] += 1;
+    }
+    if last_pushed != Some(pushed) || last_dirs[pushed] != Some(push.dir) {
+      lines += 1;
+    }
+    last_dirs[pushed] = Some(push.dir);
+    last_pushed = Some(pushed);
+  }
+  BoxLines { lines, direction_changes }
+}
+
+// On the shortest solution `solve` finds, `None` for unsolvable levels
+pub fn box_lines(level: &Level) -> Option<BoxLines> {
+  solve(level).map(|solution| box_lines_of(&solution))
+}
+
+// How often the tractor stops on each cell in an optimal solution by pushes,
+// over all of them taken as equally likely. Ahead of each push the tractor
+// walks one of the shortest ways, as in `solve`. The cell it starts on
+// counts once. `None` for unsolvable levels.
+pub fn tractor_heatmap(level: &Level) -> Option<Vec<f64>> {
+  let graph = find_pushed_states(level);
+  let dist = graph.distances_to_goal();
+  // Where the tractor stands matters for the walks, so every state comes
+  // paired with the cell the push into it left the tractor on
+  let start = (graph.root(), level.tractor);
+  let mut layers = vec![vec![start]];
+  let mut edges = vec![];
+  let mut paths_to = HashMap::new();
+  paths_to.insert(start, 1.0);
+  for remaining in (1..=*dist.get(&start.0)?).rev() {
+    let mut next_layer = vec![];
+    for node in layers.last().unwrap() {
+      for (to, push) in graph.edges_from(&node.0).filter(|(to, _)| dist.get(to) == Some(&(remaining - 1))) {
+        let next = (to, push.tractor);
+        if !paths_to.contains_key(&next) {
+          next_layer.push(next);
+        }
+        *paths_to.entry(next).or_insert(0.0) += paths_to[node];
+        edges.push((*node, *push, next));
+      }
+    }
+    layers.push(next_layer);
+  }
+  let mut paths_from = layers.last().unwrap().iter().map(|node| (*node, 1.0)).collect::<HashMap<(StateId, usize), f64>>();
+  for (node, _, next) in edges.iter().rev() {
+    let paths = paths_from[next];
+    *paths_from.entry(*node).or_insert(0.0) += paths;
+  }
+  let total = paths_from[&start];
+  let mut heat = vec![0.0; level.grid.len()];
+  heat[level.tractor] = 1.0;
+  for (node, push, next) in &edges {
+    let share = paths_to[node] * paths_from[next] / total;
+    for idx in tractor_stops(&graph.get_state(&node.0)?, node.1, push, level.dims)? {
+      heat[idx] += share;
+    }
+  }
+  Some(heat)
+}
+
+// The cells the tractor stops on walking from `tractor` to make `push`,
+// ending where the push leaves it
+fn tractor_stops(state: &[Cell], tractor: usize, push: &Push, dims: Dims) -> Option<Vec<usize>> {
+  let mut stops = vec![];
+  let mut current = tractor;
+  for dir in walking_path(tractor, push.tractor_start(state, dims)?, state, dims)? {
+    current = slide(current, dir, state, dims)?;
+    stops.push(current);
+  }
+  stops.push(push.tractor);
+  Some(stops)
+}
+
+// Fewest pushes to solve the level, searched for by `solve`. `None` for
+// unsolvable levels.
+pub fn solution_length_pushes(level: &Level) -> Option<usize> {
+  solve(level).map(|solution| solution.len())
+}
+
+// Fewest tractor moves to solve the level, walking included, searched for
+// by `solve_by_moves`. `None` for unsolvable levels.
+pub fn solution_length_moves(level: &Level) -> Option<usize> {
+  solve_by_moves(level).map(|solution| solution.moves.len())
+}
+
+// From `start` to the nearest goal, taking the lowest state id on ties like
+// `StateGraph::hint` does
+fn solution_path(graph: &StateGraph, dist: &HashMap<StateId, usize>, start: &StateId) -> Option<Vec<StateId>> {
+  let mut path = vec![*start];
+  let mut remaining = *dist.get(start)?;
+  while remaining > 0 {
+    let next = graph.neighbors(path.last().unwrap())
+      .filter(|id| dist.get(id) == Some(&(remaining - 1)))
+      .min()?;
+    path.push(next);
+    remaining -= 1;
+  }
+  Some(path)
+}
+
+// The pushes between `states` that raise the matching lower bound, leaving
+// the boulders further from the holes than before, by their position. The
+// states run as along a `Solution`, all on the first one's board.
+pub fn counter_intuitive_pushes(states: &[Vec<Cell>], dims: Dims) -> Vec<usize> {
+  let bound = match states.first() {
+    Some(first) => MatchingBound::new(first, dims),
+    None => return vec![],
+  };
+  let estimates = states.iter().map(|state| bound.estimate(state)).collect::<Vec<Option<usize>>>();
+  (0..states.len().saturating_sub(1)).filter(|nth| estimates[nth + 1] > estimates[*nth]).collect()
+}
+
+// How many pushes of the level's shortest solution are counter-intuitive,
+// `None` for unsolvable levels
+pub fn trickiness(level: &Level) -> Option<usize> {
+  solve(level).map(|solution| counter_intuitive_pushes(&solution.states, level.dims).len())
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::state_graph::is_goal_state;
+
+  #[test]
+  fn test_difficulty() {
+    let grid = vec![
+      Cell::Hole, Cell::Unreachable, Cell::Unreachable, Cell::Unreachable,
+      Cell::Block, Cell::Block, Cell::Boulder, Cell::Unreachable,
+      Cell::Block, Cell::Block, Cell::Unreachable, Cell::Unreachable,
+      Cell::Block, Cell::Block, Cell::Unreachable, Cell::Unreachable,
+    ];
+    let level = Level::new(grid, Dims::square(4), 15);
+    let graph = find_pushed_states(&level);
+    let score = difficulty(&level, &graph).unwrap();
+    assert_eq!(score.pushes, 3);
+    // Up, then left twice, each bringing the boulder closer to the hole
+    assert_eq!(score.counter_intuitive, 0);
+    assert_eq!(box_lines(&level), Some(BoxLines { lines: 2, direction_changes: vec![1] }));
+    assert!(score.branching > 0.0);
+    // Pushing the boulder down twice strands it on the bottom edge
+    assert!(score.dead_density > 0.0 && score.dead_density < 1.0);
+    let weights = DifficultyWeights { pushes: 1.0, counter_intuitive: 0.0, branching: 0.0, dead_density: 0.0, trap_density: 0.0 };
+    assert_eq!(difficulty_with(&level, &graph, &weights).unwrap().score, 3.0);
+    // Without its boulder the board was never met exploring
+    let mut other = level.clone();
+    other.grid[6] = Cell::Unreachable;
+    assert_eq!(difficulty(&other, &graph), None);
+  }
+
+  #[test]
+  fn test_trap_metrics() {
+    let grid = vec![
+      Cell::Hole, Cell::Unreachable, Cell::Unreachable, Cell::Unreachable,
+      Cell::Block, Cell::Block, Cell::Boulder, Cell::Unreachable,
+      Cell::Block, Cell::Block, Cell::Unreachable, Cell::Unreachable,
+      Cell::Block, Cell::Block, Cell::Unreachable, Cell::Unreachable,
+    ];
+    let mut graph = find_pushed_states(&Level::new(grid, Dims::square(4), 15));
+    // Of the six pushes to be made on the way, only the second push down
+    // strands the boulder, on the bottom edge
+    let traps = trap_metrics(&graph, &graph.root());
+    assert_eq!(traps.dead_fraction, 1.0 / 6.0);
+    assert_eq!(traps.trap_density, 1.0 / 6.0);
+    assert_eq!(traps.trap_depth, Some(2.0));
+    graph.prune_unwinnable(&is_goal_state);
+    let traps = trap_metrics(&graph, &graph.root());
+    assert_eq!((traps.dead_fraction, traps.trap_density, traps.trap_depth), (0.0, 0.0, None));
+  }
+
+  #[test]
+  fn test_tractor_heatmap() {
+    let grid = vec![
+      Cell::Hole, Cell::Boulder, Cell::Unreachable, Cell::Unreachable,
+      Cell::Unreachable, Cell::Unreachable, Cell::Unreachable, Cell::Unreachable,
+    ];
+    let level = Level::new(grid, Dims::new(2, 4), 4);
+    assert_eq!(tractor_heatmap(&level), Some(vec![0.0, 1.0, 1.0, 0.0, 1.0, 1.0, 1.0, 0.0]));
+    // The boulder goes left then down as often as down then left
+    let grid = vec![
+      Cell::Unreachable, Cell::Unreachable, Cell::Unreachable,
+      Cell::Unreachable, Cell::Boulder, Cell::Unreachable,
+      Cell::Hole, Cell::Unreachable, Cell::Unreachable,
+    ];
+    let heat = tractor_heatmap(&Level::new(grid, Dims::square(3), 7)).unwrap();
+    assert_eq!(heat, vec![0.5, 1.0, 0.5, 0.5, 1.0, 1.5, 0.0, 1.5, 1.5]);
+    let mut stuck = level.clone();
+    stuck.grid.swap(0, 1);
+    assert_eq!(tractor_heatmap(&stuck), None);
+  }
+
+  #[test]
+  fn test_corpus() {
+    let corpus = Corpus::new(vec![4.0, 1.0, 3.0, f64::NAN, 2.0, 2.0]);
+    assert_eq!(corpus.len(), 5);
+    assert_eq!(corpus.percentile(0.0), 0.0);
+    assert_eq!(corpus.percentile(2.0), 40.0);
+    assert_eq!(corpus.percentile(2.5), 60.0);
+    assert_eq!(corpus.percentile(9.0), 100.0);
+    assert_eq!((corpus.grade(0.0), corpus.grade(2.5), corpus.grade(9.0)), ('E', 'B', 'A'));
+    assert_eq!(Corpus::default().percentile(1.0), 50.0);
+    let path = std::env::temp_dir().join("lvlgen_test_corpus.json");
+    corpus.save(&path).unwrap();
+    let loaded = Corpus::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(loaded, corpus);
+  }
+
+  #[test]
+  fn test_counter_intuitive() {
+    // The boulder sits right above its hole, but the tractor can only get
+    // behind it after pushing it out of the way
+    let grid = vec![
+      Cell::Unreachable, Cell::Unreachable, Cell::Unreachable, Cell::Block,
+      Cell::Unreachable, Cell::Unreachable, Cell::Boulder, Cell::Unreachable,
+      Cell::Unreachable, Cell::Block, Cell::Hole, Cell::Unreachable,
+    ];
+    let level = Level::new(grid, Dims::new(3, 4), 7);
+    let score = difficulty(&level, &find_pushed_states(&level)).unwrap();
+    assert_eq!(score.pushes, 3);
+    assert_eq!(score.counter_intuitive, 1);
+    let solution = solve(&level).unwrap();
+    assert_eq!(counter_intuitive_pushes(&solution.states, level.dims), vec![0]);
+    assert_eq!(trickiness(&level), Some(1));
+    // Left, then round the back to push it right and down into the hole
+    assert_eq!(box_lines(&level), Some(BoxLines { lines: 3, direction_changes: vec![2] }));
+  }
+
+  #[test]
+  fn test_solution_lengths() {
+    let grid = vec![
+      Cell::Hole, Cell::Boulder, Cell::Unreachable, Cell::Unreachable,
+      Cell::Unreachable, Cell::Unreachable, Cell::Unreachable, Cell::Unreachable,
+    ];
+    // Three steps round to the right of the boulder, then the push
+    let level = Level::new(grid, Dims::new(2, 4), 4);
+    assert_eq!(solution_length_pushes(&level), Some(1));
+    assert_eq!(solution_length_moves(&level), Some(4));
+    let mut stuck = level.clone();
+    stuck.grid.swap(0, 1);
+    assert_eq!(solution_length_pushes(&stuck), None);
+    assert_eq!(solution_length_moves(&stuck), None);
+  }
+}
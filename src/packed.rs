@@ -0,0 +1,297 @@
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+
+use crate::cell::{Cell, Color};
+use crate::direction::DIRECTIONS;
+use crate::grid::*;
+use crate::symmetry::Transform;
+use crate::zobrist;
+
+// The parts of a board that never change while exploring: blocks, walls,
+// holes, ice, one-way cells, portals and open floor. Boulders, the tractor
+// regions and which cracked cells caved in are stored per state.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Layout {
+  cells: Vec<Cell>,
+  dims: Dims,
+  // Some walks can't be walked back, see `encode`
+  #[serde(default)]
+  directed: bool,
+  // Boards with colored boulders keep every boulder's color per state
+  #[serde(default)]
+  colored: bool,
+}
+
+// A state relative to its `Layout`: one bit per cell holding a boulder, plus
+// the smallest cell index of each tractor's reachable region, sorted. Tractors
+// sharing a region list it once each. On colored boards `colors` holds the
+// color of each boulder in cell order, and `collapsed` has a bit for each
+// cracked cell that caved in, empty while none has. `heavy` marks the heavy
+// boulders the same way.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+pub struct PackedState {
+  boulders: Vec<u64>,
+  tractors: Vec<usize>,
+  #[serde(default)]
+  colors: Vec<Option<Color>>,
+  #[serde(default)]
+  collapsed: Vec<u64>,
+  #[serde(default)]
+  heavy: Vec<u64>,
+}
+
+impl Layout {
+  pub fn from_state(state: &[Cell], dims: Dims) -> Self {
+    let cells = state.iter().map(|cell| match cell {
+      Cell::BoulderInHole | Cell::HeavyBoulderInHole | Cell::Hole => Cell::Hole,
+      Cell::ColoredBoulderInHole(color) | Cell::ColoredHole(color) => Cell::ColoredHole(*color),
+      Cell::Block => Cell::Block,
+      Cell::Wall => Cell::Wall,
+      Cell::Ice | Cell::BoulderOnIce => Cell::Ice,
+      Cell::OneWay(dir) | Cell::BoulderOnOneWay(dir) => Cell::OneWay(*dir),
+      Cell::Portal(id) => Cell::Portal(*id),
+      Cell::Cracked | Cell::Collapsed => Cell::Cracked,
+      _ => Cell::Unreachable,
+    }).collect::<Vec<Cell>>();
+    let directed = cells.iter().any(|cell| cell.is_directed());
+    let colored = state.iter().any(|cell| cell.color().is_some());
+    Layout { cells, dims, directed, colored }
+  }
+  pub fn dims(&self) -> Dims {
+    self.dims
+  }
+  pub fn is_directed(&self) -> bool {
+    self.directed
+  }
+  pub fn cells(&self) -> &[Cell] {
+    &self.cells
+  }
+  pub fn is_invariant_under(&self, transform: Transform) -> bool {
+    if transform.swaps_axes() && !self.dims.is_square() {
+      return false;
+    }
+    transform.apply(&self.cells, self.dims) == self.cells
+  }
+  // Assumes the `Reachable` cells of `state` form the single region the
+  // tractor can walk, as produced by `fill_reachable_cells`. With ice or
+  // one-way cells, cells of a region can't always walk back to the others,
+  // so the region is kept by the first cell it can be filled from again.
+  pub fn encode(&self, state: &[Cell]) -> PackedState {
+    assert!(state.len() == self.cells.len());
+    let mut boulders = vec![0u64; state.len().div_ceil(64)];
+    let mut tractors = vec![];
+    let mut colors = vec![];
+    let mut collapsed = vec![];
+    let mut heavy = vec![];
+    for (idx, cell) in state.iter().enumerate() {
+      match cell {
+        Cell::Collapsed => {
+          collapsed.resize(boulders.len(), 0);
+          collapsed[idx / 64] |= 1 << (idx % 64);
+        },
+        cell if cell.has_boulder() => {
+          boulders[idx / 64] |= 1 << (idx % 64);
+          if cell.is_heavy() {
+            heavy.resize(boulders.len(), 0);
+            heavy[idx / 64] |= 1 << (idx % 64);
+          }
+          if self.colored {
+            colors.push(cell.color());
+          }
+        },
+        Cell::Reachable if tractors.is_empty() => tractors.push(idx),
+        _ => (),
+      }
+    }
+    if self.directed && !tractors.is_empty() {
+      tractors = vec![self.fill_source(state)];
+    }
+    PackedState { boulders, tractors, colors, collapsed, heavy }
+  }
+  fn fill_source(&self, state: &[Cell]) -> usize {
+    let floor = state.iter()
+      .map(|cell| if *cell == Cell::Reachable { Cell::Unreachable } else { *cell })
+      .collect::<Vec<Cell>>();
+    let region = (0..state.len()).filter(|idx| state[*idx] == Cell::Reachable).collect::<HashSet<usize>>();
+    (0..state.len())
+      .filter(|idx| state[*idx] == Cell::Reachable || state[*idx] == Cell::Ice || matches!(state[*idx], Cell::OneWay(_)))
+      .find(|idx| find_reachable_empty_cells_unchecked(*idx, &floor, self.dims) == region)
+      .unwrap_or_else(|| *region.iter().min().unwrap())
+  }
+  // For boards with several tractors, `tractors` holding any cell of each
+  // one's region
+  pub fn encode_with(&self, state: &[Cell], tractors: &[usize]) -> PackedState {
+    let mut packed = self.encode(state);
+    packed.tractors = tractors.iter().map(|tractor| region_min(*tractor, state, self.dims)).collect();
+    packed.tractors.sort();
+    packed
+  }
+  pub fn decode(&self, packed: &PackedState) -> Vec<Cell> {
+    let mut state = self.cells.clone();
+    let mut colors = packed.colors.iter();
+    for (idx, cell) in state.iter_mut().enumerate() {
+      if packed.has_boulder(idx) {
+        let color = colors.next().cloned().flatten();
+        *cell = if is_set(&packed.heavy, idx) {
+          cell.with_boulder_like(Cell::HeavyBoulder)
+        } else {
+          cell.with_boulder_of(color)
+        };
+      } else if packed.has_collapsed(idx) {
+        *cell = Cell::Collapsed;
+      }
+    }
+    for tractor in &packed.tractors {
+      if state[*tractor] != Cell::Reachable {
+        fill_reachable_cells_unchecked(*tractor, &mut state, self.dims);
+      }
+    }
+    state
+  }
+}
+
+impl PackedState {
+  pub fn has_boulder(&self, idx: usize) -> bool {
+    self.boulders[idx / 64] & (1 << (idx % 64)) != 0
+  }
+  pub fn has_collapsed(&self, idx: usize) -> bool {
+    is_set(&self.collapsed, idx)
+  }
+  pub fn tractors(&self) -> &[usize] {
+    &self.tractors
+  }
+  // Taking the regions as given, for states read back rather than encoded
+  pub fn with_tractors(mut self, tractors: Vec<usize>) -> Self {
+    self.tractors = tractors;
+    self
+  }
+  // Its Zobrist key, see `zobrist`
+  pub fn zobrist(&self) -> u64 {
+    let mut key = 0;
+    let mut colors = self.colors.iter();
+    for (word, bits) in self.boulders.iter().enumerate() {
+      let mut bits = *bits;
+      while bits != 0 {
+        let idx = word * 64 + bits.trailing_zeros() as usize;
+        bits &= bits - 1;
+        key ^= zobrist::boulder_key(idx, is_set(&self.heavy, idx), colors.next().cloned().flatten());
+      }
+    }
+    for (nth, tractor) in self.tractors.iter().enumerate() {
+      key ^= zobrist::tractor_key(*tractor, nth);
+    }
+    for (word, bits) in self.collapsed.iter().enumerate() {
+      let mut bits = *bits;
+      while bits != 0 {
+        key ^= zobrist::collapsed_key(word * 64 + bits.trailing_zeros() as usize);
+        bits &= bits - 1;
+      }
+    }
+    key
+  }
+  // Whether a board with a single tractor on a layout without directed cells
+  // encodes to this, without encoding it
+  pub fn matches(&self, state: &[Cell]) -> bool {
+    if self.tractors.len() != 1 || state.get(self.tractors[0]) != Some(&Cell::Reachable) {
+      return false;
+    }
+    let mut colors = self.colors.iter();
+    state.iter().enumerate().all(|(idx, cell)| {
+      let boulder = cell.has_boulder();
+      boulder == self.has_boulder(idx)
+        && (*cell == Cell::Collapsed) == self.has_collapsed(idx)
+        && (!boulder || (cell.is_heavy() == is_set(&self.heavy, idx)
+          && (self.colors.is_empty() || colors.next() == Some(&cell.color()))))
+    })
+  }
+}
+
+fn is_set(bits: &[u64], idx: usize) -> bool {
+  bits.get(idx / 64).is_some_and(|bits| bits & (1 << (idx % 64)) != 0)
+}
+
+// Smallest index of the `Reachable` region around `from`
+fn region_min(from: usize, state: &[Cell], dims: Dims) -> usize {
+  let mut seen = vec![false; state.len()];
+  seen[from] = true;
+  let mut stack = vec![from];
+  let mut min = from;
+  while let Some(idx) = stack.pop() {
+    min = min.min(idx);
+    for next in DIRECTIONS.iter().filter_map(|dir| step(idx, *dir, state, dims)) {
+      if !seen[next] && state[next] == Cell::Reachable {
+        seen[next] = true;
+        stack.push(next);
+      }
+    }
+  }
+  min
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn test_round_trip() {
+    let mut state = vec![
+      Cell::BoulderInHole, Cell::Unreachable, Cell::Unreachable, Cell::Hole,
+      Cell::Unreachable, Cell::Boulder, Cell::Block, Cell::Unreachable,
+      Cell::Unreachable, Cell::Block, Cell::Unreachable, Cell::Unreachable,
+      Cell::Wall, Cell::Unreachable, Cell::Boulder, Cell::BoulderInHole,
+    ];
+    fill_reachable_cells_unchecked(10, &mut state, Dims::square(4));
+    let layout = Layout::from_state(&state, Dims::square(4));
+    let packed = layout.encode(&state);
+    assert!(packed.has_boulder(0));
+    assert!(packed.has_boulder(5));
+    assert!(!packed.has_boulder(3));
+    assert_eq!(layout.decode(&packed), state);
+  }
+
+  #[test]
+  fn test_round_trip_on_ice() {
+    // Filling from 0 would miss 5, only reached by stopping on the ice at 1
+    let mut state = vec![
+      Cell::Unreachable, Cell::Ice, Cell::Ice, Cell::Unreachable,
+      Cell::Block, Cell::Unreachable, Cell::BoulderOnIce, Cell::Hole,
+    ];
+    let dims = Dims::new(2, 4);
+    fill_reachable_cells_unchecked(5, &mut state, dims);
+    let layout = Layout::from_state(&state, dims);
+    let packed = layout.encode(&state);
+    assert_eq!(packed.tractors(), &[1]);
+    assert_eq!(layout.decode(&packed), state);
+  }
+
+  #[test]
+  fn test_round_trip_colored() {
+    let mut state = vec![
+      Cell::ColoredBoulder(Color::Green), Cell::Unreachable, Cell::ColoredBoulder(Color::Red),
+      Cell::ColoredBoulderInHole(Color::Blue), Cell::ColoredHole(Color::Red), Cell::ColoredHole(Color::Green),
+    ];
+    fill_reachable_cells_unchecked(1, &mut state, Dims::new(2, 3));
+    let layout = Layout::from_state(&state, Dims::new(2, 3));
+    let packed = layout.encode(&state);
+    assert_eq!(layout.decode(&packed), state);
+    assert!(packed.matches(&state));
+    state.swap(0, 2);
+    assert_ne!(layout.encode(&state), packed);
+    assert!(!packed.matches(&state));
+    assert_ne!(layout.encode(&state).zobrist(), packed.zobrist());
+  }
+
+  #[test]
+  fn test_round_trip_collapsed() {
+    let mut state = vec![
+      Cell::Unreachable, Cell::Cracked, Cell::Unreachable, Cell::Boulder, Cell::Hole,
+    ];
+    let layout = Layout::from_state(&state, Dims::new(1, 5));
+    state[1] = Cell::Collapsed;
+    fill_reachable_cells_unchecked(2, &mut state, Dims::new(1, 5));
+    let packed = layout.encode(&state);
+    assert!(packed.has_collapsed(1));
+    assert_eq!(layout.decode(&packed), state);
+  }
+}
@@ -0,0 +1,210 @@
+use crate::cell::Cell;
+use crate::direction::Direction;
+use crate::grid::{slide, step};
+use crate::level::Level;
+use crate::solver::Solution;
+use crate::state_graph::*;
+
+// A level being played a step at a time. Pushes follow the same rules as the
+// solver's `next_pushed_states`, and every step can be taken back.
+pub struct Game {
+  level: Level,
+  // With the tractor's region marked `Reachable`
+  state: Vec<Cell>,
+  tractor: usize,
+  moves: String,
+  // The state, tractor and number of moves before each step, and the push
+  // the step made if any
+  history: Vec<(Vec<Cell>, usize, usize, Option<Push>)>,
+  // Built on the first hint
+  graph: Option<StateGraph>,
+}
+
+impl Game {
+  pub fn new(level: Level) -> Self {
+    let state = level.initial_state();
+    let tractor = level.tractor;
+    Game { level, state, tractor, moves: String::new(), history: vec![], graph: None }
+  }
+  // Plays out a move string like `Solution::moves`, where walks and pushes
+  // can be either case. Fails with the number of the first move that couldn't
+  // be made, counting from zero and a crossing as one move.
+  pub fn replay(level: Level, moves: &str) -> Result<Self, usize> {
+    let mut game = Game::new(level);
+    let mut chars = moves.chars().filter(|c| !c.is_whitespace());
+    let mut made = 0;
+    while let Some(c) = chars.next() {
+      let dir = match c.to_ascii_lowercase() {
+        'u' => Direction::Up,
+        'd' => Direction::Down,
+        'l' => Direction::Left,
+        'r' => Direction::Right,
+        _ => return Err(made),
+      };
+      let before = game.moves.len();
+      if !game.step(dir) {
+        return Err(made);
+      }
+      made += 1;
+      // Crossing cracked floor spells out both steps for one
+      if game.moves.len() - before == 2 {
+        chars.next();
+      }
+    }
+    Ok(game)
+  }
+  // The pushes made so far, with the states between them
+  pub fn solution(&self) -> Solution {
+    let mut states = vec![self.level.initial_state()];
+    let mut pushes = vec![];
+    for (idx, (_, _, _, push)) in self.history.iter().enumerate() {
+      if let Some(push) = push {
+        pushes.push(*push);
+        states.push(self.history.get(idx + 1).map_or(&self.state, |next| &next.0).clone());
+      }
+    }
+    Solution { pushes, states, moves: self.moves.clone() }
+  }
+  pub fn state(&self) -> &[Cell] {
+    &self.state
+  }
+  pub fn tractor(&self) -> usize {
+    self.tractor
+  }
+  // Every step so far, spelled as in `Solution::moves`
+  pub fn moves(&self) -> &str {
+    &self.moves
+  }
+  pub fn pushes(&self) -> usize {
+    self.moves.chars().filter(|c| c.is_ascii_uppercase()).count()
+  }
+  pub fn is_solved(&self) -> bool {
+    is_goal_state(&self.state)
+  }
+  // The board as it stands, with the tractor where it is now
+  pub fn current(&self) -> Level {
+    Level::new(self.state.clone(), self.level.dims, self.tractor)
+  }
+  // Walks, slides or pushes towards `dir`, returning whether anything moved
+  pub fn step(&mut self, dir: Direction) -> bool {
+    let dims = self.level.dims;
+    let ahead = match step(self.tractor, dir, &self.state, dims) {
+      Some(ahead) => ahead,
+      None => return false,
+    };
+    let pushed = next_pushed_states(&self.state, dims).into_iter()
+      .find(|(_, push)| push.boulder == ahead && push.dir == dir && push.tractor_start(&self.state, dims) == Some(self.tractor));
+    let (state, tractor, moves) = (self.state.clone(), self.tractor, self.moves.len());
+    if let Some((next, push)) = pushed {
+      let letter = direction_char(dir);
+      if push.is_crossing(&self.state) {
+        self.moves.push(letter);
+        self.moves.push(letter);
+      } else {
+        self.moves.push(letter.to_ascii_uppercase());
+      }
+      self.history.push((state, tractor, moves, Some(push)));
+      self.state = next;
+      self.tractor = push.tractor;
+      return true;
+    }
+    match slide(self.tractor, dir, &self.state, dims) {
+      Some(to) => {
+        self.moves.push(direction_char(dir));
+        self.history.push((state, tractor, moves, None));
+        self.tractor = to;
+        true
+      },
+      None => false,
+    }
+  }
+  // Takes back the last step, returning whether there was one
+  pub fn undo(&mut self) -> bool {
+    match self.history.pop() {
+      Some((state, tractor, moves, _)) => {
+        self.state = state;
+        self.tractor = tractor;
+        self.moves.truncate(moves);
+        true
+      },
+      None => false,
+    }
+  }
+  // The next push on a shortest solution from here, `None` once solved or
+  // when the level can't be solved any more
+  pub fn hint(&mut self) -> Option<Push> {
+    let level = &self.level;
+    let graph = self.graph.get_or_insert_with(|| find_pushed_states(level));
+    let id = graph.get_id(&self.state)?;
+    graph.hint(&id).map(|hint| hint.push)
+  }
+}
+
+fn direction_char(dir: Direction) -> char {
+  match dir {
+    Direction::Up => 'u',
+    Direction::Down => 'd',
+    Direction::Left => 'l',
+    Direction::Right => 'r',
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::grid::Dims;
+
+  #[test]
+  fn test_play() {
+    let grid = vec![
+      Cell::Unreachable, Cell::Boulder, Cell::Unreachable, Cell::Hole,
+      Cell::Unreachable, Cell::Unreachable, Cell::Unreachable, Cell::Unreachable,
+    ];
+    let mut game = Game::new(Level::new(grid, Dims::new(2, 4), 4));
+    assert!(!game.step(Direction::Left));
+    assert_eq!(game.hint().map(|push| push.dir), Some(Direction::Right));
+    assert!(game.step(Direction::Up));
+    assert!(game.step(Direction::Right));
+    assert_eq!(game.tractor(), 1);
+    assert_eq!(game.state()[2], Cell::Boulder);
+    assert!(game.undo());
+    assert_eq!(game.state()[1], Cell::Boulder);
+    assert_eq!(game.moves(), "u");
+    assert!(game.step(Direction::Right));
+    assert!(game.step(Direction::Right));
+    assert!(game.is_solved());
+    assert_eq!((game.moves(), game.pushes()), ("uRR", 2));
+    assert_eq!(game.hint(), None);
+  }
+
+  #[test]
+  fn test_dead_end_has_no_hint() {
+    let grid = vec![
+      Cell::Unreachable, Cell::Boulder, Cell::Unreachable, Cell::Hole,
+      Cell::Unreachable, Cell::Unreachable, Cell::Unreachable, Cell::Unreachable,
+    ];
+    let mut game = Game::new(Level::new(grid, Dims::new(2, 4), 6));
+    assert!(game.step(Direction::Up));
+    // Into the corner
+    assert!(game.step(Direction::Left));
+    assert_eq!(game.state()[0], Cell::Boulder);
+    assert_eq!(game.hint(), None);
+    assert!(game.undo());
+    assert!(game.hint().is_some());
+  }
+
+  #[test]
+  fn test_replay() {
+    let grid = vec![
+      Cell::Unreachable, Cell::Boulder, Cell::Hole,
+      Cell::Unreachable, Cell::Boulder, Cell::Hole,
+    ];
+    let level = Level::new(grid, Dims::new(2, 3), 0);
+    let solution = crate::solver::solve(&level).unwrap();
+    let game = Game::replay(level.clone(), &solution.moves).unwrap();
+    assert!(game.is_solved());
+    assert_eq!(game.solution(), solution);
+    assert_eq!(Game::replay(level.clone(), "d x").err(), Some(1));
+    assert_eq!(Game::replay(level, "L").err(), Some(0));
+  }
+}
@@ -0,0 +1,299 @@
+use std::convert::{TryFrom, TryInto};
+use std::io;
+
+use crate::cell::Cell;
+use crate::difficulty::PushGraphStats;
+use crate::direction::{Direction, DIRECTIONS};
+use crate::formats::json::{JsonLevel, Position, VERSION};
+use crate::grid::Dims;
+use crate::level::{Level, Metadata};
+use crate::solver::Solution;
+use crate::state_graph::Push;
+
+// The messages of proto/lvlgen.proto, encoded and decoded by hand so that
+// no code generator is needed. As in any protobuf reader, unknown fields are
+// skipped and missing ones take their defaults.
+
+impl Level {
+  pub fn to_proto(&self) -> Vec<u8> {
+    let json = JsonLevel::new(self);
+    let mut buf = vec![];
+    write_uint(&mut buf, 1, json.rows as u64);
+    write_uint(&mut buf, 2, json.cols as u64);
+    for row in &json.cells {
+      write_bytes(&mut buf, 3, row.as_bytes());
+    }
+    let mut tractor = vec![];
+    write_uint(&mut tractor, 1, json.tractor.row as u64);
+    write_uint(&mut tractor, 2, json.tractor.col as u64);
+    write_bytes(&mut buf, 4, &tractor);
+    let mut metadata = vec![];
+    if let Some(title) = &json.metadata.title {
+      write_bytes(&mut metadata, 1, title.as_bytes());
+    }
+    if let Some(author) = &json.metadata.author {
+      write_bytes(&mut metadata, 2, author.as_bytes());
+    }
+    if let Some(seed) = json.metadata.seed {
+      write_key(&mut metadata, 3, VARINT);
+      write_varint(&mut metadata, seed);
+    }
+    if let Some(pushes) = json.metadata.pushes {
+      write_key(&mut metadata, 4, VARINT);
+      write_varint(&mut metadata, pushes as u64);
+    }
+    write_bytes(&mut buf, 5, &metadata);
+    buf
+  }
+  pub fn from_proto(buf: &[u8]) -> io::Result<Level> {
+    let mut json = JsonLevel {
+      version: VERSION,
+      rows: 0,
+      cols: 0,
+      cells: vec![],
+      tractor: Position { row: 0, col: 0 },
+      metadata: Metadata::default(),
+    };
+    for field in fields(buf)? {
+      match field {
+        (1, Value::Varint(rows)) => json.rows = rows as usize,
+        (2, Value::Varint(cols)) => json.cols = cols as usize,
+        (3, Value::Bytes(row)) => json.cells.push(string(row)?),
+        (4, Value::Bytes(tractor)) => for field in fields(tractor)? {
+          match field {
+            (1, Value::Varint(row)) => json.tractor.row = row as usize,
+            (2, Value::Varint(col)) => json.tractor.col = col as usize,
+            _ => {},
+          }
+        },
+        (5, Value::Bytes(metadata)) => for field in fields(metadata)? {
+          match field {
+            (1, Value::Bytes(title)) => json.metadata.title = Some(string(title)?),
+            (2, Value::Bytes(author)) => json.metadata.author = Some(string(author)?),
+            (3, Value::Varint(seed)) => json.metadata.seed = Some(seed),
+            (4, Value::Varint(pushes)) => json.metadata.pushes = Some(pushes as usize),
+            _ => {},
+          }
+        },
+        _ => {},
+      }
+    }
+    json.to_level().map_err(|err| invalid_data(&err))
+  }
+}
+
+impl Solution {
+  // States are written as rows of `dims.cols` cells
+  pub fn to_proto(&self, dims: Dims) -> Vec<u8> {
+    let mut buf = vec![];
+    for push in &self.pushes {
+      let mut message = vec![];
+      write_uint(&mut message, 1, push.boulder as u64);
+      write_uint(&mut message, 2, DIRECTIONS.iter().position(|dir| *dir == push.dir).unwrap() as u64);
+      write_uint(&mut message, 3, push.tractor as u64);
+      write_bytes(&mut buf, 1, &message);
+    }
+    for state in &self.states {
+      let mut board = vec![];
+      for row in state.chunks(dims.cols) {
+        write_bytes(&mut board, 1, row.iter().map(|cell| cell.to_char()).collect::<String>().as_bytes());
+      }
+      write_bytes(&mut buf, 2, &board);
+    }
+    write_bytes(&mut buf, 3, self.moves.as_bytes());
+    buf
+  }
+  pub fn from_proto(buf: &[u8]) -> io::Result<Solution> {
+    let mut solution = Solution { pushes: vec![], states: vec![], moves: String::new() };
+    for field in fields(buf)? {
+      match field {
+        (1, Value::Bytes(message)) => {
+          let mut push = Push { boulder: 0, dir: Direction::Up, tractor: 0 };
+          for field in fields(message)? {
+            match field {
+              (1, Value::Varint(boulder)) => push.boulder = boulder as usize,
+              (2, Value::Varint(dir)) => {
+                push.dir = *DIRECTIONS.get(dir as usize).ok_or_else(|| invalid_data("unknown direction"))?;
+              },
+              (3, Value::Varint(tractor)) => push.tractor = tractor as usize,
+              _ => {},
+            }
+          }
+          solution.pushes.push(push);
+        },
+        (2, Value::Bytes(board)) => {
+          let mut state = vec![];
+          for field in fields(board)? {
+            if let (1, Value::Bytes(row)) = field {
+              for c in string(row)?.chars() {
+                state.push(Cell::try_from_char(c).ok_or_else(|| invalid_data(&format!("unrecognized character `{}`", c)))?);
+              }
+            }
+          }
+          solution.states.push(state);
+        },
+        (3, Value::Bytes(moves)) => solution.moves = string(moves)?,
+        _ => {},
+      }
+    }
+    Ok(solution)
+  }
+}
+
+// As the `GraphSummary` message
+impl PushGraphStats {
+  pub fn to_proto(&self) -> Vec<u8> {
+    let mut buf = vec![];
+    write_uint(&mut buf, 1, self.states as u64);
+    write_uint(&mut buf, 2, self.edges as u64);
+    write_uint(&mut buf, 3, self.goals as u64);
+    if let Some(pushes) = self.pushes {
+      write_key(&mut buf, 4, VARINT);
+      write_varint(&mut buf, pushes as u64);
+    }
+    write_double(&mut buf, 5, self.branching);
+    write_double(&mut buf, 6, self.dead_fraction);
+    buf
+  }
+  pub fn from_proto(buf: &[u8]) -> io::Result<PushGraphStats> {
+    let mut stats = PushGraphStats { states: 0, edges: 0, goals: 0, pushes: None, branching: 0.0, dead_fraction: 0.0 };
+    for field in fields(buf)? {
+      match field {
+        (1, Value::Varint(states)) => stats.states = states as usize,
+        (2, Value::Varint(edges)) => stats.edges = edges as usize,
+        (3, Value::Varint(goals)) => stats.goals = goals as usize,
+        (4, Value::Varint(pushes)) => stats.pushes = Some(pushes as usize),
+        (5, Value::Fixed64(bits)) => stats.branching = f64::from_bits(bits),
+        (6, Value::Fixed64(bits)) => stats.dead_fraction = f64::from_bits(bits),
+        _ => {},
+      }
+    }
+    Ok(stats)
+  }
+}
+
+// Wire types
+const VARINT: u64 = 0;
+const FIXED64: u64 = 1;
+const BYTES: u64 = 2;
+const FIXED32: u64 = 5;
+
+fn write_varint(buf: &mut Vec<u8>, mut value: u64) {
+  while value >= 0x80 {
+    buf.push(value as u8 | 0x80);
+    value >>= 7;
+  }
+  buf.push(value as u8);
+}
+
+fn write_key(buf: &mut Vec<u8>, field: u64, wire_type: u64) {
+  write_varint(buf, field << 3 | wire_type);
+}
+
+// Left out when zero, as proto3 does for fields that aren't `optional`
+fn write_uint(buf: &mut Vec<u8>, field: u64, value: u64) {
+  if value != 0 {
+    write_key(buf, field, VARINT);
+    write_varint(buf, value);
+  }
+}
+
+fn write_double(buf: &mut Vec<u8>, field: u64, value: f64) {
+  if value != 0.0 {
+    write_key(buf, field, FIXED64);
+    buf.extend(value.to_bits().to_le_bytes());
+  }
+}
+
+fn write_bytes(buf: &mut Vec<u8>, field: u64, bytes: &[u8]) {
+  write_key(buf, field, BYTES);
+  write_varint(buf, bytes.len() as u64);
+  buf.extend(bytes);
+}
+
+enum Value<'a> {
+  Varint(u64),
+  Fixed64(u64),
+  Bytes(&'a [u8]),
+  Fixed32,
+}
+
+// The fields of a message in order, by number
+fn fields(mut buf: &[u8]) -> io::Result<Vec<(u64, Value<'_>)>> {
+  let mut fields = vec![];
+  while !buf.is_empty() {
+    let key = read_varint(&mut buf)?;
+    let value = match key & 7 {
+      VARINT => Value::Varint(read_varint(&mut buf)?),
+      FIXED64 => Value::Fixed64(u64::from_le_bytes(take(&mut buf, 8)?.try_into().unwrap())),
+      BYTES => {
+        let len = read_varint(&mut buf)?;
+        Value::Bytes(take(&mut buf, usize::try_from(len).map_err(|_| invalid_data("truncated message"))?)?)
+      },
+      FIXED32 => {
+        take(&mut buf, 4)?;
+        Value::Fixed32
+      },
+      wire_type => return Err(invalid_data(&format!("unsupported wire type {}", wire_type))),
+    };
+    fields.push((key >> 3, value));
+  }
+  Ok(fields)
+}
+
+fn read_varint(buf: &mut &[u8]) -> io::Result<u64> {
+  let mut value = 0;
+  for shift in (0..u64::BITS).step_by(7) {
+    let byte = take(buf, 1)?[0];
+    value |= ((byte & 0x7f) as u64) << shift;
+    if byte & 0x80 == 0 {
+      return Ok(value);
+    }
+  }
+  Err(invalid_data("oversized varint"))
+}
+
+fn take<'a>(buf: &mut &'a [u8], len: usize) -> io::Result<&'a [u8]> {
+  if len > buf.len() {
+    return Err(invalid_data("truncated message"));
+  }
+  let (bytes, rest) = buf.split_at(len);
+  *buf = rest;
+  Ok(bytes)
+}
+
+fn string(bytes: &[u8]) -> io::Result<String> {
+  String::from_utf8(bytes.to_vec()).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+  io::Error::new(io::ErrorKind::InvalidData, format!("bad protobuf message: {}", msg))
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::difficulty::push_graph_stats;
+  use crate::solver::solve;
+
+  #[test]
+  fn test_round_trip() {
+    let grid = vec![
+      Cell::Unreachable, Cell::Boulder, Cell::Unreachable, Cell::Hole,
+      Cell::Unreachable, Cell::Ice, Cell::Unreachable, Cell::Unreachable,
+    ];
+    let metadata = Metadata { title: Some("Ice".into()), seed: Some(0), ..Default::default() };
+    let level = Level::new(grid, Dims::new(2, 4), 4).with_metadata(metadata);
+    assert_eq!(Level::from_proto(&level.to_proto()).unwrap(), level);
+    let solution = solve(&level).unwrap();
+    assert_eq!(Solution::from_proto(&solution.to_proto(level.dims)).unwrap(), solution);
+    let stats = push_graph_stats(&level).unwrap();
+    assert_eq!(PushGraphStats::from_proto(&stats.to_proto()).unwrap(), stats);
+    // Rows 2 and cols 4, then an unknown field a newer writer might add
+    let mut buf = vec![0x08, 2, 0x10, 4, 0xb8, 0x01, 7];
+    assert_eq!(PushGraphStats::from_proto(&buf).unwrap().edges, 4);
+    assert!(Level::from_proto(&buf).is_err());
+    buf.push(0x80);
+    assert!(Level::from_proto(&buf).is_err());
+  }
+}
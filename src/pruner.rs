@@ -0,0 +1,41 @@
+use crate::cell::Cell;
+use crate::grid::Dims;
+
+// A cut-off applied to every new state found while exploring. Pruned states
+// are neither stored nor expanded.
+pub trait StatePruner: Send + Sync {
+  fn should_prune(&self, state: &[Cell], dims: Dims) -> bool;
+}
+
+impl<F: Fn(&[Cell], Dims) -> bool + Send + Sync> StatePruner for F {
+  fn should_prune(&self, state: &[Cell], dims: Dims) -> bool {
+    self(state, dims)
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::sync::Arc;
+
+  use super::*;
+  use crate::grid::Dims;
+  use crate::level::Level;
+  use crate::state_graph::*;
+
+  #[test]
+  fn test_pruned_states_are_skipped() {
+    let grid = vec![
+      Cell::BoulderInHole, Cell::Unreachable, Cell::Unreachable, Cell::BoulderInHole,
+      Cell::Unreachable, Cell::Unreachable, Cell::Unreachable, Cell::Unreachable,
+      Cell::Unreachable, Cell::Unreachable, Cell::Unreachable, Cell::Unreachable,
+      Cell::BoulderInHole, Cell::Unreachable, Cell::Unreachable, Cell::BoulderInHole,
+    ];
+    let full = find_solvable_states(&Level::new(grid.clone(), Dims::square(4), 5));
+    let center_free = |state: &[Cell], _dims: Dims| state[10] == Cell::Boulder;
+    let options = ExploreOptions { pruners: vec![Arc::new(center_free)], ..Default::default() };
+    let pruned = find_solvable_states_with(&Level::new(grid, Dims::square(4), 5), &options);
+    assert!(pruned.len() < full.len());
+    assert!(pruned.states().all(|(_, state)| state[10] != Cell::Boulder));
+    assert!(pruned.edges().all(|(from, to)| pruned.contains_id(&from) && pruned.contains_id(&to)));
+  }
+}
@@ -0,0 +1,131 @@
+use rand::{seq::SliceRandom, Rng, SeedableRng};
+use rand_pcg::Pcg64;
+
+use crate::cell::Cell;
+use crate::difficulty::{measure, LevelMetrics};
+use crate::direction::DIRECTIONS;
+use crate::level::Level;
+
+// Geometric cooling: the temperature starts at `temperature` and is scaled by
+// `cooling` after every step
+#[derive(Clone, Debug, PartialEq)]
+pub struct Schedule {
+  pub temperature: f64,
+  pub cooling: f64,
+  pub steps: usize,
+}
+
+impl Default for Schedule {
+  fn default() -> Self {
+    Schedule { temperature: 2.0, cooling: 0.98, steps: 200 }
+  }
+}
+
+// Polishes a solvable level by simulated annealing on `objective`, higher is
+// better. Every step changes a single cell, and unsolvable candidates are
+// never accepted. Returns the best level seen and its objective, or `None`
+// if `level` itself can't be solved.
+pub fn refine<F>(level: &Level, objective: F, schedule: &Schedule, seed: u64) -> Option<(Level, f64)>
+where
+  F: Fn(&Level, &LevelMetrics) -> f64,
+{
+  let mut rng = Pcg64::seed_from_u64(seed);
+  let score = |level: &Level| measure(level).map(|metrics| objective(level, &metrics));
+  let mut current = (level.clone(), score(level)?);
+  let mut best = current.clone();
+  let mut temperature = schedule.temperature;
+  for _ in 0..schedule.steps {
+    let mut candidate = current.0.clone();
+    if perturb(&mut candidate, &mut rng) {
+      if let Some(value) = score(&candidate) {
+        let gain = value - current.1;
+        if gain >= 0.0 || (temperature > 0.0 && rng.gen::<f64>() < (gain / temperature).exp()) {
+          current = (candidate, value);
+          if current.1 > best.1 {
+            best = current.clone();
+          }
+        }
+      }
+    }
+    temperature *= schedule.cooling;
+  }
+  Some(best)
+}
+
+// Changes one cell: a block is cleared, a floor cell is blocked, or a boulder
+// or hole steps onto a neighboring floor cell. The tractor's cell is left
+// alone. Returns whether anything changed.
+pub fn perturb<R: Rng>(level: &mut Level, rng: &mut R) -> bool {
+  let cells = (0..level.grid.len()).filter(|idx| *idx != level.tractor).collect::<Vec<usize>>();
+  let idx = match cells.choose(rng) {
+    Some(idx) => *idx,
+    None => return false,
+  };
+  match level.grid[idx] {
+    Cell::Block => level.grid[idx] = Cell::Unreachable,
+    Cell::Unreachable => level.grid[idx] = Cell::Block,
+    Cell::Boulder | Cell::Hole => {
+      let floor = DIRECTIONS.iter()
+        .filter_map(|dir| dir.apply(idx, level.dims))
+        .filter(|next| *next != level.tractor && level.grid[*next] == Cell::Unreachable)
+        .collect::<Vec<usize>>();
+      match floor.choose(rng) {
+        Some(next) => level.grid.swap(idx, *next),
+        None => return false,
+      }
+    },
+    _ => return false,
+  }
+  true
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::grid::Dims;
+
+  fn corner_level() -> Level {
+    let grid = vec![
+      Cell::Hole, Cell::Unreachable, Cell::Unreachable, Cell::Unreachable,
+      Cell::Block, Cell::Block, Cell::Boulder, Cell::Unreachable,
+      Cell::Block, Cell::Block, Cell::Unreachable, Cell::Unreachable,
+      Cell::Block, Cell::Block, Cell::Unreachable, Cell::Unreachable,
+    ];
+    Level::new(grid, Dims::square(4), 15)
+  }
+
+  #[test]
+  fn test_refine_keeps_the_best() {
+    let level = corner_level();
+    let objective = |_: &Level, metrics: &LevelMetrics| metrics.pushes as f64;
+    let (refined, value) = refine(&level, objective, &Schedule::default(), 3).unwrap();
+    assert!(value >= 3.0);
+    assert_eq!(measure(&refined).unwrap().pushes as f64, value);
+    assert_eq!(refined.tractor, level.tractor);
+  }
+
+  #[test]
+  fn test_refine_unsolvable() {
+    let grid = vec![
+      Cell::Hole, Cell::Block, Cell::Unreachable,
+      Cell::Block, Cell::Boulder, Cell::Unreachable,
+      Cell::Unreachable, Cell::Unreachable, Cell::Unreachable,
+    ];
+    let objective = |_: &Level, metrics: &LevelMetrics| metrics.score();
+    assert_eq!(refine(&Level::new(grid, Dims::square(3), 8), objective, &Schedule::default(), 0), None);
+  }
+
+  #[test]
+  fn test_perturb_changes_one_cell() {
+    let mut rng = Pcg64::seed_from_u64(4);
+    let mut level = corner_level();
+    for _ in 0..20 {
+      let before = level.clone();
+      if perturb(&mut level, &mut rng) {
+        let changed = (0..16).filter(|idx| before.grid[*idx] != level.grid[*idx]).count();
+        assert!(changed == 1 || changed == 2);
+      }
+      assert_eq!(level.grid[15], Cell::Unreachable);
+    }
+  }
+}
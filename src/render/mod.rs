@@ -0,0 +1,283 @@
+use serde::{Deserialize, Serialize};
+
+pub mod svg;
+pub mod text;
+
+use crate::cell::{Cell, Color};
+use crate::direction::Direction;
+use crate::grid::Dims;
+use crate::level::Level;
+use crate::solver::Solution;
+
+pub type Rgb = [u8; 3];
+
+// Colors for each part of a drawing, and how many pixels wide a cell is
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(default)]
+pub struct Style {
+  pub tile: u32,
+  pub floor: Rgb,
+  // The tractor's region in states
+  pub reachable: Rgb,
+  pub wall: Rgb,
+  pub block: Rgb,
+  pub hole: Rgb,
+  pub boulder: Rgb,
+  pub heavy_boulder: Rgb,
+  pub ice: Rgb,
+  pub one_way: Rgb,
+  pub portal: Rgb,
+  pub cracked: Rgb,
+  pub collapsed: Rgb,
+  pub tractor: Rgb,
+}
+
+impl Default for Style {
+  fn default() -> Self {
+    Style {
+      tile: 24,
+      floor: [0xe8, 0xe0, 0xd0],
+      reachable: [0xc8, 0xe6, 0xc0],
+      wall: [0x30, 0x30, 0x30],
+      block: [0x70, 0x60, 0x50],
+      hole: [0x20, 0x18, 0x10],
+      boulder: [0xa0, 0x80, 0x50],
+      heavy_boulder: [0x50, 0x40, 0x30],
+      ice: [0xc0, 0xe8, 0xf8],
+      one_way: [0xd8, 0xd0, 0xb0],
+      portal: [0xb0, 0x60, 0xd0],
+      cracked: [0xb8, 0xa8, 0x90],
+      collapsed: [0x10, 0x10, 0x10],
+      tractor: [0xe0, 0x40, 0x20],
+    }
+  }
+}
+
+impl Style {
+  pub fn color(&self, color: Color) -> Rgb {
+    match color {
+      Color::Red => [0xd0, 0x30, 0x30],
+      Color::Green => [0x30, 0xa0, 0x40],
+      Color::Blue => [0x30, 0x50, 0xd0],
+    }
+  }
+}
+
+// The parts a cell is drawn from, each relative to the cell's square
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Shape {
+  Square,
+  // A smaller square in the middle, for holes
+  Inset,
+  Circle,
+  // A small circle, for the tractor
+  Dot,
+  // A bar along the side of the cell facing the direction
+  Edge(Direction),
+}
+
+impl Shape {
+  // Whether the point `(x, y)`, both in `0.0..1.0` across the cell, is inside
+  pub fn contains(self, x: f64, y: f64) -> bool {
+    let dist = |r: f64| (x - 0.5).powi(2) + (y - 0.5).powi(2) <= r * r;
+    match self {
+      Shape::Square => true,
+      Shape::Inset => (0.15..0.85).contains(&x) && (0.15..0.85).contains(&y),
+      Shape::Circle => dist(0.38),
+      Shape::Dot => dist(0.22),
+      Shape::Edge(Direction::Up) => y < 0.15,
+      Shape::Edge(Direction::Down) => y >= 0.85,
+      Shape::Edge(Direction::Left) => x < 0.15,
+      Shape::Edge(Direction::Right) => x >= 0.85,
+    }
+  }
+}
+
+// What to draw for `cell`, bottom layer first
+pub fn layers(cell: Cell, is_tractor: bool, style: &Style) -> Vec<(Shape, Rgb)> {
+  let floor = cell.without_boulder();
+  let mut layers = vec![(Shape::Square, match floor {
+    Cell::Reachable => style.reachable,
+    Cell::Wall => style.wall,
+    Cell::Block => style.block,
+    Cell::Ice => style.ice,
+    Cell::OneWay(_) => style.one_way,
+    Cell::Portal(_) => style.portal,
+    Cell::Cracked => style.cracked,
+    Cell::Collapsed => style.collapsed,
+    _ => style.floor,
+  })];
+  if let Cell::OneWay(dir) = floor {
+    layers.push((Shape::Edge(dir), style.wall));
+  }
+  if floor.is_hole() {
+    layers.push((Shape::Inset, floor.color().map_or(style.hole, |color| style.color(color))));
+  }
+  if cell.has_boulder() {
+    let color = match cell.color() {
+      Some(color) => style.color(color),
+      None if cell.is_heavy() => style.heavy_boulder,
+      None => style.boulder,
+    };
+    layers.push((Shape::Circle, color));
+  }
+  if is_tractor {
+    layers.push((Shape::Dot, style.tractor));
+  }
+  layers
+}
+
+// An RGB image, row by row
+#[derive(Clone, Debug, PartialEq)]
+pub struct Image {
+  pub width: u32,
+  pub height: u32,
+  pub pixels: Vec<u8>,
+}
+
+impl Image {
+  pub fn pixel(&self, x: u32, y: u32) -> Rgb {
+    let at = 3 * (y * self.width + x) as usize;
+    [self.pixels[at], self.pixels[at + 1], self.pixels[at + 2]]
+  }
+}
+
+// Draws a board, marking the tractor at `tractor` if given. The `Reachable`
+// cells of a state are shaded instead.
+pub fn raster(grid: &[Cell], dims: Dims, tractor: Option<usize>, style: &Style) -> Image {
+  let tile = style.tile.max(1);
+  let width = dims.cols as u32 * tile;
+  let height = dims.rows as u32 * tile;
+  let mut pixels = vec![0; 3 * (width * height) as usize];
+  for (idx, cell) in grid.iter().enumerate() {
+    let layers = layers(*cell, tractor == Some(idx), style);
+    let (left, top) = (dims.col(idx) as u32 * tile, dims.row(idx) as u32 * tile);
+    for dy in 0..tile {
+      for dx in 0..tile {
+        let (x, y) = ((dx as f64 + 0.5) / tile as f64, (dy as f64 + 0.5) / tile as f64);
+        if let Some((_, rgb)) = layers.iter().rev().find(|(shape, _)| shape.contains(x, y)) {
+          let at = 3 * ((top + dy) * width + left + dx) as usize;
+          pixels[at..at + 3].copy_from_slice(rgb);
+        }
+      }
+    }
+  }
+  Image { width, height, pixels }
+}
+
+// One frame per state of `solution`: the level as it starts, then the board
+// after each push with the tractor where the push left it. The `Reachable`
+// shading is left off.
+pub fn playback_levels(level: &Level, solution: &Solution) -> Vec<Level> {
+  let tractors = std::iter::once(level.tractor).chain(solution.pushes.iter().map(|push| push.tractor));
+  solution.states.iter().zip(tractors)
+    .map(|(state, tractor)| {
+      let board = state.iter()
+        .map(|cell| if *cell == Cell::Reachable { Cell::Unreachable } else { *cell })
+        .collect::<Vec<Cell>>();
+      Level::new(board, level.dims, tractor)
+    })
+    .collect()
+}
+
+// `playback_levels` drawn
+pub fn playback(level: &Level, solution: &Solution, style: &Style) -> Vec<Image> {
+  playback_levels(level, solution).iter()
+    .map(|frame| raster(&frame.grid, frame.dims, Some(frame.tractor), style))
+    .collect()
+}
+
+#[cfg(feature = "png")]
+pub fn encode_png(image: &Image) -> Vec<u8> {
+  let mut buf = vec![];
+  let mut encoder = png::Encoder::new(&mut buf, image.width, image.height);
+  encoder.set_color(png::ColorType::Rgb);
+  encoder.set_depth(png::BitDepth::Eight);
+  // Writing to memory only fails on a bad header, which `raster` never makes
+  let mut writer = encoder.write_header().unwrap();
+  writer.write_image_data(&image.pixels).unwrap();
+  writer.finish().unwrap();
+  buf
+}
+
+// Needs the `png` feature
+#[cfg(feature = "png")]
+pub fn render_grid_png(grid: &[Cell], dims: Dims, style: &Style) -> Vec<u8> {
+  encode_png(&raster(grid, dims, None, style))
+}
+
+#[cfg(feature = "png")]
+pub fn render_level_png(level: &Level, style: &Style) -> Vec<u8> {
+  encode_png(&raster(&level.grid, level.dims, Some(level.tractor), style))
+}
+
+// Plays `playback` on a loop, `delay` hundredths of a second per frame. Needs
+// the `gif` feature.
+#[cfg(feature = "gif")]
+pub fn render_solution_gif(level: &Level, solution: &Solution, style: &Style, delay: u16) -> Vec<u8> {
+  let frames = playback(level, solution, style);
+  let (width, height) = (frames[0].width as u16, frames[0].height as u16);
+  let mut buf = vec![];
+  {
+    // As with PNG, encoding to memory can't fail on frames `raster` made
+    let mut encoder = gif::Encoder::new(&mut buf, width, height, &[]).unwrap();
+    encoder.set_repeat(gif::Repeat::Infinite).unwrap();
+    for image in &frames {
+      let mut frame = gif::Frame::from_rgb(width, height, &image.pixels);
+      frame.delay = delay;
+      encoder.write_frame(&frame).unwrap();
+    }
+  }
+  buf
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn test_raster() {
+    let grid = vec![Cell::Reachable, Cell::BoulderInHole, Cell::Wall, Cell::OneWay(Direction::Left)];
+    let style = Style { tile: 10, ..Style::default() };
+    let image = raster(&grid, Dims::new(2, 2), Some(0), &style);
+    assert_eq!((image.width, image.height), (20, 20));
+    assert_eq!(image.pixel(0, 0), style.reachable);
+    assert_eq!(image.pixel(5, 5), style.tractor);
+    assert_eq!(image.pixel(11, 1), style.hole);
+    assert_eq!(image.pixel(15, 5), style.boulder);
+    assert_eq!(image.pixel(5, 15), style.wall);
+    assert_eq!(image.pixel(10, 15), style.wall);
+    assert_eq!(image.pixel(15, 15), style.one_way);
+  }
+
+  #[cfg(feature = "png")]
+  #[test]
+  fn test_render_png() {
+    let level = Level::new(vec![Cell::Unreachable, Cell::Boulder, Cell::Hole], Dims::new(1, 3), 0);
+    let png = render_level_png(&level, &Style::default());
+    assert!(png.starts_with(&[0x89, b'P', b'N', b'G']));
+  }
+
+  #[test]
+  fn test_playback() {
+    let level = Level::new(vec![Cell::Unreachable, Cell::Boulder, Cell::Unreachable, Cell::Hole], Dims::new(1, 4), 0);
+    let solution = crate::solver::solve(&level).unwrap();
+    let style = Style { tile: 10, ..Style::default() };
+    let frames = playback(&level, &solution, &style);
+    assert_eq!(frames.len(), 3);
+    assert_eq!(frames[0].pixel(5, 5), style.tractor);
+    assert_eq!(frames[0].pixel(15, 5), style.boulder);
+    assert_eq!(frames[2].pixel(25, 5), style.tractor);
+    assert_eq!(frames[2].pixel(35, 5), style.boulder);
+    assert_eq!(frames[2].pixel(5, 5), style.floor);
+  }
+
+  #[cfg(feature = "gif")]
+  #[test]
+  fn test_render_gif() {
+    let level = Level::new(vec![Cell::Unreachable, Cell::Boulder, Cell::Hole], Dims::new(1, 3), 0);
+    let solution = crate::solver::solve(&level).unwrap();
+    let gif = render_solution_gif(&level, &solution, &Style::default(), 50);
+    assert!(gif.starts_with(b"GIF89a"));
+  }
+}
@@ -0,0 +1,185 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use crate::cell::Cell;
+use crate::direction::Direction;
+use crate::grid::Dims;
+use crate::level::Level;
+use crate::render::{layers, Rgb, Shape, Style};
+use crate::state_graph::{StateGraph, StateId};
+
+// Boards in a graph drawing are this many tiles apart
+const GAP: u32 = 2;
+
+// Like `raster`, as SVG markup
+pub fn render_grid_svg(grid: &[Cell], dims: Dims, tractor: Option<usize>, style: &Style) -> String {
+  let tile = style.tile.max(1);
+  let (width, height) = (dims.cols as u32 * tile, dims.rows as u32 * tile);
+  let mut out = header(width, height);
+  board(&mut out, grid, dims, tractor, style, 0, 0);
+  out.push_str("</svg>\n");
+  out
+}
+
+pub fn render_level_svg(level: &Level, style: &Style) -> String {
+  render_grid_svg(&level.grid, level.dims, Some(level.tractor), style)
+}
+
+// Every state as a small board, one column per number of pushes from the
+// root, with an arrow for each edge. The root is outlined thick and goal
+// states in the tractor color. Meant for graphs of a few dozen states.
+pub fn render_graph_svg(graph: &StateGraph, style: &Style) -> String {
+  let tile = style.tile.max(1);
+  let dims = graph.dims();
+  let (board_width, board_height) = (dims.cols as u32 * tile, dims.rows as u32 * tile);
+  let shortest = graph.build_shortest_path_from(&graph.root());
+  let mut columns = BTreeMap::<usize, Vec<StateId>>::new();
+  for (id, _) in graph.states() {
+    columns.entry(shortest.depth(&id).unwrap_or(usize::MAX)).or_default().push(id);
+  }
+  let mut at = BTreeMap::new();
+  for (col, ids) in columns.values_mut().enumerate() {
+    ids.sort();
+    for (row, id) in ids.iter().enumerate() {
+      at.insert(*id, (col as u32 * (board_width + GAP * tile), row as u32 * (board_height + GAP * tile)));
+    }
+  }
+  let tallest = columns.values().map(|ids| ids.len() as u32).max().unwrap_or(0);
+  let width = (columns.len() as u32 * (board_width + GAP * tile)).saturating_sub(GAP * tile);
+  let height = (tallest * (board_height + GAP * tile)).saturating_sub(GAP * tile);
+  let mut out = header(width, height);
+  writeln!(out, "<defs><marker id=\"arrow\" viewBox=\"0 0 10 10\" refX=\"10\" refY=\"5\" \
+    markerWidth=\"6\" markerHeight=\"6\" orient=\"auto\"><path d=\"M0,0 L10,5 L0,10 z\"/></marker></defs>").unwrap();
+  let mut edges = graph.edges().collect::<Vec<_>>();
+  edges.sort();
+  for (from, to) in edges {
+    let ((x1, y1), (x2, y2)) = (at[&from], at[&to]);
+    writeln!(out, "<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"black\" marker-end=\"url(#arrow)\"/>",
+      x1 + board_width, y1 + board_height / 2, x2, y2 + board_height / 2).unwrap();
+  }
+  for (id, (left, top)) in &at {
+    let state = graph.get_state(id).unwrap();
+    writeln!(out, "<g id=\"s{}\">", id.index()).unwrap();
+    board(&mut out, &state, dims, None, style, *left, *top);
+    let outline = match (*id == graph.root(), graph.is_goal(id)) {
+      (true, _) => Some(("black", 3)),
+      (false, true) => Some((&*hex(style.tractor), 2)),
+      _ => None,
+    };
+    if let Some((color, width)) = outline {
+      writeln!(out, "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"none\" stroke=\"{}\" stroke-width=\"{}\"/>",
+        left, top, board_width, board_height, color, width).unwrap();
+    }
+    writeln!(out, "</g>").unwrap();
+  }
+  out.push_str("</svg>\n");
+  out
+}
+
+// The level with a square of the tractor color over each cell, as opaque as
+// its share of `heat`'s largest value, such as from `tractor_heatmap`
+pub fn render_heatmap_svg(level: &Level, heat: &[f64], style: &Style) -> String {
+  let tile = style.tile.max(1);
+  let dims = level.dims;
+  let mut out = header(dims.cols as u32 * tile, dims.rows as u32 * tile);
+  board(&mut out, &level.grid, dims, Some(level.tractor), style, 0, 0);
+  let most = heat.iter().cloned().fold(0.0, f64::max);
+  for (idx, value) in heat.iter().enumerate().filter(|(_, value)| **value > 0.0) {
+    writeln!(out, "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\" fill-opacity=\"{}\"/>",
+      dims.col(idx) as u32 * tile, dims.row(idx) as u32 * tile, tile, tile, hex(style.tractor), num(value / most)).unwrap();
+  }
+  out.push_str("</svg>\n");
+  out
+}
+
+fn header(width: u32, height: u32) -> String {
+  format!("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{1}\" viewBox=\"0 0 {0} {1}\">\n", width, height)
+}
+
+fn board(out: &mut String, grid: &[Cell], dims: Dims, tractor: Option<usize>, style: &Style, left: u32, top: u32) {
+  let tile = style.tile.max(1) as f64;
+  for (idx, cell) in grid.iter().enumerate() {
+    let x = left as f64 + dims.col(idx) as f64 * tile;
+    let y = top as f64 + dims.row(idx) as f64 * tile;
+    for (shape, rgb) in layers(*cell, tractor == Some(idx), style) {
+      out.push_str(&element(shape, x, y, tile, rgb));
+      out.push('\n');
+    }
+  }
+}
+
+// The markup for `shape` on the cell with its top left corner at `(x, y)`,
+// matching `Shape::contains`
+fn element(shape: Shape, x: f64, y: f64, tile: f64, rgb: Rgb) -> String {
+  let rect = |x: f64, y: f64, w: f64, h: f64| format!(
+    "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\"/>", num(x), num(y), num(w), num(h), hex(rgb));
+  let circle = |r: f64| format!(
+    "<circle cx=\"{}\" cy=\"{}\" r=\"{}\" fill=\"{}\"/>", num(x + tile / 2.0), num(y + tile / 2.0), num(r * tile), hex(rgb));
+  let bar = 0.15 * tile;
+  match shape {
+    Shape::Square => rect(x, y, tile, tile),
+    Shape::Inset => rect(x + bar, y + bar, tile - 2.0 * bar, tile - 2.0 * bar),
+    Shape::Circle => circle(0.38),
+    Shape::Dot => circle(0.22),
+    Shape::Edge(Direction::Up) => rect(x, y, tile, bar),
+    Shape::Edge(Direction::Down) => rect(x, y + tile - bar, tile, bar),
+    Shape::Edge(Direction::Left) => rect(x, y, bar, tile),
+    Shape::Edge(Direction::Right) => rect(x + tile - bar, y, bar, tile),
+  }
+}
+
+fn hex(rgb: Rgb) -> String {
+  format!("#{:02x}{:02x}{:02x}", rgb[0], rgb[1], rgb[2])
+}
+
+// Whole numbers without a fraction, anything else to two places
+fn num(value: f64) -> String {
+  if value.fract() == 0.0 {
+    format!("{}", value)
+  } else {
+    format!("{:.2}", value)
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::state_graph::find_solvable_states;
+
+  #[test]
+  fn test_render_grid_svg() {
+    let level = Level::new(vec![Cell::Unreachable, Cell::BoulderInHole, Cell::OneWay(Direction::Right)], Dims::new(1, 3), 0);
+    let style = Style { tile: 10, ..Style::default() };
+    let svg = render_level_svg(&level, &style);
+    assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"30\" height=\"10\""));
+    assert!(svg.ends_with("</svg>\n"));
+    assert_eq!(svg.matches("<rect ").count(), 5);
+    assert_eq!(svg.matches("<circle ").count(), 2);
+    assert!(svg.contains("<circle cx=\"5\" cy=\"5\" r=\"2.20\" fill=\"#e04020\"/>"));
+    assert!(svg.contains("<rect x=\"28.50\" y=\"0\" width=\"1.50\" height=\"10\" fill=\"#303030\"/>"));
+  }
+
+  #[test]
+  fn test_render_heatmap_svg() {
+    let level = Level::new(vec![Cell::Unreachable, Cell::Boulder, Cell::Hole], Dims::new(1, 3), 0);
+    let svg = render_heatmap_svg(&level, &[1.0, 0.5, 0.0], &Style { tile: 10, ..Style::default() });
+    assert_eq!(svg.matches("fill-opacity=").count(), 2);
+    assert!(svg.contains("<rect x=\"10\" y=\"0\" width=\"10\" height=\"10\" fill=\"#e04020\" fill-opacity=\"0.50\"/>"));
+    assert!(svg.ends_with("</svg>\n"));
+  }
+
+  #[test]
+  fn test_render_graph_svg() {
+    let grid = vec![
+      Cell::BoulderInHole, Cell::Unreachable, Cell::Unreachable,
+      Cell::Block, Cell::Block, Cell::Unreachable,
+      Cell::Block, Cell::Block, Cell::Unreachable,
+    ];
+    let graph = find_solvable_states(&Level::new(grid, Dims::square(3), 8));
+    let svg = render_graph_svg(&graph, &Style::default());
+    assert_eq!(svg.matches("<g id=").count(), graph.len());
+    assert_eq!(svg.matches("<line ").count(), graph.edges().count());
+    assert!(svg.contains("<g id=\"s0\">"));
+    assert!(svg.contains("stroke-width=\"3\""));
+  }
+}
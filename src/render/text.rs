@@ -0,0 +1,123 @@
+use std::collections::HashMap;
+
+use crate::cell::Cell;
+use crate::direction::Direction;
+use crate::grid::Dims;
+use crate::level::Level;
+
+// The characters a board is drawn with. Cells without a glyph of their own
+// fall back to `Cell::to_char`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Glyphs {
+  // Top left, top right, bottom left and bottom right corners, then the
+  // horizontal and vertical sides
+  pub border: [char; 6],
+  pub cells: HashMap<Cell, char>,
+  // Drawn over the tractor's start in levels
+  pub tractor: char,
+}
+
+impl Glyphs {
+  // The usual text format, as `Template::parse` and the CLI read it
+  pub fn ascii() -> Self {
+    Glyphs { border: ['+', '+', '+', '+', '-', '|'], cells: HashMap::new(), tractor: '.' }
+  }
+  // Box drawing and shapes, with the tractor's region shaded. Every glyph is
+  // one column wide in a monospace terminal.
+  pub fn unicode() -> Self {
+    let cells = [
+      (Cell::Reachable, '░'),
+      (Cell::Wall, '█'),
+      (Cell::Block, '▓'),
+      (Cell::Boulder, '●'),
+      (Cell::Hole, '○'),
+      (Cell::BoulderInHole, '◉'),
+      (Cell::Ice, '≈'),
+      (Cell::BoulderOnIce, '◍'),
+      (Cell::OneWay(Direction::Up), '↑'),
+      (Cell::OneWay(Direction::Down), '↓'),
+      (Cell::OneWay(Direction::Left), '←'),
+      (Cell::OneWay(Direction::Right), '→'),
+      (Cell::BoulderOnOneWay(Direction::Up), '⇑'),
+      (Cell::BoulderOnOneWay(Direction::Down), '⇓'),
+      (Cell::BoulderOnOneWay(Direction::Left), '⇐'),
+      (Cell::BoulderOnOneWay(Direction::Right), '⇒'),
+      (Cell::Cracked, '┼'),
+      (Cell::Collapsed, '╳'),
+      (Cell::HeavyBoulder, '◆'),
+      (Cell::HeavyBoulderInHole, '◈'),
+    ].iter().cloned().collect();
+    Glyphs { border: ['┌', '┐', '└', '┘', '─', '│'], cells, tractor: '☻' }
+  }
+  pub fn with(mut self, cell: Cell, glyph: char) -> Self {
+    self.cells.insert(cell, glyph);
+    self
+  }
+  pub fn glyph(&self, cell: Cell) -> char {
+    self.cells.get(&cell).cloned().unwrap_or_else(|| cell.to_char())
+  }
+}
+
+// The board in its box, one row per line
+pub fn grid_to_string(grid: &[Cell], dims: Dims) -> String {
+  grid_to_string_with(grid, dims, &Glyphs::ascii())
+}
+
+pub fn grid_to_string_with(grid: &[Cell], dims: Dims, glyphs: &Glyphs) -> String {
+  boxed(grid, dims, None, glyphs)
+}
+
+// A level in the boxed format, as `formats::parse_level` reads it back
+pub fn level_to_string(level: &Level) -> String {
+  level_to_string_with(level, &Glyphs::ascii())
+}
+
+// Only the tractor's start is drawn, not the rest of its region
+pub fn level_to_string_with(level: &Level, glyphs: &Glyphs) -> String {
+  let grid = level.grid.iter()
+    .map(|cell| if *cell == Cell::Reachable { Cell::Unreachable } else { *cell })
+    .collect::<Vec<Cell>>();
+  boxed(&grid, level.dims, Some(level.tractor), glyphs)
+}
+
+fn boxed(grid: &[Cell], dims: Dims, tractor: Option<usize>, glyphs: &Glyphs) -> String {
+  let [top_left, top_right, bottom_left, bottom_right, side, edge] = glyphs.border;
+  let rule = side.to_string().repeat(dims.cols);
+  let mut text = format!("{}{}{}\n", top_left, rule, top_right);
+  for (row, cells) in grid.chunks(dims.cols.max(1)).enumerate() {
+    text.push(edge);
+    for (col, cell) in cells.iter().enumerate() {
+      text.push(if tractor == Some(dims.index(row, col)) { glyphs.tractor } else { glyphs.glyph(*cell) });
+    }
+    text.push(edge);
+    text.push('\n');
+  }
+  text.push_str(&format!("{}{}{}\n", bottom_left, rule, bottom_right));
+  text
+}
+
+// Prints `state` to stderr with its reachable region shaded, for looking at
+// boards while debugging or when a test fails
+pub fn debug_state(state: &[Cell], dims: Dims) {
+  eprint!("{}", grid_to_string_with(state, dims, &Glyphs::unicode()));
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn test_grid_to_string() {
+    let grid = vec![
+      Cell::Reachable, Cell::Boulder, Cell::Hole,
+      Cell::Wall, Cell::Unreachable, Cell::BoulderInHole,
+    ];
+    let dims = Dims::new(2, 3);
+    assert_eq!(grid_to_string(&grid, dims), "+---+\n|.*O|\n|X @|\n+---+\n");
+    assert_eq!(grid_to_string_with(&grid, dims, &Glyphs::unicode()), "┌───┐\n│░●○│\n│█ ◉│\n└───┘\n");
+    let glyphs = Glyphs::ascii().with(Cell::Wall, '#');
+    assert_eq!(grid_to_string_with(&grid, dims, &glyphs), "+---+\n|.*O|\n|# @|\n+---+\n");
+    let level = Level::new(grid, dims, 4);
+    assert_eq!(level_to_string(&level), "+---+\n| *O|\n|X.@|\n+---+\n");
+  }
+}
@@ -0,0 +1,216 @@
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+
+use crate::cell::Cell;
+use crate::difficulty::{push_graph_stats, MAX_MEASURED_STATES};
+use crate::formats::json::JsonLevel;
+use crate::formats::parse_level;
+use crate::generator::{generate, GenConfig};
+use crate::grid::Dims;
+use crate::level::Level;
+use crate::metrics::{measure_difficulty, trickiness};
+use crate::play::Game;
+use crate::solver::solve_with;
+use crate::state_graph::{find_pushed_states_up_to, StateGraph};
+use crate::transposition::TranspositionTable;
+
+// States whose outcome the server remembers between solves
+const TRANSPOSITIONS: usize = 1 << 20;
+// Push graphs kept for hints, all dropped once there are more
+const CACHED_GRAPHS: usize = 16;
+
+// Standard JSON-RPC error codes, and one for requests that were understood
+// but couldn't be carried out
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const FAILED: i64 = -32000;
+
+struct RpcError {
+  code: i64,
+  message: String,
+}
+
+impl RpcError {
+  fn new<S: Into<String>>(code: i64, message: S) -> Self {
+    RpcError { code, message: message.into() }
+  }
+}
+
+// Answers JSON-RPC 2.0 requests one line at a time, keeping what it learns
+// between them: solves share a transposition table, and hints for a level
+// reuse its push graph. The methods are:
+//
+//   parse    {level}                   -> the JSON level
+//   generate {GenConfig fields}        -> a JSON level, or null
+//   solve    {level}                   -> {pushes, moves}, or null
+//   analyze  {level}                   -> the counts `lvlgen analyze` prints
+//   hint     {level, moves (optional)} -> the next push, or null
+//
+// where a level is given as a JSON level object or as text in any format
+// `parse_level` reads.
+pub struct Server {
+  table: TranspositionTable,
+  graphs: HashMap<(Vec<Cell>, Dims), StateGraph>,
+}
+
+impl Default for Server {
+  fn default() -> Self {
+    Server { table: TranspositionTable::new(TRANSPOSITIONS), graphs: HashMap::new() }
+  }
+}
+
+impl Server {
+  // The response to one line, `None` for notifications
+  pub fn handle_line(&mut self, line: &str) -> Option<String> {
+    let request = match serde_json::from_str::<Value>(line) {
+      Ok(request) => request,
+      Err(err) => return Some(respond(Value::Null, Err(RpcError::new(PARSE_ERROR, err.to_string())))),
+    };
+    let id = request.get("id").cloned();
+    let method = match (request.get("jsonrpc"), request.get("method").and_then(Value::as_str)) {
+      (Some(version), Some(method)) if version == "2.0" => method,
+      _ => {
+        let err = RpcError::new(INVALID_REQUEST, "not a JSON-RPC 2.0 request");
+        return Some(respond(id.unwrap_or(Value::Null), Err(err)));
+      },
+    };
+    let result = self.call(method, request.get("params").cloned().unwrap_or(Value::Null));
+    id.map(|id| respond(id, result))
+  }
+  // The result of one method without the JSON-RPC framing, or the message of
+  // its error
+  pub fn call_method(&mut self, method: &str, params: Value) -> Result<Value, String> {
+    self.call(method, params).map_err(|err| err.message)
+  }
+  fn call(&mut self, method: &str, params: Value) -> Result<Value, RpcError> {
+    match method {
+      "parse" => Ok(json!(JsonLevel::new(&level_param(&params)?))),
+      "generate" => {
+        let config: GenConfig = from_params(if params.is_null() { json!({}) } else { params })?;
+        Ok(json!(generate(&config).map(|level| JsonLevel::new(&level))))
+      },
+      "solve" => {
+        let level = level_param(&params)?;
+        let solution = solve_with(&level, &mut self.table);
+        Ok(json!(solution.map(|solution| json!({ "pushes": solution.pushes, "moves": solution.moves }))))
+      },
+      "analyze" => {
+        let level = level_param(&params)?;
+        let stats = push_graph_stats(&level).ok_or_else(too_many_states)?;
+        Ok(json!({
+          "states": stats.states,
+          "edges": stats.edges,
+          "goals": stats.goals,
+          "pushes": stats.pushes,
+          "branching": stats.branching,
+          "dead_fraction": stats.dead_fraction,
+          "score": measure_difficulty(&level).map(|difficulty| difficulty.score),
+          "trickiness": stats.pushes.and_then(|_| trickiness(&level)),
+        }))
+      },
+      "hint" => {
+        let level = level_param(&params)?;
+        let moves = params.get("moves").and_then(Value::as_str).unwrap_or("");
+        let game = Game::replay(level.clone(), moves)
+          .map_err(|at| RpcError::new(INVALID_PARAMS, format!("move {} can't be made", at + 1)))?;
+        let graph = self.graph(&level)?;
+        let hint = graph.get_id(game.state()).and_then(|id| graph.hint(&id));
+        Ok(json!(hint.map(|hint| hint.push)))
+      },
+      _ => Err(RpcError::new(METHOD_NOT_FOUND, format!("no method `{}`", method))),
+    }
+  }
+  // Fails for levels too big for `analyze` to measure
+  fn graph(&mut self, level: &Level) -> Result<&StateGraph, RpcError> {
+    let key = (level.initial_state(), level.dims);
+    if !self.graphs.contains_key(&key) {
+      let graph = find_pushed_states_up_to(level, MAX_MEASURED_STATES).ok_or_else(too_many_states)?;
+      if self.graphs.len() >= CACHED_GRAPHS {
+        self.graphs.clear();
+      }
+      self.graphs.insert(key.clone(), graph);
+    }
+    Ok(&self.graphs[&key])
+  }
+}
+
+fn too_many_states() -> RpcError {
+  RpcError::new(FAILED, format!("more than {} states", MAX_MEASURED_STATES))
+}
+
+// Serves requests from `input` until it ends, a response line for each
+pub fn serve<R: BufRead, W: Write>(input: R, mut output: W) -> io::Result<()> {
+  let mut server = Server::default();
+  for line in input.lines() {
+    let line = line?;
+    if line.trim().is_empty() {
+      continue;
+    }
+    if let Some(response) = server.handle_line(&line) {
+      writeln!(output, "{}", response)?;
+      output.flush()?;
+    }
+  }
+  Ok(())
+}
+
+fn respond(id: Value, result: Result<Value, RpcError>) -> String {
+  let response = match result {
+    Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+    Err(err) => json!({ "jsonrpc": "2.0", "id": id, "error": { "code": err.code, "message": err.message } }),
+  };
+  response.to_string()
+}
+
+fn from_params<T: DeserializeOwned>(params: Value) -> Result<T, RpcError> {
+  serde_json::from_value(params).map_err(|err| RpcError::new(INVALID_PARAMS, err.to_string()))
+}
+
+fn level_param(params: &Value) -> Result<Level, RpcError> {
+  let parsed = match params.get("level") {
+    Some(Value::String(text)) => parse_level(text),
+    Some(level) => from_params::<JsonLevel>(level.clone())?.to_level(),
+    None => Err("missing `level`".into()),
+  };
+  parsed.map_err(|message| RpcError::new(INVALID_PARAMS, message))
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn test_serve() {
+    let level = "+----+\n|.* O|\n+----+\n";
+    let requests = [
+      json!({ "jsonrpc": "2.0", "id": 1, "method": "solve", "params": { "level": level } }),
+      json!({ "jsonrpc": "2.0", "id": 2, "method": "hint", "params": { "level": level, "moves": "R" } }),
+      json!({ "jsonrpc": "2.0", "method": "hint", "params": { "level": level } }),
+      json!({ "jsonrpc": "2.0", "id": 3, "method": "analyze", "params": {} }),
+      json!({ "jsonrpc": "2.0", "id": 4, "method": "shuffle" }),
+      json!({ "jsonrpc": "2.0", "id": 5, "method": "hint", "params": { "level": level, "moves": "L" } }),
+    ];
+    let input = requests.iter().map(Value::to_string).collect::<Vec<String>>().join("\n") + "\nnot json\n";
+    let mut output = vec![];
+    serve(input.as_bytes(), &mut output).unwrap();
+    let responses = String::from_utf8(output).unwrap().lines()
+      .map(|line| serde_json::from_str::<Value>(line).unwrap())
+      .collect::<Vec<Value>>();
+    assert_eq!(responses.len(), 6);
+    assert_eq!(responses[0]["result"]["moves"], "RR");
+    assert_eq!(responses[1]["result"], json!({ "boulder": 2, "dir": "Right", "tractor": 2 }));
+    assert_eq!(responses[2]["error"]["code"], INVALID_PARAMS);
+    assert_eq!(responses[3]["error"]["code"], METHOD_NOT_FOUND);
+    assert_eq!(responses[4]["error"]["message"], "move 1 can't be made");
+    assert_eq!(responses[5], json!({ "jsonrpc": "2.0", "id": null, "error": { "code": PARSE_ERROR, "message": responses[5]["error"]["message"] } }));
+    let mut server = Server::default();
+    let parsed = server.call_method("parse", json!({ "level": level })).unwrap();
+    assert_eq!(from_params::<JsonLevel>(parsed).ok().unwrap().to_level(), parse_level(level));
+    assert_eq!(server.call_method("parse", json!({})), Err("missing `level`".to_string()));
+  }
+}
@@ -1,41 +1,40 @@
+use std::cmp::Reverse;
 use std::collections::hash_map::HashMap;
+use std::collections::BinaryHeap;
 
 use serde::{Deserialize, Serialize};
 
+use crate::grid::walking_distances;
+use crate::state_graph::{StateGraph, StateId};
+
 #[derive(Deserialize, Serialize)]
 pub struct ShortestGraph {
-  graph: HashMap<usize, Step>,
+  graph: HashMap<StateId, Step>,
 }
 
 impl ShortestGraph {
-  pub fn new(root: usize) -> Self {
+  pub fn new(root: StateId) -> Self {
     let mut graph = HashMap::new();
     graph.insert(root, Step::root(root));
     Self { graph }
   }
-  pub fn insert(&mut self, from_id: &usize, to_id: usize) {
+  pub fn insert(&mut self, from_id: &StateId, to_id: StateId) {
     self.graph.insert(to_id, self.graph[from_id].extend(to_id));
   }
-  pub fn depth(&self, id: &usize) -> Option<usize> {
+  pub fn depth(&self, id: &StateId) -> Option<usize> {
     self.graph.get(id).map(|s| s.depth)
   }
-  pub fn path(&self, id: &usize) -> Option<Vec<usize>> {
-    let mut path: Vec<usize> = vec![];
+  pub fn path(&self, id: &StateId) -> Option<Vec<StateId>> {
+    let mut path: Vec<StateId> = vec![];
     path.push(*id);
-    let mut current = match self.graph.get(id) {
-      Some(c) => c,
-      None => return None,
-    };
+    let mut current = self.graph.get(id)?;
     while let Some(prev) = current.prev {
       path.push(prev);
-      current = match self.graph.get(&prev) {
-        Some(c) => c,
-        None => return None,
-      };
+      current = self.graph.get(&prev)?;
     }
     Some(path)
   }
-  pub fn build_dist(&self) -> Vec<Vec<usize>> {
+  pub fn build_dist(&self) -> Vec<Vec<StateId>> {
     let mut dist = vec![];
     for (id, step) in self.graph.iter() {
       while dist.len() < step.depth + 1 {
@@ -49,16 +48,96 @@ impl ShortestGraph {
 
 #[derive(Clone, Deserialize, Serialize)]
 pub struct Step {
-  pub id: usize,
+  pub id: StateId,
   pub depth: usize,
-  pub prev: Option<usize>,
+  pub prev: Option<StateId>,
 }
 
 impl Step {
-  pub fn root(id: usize) -> Self {
+  pub fn root(id: StateId) -> Self {
     Self { id, depth: 0, prev: None }
   }
-  pub fn extend(&self, to: usize) -> Self {
+  pub fn extend(&self, to: StateId) -> Self {
     Self { id: to, depth: self.depth + 1, prev: Some(self.id) }
   }
 }
+
+impl StateGraph {
+  // Like `shortest_path`, but minimizing tractor moves rather than pushes:
+  // each push costs the walk to the cell it is made from plus the push itself.
+  // Returns the path and its total number of moves.
+  pub fn shortest_path_by_moves(&self, from: &StateId, tractor: usize, to: &StateId) -> Option<(Vec<StateId>, usize)> {
+    if !self.contains_id(from) || !self.contains_id(to) {
+      return None;
+    }
+    let dims = self.dims();
+    let start = (*from, tractor);
+    let mut best = HashMap::new();
+    best.insert(start, 0);
+    let mut parents = HashMap::new();
+    let mut heap = BinaryHeap::new();
+    heap.push(Reverse((0, start)));
+    let end = loop {
+      let Reverse((moves, node)) = heap.pop()?;
+      if node.0 == *to {
+        break (node, moves);
+      }
+      if best[&node] < moves {
+        continue;
+      }
+      let state = self.get_state(&node.0)?;
+      let dist = walking_distances(node.1, &state);
+      for (next, push) in self.edges_from(&node.0) {
+        let walk = match push.tractor_start(&state, dims).and_then(|idx| dist[idx]) {
+          Some(walk) => walk,
+          None => continue,
+        };
+        let transform = self.get_transform(&node.0, &next);
+        let next = (next, transform.apply_index(push.tractor, dims));
+        let total = moves + walk + if push.is_crossing(&state) { 2 } else { 1 };
+        if best.get(&next).is_none_or(|known| total < *known) {
+          best.insert(next, total);
+          parents.insert(next, node);
+          heap.push(Reverse((total, next)));
+        }
+      }
+    };
+    let mut path = vec![end.0];
+    while let Some(prev) = parents.get(path.last().unwrap()) {
+      path.push(*prev);
+    }
+    let (_, moves) = end;
+    let mut path = path.iter().map(|(id, _)| *id).collect::<Vec<StateId>>();
+    path.reverse();
+    Some((path, moves))
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use crate::cell::Cell;
+  use crate::grid::Dims;
+  use crate::level::Level;
+  use crate::state_graph::*;
+
+  #[test]
+  fn test_shortest_path_by_moves() {
+    let grid = vec![
+      Cell::BoulderInHole, Cell::Unreachable, Cell::Unreachable, Cell::Unreachable,
+      Cell::Block, Cell::Block, Cell::Block, Cell::Unreachable,
+      Cell::Block, Cell::Block, Cell::Block, Cell::Unreachable,
+      Cell::Block, Cell::Block, Cell::Block, Cell::Unreachable,
+    ];
+    let graph = find_solvable_states(&Level::new(grid, Dims::square(4), 15));
+    let root = graph.root();
+    let (end, _) = graph.states().find(|(_, state)| state[2] == Cell::Boulder).unwrap();
+    // Walk 15 -> 1 and pull the boulder to 1, then pull it on to 2
+    let (path, moves) = graph.shortest_path_by_moves(&root, 15, &end).unwrap();
+    assert_eq!(path.len(), 3);
+    assert_eq!(moves, 6 + 1);
+    // Starting next to the boulder saves the walk
+    let (_, moves) = graph.shortest_path_by_moves(&root, 1, &end).unwrap();
+    assert_eq!(moves, 1 + 1);
+    assert_eq!(graph.shortest_path_by_moves(&end, 3, &root), None);
+  }
+}
@@ -0,0 +1,687 @@
+use std::cmp::Reverse;
+use std::collections::hash_map::{Entry, HashMap};
+use std::collections::hash_set::HashSet;
+use std::collections::{BinaryHeap, VecDeque};
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::cell::{Cell, COLORS};
+use crate::direction::{Direction, DIRECTIONS};
+use crate::deadlock::*;
+use crate::grid::{slide, step, walking_distances_unchecked, Dims};
+use crate::heuristics::MatchingBound;
+use crate::level::Level;
+use crate::packed::*;
+use crate::state_graph::*;
+use crate::transposition::{state_key, Outcome, TranspositionTable};
+
+// Pushes that take a level to a goal state, with the state before the first
+// push and after every push. `moves` spells out every tractor step, lowercase
+// `udlr` for walking and uppercase `UDLR` for pushes.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Solution {
+  pub pushes: Vec<Push>,
+  pub states: Vec<Vec<Cell>>,
+  pub moves: String,
+}
+
+impl Solution {
+  pub fn len(&self) -> usize {
+    self.pushes.len()
+  }
+  pub fn is_empty(&self) -> bool {
+    self.pushes.is_empty()
+  }
+}
+
+// Breadth first over pushes from the level, so the solution found uses the
+// fewest pushes
+pub fn solve(level: &Level) -> Option<Solution> {
+  search_pushes(level, None).ok().map(|pushes| build_solution(level, pushes))
+}
+
+// Like `solve`, skipping states `table` knows are dead and recording what the
+// search learns: every state met is dead if there is no solution, otherwise
+// the states along it are solved
+pub fn solve_with(level: &Level, table: &mut TranspositionTable) -> Option<Solution> {
+  if table.get(state_key(&level.initial_state(), level.dims)) == Some(Outcome::Dead) {
+    return None;
+  }
+  let solution = build_solution(level, search_pushes(level, Some(table)).ok()?);
+  // A shorter way from any of them would shorten the whole solution
+  for (nth, state) in solution.states.iter().enumerate() {
+    table.insert(state_key(state, level.dims), Outcome::Solved(solution.len() - nth));
+  }
+  Some(solution)
+}
+
+// Why a level can't be solved
+#[derive(Clone, Debug, PartialEq)]
+pub enum Unsolvable {
+  // Every hole needs exactly one loose boulder of its color
+  CountMismatch { boulders: usize, holes: usize },
+  // Boulders that start where no push can take them to a hole
+  DeadBoulders(Vec<usize>),
+  // Boulders that start pinned off their holes
+  FrozenBoulders(Vec<usize>),
+  // The tractor can't push anything from where it starts
+  NoPushes,
+  // Every branch ran into a deadlock or back into a known state
+  Exhausted { states: usize, deadlocks: usize },
+}
+
+impl fmt::Display for Unsolvable {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Unsolvable::CountMismatch { boulders, holes } => {
+        write!(f, "{} loose boulders for {} empty holes", boulders, holes)
+      },
+      Unsolvable::DeadBoulders(cells) => write!(f, "boulders on dead squares at {:?}", cells),
+      Unsolvable::FrozenBoulders(cells) => write!(f, "frozen boulders at {:?}", cells),
+      Unsolvable::NoPushes => write!(f, "no boulder can be pushed"),
+      Unsolvable::Exhausted { states, deadlocks } => {
+        write!(f, "all {} reachable states are dead ends, {} pushes deadlocked", states, deadlocks)
+      },
+    }
+  }
+}
+
+// Like `solve`, but says why there is no solution
+pub fn solve_or_explain(level: &Level) -> Result<Solution, Unsolvable> {
+  let start = level.initial_state();
+  // Boulders of each color need as many holes of that color
+  for color in std::iter::once(None).chain(COLORS.iter().map(|color| Some(*color))) {
+    let boulders = start.iter().filter(|cell| cell.is_loose_boulder() && cell.color() == color).count();
+    let holes = start.iter().filter(|cell| cell.is_open_hole() && cell.color() == color).count();
+    if boulders != holes {
+      return Err(Unsolvable::CountMismatch { boulders, holes });
+    }
+  }
+  let dead = find_dead_squares(&start, level.dims);
+  let loose = (0..start.len()).filter(|idx| start[*idx].is_loose_boulder());
+  let stranded = loose.clone().filter(|idx| dead.is_dead(*idx, start[*idx])).collect::<Vec<usize>>();
+  if !stranded.is_empty() {
+    return Err(Unsolvable::DeadBoulders(stranded));
+  }
+  let frozen = loose.filter(|idx| is_frozen(*idx, &start, &dead, level.dims)).collect::<Vec<usize>>();
+  if !frozen.is_empty() {
+    return Err(Unsolvable::FrozenBoulders(frozen));
+  }
+  if !is_goal_state(&start) && next_pushed_states(&start, level.dims).is_empty() {
+    return Err(Unsolvable::NoPushes);
+  }
+  search_pushes(level, None).map(|pushes| build_solution(level, pushes))
+}
+
+fn search_pushes(level: &Level, mut table: Option<&mut TranspositionTable>) -> Result<Vec<Push>, Unsolvable> {
+  let start = level.initial_state();
+  let layout = Layout::from_state(&start, level.dims);
+  let dead = find_dead_squares(&start, level.dims);
+  let mut parents: HashMap<PackedState, Option<(PackedState, Push)>> = HashMap::new();
+  parents.insert(layout.encode(&start), None);
+  let mut queue = VecDeque::new();
+  queue.push_back(start);
+  let mut deadlocks = 0;
+  while let Some(state) = queue.pop_front() {
+    let packed = layout.encode(&state);
+    if is_goal_state(&state) {
+      let mut pushes = vec![];
+      let mut current = packed;
+      while let Some((prev, push)) = parents[&current].clone() {
+        pushes.push(push);
+        current = prev;
+      }
+      pushes.reverse();
+      return Ok(pushes);
+    }
+    for (next, push) in next_pushed_states(&state, level.dims) {
+      if is_deadlocked(&push, &next, &dead, level.dims) {
+        deadlocks += 1;
+        continue;
+      }
+      if let Entry::Vacant(entry) = parents.entry(layout.encode(&next)) {
+        let known = table.as_deref_mut().and_then(|table| table.get(state_key(&next, level.dims)));
+        if known == Some(Outcome::Dead) {
+          deadlocks += 1;
+          continue;
+        }
+        entry.insert(Some((packed.clone(), push)));
+        queue.push_back(next);
+      }
+    }
+  }
+  if let Some(table) = table {
+    for packed in parents.keys() {
+      table.insert(state_key(&layout.decode(packed), level.dims), Outcome::Dead);
+    }
+  }
+  Err(Unsolvable::Exhausted { states: parents.len(), deadlocks })
+}
+
+// Number of essentially different shortest solutions, counting up to `cap`.
+// Solutions that make the same pushes in another order are the same, so
+// pushing two boulders one after the other either way round counts once.
+pub fn count_distinct_solutions(level: &Level, cap: usize) -> usize {
+  let start = level.initial_state();
+  let layout = Layout::from_state(&start, level.dims);
+  let dead = find_dead_squares(&start, level.dims);
+  let mut index = HashMap::new();
+  index.insert(layout.encode(&start), 0);
+  let mut states = vec![start];
+  let mut edges: Vec<Vec<(usize, Push)>> = vec![];
+  let mut current = 0;
+  while current < states.len() {
+    let mut out = vec![];
+    if !is_goal_state(&states[current]) {
+      for (next, push) in next_pushed_states(&states[current], level.dims) {
+        if is_deadlocked(&push, &next, &dead, level.dims) {
+          continue;
+        }
+        let id = *index.entry(layout.encode(&next)).or_insert(states.len());
+        if id == states.len() {
+          states.push(next);
+        }
+        out.push((id, push));
+      }
+    }
+    edges.push(out);
+    current += 1;
+  }
+  let mut predecessors = vec![vec![]; states.len()];
+  for (id, out) in edges.iter().enumerate() {
+    for (next, _) in out {
+      predecessors[*next].push(id);
+    }
+  }
+  let mut dist = vec![None; states.len()];
+  let mut order = VecDeque::new();
+  let mut queue = (0..states.len()).filter(|id| is_goal_state(&states[*id])).collect::<VecDeque<_>>();
+  for id in &queue {
+    dist[*id] = Some(0);
+  }
+  while let Some(id) = queue.pop_front() {
+    order.push_back(id);
+    for prev in &predecessors[id] {
+      if dist[*prev].is_none() {
+        dist[*prev] = dist[id].map(|d| d + 1);
+        queue.push_back(*prev);
+      }
+    }
+  }
+  // The sorted pushes still to make from each state, at most `cap` distinct
+  // ones. Closest to a goal first, so every step down is known in time.
+  let mut remaining: Vec<Vec<Vec<(usize, u8)>>> = vec![vec![]; states.len()];
+  for id in order {
+    if dist[id] == Some(0) {
+      remaining[id] = vec![vec![]];
+      continue;
+    }
+    let mut found: Vec<Vec<(usize, u8)>> = vec![];
+    for (next, push) in &edges[id] {
+      if dist[*next].map(|d| d + 1) != dist[id] {
+        continue;
+      }
+      for rest in &remaining[*next] {
+        let mut pushes = rest.clone();
+        let key = (push.boulder, push.dir as u8);
+        let at = pushes.binary_search(&key).unwrap_or_else(|at| at);
+        pushes.insert(at, key);
+        if found.len() < cap && !found.contains(&pushes) {
+          found.push(pushes);
+        }
+      }
+    }
+    remaining[id] = found;
+  }
+  remaining[0].len()
+}
+
+// Dijkstra over states and tractor positions, so the solution found uses the
+// fewest tractor moves, walking included
+pub fn solve_by_moves(level: &Level) -> Option<Solution> {
+  let start = level.initial_state();
+  let layout = Layout::from_state(&start, level.dims);
+  let dead = find_dead_squares(&start, level.dims);
+  let start = (layout.encode(&start), level.tractor);
+  let mut best = HashMap::new();
+  best.insert(start.clone(), 0);
+  let mut parents: HashMap<(PackedState, usize), ((PackedState, usize), Push)> = HashMap::new();
+  let mut heap = BinaryHeap::new();
+  heap.push(Reverse((0, start)));
+  while let Some(Reverse((moves, node))) = heap.pop() {
+    if best[&node] < moves {
+      continue;
+    }
+    let state = layout.decode(&node.0);
+    if is_goal_state(&state) {
+      let mut pushes = vec![];
+      let mut current = node;
+      while let Some((prev, push)) = parents.get(&current) {
+        pushes.push(*push);
+        current = prev.clone();
+      }
+      pushes.reverse();
+      return Some(build_solution(level, pushes));
+    }
+    let dist = walking_distances_unchecked(node.1, &state, level.dims);
+    for (next, push) in next_pushed_states(&state, level.dims) {
+      if is_deadlocked(&push, &next, &dead, level.dims) {
+        continue;
+      }
+      let walk = match push.tractor_start(&state, level.dims).and_then(|idx| dist[idx]) {
+        Some(walk) => walk,
+        None => continue,
+      };
+      let total = moves + walk + if push.is_crossing(&state) { 2 } else { 1 };
+      let next = (layout.encode(&next), push.tractor);
+      if best.get(&next).is_none_or(|known| total < *known) {
+        best.insert(next.clone(), total);
+        parents.insert(next.clone(), (node.clone(), push));
+        heap.push(Reverse((total, next)));
+      }
+    }
+  }
+  None
+}
+
+// Iterative deepening A* over pushes. Only the current path is kept in
+// memory, so this reaches further than the breadth first solvers on large
+// boards at the cost of revisiting states. The bound pairs every loose
+// boulder with a hole of its own as cheaply as push distances allow, which
+// never overestimates, so the solution found still uses the fewest pushes.
+pub fn solve_ida(level: &Level) -> Option<Solution> {
+  let start = level.initial_state();
+  let layout = Layout::from_state(&start, level.dims);
+  let mut search = IdaSearch {
+    bound: MatchingBound::new(&start, level.dims),
+    dead: find_dead_squares(&start, level.dims),
+    dims: level.dims,
+    on_path: HashSet::new(),
+    pushes: vec![],
+    layout,
+  };
+  let mut bound = search.estimate(&start)?;
+  search.on_path.insert(search.layout.encode(&start));
+  loop {
+    match search.descend(&start, 0, bound) {
+      Bound::Found => return Some(build_solution(level, search.pushes)),
+      Bound::Exceeded(next) => bound = next,
+      Bound::Exhausted => return None,
+    }
+  }
+}
+
+enum Bound {
+  Found,
+  // The smallest estimate that went over the bound
+  Exceeded(usize),
+  Exhausted,
+}
+
+struct IdaSearch {
+  layout: Layout,
+  bound: MatchingBound,
+  dead: DeadSquares,
+  dims: Dims,
+  on_path: HashSet<PackedState>,
+  pushes: Vec<Push>,
+}
+
+impl IdaSearch {
+  // Lower bound on the pushes left, `None` if the boulders can't all reach
+  // holes of their own
+  fn estimate(&self, state: &[Cell]) -> Option<usize> {
+    self.bound.estimate(state)
+  }
+  fn descend(&mut self, state: &[Cell], pushed: usize, bound: usize) -> Bound {
+    let estimate = match self.estimate(state) {
+      Some(left) => pushed + left,
+      None => return Bound::Exhausted,
+    };
+    if estimate > bound {
+      return Bound::Exceeded(estimate);
+    }
+    if is_goal_state(state) {
+      return Bound::Found;
+    }
+    let mut result = Bound::Exhausted;
+    for (next, push) in next_pushed_states(state, self.dims) {
+      if is_deadlocked(&push, &next, &self.dead, self.dims) {
+        continue;
+      }
+      let packed = self.layout.encode(&next);
+      if !self.on_path.insert(packed.clone()) {
+        continue;
+      }
+      self.pushes.push(push);
+      match self.descend(&next, pushed + 1, bound) {
+        Bound::Found => return Bound::Found,
+        Bound::Exceeded(over) => match result {
+          Bound::Exceeded(known) if known <= over => {},
+          _ => result = Bound::Exceeded(over),
+        },
+        Bound::Exhausted => {},
+      }
+      self.pushes.pop();
+      self.on_path.remove(&packed);
+    }
+    result
+  }
+}
+
+// Replays `pushes` from the start of the level, walking the tractor the
+// shortest way to each one
+fn build_solution(level: &Level, pushes: Vec<Push>) -> Solution {
+  let mut state = level.initial_state();
+  let mut tractor = level.tractor;
+  let mut states = vec![state.clone()];
+  let mut moves = String::new();
+  for push in &pushes {
+    let from = push.tractor_start(&state, level.dims).unwrap();
+    for dir in walking_path(tractor, from, &state, level.dims).unwrap() {
+      moves.push(direction_char(dir).to_ascii_lowercase());
+    }
+    if push.is_crossing(&state) {
+      // Onto the cracked cell and off the far side
+      moves.push(direction_char(push.dir).to_ascii_lowercase());
+      moves.push(direction_char(push.dir).to_ascii_lowercase());
+    } else {
+      moves.push(direction_char(push.dir));
+    }
+    state = next_pushed_states(&state, level.dims).into_iter()
+      .find(|(_, next_push)| next_push == push)
+      .unwrap()
+      .0;
+    tractor = push.tractor;
+    states.push(state.clone());
+  }
+  Solution { pushes, states, moves }
+}
+
+// One of the shortest walks from `from` to `to`, as the way of each step
+pub fn walking_path(from: usize, to: usize, state: &[Cell], dims: Dims) -> Option<Vec<Direction>> {
+  let dist = walking_distances_unchecked(from, state, dims);
+  let mut remaining = dist[to]?;
+  let mut path = vec![];
+  let mut current = to;
+  // Walk back downhill from `to`, recording each step the other way round. A
+  // step may have slid in from further along a line of ice.
+  while remaining > 0 {
+    let (prev, dir) = DIRECTIONS.iter()
+      .flat_map(|dir| {
+        std::iter::successors(step(current, *dir, state, dims), move |idx| step(*idx, *dir, state, dims))
+          .map(move |prev| (prev, dir.opposite()))
+      })
+      .find(|(prev, dir)| dist[*prev] == Some(remaining - 1) && slide(*prev, *dir, state, dims) == Some(current))?;
+    path.push(dir);
+    current = prev;
+    remaining -= 1;
+  }
+  path.reverse();
+  Some(path)
+}
+
+fn direction_char(dir: Direction) -> char {
+  match dir {
+    Direction::Up => 'U',
+    Direction::Down => 'D',
+    Direction::Left => 'L',
+    Direction::Right => 'R',
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::cell::Color;
+  use crate::symmetry::Transform;
+
+  fn corner_level() -> Level {
+    let grid = vec![
+      Cell::Hole, Cell::Unreachable, Cell::Unreachable, Cell::Unreachable,
+      Cell::Block, Cell::Block, Cell::Boulder, Cell::Unreachable,
+      Cell::Block, Cell::Block, Cell::Unreachable, Cell::Unreachable,
+      Cell::Block, Cell::Block, Cell::Unreachable, Cell::Unreachable,
+    ];
+    Level::new(grid, Dims::square(4), 15)
+  }
+
+  #[test]
+  fn test_solve() {
+    let level = corner_level();
+    let solution = solve(&level).unwrap();
+    // Push the boulder up from below, then walk round and push it left twice
+    assert_eq!(solution.pushes, vec![
+      Push { boulder: 6, dir: Direction::Up, tractor: 6 },
+      Push { boulder: 2, dir: Direction::Left, tractor: 2 },
+      Push { boulder: 1, dir: Direction::Left, tractor: 1 },
+    ]);
+    assert_eq!(solution.states.len(), 4);
+    assert_eq!(solution.states[0], level.initial_state());
+    assert!(is_goal_state(solution.states.last().unwrap()));
+    assert_eq!(solution.moves, "luUruLL");
+    let json = serde_json::to_string(&solution).unwrap();
+    assert_eq!(serde_json::from_str::<Solution>(&json).unwrap(), solution);
+  }
+
+  #[test]
+  fn test_solve_with() {
+    let mut table = TranspositionTable::new(100);
+    let level = corner_level();
+    assert_eq!(solve_with(&level, &mut table), solve(&level));
+    assert_eq!(table.len(), 4);
+    let turned = Transform::FlipHorizontal.apply_level(&level);
+    assert_eq!(solve_with(&turned, &mut table).unwrap().len(), 3);
+    assert!(table.hits() > 0);
+    let mut stuck = level.clone();
+    stuck.grid[10] = Cell::Block;
+    assert_eq!(solve_with(&stuck, &mut table), None);
+    let hits = table.hits();
+    assert_eq!(solve_with(&stuck, &mut table), None);
+    assert_eq!(table.hits(), hits + 1);
+  }
+
+  #[test]
+  fn test_solve_ida() {
+    let level = corner_level();
+    assert_eq!(solve_ida(&level), solve(&level));
+    let grid = vec![
+      Cell::Hole, Cell::Boulder, Cell::Unreachable, Cell::Unreachable, Cell::Unreachable,
+      Cell::Unreachable, Cell::Unreachable, Cell::Unreachable, Cell::Unreachable, Cell::Unreachable,
+      Cell::Unreachable, Cell::Unreachable, Cell::Boulder, Cell::Unreachable, Cell::Unreachable,
+      Cell::Unreachable, Cell::Unreachable, Cell::Unreachable, Cell::Unreachable, Cell::Unreachable,
+      Cell::Unreachable, Cell::Unreachable, Cell::Unreachable, Cell::Unreachable, Cell::Hole,
+    ];
+    let level = Level::new(grid, Dims::square(5), 6);
+    let solution = solve_ida(&level).unwrap();
+    assert_eq!(solution.len(), solve(&level).unwrap().len());
+    assert!(is_goal_state(solution.states.last().unwrap()));
+  }
+
+  #[test]
+  fn test_solve_corridor() {
+    // A 12 wide, 6 tall board with only the middle two rows open
+    let dims = Dims::new(6, 12);
+    let mut grid = vec![Cell::Block; dims.len()];
+    for col in 0..dims.cols {
+      grid[dims.index(2, col)] = Cell::Unreachable;
+      grid[dims.index(3, col)] = Cell::Unreachable;
+    }
+    grid[dims.index(2, 6)] = Cell::Boulder;
+    grid[dims.index(2, 11)] = Cell::Hole;
+    grid[dims.index(3, 3)] = Cell::Boulder;
+    grid[dims.index(3, 1)] = Cell::Hole;
+    let level = Level::new(grid, dims, dims.index(2, 0));
+    let solution = solve(&level).unwrap();
+    assert_eq!(solution.len(), 7);
+    assert!(is_goal_state(solution.states.last().unwrap()));
+    assert_eq!(solve_ida(&level).unwrap().len(), 7);
+  }
+
+  #[test]
+  fn test_solve_on_ice() {
+    // One push slides the top boulder across the ice into its hole
+    let grid = vec![
+      Cell::Unreachable, Cell::Boulder, Cell::Ice, Cell::Ice, Cell::Hole,
+      Cell::Unreachable, Cell::Unreachable, Cell::Unreachable, Cell::Boulder, Cell::Hole,
+    ];
+    let level = Level::new(grid, Dims::new(2, 5), 0);
+    let solution = solve(&level).unwrap();
+    assert_eq!(solution.len(), 2);
+    assert!(is_goal_state(solution.states.last().unwrap()));
+    assert_eq!(solve_ida(&level).map(|s| s.len()), Some(2));
+    assert_eq!(solve_by_moves(&level).unwrap().moves, solution.moves);
+  }
+
+  #[test]
+  fn test_solve_colored() {
+    // Plain, each boulder would go straight down into the hole below it
+    let dims = Dims::new(4, 5);
+    let mut grid = vec![Cell::Unreachable; dims.len()];
+    grid[dims.index(1, 1)] = Cell::ColoredBoulder(Color::Red);
+    grid[dims.index(1, 3)] = Cell::ColoredBoulder(Color::Green);
+    grid[dims.index(3, 1)] = Cell::ColoredHole(Color::Green);
+    grid[dims.index(3, 3)] = Cell::ColoredHole(Color::Red);
+    let level = Level::new(grid, dims, 0);
+    let solution = solve(&level).unwrap();
+    assert!(solution.len() > 4);
+    let last = solution.states.last().unwrap();
+    assert_eq!(last[dims.index(3, 1)], Cell::ColoredBoulderInHole(Color::Green));
+    assert_eq!(last[dims.index(3, 3)], Cell::ColoredBoulderInHole(Color::Red));
+    assert_eq!(solve_ida(&level).map(|s| s.len()), Some(solution.len()));
+  }
+
+  #[test]
+  fn test_solve_through_portal() {
+    let grid = vec![
+      Cell::Unreachable, Cell::Boulder, Cell::Portal(0), Cell::Block, Cell::Portal(0), Cell::Unreachable, Cell::Hole,
+    ];
+    let level = Level::new(grid, Dims::new(1, 7), 0);
+    let solution = solve(&level).unwrap();
+    assert_eq!(solution.moves, "RR");
+    assert_eq!(solution.states[1][5], Cell::Boulder);
+    assert_eq!(solve_ida(&level).map(|s| s.len()), Some(2));
+  }
+
+  #[test]
+  fn test_solve_across_cracked_floor() {
+    let grid = vec![
+      Cell::Unreachable, Cell::Cracked, Cell::Unreachable, Cell::Boulder, Cell::Hole,
+    ];
+    let level = Level::new(grid, Dims::new(1, 5), 0);
+    let solution = solve(&level).unwrap();
+    assert_eq!(solution.moves, "rrR");
+    assert_eq!(solution.states.last().unwrap()[1], Cell::Collapsed);
+    assert_eq!(solve_by_moves(&level).unwrap().moves, "rrR");
+    assert_eq!(solve_ida(&level).map(|s| s.len()), Some(2));
+  }
+
+  #[test]
+  fn test_solve_by_moves() {
+    // Two boulders, each pushed straight into its own hole. Pushing the far
+    // one first takes the same number of pushes but more walking.
+    let grid = vec![
+      Cell::Hole, Cell::Boulder, Cell::Unreachable, Cell::Unreachable, Cell::Unreachable,
+      Cell::Unreachable, Cell::Unreachable, Cell::Unreachable, Cell::Unreachable, Cell::Unreachable,
+      Cell::Unreachable, Cell::Unreachable, Cell::Unreachable, Cell::Unreachable, Cell::Unreachable,
+      Cell::Unreachable, Cell::Unreachable, Cell::Unreachable, Cell::Unreachable, Cell::Unreachable,
+      Cell::Unreachable, Cell::Unreachable, Cell::Unreachable, Cell::Boulder, Cell::Hole,
+    ];
+    let level = Level::new(grid, Dims::square(5), 22);
+    let by_pushes = solve(&level).unwrap();
+    let by_moves = solve_by_moves(&level).unwrap();
+    assert_eq!(by_pushes.len(), 2);
+    assert_eq!(by_moves.len(), 2);
+    assert_eq!(by_moves.moves, "RluuuuL");
+    assert!(by_moves.moves.len() <= by_pushes.moves.len());
+    assert!(is_goal_state(by_moves.states.last().unwrap()));
+  }
+
+  #[test]
+  fn test_unsolvable() {
+    let grid = vec![
+      Cell::Hole, Cell::Block, Cell::Unreachable,
+      Cell::Block, Cell::Boulder, Cell::Unreachable,
+      Cell::Unreachable, Cell::Unreachable, Cell::Unreachable,
+    ];
+    assert_eq!(solve(&Level::new(grid.clone(), Dims::square(3), 8)), None);
+    assert_eq!(solve_by_moves(&Level::new(grid.clone(), Dims::square(3), 8)), None);
+    assert_eq!(solve_ida(&Level::new(grid, Dims::square(3), 8)), None);
+  }
+
+  #[test]
+  fn test_explain_unsolvable() {
+    let grid = vec![
+      Cell::Hole, Cell::Block, Cell::Unreachable,
+      Cell::Block, Cell::Boulder, Cell::Unreachable,
+      Cell::Unreachable, Cell::Unreachable, Cell::Unreachable,
+    ];
+    assert_eq!(solve_or_explain(&Level::new(grid.clone(), Dims::square(3), 8)), Err(Unsolvable::DeadBoulders(vec![4])));
+    let mut extra = grid.clone();
+    extra[2] = Cell::Boulder;
+    assert_eq!(
+      solve_or_explain(&Level::new(extra, Dims::square(3), 8)),
+      Err(Unsolvable::CountMismatch { boulders: 2, holes: 1 }));
+    // Walled off from the boulder along the bottom row
+    let grid = vec![
+      Cell::Block, Cell::Block, Cell::Block, Cell::Block,
+      Cell::Hole, Cell::Boulder, Cell::Unreachable, Cell::Unreachable,
+      Cell::Block, Cell::Block, Cell::Block, Cell::Block,
+      Cell::Unreachable, Cell::Unreachable, Cell::Unreachable, Cell::Unreachable,
+    ];
+    assert_eq!(solve_or_explain(&Level::new(grid.clone(), Dims::square(4), 12)), Err(Unsolvable::NoPushes));
+    assert!(solve_or_explain(&Level::new(grid, Dims::square(4), 7)).is_ok());
+    // The only push strands the boulder against the left wall
+    let grid = vec![
+      Cell::Block, Cell::Block, Cell::Block, Cell::Block,
+      Cell::Unreachable, Cell::Boulder, Cell::Unreachable, Cell::Hole,
+      Cell::Block, Cell::Block, Cell::Block, Cell::Block,
+      Cell::Block, Cell::Block, Cell::Block, Cell::Block,
+    ];
+    assert_eq!(
+      solve_or_explain(&Level::new(grid, Dims::square(4), 6)),
+      Err(Unsolvable::Exhausted { states: 1, deadlocks: 1 }));
+    let level = corner_level();
+    assert_eq!(solve_or_explain(&level).ok(), solve(&level));
+  }
+
+  #[test]
+  fn test_count_distinct_solutions() {
+    let level = corner_level();
+    assert_eq!(count_distinct_solutions(&level, 5), 1);
+    // Two boulders pushed straight into their holes, in either order
+    let grid = vec![
+      Cell::Hole, Cell::Boulder, Cell::Unreachable, Cell::Unreachable, Cell::Unreachable,
+      Cell::Unreachable, Cell::Unreachable, Cell::Unreachable, Cell::Unreachable, Cell::Unreachable,
+      Cell::Unreachable, Cell::Unreachable, Cell::Unreachable, Cell::Unreachable, Cell::Unreachable,
+      Cell::Unreachable, Cell::Unreachable, Cell::Unreachable, Cell::Unreachable, Cell::Unreachable,
+      Cell::Unreachable, Cell::Unreachable, Cell::Unreachable, Cell::Boulder, Cell::Hole,
+    ];
+    assert_eq!(count_distinct_solutions(&Level::new(grid, Dims::square(5), 22), 5), 1);
+    // A boulder diagonal from its hole goes down then right, or right then
+    // down, which are different pushes
+    let grid = vec![
+      Cell::Unreachable, Cell::Unreachable, Cell::Unreachable, Cell::Unreachable, Cell::Unreachable,
+      Cell::Unreachable, Cell::Unreachable, Cell::Unreachable, Cell::Unreachable, Cell::Unreachable,
+      Cell::Unreachable, Cell::Unreachable, Cell::Boulder, Cell::Unreachable, Cell::Unreachable,
+      Cell::Unreachable, Cell::Unreachable, Cell::Unreachable, Cell::Hole, Cell::Unreachable,
+      Cell::Unreachable, Cell::Unreachable, Cell::Unreachable, Cell::Unreachable, Cell::Unreachable,
+    ];
+    let level = Level::new(grid, Dims::square(5), 0);
+    assert_eq!(count_distinct_solutions(&level, 5), 2);
+    assert_eq!(count_distinct_solutions(&level, 1), 1);
+  }
+
+  #[test]
+  fn test_already_solved() {
+    let grid = vec![
+      Cell::BoulderInHole, Cell::Unreachable,
+      Cell::Unreachable, Cell::Unreachable,
+    ];
+    let solution = solve(&Level::new(grid, Dims::square(2), 3)).unwrap();
+    assert!(solution.is_empty());
+    assert_eq!(solution.states.len(), 1);
+    assert_eq!(solution.moves, "");
+  }
+}
@@ -1,195 +1,1724 @@
 use crate::cell::Cell;
+use crate::compression;
+use crate::direction::*;
+use crate::error::Error;
 use crate::shortest_path::*;
 use crate::grid::*;
+use crate::interner::Interner;
+use crate::level::Level;
+use crate::packed::*;
+use crate::pruner::StatePruner;
+use crate::storage::StateStore;
+use crate::symmetry::*;
+use crate::zobrist;
 
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 use std::collections::hash_set::HashSet;
 use std::collections::hash_map::HashMap;
 use std::collections::VecDeque;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+#[cfg(not(feature = "wasm"))]
+use std::time::Instant;
+// std's clock panics in the browser
+#[cfg(feature = "wasm")]
+use web_time::Instant;
 
-#[derive(Default, Deserialize, Serialize)]
+#[derive(Copy, Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+pub struct StateId(usize);
+
+impl StateId {
+  pub fn new(id: usize) -> Self {
+    StateId(id)
+  }
+  pub fn index(self) -> usize {
+    self.0
+  }
+}
+
+impl From<usize> for StateId {
+  fn from(id: usize) -> Self {
+    StateId(id)
+  }
+}
+
+impl From<StateId> for usize {
+  fn from(id: StateId) -> Self {
+    id.0
+  }
+}
+
+impl fmt::Display for StateId {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "#{}", self.0)
+  }
+}
+
+// The boulder at `boulder` moved one cell towards `dir`, leaving the tractor
+// at `tractor`.
+#[derive(Copy, Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct Push {
+  pub boulder: usize,
+  pub dir: Direction,
+  pub tractor: usize,
+}
+
+impl Push {
+  // Every push moves the tractor one step towards `dir`, ending on `tractor`,
+  // apart from crossing cracked floor, which takes it over `boulder`
+  pub fn tractor_start(&self, grid: &[Cell], dims: Dims) -> Option<usize> {
+    if self.is_crossing(grid) {
+      return step(self.boulder, self.dir.opposite(), grid, dims);
+    }
+    step(self.tractor, self.dir.opposite(), grid, dims)
+  }
+  // Crossings are recorded like pushes, with the cracked cell as `boulder`
+  pub fn is_crossing(&self, grid: &[Cell]) -> bool {
+    matches!(grid[self.boulder], Cell::Cracked | Cell::Collapsed)
+  }
+}
+
+#[derive(Deserialize, Serialize)]
 pub struct StateGraph {
-  state_to_id: HashMap<Vec<Cell>, usize>,
-  id_to_state: HashMap<usize, Vec<Cell>>,
-  neighbors: HashMap<usize, Vec<usize>>,
+  layout: Layout,
+  // Each state once, its handle being its id, hashed by Zobrist key
+  #[serde(deserialize_with = "deserialize_states")]
+  states: Interner<PackedState, Box<dyn StateStore>>,
+  // Indexed by id, left empty for removed states
+  neighbors: Vec<Vec<StateId>>,
+  // Parallel to `neighbors`
+  pushes: Vec<Vec<Push>>,
+  predecessors: Vec<Vec<StateId>>,
+  // Board symmetries states are canonicalized under, empty unless opted in
+  symmetries: Vec<Transform>,
+  // Maps the root as given onto its stored form
+  root_transform: Transform,
+  // Maps the state reached by an edge's push onto the stored `to` state,
+  // only kept when that is not the identity
+  transforms: HashMap<(StateId, StateId), Transform>,
+  // Whether edges were found by pulling back from a solved root, so that
+  // each one leads to a state its push comes from rather than goes to
+  #[serde(default)]
+  pulled: bool,
 }
 
 impl StateGraph {
-  pub fn new(root: Vec<Cell>) -> Self {
-    let mut graph = StateGraph::default();
-    graph.set_root(root);
-    graph
+  pub fn new(root: Grid) -> Self {
+    Self::with_tractors(root, &[], false)
+  }
+  // Stores each state once per orbit under the symmetries of the board layout
+  pub fn new_symmetric(root: Grid) -> Self {
+    Self::with_tractors(root, &[], true)
+  }
+  // A root with several tractors, `tractors` holding a cell of each one's
+  // region. Empty for the usual single tractor.
+  pub fn with_tractors(root: Grid, tractors: &[usize], symmetric: bool) -> Self {
+    Self::with_store(root, tractors, symmetric, Box::new(vec![])).expect("a new graph starts empty")
+  }
+  // Keeping the states in `store`, which must be empty
+  pub fn with_store(root: Grid, tractors: &[usize], symmetric: bool, store: Box<dyn StateStore>) -> Result<Self, Error> {
+    let dims = root.dims();
+    let root = root.into_cells();
+    let layout = Layout::from_state(&root, dims);
+    let symmetries = TRANSFORMS.iter()
+      .filter(|t| symmetric && **t != Transform::Identity && layout.is_invariant_under(**t))
+      .cloned()
+      .collect();
+    Self::with_symmetries(root, tractors, dims, symmetries, store)
+  }
+  fn with_symmetries(root: Vec<Cell>, tractors: &[usize], dims: Dims, symmetries: Vec<Transform>, store: Box<dyn StateStore>) -> Result<Self, Error> {
+    let mut graph = StateGraph {
+      layout: Layout::from_state(&root, dims),
+      states: Interner::from_arena(store, PackedState::zobrist),
+      neighbors: vec![],
+      pushes: vec![],
+      predecessors: vec![],
+      symmetries,
+      root_transform: Transform::Identity,
+      transforms: HashMap::new(),
+      pulled: false,
+    };
+    graph.set_root(root, tractors)?;
+    Ok(graph)
   }
   // Basic accessors
-  pub fn get_neighbors(&self, id: &usize) -> Option<&Vec<usize>> {
-    self.neighbors.get(id)
+  pub fn root(&self) -> StateId {
+    StateId(0)
+  }
+  pub fn dims(&self) -> Dims {
+    self.layout.dims()
+  }
+  pub fn get_neighbors(&self, id: &StateId) -> Option<&Vec<StateId>> {
+    self.neighbors.get(id.0).filter(|_| self.contains_id(id))
+  }
+  // Nothing for unknown ids
+  pub fn neighbors(&self, id: &StateId) -> impl Iterator<Item = StateId> + '_ {
+    self.get_neighbors(id).into_iter().flatten().cloned()
+  }
+  pub fn out_degree(&self, id: &StateId) -> usize {
+    self.get_neighbors(id).map_or(0, Vec::len)
+  }
+  pub fn get_predecessors(&self, id: &StateId) -> Option<&Vec<StateId>> {
+    self.predecessors.get(id.0).filter(|_| self.contains_id(id))
+  }
+  // Explored by `find_solvable_states` and the like rather than by pushing
+  pub fn is_pulled(&self) -> bool {
+    self.pulled
+  }
+  // The states one push away from which lead to `id`, whichever way the
+  // edges run. Nothing for unknown ids.
+  pub fn pushed_from(&self, id: &StateId) -> &[StateId] {
+    let edges = if self.pulled { self.get_neighbors(id) } else { self.get_predecessors(id) };
+    edges.map_or(&[], Vec::as_slice)
+  }
+  // The states one push from `id` leads to
+  pub fn pushed_to(&self, id: &StateId) -> &[StateId] {
+    let edges = if self.pulled { self.get_predecessors(id) } else { self.get_neighbors(id) };
+    edges.map_or(&[], Vec::as_slice)
+  }
+  pub fn get_edge(&self, from: &StateId, to: &StateId) -> Option<&Push> {
+    let idx = self.neighbors.get(from.0)?.iter().position(|id| id == to)?;
+    self.pushes[from.0].get(idx)
+  }
+  pub fn edges_from(&self, id: &StateId) -> impl Iterator<Item = (StateId, &Push)> {
+    let neighbors = self.neighbors.get(id.0).into_iter().flatten();
+    let pushes = self.pushes.get(id.0).into_iter().flatten();
+    neighbors.cloned().zip(pushes)
+  }
+  // States are stored packed, so each one is decoded as it is yielded
+  pub fn states(&self) -> impl Iterator<Item = (StateId, Grid)> + '_ {
+    self.states.iter().map(move |(id, packed)| (StateId(id), Grid::new_unchecked(self.layout.decode(&packed), self.dims())))
+  }
+  pub fn edges(&self) -> impl Iterator<Item = (StateId, StateId)> + '_ {
+    self.neighbors.iter().enumerate()
+      .flat_map(|(from, neighbors)| neighbors.iter().map(move |to| (StateId(from), *to)))
+  }
+  pub fn get_transform(&self, from: &StateId, to: &StateId) -> Transform {
+    self.transforms.get(&(*from, *to)).cloned().unwrap_or(Transform::Identity)
+  }
+  pub fn get_state(&self, id: &StateId) -> Option<Grid> {
+    self.states.resolve(id.0).map(|packed| Grid::new_unchecked(self.layout.decode(&packed), self.dims()))
+  }
+  // The smallest cell of each tractor's region, one per tractor
+  pub fn get_tractors(&self, id: &StateId) -> Option<Vec<usize>> {
+    self.states.resolve(id.0).map(|packed| packed.tractors().to_vec())
   }
-  pub fn get_state(&self, id: &usize) -> Option<&Vec<Cell>> {
-    self.id_to_state.get(id)
+  pub fn get_id(&self, state: &[Cell]) -> Option<StateId> {
+    self.find_state(state).0
   }
-  pub fn contains_id(&self, id: &usize) -> bool {
-    self.id_to_state.contains_key(id)
+  // Also returns the transform mapping `state` onto its stored form
+  pub fn find_state(&self, state: &[Cell]) -> (Option<StateId>, Transform) {
+    self.find_state_with(state, &[])
   }
-  pub fn contains_state(&self, state: &Vec<Cell>) -> bool {
-    self.state_to_id.contains_key(state)
+  // Like `find_state` for a board with several tractors
+  pub fn find_state_with(&self, state: &[Cell], tractors: &[usize]) -> (Option<StateId>, Transform) {
+    let (packed, transform) = self.canonicalize(state, tractors);
+    (self.states.get(&packed).map(StateId), transform)
+  }
+  // States can be looked up by a key worked out along the way instead of
+  // being encoded first, as long as they're stored just as encoded: no
+  // symmetries, no directed cells and a single tractor
+  pub fn state_key(&self, id: &StateId) -> Option<u64> {
+    if !self.symmetries.is_empty() || self.layout.is_directed() {
+      return None;
+    }
+    self.states.resolve(id.0).filter(|packed| packed.tractors().len() == 1).map(|packed| packed.zobrist())
+  }
+  // Like `get_id`, for a state whose key is known, see `state_key`
+  pub fn find_keyed(&self, state: &[Cell], key: u64) -> Option<StateId> {
+    debug_assert_eq!(key, self.layout.encode(state).zobrist());
+    self.states.find(key, |packed| packed.matches(state)).map(StateId)
+  }
+  pub fn contains_id(&self, id: &StateId) -> bool {
+    self.states.resolve(id.0).is_some()
+  }
+  pub fn contains_state(&self, state: &[Cell]) -> bool {
+    self.get_id(state).is_some()
+  }
+  pub fn is_goal(&self, id: &StateId) -> bool {
+    self.get_state(id).is_some_and(|state| is_goal_state(&state))
+  }
+  pub fn goal_states(&self) -> Vec<StateId> {
+    let mut goals = self.states()
+      .filter(|(_, state)| is_goal_state(state))
+      .map(|(id, _)| id)
+      .collect::<Vec<StateId>>();
+    goals.sort();
+    goals
+  }
+  // Fewest pushes from each state to any goal state, states that cannot reach
+  // a goal are left out. Edges are followed backwards in graphs explored by
+  // pushing and forwards in pulled ones.
+  pub fn distances_to_goal(&self) -> HashMap<StateId, usize> {
+    let mut dist = HashMap::new();
+    let mut queue = VecDeque::new();
+    for goal in self.goal_states() {
+      dist.insert(goal, 0);
+      queue.push_back(goal);
+    }
+    while let Some(next) = queue.pop_front() {
+      let depth = dist[&next] + 1;
+      for prev in self.pushed_from(&next) {
+        if !dist.contains_key(prev) {
+          dist.insert(*prev, depth);
+          queue.push_back(*prev);
+        }
+      }
+    }
+    dist
+  }
+  fn canonicalize(&self, state: &[Cell], tractors: &[usize]) -> (PackedState, Transform) {
+    let dims = self.layout.dims();
+    let encode = |state: &[Cell], tractors: &[usize]| if tractors.is_empty() {
+      self.layout.encode(state)
+    } else {
+      self.layout.encode_with(state, tractors)
+    };
+    let mut best = (encode(state, tractors), Transform::Identity);
+    for transform in &self.symmetries {
+      let moved = tractors.iter().map(|idx| transform.apply_index(*idx, dims)).collect::<Vec<usize>>();
+      let packed = encode(&transform.apply(state, dims), &moved);
+      if packed < best.0 {
+        best = (packed, *transform);
+      }
+    }
+    best
+  }
+  // Maps a path of stored states back onto the board as the player sees it,
+  // returning every state along it and the pushes between them. Paths from
+  // the root come out in the orientation the root was given in.
+  pub fn resolve_path(&self, path: &[StateId]) -> Option<(Vec<Vec<Cell>>, Vec<Push>)> {
+    let dims = self.layout.dims();
+    let first = path.first()?;
+    let mut frame = if *first == self.root() {
+      self.root_transform.inverse()
+    } else {
+      Transform::Identity
+    };
+    let mut states = vec![frame.apply(&self.get_state(first)?, dims)];
+    let mut pushes = vec![];
+    for step in path.windows(2) {
+      let push = self.get_edge(&step[0], &step[1])?;
+      pushes.push(frame.apply_push(push, dims));
+      frame = frame.compose(self.get_transform(&step[0], &step[1]).inverse());
+      states.push(frame.apply(&self.get_state(&step[1])?, dims));
+    }
+    Some((states, pushes))
   }
   // Special accessors
-  pub fn build_shortest_path_from(&self, from: &usize) -> ShortestGraph {
+  pub fn build_shortest_path_from(&self, from: &StateId) -> ShortestGraph {
+    build_shortest_path(from, &self.neighbors)
+  }
+  // Paths in the result run from each state towards `goal`
+  pub fn build_shortest_path_to(&self, goal: &StateId) -> ShortestGraph {
+    build_shortest_path(goal, &self.predecessors)
+  }
+  pub fn shortest_path(&self, from: &StateId, to: &StateId) -> Option<Vec<StateId>> {
+    if !self.contains_id(from) || !self.contains_id(to) {
+      return None;
+    }
+    let mut parents = HashMap::new();
+    parents.insert(*from, *from);
     let mut queue = VecDeque::new();
     queue.push_back(*from);
-    let mut visited = HashSet::new();
-    visited.insert(*from);
-    let mut shortest = ShortestGraph::new(*from);
     while let Some(next) = queue.pop_front() {
-      visited.insert(next);
-      if let Some(neighbors) = self.neighbors.get(&next) {
-        for neighbor in neighbors {
-          if visited.contains(neighbor) {
+      if next == *to {
+        break;
+      }
+      for neighbor in &self.neighbors[next.0] {
+        if !parents.contains_key(neighbor) {
+          parents.insert(*neighbor, next);
+          queue.push_back(*neighbor);
+        }
+      }
+    }
+    let mut path = vec![*to];
+    let mut current = *parents.get(to)?;
+    while path.last() != Some(from) {
+      path.push(current);
+      current = parents[&current];
+    }
+    path.reverse();
+    Some(path)
+  }
+  // Same result length as `shortest_path`, but searches from both ends at once
+  // over forward and predecessor edges, meeting in the middle
+  pub fn bidirectional_shortest_path(&self, from: &StateId, to: &StateId) -> Option<Vec<StateId>> {
+    if !self.contains_id(from) || !self.contains_id(to) {
+      return None;
+    }
+    if from == to {
+      return Some(vec![*from]);
+    }
+    // Parent towards `from` and towards `to` respectively
+    let mut forward = HashMap::new();
+    forward.insert(*from, (*from, 0));
+    let mut backward = HashMap::new();
+    backward.insert(*to, (*to, 0));
+    let mut forward_frontier = vec![*from];
+    let mut backward_frontier = vec![*to];
+    let meet = loop {
+      if forward_frontier.is_empty() || backward_frontier.is_empty() {
+        return None;
+      }
+      // Grow whichever side has the smaller frontier by one full layer
+      let (frontier, seen, other, edges) = if forward_frontier.len() <= backward_frontier.len() {
+        (&mut forward_frontier, &mut forward, &backward, &self.neighbors)
+      } else {
+        (&mut backward_frontier, &mut backward, &forward, &self.predecessors)
+      };
+      let mut best: Option<(usize, StateId)> = None;
+      let mut next_frontier = vec![];
+      for id in frontier.iter() {
+        let depth = seen[id].1 + 1;
+        for neighbor in &edges[id.0] {
+          if seen.contains_key(neighbor) {
+            continue;
+          }
+          seen.insert(*neighbor, (*id, depth));
+          next_frontier.push(*neighbor);
+          if let Some((_, other_depth)) = other.get(neighbor) {
+            if best.is_none_or(|(total, _)| depth + other_depth < total) {
+              best = Some((depth + other_depth, *neighbor));
+            }
+          }
+        }
+      }
+      *frontier = next_frontier;
+      if let Some((_, meet)) = best {
+        break meet;
+      }
+    };
+    let mut path = vec![meet];
+    while let Some(last) = path.last().cloned().filter(|id| id != from) {
+      path.push(forward[&last].0);
+    }
+    path.reverse();
+    while let Some(last) = path.last().cloned().filter(|id| id != to) {
+      path.push(backward[&last].0);
+    }
+    Some(path)
+  }
+  // The pushes along a shortest path, in the orientation of `from`
+  pub fn shortest_pushes(&self, from: &StateId, to: &StateId) -> Option<Vec<Push>> {
+    let path = self.bidirectional_shortest_path(from, to)?;
+    self.resolve_path(&path).map(|(_, pushes)| pushes)
+  }
+  // Graph builder methods
+  fn set_root(&mut self, state: Vec<Cell>, tractors: &[usize]) -> Result<(), Error> {
+    if self.states.next_handle() != 0 {
+      return Err(Error::StoreNotEmpty);
+    }
+    self.root_transform = self.canonicalize(&state, tractors).1;
+    self.insert_state_with_unchecked(state, tractors);
+    Ok(())
+  }
+  pub fn insert_state(&mut self, state: Vec<Cell>) -> Result<StateId, Error> {
+    self.insert_state_with(state, &[])
+  }
+  pub fn insert_state_with(&mut self, state: Vec<Cell>, tractors: &[usize]) -> Result<StateId, Error> {
+    match self.find_state_with(&state, tractors).0 {
+      Some(id) => Err(Error::DuplicateState(id)),
+      None => Ok(self.insert_state_with_unchecked(state, tractors)),
+    }
+  }
+  pub fn insert_state_unchecked(&mut self, state: Vec<Cell>) -> StateId {
+    self.insert_state_with_unchecked(state, &[])
+  }
+  // Panics if the state is already in the graph
+  pub fn insert_state_with_unchecked(&mut self, state: Vec<Cell>, tractors: &[usize]) -> StateId {
+    let packed = self.canonicalize(&state, tractors).0;
+    assert!(self.states.get(&packed).is_none());
+    let id = StateId(self.states.intern(packed));
+    self.neighbors.push(vec![]);
+    self.pushes.push(vec![]);
+    self.predecessors.push(vec![]);
+    id
+  }
+  // `to` state can be reached from `from` state
+  pub fn connect_states(&mut self, from: &[Cell], to: &[Cell], push: Push) -> Result<(), Error> {
+    let from_id = self.get_id(from).ok_or(Error::UnknownState)?;
+    let to_id = self.get_id(to).ok_or(Error::UnknownState)?;
+    self.connect(&from_id, to_id, push);
+    Ok(())
+  }
+  pub fn connect_states_unchecked(&mut self, from: &[Cell], to: &[Cell], push: Push) {
+    self.connect_states(from, to, push).unwrap()
+  }
+  pub fn connect(&mut self, from_id: &StateId, to_id: StateId, push: Push) {
+    self.connect_transformed(from_id, to_id, push, Transform::Identity);
+  }
+  // `transform` maps the state `push` leads to onto the stored `to_id` state.
+  // Only the first push between two states is kept.
+  pub fn connect_transformed(&mut self, from_id: &StateId, to_id: StateId, push: Push, transform: Transform) {
+    if self.contains_id(from_id) && self.contains_id(&to_id) {
+      if self.neighbors[from_id.0].contains(&to_id) {
+        return;
+      }
+      self.neighbors[from_id.0].push(to_id);
+      self.pushes[from_id.0].push(push);
+      self.predecessors[to_id.0].push(*from_id);
+      if transform != Transform::Identity {
+        self.transforms.insert((*from_id, to_id), transform);
+      }
+    }
+  }
+  // Removes every state matching `is_goal` can't be reached from by pushing,
+  // following edges the way `distances_to_goal` does, returns how many were
+  // removed
+  pub fn prune_unwinnable<F: Fn(&[Cell]) -> bool>(&mut self, is_goal: &F) -> usize {
+    let mut winnable = HashSet::new();
+    let mut queue = VecDeque::new();
+    for (id, state) in self.states() {
+      if is_goal(&state) {
+        winnable.insert(id);
+        queue.push_back(id);
+      }
+    }
+    while let Some(next) = queue.pop_front() {
+      for prev in self.pushed_from(&next) {
+        if winnable.insert(*prev) {
+          queue.push_back(*prev);
+        }
+      }
+    }
+    let unwinnable = self.states.iter()
+      .map(|(id, _)| StateId(id))
+      .filter(|id| !winnable.contains(id))
+      .collect::<HashSet<StateId>>();
+    self.remove_states(&unwinnable);
+    unwinnable.len()
+  }
+  fn remove_states(&mut self, ids: &HashSet<StateId>) {
+    for id in ids {
+      self.states.remove(id.0);
+      self.neighbors[id.0] = vec![];
+      self.pushes[id.0] = vec![];
+      self.predecessors[id.0] = vec![];
+    }
+    for (neighbors, pushes) in self.neighbors.iter_mut().zip(self.pushes.iter_mut()) {
+      let mut idx = 0;
+      while idx < neighbors.len() {
+        if ids.contains(&neighbors[idx]) {
+          neighbors.remove(idx);
+          pushes.remove(idx);
+        } else {
+          idx += 1;
+        }
+      }
+    }
+    for predecessors in self.predecessors.iter_mut() {
+      predecessors.retain(|id| !ids.contains(id));
+    }
+    self.transforms.retain(|(from, to), _| !ids.contains(from) && !ids.contains(to));
+  }
+  // Renumbers the states in id order with no gaps, as removing states leaves
+  // them, and returns the new id of every state kept. The states end up in
+  // memory, see `compact_into`.
+  pub fn compact(&mut self) -> HashMap<StateId, StateId> {
+    self.compact_into(Box::new(vec![])).expect("a new store starts empty")
+  }
+  // Moves the states into `store` as they are renumbered
+  pub fn compact_into(&mut self, store: Box<dyn StateStore>) -> Result<HashMap<StateId, StateId>, Error> {
+    if !store.is_empty() {
+      return Err(Error::StoreNotEmpty);
+    }
+    let ids = self.states.iter().map(|(id, _)| StateId(id)).collect::<Vec<StateId>>();
+    let renumbered = ids.iter().enumerate().map(|(new, old)| (*old, StateId(new))).collect::<HashMap<StateId, StateId>>();
+    let mut states = Interner::from_arena(store, PackedState::zobrist);
+    for id in &ids {
+      states.intern(self.states.remove(id.0).unwrap());
+    }
+    self.states = states;
+    let neighbors = std::mem::take(&mut self.neighbors);
+    let mut pushes = std::mem::take(&mut self.pushes);
+    let predecessors = std::mem::take(&mut self.predecessors);
+    for id in &ids {
+      self.neighbors.push(neighbors[id.0].iter().map(|to| renumbered[to]).collect());
+      self.pushes.push(std::mem::take(&mut pushes[id.0]));
+      self.predecessors.push(predecessors[id.0].iter().map(|from| renumbered[from]).collect());
+    }
+    self.transforms = self.transforms.drain()
+      .map(|((from, to), transform)| ((renumbered[&from], renumbered[&to]), transform))
+      .collect();
+    Ok(renumbered)
+  }
+  pub fn len(&self) -> usize {
+    self.states.len()
+  }
+  pub fn is_empty(&self) -> bool {
+    self.states.is_empty()
+  }
+  // A much smaller encoding than the serde one, see `write_bin`
+  pub fn to_bin(&self) -> Vec<u8> {
+    let mut buf = BIN_MAGIC.to_vec();
+    buf.push(BIN_VERSION);
+    buf.push(self.pulled as u8);
+    write_bin(&self.to_compact(), &mut buf);
+    buf
+  }
+  // Graphs written by older versions load too, taken as pulled since that is
+  // the only kind they were saved from
+  pub fn from_bin(buf: &[u8]) -> io::Result<Self> {
+    let body = buf.strip_prefix(BIN_MAGIC).ok_or_else(|| invalid_data("not a binary state graph"))?;
+    let (compact, pulled) = match body.first() {
+      Some(1) => {
+        let compact = rmp_serde::decode::from_read_ref(&body[1..])
+          .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        (compact, true)
+      },
+      Some(2) => (read_bin(&body[1..])?, true),
+      Some(&BIN_VERSION) => match body.get(1) {
+        Some(flag @ (0 | 1)) => (read_bin(&body[2..])?, *flag == 1),
+        _ => return Err(invalid_data("bad direction flag in binary graph")),
+      },
+      Some(version) => return Err(invalid_data(&format!("unknown binary graph version {}", version))),
+      None => return Err(invalid_data("missing binary graph version")),
+    };
+    // Ids are allocated up to the next free one before anything is checked
+    // against them, so a header can't be allowed to ask for more ids than
+    // the file has bits
+    if compact.next_id > 8 * buf.len() {
+      return Err(invalid_data("binary graph ids out of range"));
+    }
+    Self::from_compact(compact, pulled)
+  }
+  fn to_compact(&self) -> CompactGraph {
+    let ids = self.states.iter().map(|(id, _)| StateId(id)).collect::<Vec<StateId>>();
+    let position = ids.iter().enumerate().map(|(pos, id)| (*id, pos as u32)).collect::<HashMap<StateId, u32>>();
+    let mut compact = CompactGraph {
+      layout: self.layout.clone(),
+      ids: ids.iter().map(|id| id.0).collect(),
+      states: self.states.iter().map(|(_, packed)| packed.into_owned()).collect(),
+      offsets: vec![0],
+      targets: vec![],
+      pushes: vec![],
+      symmetries: self.symmetries.clone(),
+      root_transform: self.root_transform,
+      transforms: self.transforms.iter().map(|((from, to), t)| (from.0, to.0, *t)).collect(),
+      next_id: self.states.next_handle(),
+    };
+    compact.transforms.sort_by_key(|(from, to, _)| (*from, *to));
+    for id in &ids {
+      for (to, push) in self.edges_from(id) {
+        compact.targets.push(position[&to]);
+        compact.pushes.push(*push);
+      }
+      compact.offsets.push(compact.targets.len() as u32);
+    }
+    compact
+  }
+  fn from_compact(compact: CompactGraph, pulled: bool) -> io::Result<Self> {
+    if compact.states.len() != compact.ids.len() || compact.offsets.len() != compact.ids.len() + 1
+      || compact.targets.len() != compact.pushes.len()
+      || compact.offsets.last().map(|last| *last as usize) != Some(compact.targets.len())
+      || compact.offsets.windows(2).any(|pair| pair[0] > pair[1])
+      || compact.targets.iter().any(|to| *to as usize >= compact.ids.len())
+      || compact.ids.windows(2).any(|pair| pair[0] >= pair[1])
+      || compact.ids.last().is_some_and(|last| *last >= compact.next_id) {
+      return Err(invalid_data("inconsistent binary graph"));
+    }
+    let mut arena = (0..compact.next_id).map(|_| None).collect::<Vec<Option<PackedState>>>();
+    for (id, packed) in compact.ids.iter().zip(compact.states) {
+      arena[*id] = Some(packed);
+    }
+    let mut graph = StateGraph {
+      layout: compact.layout,
+      states: Interner::from_arena(Box::new(arena), PackedState::zobrist),
+      neighbors: vec![],
+      pushes: vec![],
+      predecessors: vec![],
+      symmetries: compact.symmetries,
+      root_transform: compact.root_transform,
+      transforms: compact.transforms.into_iter().map(|(from, to, t)| ((StateId(from), StateId(to)), t)).collect(),
+      pulled,
+    };
+    graph.neighbors.resize(compact.next_id, vec![]);
+    graph.pushes.resize(compact.next_id, vec![]);
+    graph.predecessors.resize(compact.next_id, vec![]);
+    for (pos, id) in compact.ids.iter().enumerate() {
+      let from = StateId(*id);
+      let edges = compact.offsets[pos] as usize..compact.offsets[pos + 1] as usize;
+      for (to, push) in compact.targets[edges.clone()].iter().zip(&compact.pushes[edges]) {
+        let to = StateId(compact.ids[*to as usize]);
+        graph.neighbors[from.0].push(to);
+        graph.pushes[from.0].push(*push);
+        graph.predecessors[to.0].push(from);
+      }
+    }
+    Ok(graph)
+  }
+  // Also reads graphs saved compressed
+  pub fn load_bin(path: &Path) -> io::Result<Self> {
+    Self::from_bin(&compression::read(path)?)
+  }
+  pub fn save_bin(&self, path: &Path) -> io::Result<()> {
+    let mut fout = File::create(path)?;
+    fout.write_all(&self.to_bin())?;
+    fout.flush()
+  }
+  // Needs the `compression` feature
+  pub fn save_bin_compressed(&self, path: &Path) -> io::Result<()> {
+    compression::write(path, &self.to_bin(), true)
+  }
+}
+
+const BIN_MAGIC: &[u8] = b"LVSG";
+// The version `to_bin` writes
+const BIN_VERSION: u8 = 3;
+
+// The graph as `to_bin` lays it out. States are kept once, in id order, and
+// the edges out of the state at position `pos` are
+// `targets[offsets[pos]..offsets[pos + 1]]`, with the matching `pushes`. The
+// reverse lookups are rebuilt on load.
+//
+// Version 1 files are this struct as MessagePack, which is why it still
+// derives serde and mustn't change.
+#[derive(Deserialize, Serialize)]
+struct CompactGraph {
+  layout: Layout,
+  ids: Vec<usize>,
+  states: Vec<PackedState>,
+  offsets: Vec<u32>,
+  targets: Vec<u32>,
+  pushes: Vec<Push>,
+  symmetries: Vec<Transform>,
+  root_transform: Transform,
+  transforms: Vec<(usize, usize, Transform)>,
+  next_id: usize,
+}
+
+// Versions 2 and up are laid out by hand, so that they only change along with
+// `BIN_VERSION`. Numbers are LEB128 varints, cells are written as their
+// characters in level files, and directions and transforms by their position
+// in `DIRECTIONS` and `TRANSFORMS`. Following the magic bytes and version:
+//
+// - from version 3, a byte that is 1 if the graph is pulled and 0 otherwise
+// - rows and columns, then the board's fixed cells, row by row
+// - the next free id and the number of states
+// - each state: its id and the first cell of each tractor region, then which
+//   cells it changes on the board, leaving out the regions, as a bit per cell
+//   from the low bit of the first byte, then what each of those holds
+// - the edge offsets, one more than there are states
+// - each edge: its target's position among the states, then the push's
+//   boulder, direction and tractor
+// - the symmetries, the root transform and the edge transforms, by ids
+fn write_bin(compact: &CompactGraph, buf: &mut Vec<u8>) {
+  let board = compact.layout.cells();
+  let dims = compact.layout.dims();
+  write_varint(buf, dims.rows);
+  write_varint(buf, dims.cols);
+  buf.extend(board.iter().map(|cell| cell.to_char() as u8));
+  write_varint(buf, compact.next_id);
+  write_varint(buf, compact.ids.len());
+  for (id, packed) in compact.ids.iter().zip(&compact.states) {
+    write_varint(buf, *id);
+    write_varint(buf, packed.tractors().len());
+    for tractor in packed.tractors() {
+      write_varint(buf, *tractor);
+    }
+    let cells = compact.layout.decode(&packed.clone().with_tractors(vec![]));
+    let changed = (0..cells.len()).filter(|idx| cells[*idx] != board[*idx]).collect::<Vec<usize>>();
+    let mut bits = vec![0u8; cells.len().div_ceil(8)];
+    for idx in &changed {
+      bits[idx / 8] |= 1 << (idx % 8);
+    }
+    buf.extend(bits);
+    buf.extend(changed.iter().map(|idx| cells[*idx].to_char() as u8));
+  }
+  for offset in &compact.offsets {
+    write_varint(buf, *offset as usize);
+  }
+  for (to, push) in compact.targets.iter().zip(&compact.pushes) {
+    write_varint(buf, *to as usize);
+    write_varint(buf, push.boulder);
+    buf.push(DIRECTIONS.iter().position(|dir| *dir == push.dir).unwrap() as u8);
+    write_varint(buf, push.tractor);
+  }
+  write_varint(buf, compact.symmetries.len());
+  buf.extend(compact.symmetries.iter().map(|transform| transform_code(*transform)));
+  buf.push(transform_code(compact.root_transform));
+  write_varint(buf, compact.transforms.len());
+  for (from, to, transform) in &compact.transforms {
+    write_varint(buf, *from);
+    write_varint(buf, *to);
+    buf.push(transform_code(*transform));
+  }
+}
+
+fn read_bin(buf: &[u8]) -> io::Result<CompactGraph> {
+  let mut reader = BinReader { buf };
+  let dims = Dims::new(reader.varint()?, reader.varint()?);
+  let len = dims.rows.checked_mul(dims.cols).ok_or_else(|| invalid_data("binary graph board too large"))?;
+  let board = reader.bytes(len)?.iter().map(|c| cell_of(*c)).collect::<io::Result<Vec<Cell>>>()?;
+  let next_id = reader.varint()?;
+  let count = reader.varint()?;
+  // Each state takes a byte at least
+  if count > reader.buf.len() {
+    return Err(invalid_data("truncated binary graph"));
+  }
+  let mut states = vec![];
+  for _ in 0..count {
+    let id = reader.varint()?;
+    let tractors = (0..reader.varint()?).map(|_| reader.index(len)).collect::<io::Result<Vec<usize>>>()?;
+    let bits = reader.bytes(len.div_ceil(8))?;
+    let mut cells = board.clone();
+    for idx in (0..len).filter(|idx| bits[idx / 8] & (1 << (idx % 8)) != 0) {
+      cells[idx] = cell_of(reader.byte()?)?;
+    }
+    if tractors.iter().any(|tractor| !matches!(cells[*tractor], Cell::Unreachable | Cell::Ice | Cell::OneWay(_))) {
+      return Err(invalid_data("tractor region off the floor in binary graph"));
+    }
+    states.push((id, tractors, cells));
+  }
+  let layout = Layout::from_state(states.first().map_or(&board, |(_, _, cells)| cells), dims);
+  if layout.cells() != board.as_slice() {
+    return Err(invalid_data("binary graph state off its board"));
+  }
+  let offsets = (0..count + 1).map(|_| reader.varint().map(|offset| offset as u32)).collect::<io::Result<Vec<u32>>>()?;
+  let mut targets = vec![];
+  let mut pushes = vec![];
+  for _ in 0..offsets.last().cloned().unwrap_or(0) {
+    targets.push(reader.index(count)? as u32);
+    let boulder = reader.index(len)?;
+    let dir = *DIRECTIONS.get(reader.byte()? as usize).ok_or_else(|| invalid_data("unknown direction in binary graph"))?;
+    pushes.push(Push { boulder, dir, tractor: reader.index(len)? });
+  }
+  let symmetries = (0..reader.varint()?).map(|_| reader.transform()).collect::<io::Result<Vec<Transform>>>()?;
+  let root_transform = reader.transform()?;
+  let transforms = (0..reader.varint()?)
+    .map(|_| Ok((reader.varint()?, reader.varint()?, reader.transform()?)))
+    .collect::<io::Result<Vec<(usize, usize, Transform)>>>()?;
+  if !reader.buf.is_empty() {
+    return Err(invalid_data("trailing bytes after binary graph"));
+  }
+  Ok(CompactGraph {
+    ids: states.iter().map(|(id, _, _)| *id).collect(),
+    states: states.into_iter().map(|(_, tractors, cells)| layout.encode(&cells).with_tractors(tractors)).collect(),
+    layout,
+    offsets,
+    targets,
+    pushes,
+    symmetries,
+    root_transform,
+    transforms,
+    next_id,
+  })
+}
+
+fn write_varint(buf: &mut Vec<u8>, mut value: usize) {
+  while value >= 0x80 {
+    buf.push(value as u8 | 0x80);
+    value >>= 7;
+  }
+  buf.push(value as u8);
+}
+
+fn transform_code(transform: Transform) -> u8 {
+  TRANSFORMS.iter().position(|t| *t == transform).unwrap() as u8
+}
+
+fn cell_of(c: u8) -> io::Result<Cell> {
+  Cell::try_from_char(c as char).ok_or_else(|| invalid_data(&format!("unknown cell {:?} in binary graph", c as char)))
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+  io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+// Reads what `write_bin` wrote, failing rather than running off the end
+struct BinReader<'a> {
+  buf: &'a [u8],
+}
+
+impl<'a> BinReader<'a> {
+  fn bytes(&mut self, len: usize) -> io::Result<&'a [u8]> {
+    if len > self.buf.len() {
+      return Err(invalid_data("truncated binary graph"));
+    }
+    let (bytes, rest) = self.buf.split_at(len);
+    self.buf = rest;
+    Ok(bytes)
+  }
+  fn byte(&mut self) -> io::Result<u8> {
+    Ok(self.bytes(1)?[0])
+  }
+  fn varint(&mut self) -> io::Result<usize> {
+    let mut value = 0;
+    for shift in (0..usize::BITS).step_by(7) {
+      let byte = self.byte()?;
+      value |= ((byte & 0x7f) as usize) << shift;
+      if byte & 0x80 == 0 {
+        return Ok(value);
+      }
+    }
+    Err(invalid_data("oversized number in binary graph"))
+  }
+  // One below `len`
+  fn index(&mut self, len: usize) -> io::Result<usize> {
+    let idx = self.varint()?;
+    if idx >= len {
+      return Err(invalid_data("index out of range in binary graph"));
+    }
+    Ok(idx)
+  }
+  fn transform(&mut self) -> io::Result<Transform> {
+    TRANSFORMS.get(self.byte()? as usize).cloned().ok_or_else(|| invalid_data("unknown transform in binary graph"))
+  }
+}
+
+// Into memory, whatever the states were stored in
+fn deserialize_states<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Interner<PackedState, Box<dyn StateStore>>, D::Error> {
+  let arena = Vec::<Option<PackedState>>::deserialize(deserializer)?;
+  Ok(Interner::from_arena(Box::new(arena), PackedState::zobrist))
+}
+
+fn build_shortest_path(from: &StateId, edges: &[Vec<StateId>]) -> ShortestGraph {
+  let mut queue = VecDeque::new();
+  queue.push_back(*from);
+  let mut visited = HashSet::new();
+  visited.insert(*from);
+  let mut shortest = ShortestGraph::new(*from);
+  while let Some(next) = queue.pop_front() {
+    if let Some(neighbors) = edges.get(next.0) {
+      for neighbor in neighbors {
+        if visited.insert(*neighbor) {
+          shortest.insert(&next, *neighbor);
+          queue.push_back(*neighbor);
+        }
+      }
+    }
+  }
+  shortest
+}
+
+// Every hole holds a boulder and no boulder is left loose
+pub fn is_goal_state(state: &[Cell]) -> bool {
+  state.iter().all(|cell| !cell.is_open_hole() && !cell.is_loose_boulder())
+}
+
+// Heavy boulders only move when pushed with this many cells of the tractor's
+// region straight behind it
+pub const RUN_UP: usize = 2;
+
+// How exploring gets from one state to the next, so variants of the game can
+// be explored without touching the explorer. States are found working back
+// from solved boards, so each move is one undone, recorded as the push that
+// redoes it. `StandardRules` are the game as the solver plays it.
+pub trait Rules: Send + Sync {
+  fn successors(&self, state: &[Cell], dims: Dims) -> Vec<(Vec<Cell>, Push)>;
+  // Also the Zobrist key of each state, where it can be worked out from
+  // `key`, that of `state` less its tractor's word. States without are
+  // looked up in full.
+  fn keyed_successors(&self, state: &[Cell], key: Option<u64>, dims: Dims) -> Vec<(Vec<Cell>, Push, Option<u64>)> {
+    let _ = key;
+    self.successors(state, dims).into_iter().map(|(state, push)| (state, push, None)).collect()
+  }
+  // Set for `StandardRules`, the only rules checkpoints keep and, as they
+  // come by default, the only ones several tractors explore by
+  fn standard(&self) -> Option<StandardRules> {
+    None
+  }
+}
+
+// Pushing, across ice, portals and cracked floor, and what the tractor may do
+// besides
+#[derive(Copy, Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct StandardRules {
+  // The tractor can also back off from a boulder next to it, dragging it
+  // along. Only plain floor is pulled across, and never a heavy boulder.
+  pub pulls: bool,
+}
+
+impl Rules for StandardRules {
+  fn successors(&self, state: &[Cell], dims: Dims) -> Vec<(Vec<Cell>, Push)> {
+    next_pulled_states_with(state, self, dims)
+  }
+  fn keyed_successors(&self, state: &[Cell], key: Option<u64>, dims: Dims) -> Vec<(Vec<Cell>, Push, Option<u64>)> {
+    pulled_states(state, key, self, dims)
+  }
+  fn standard(&self) -> Option<StandardRules> {
+    Some(*self)
+  }
+}
+
+// How far an exploration has got, handed to `ExploreOptions::progress`
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Progress {
+  pub expanded: usize,
+  // States found but not expanded yet
+  pub frontier: usize,
+  pub states: usize,
+  pub elapsed: Duration,
+}
+
+// States expanded between calls to the progress callback
+pub const PROGRESS_INTERVAL: usize = 1024;
+
+pub type ProgressCallback = Arc<dyn Fn(&Progress) + Send + Sync>;
+
+#[derive(Clone)]
+pub struct ExploreOptions {
+  // Merge states that are rotations or reflections of each other
+  pub symmetry: bool,
+  // Drop any new state one of these rejects
+  pub pruners: Vec<Arc<dyn StatePruner>>,
+  // Plain `StandardRules` unless set
+  pub rules: Arc<dyn Rules>,
+  // Called every `PROGRESS_INTERVAL` expanded states and once at the end
+  pub progress: Option<ProgressCallback>,
+  pub limits: Limits,
+}
+
+// Bounds on how far an exploration goes, each unbounded if unset. The root is
+// at depth 0 and states at `max_depth` are kept but not expanded.
+#[derive(Copy, Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct Limits {
+  pub max_states: Option<usize>,
+  pub max_depth: Option<usize>,
+  // Spent exploring, across checkpoints
+  pub timeout: Option<Duration>,
+}
+
+// Which of the `Limits` stopped an exploration
+#[derive(Copy, Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum Limit {
+  States,
+  Depth,
+  Timeout,
+}
+
+// A graph from `find_solvable_states_within`. A truncated one may be missing
+// states, and edges out of the states left unexpanded.
+pub enum Explored {
+  Complete(StateGraph),
+  Truncated(StateGraph, Limit),
+}
+
+impl Explored {
+  pub fn graph(&self) -> &StateGraph {
+    match self {
+      Explored::Complete(graph) | Explored::Truncated(graph, _) => graph,
+    }
+  }
+  pub fn into_graph(self) -> StateGraph {
+    match self {
+      Explored::Complete(graph) | Explored::Truncated(graph, _) => graph,
+    }
+  }
+  pub fn is_complete(&self) -> bool {
+    matches!(self, Explored::Complete(_))
+  }
+}
+
+impl Default for ExploreOptions {
+  fn default() -> Self {
+    ExploreOptions {
+      symmetry: false,
+      pruners: vec![],
+      rules: default_rules(),
+      progress: None,
+      limits: Limits::default(),
+    }
+  }
+}
+
+fn default_rules() -> Arc<dyn Rules> {
+  Arc::new(StandardRules::default())
+}
+
+impl fmt::Debug for ExploreOptions {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.debug_struct("ExploreOptions")
+      .field("symmetry", &self.symmetry)
+      .field("pruners", &self.pruners.len())
+      .field("rules", &self.rules.standard())
+      .field("progress", &self.progress.is_some())
+      .field("limits", &self.limits)
+      .finish()
+  }
+}
+
+pub fn find_solvable_states(level: &Level) -> StateGraph {
+  find_solvable_states_with(level, &ExploreOptions::default())
+}
+
+pub fn find_solvable_states_with(level: &Level, options: &ExploreOptions) -> StateGraph {
+  ExplorationSession::with_options(root_of(level), options).finish()
+}
+
+// The level's board with the tractor's region marked, the tractor's own
+// cell taken for floor
+fn root_of(level: &Level) -> Grid {
+  let mut grid = level.board();
+  grid[level.tractor] = Cell::Unreachable;
+  fill_reachable_cells_unchecked(level.tractor, &mut grid, level.dims);
+  grid
+}
+
+// Like `find_solvable_states_with`, saying whether `options.limits` cut the
+// exploration short
+pub fn find_solvable_states_within(level: &Level, options: &ExploreOptions) -> Explored {
+  ExplorationSession::with_options(root_of(level), options).finish_within()
+}
+
+// Like `find_solvable_states_with`, keeping the states in `store`, say a
+// `DiskStore` for graphs too big for memory
+pub fn find_solvable_states_in(level: &Level, options: &ExploreOptions, store: Box<dyn StateStore>) -> Result<StateGraph, Error> {
+  Ok(ExplorationSession::with_store(root_of(level), &[], options, store)?.finish())
+}
+
+// Several tractors, any of which can pull. They don't block each other.
+pub fn find_solvable_states_multi(tractors: &[usize], mut grid: Grid) -> StateGraph {
+  let dims = grid.dims();
+  for tractor in tractors {
+    grid[*tractor] = Cell::Unreachable;
+  }
+  for tractor in tractors {
+    if grid[*tractor] != Cell::Reachable {
+      fill_reachable_cells_unchecked(*tractor, &mut grid, dims);
+    }
+  }
+  ExplorationSession::with_tractors(grid, tractors, &ExploreOptions::default())
+    .expect("the default rules explore any tractors")
+    .finish()
+}
+
+// Every state reachable from `level` by pushing, with edges running forwards
+// as the pushes do, unlike the pulled graphs above. Suits `hint` from a state
+// met in play.
+pub fn find_pushed_states(level: &Level) -> StateGraph {
+  let mut states = explore_iter(level);
+  states.by_ref().for_each(drop);
+  states.into_graph()
+}
+
+// `find_pushed_states`, `None` once there are more than `max_states`
+pub fn find_pushed_states_up_to(level: &Level, max_states: usize) -> Option<StateGraph> {
+  let mut states = explore_iter(level);
+  match states.by_ref().nth(max_states) {
+    Some(_) => None,
+    None => Some(states.into_graph()),
+  }
+}
+
+// The states of `find_pushed_states` one at a time, breadth first, the root
+// first. A state is only expanded once everything found before it has been
+// taken, so stopping early saves exploring the rest.
+pub fn explore_iter(level: &Level) -> PushedStates {
+  let graph = StateGraph::new(Grid::new_unchecked(level.initial_state(), level.dims));
+  let root = graph.root();
+  let found = Discovered { id: root, state: graph.get_state(&root).unwrap(), parent: None, push: None };
+  PushedStates { graph, dims: level.dims, queue: VecDeque::from(vec![root]), found: VecDeque::from(vec![found]) }
+}
+
+// A state met by `explore_iter`, with the push that first reached it from
+// `parent`. The root has neither.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Discovered {
+  pub id: StateId,
+  pub state: Grid,
+  pub parent: Option<StateId>,
+  pub push: Option<Push>,
+}
+
+pub struct PushedStates {
+  graph: StateGraph,
+  dims: Dims,
+  queue: VecDeque<StateId>,
+  // Found but not handed out yet
+  found: VecDeque<Discovered>,
+}
+
+impl PushedStates {
+  // Everything found so far, with the edges out of the states expanded
+  pub fn graph(&self) -> &StateGraph {
+    &self.graph
+  }
+  pub fn into_graph(self) -> StateGraph {
+    self.graph
+  }
+}
+
+impl Iterator for PushedStates {
+  type Item = Discovered;
+
+  fn next(&mut self) -> Option<Discovered> {
+    loop {
+      if let Some(found) = self.found.pop_front() {
+        return Some(found);
+      }
+      let id = self.queue.pop_front()?;
+      let state = self.graph.get_state(&id).unwrap();
+      if is_goal_state(&state) {
+        continue;
+      }
+      for (next, push) in next_pushed_states(&state, self.dims) {
+        let next_id = match self.graph.get_id(&next) {
+          Some(next_id) => next_id,
+          None => {
+            let next_id = self.graph.insert_state_unchecked(next.clone());
+            self.queue.push_back(next_id);
+            self.found.push_back(Discovered { id: next_id, state: Grid::new_unchecked(next, self.dims), parent: Some(id), push: Some(push) });
+            next_id
+          },
+        };
+        self.graph.connect(&id, next_id, push);
+      }
+    }
+  }
+}
+
+// Undoes a push that left the boulder at `boulder`, the tractor backing off
+// towards `dir`. Pushed across ice the boulder may have come from further
+// along, so there can be several ways back. Given the Zobrist key of `grid`
+// less its tractor's word, also works out the key of each state, on layouts
+// without directed cells.
+fn extend_state(boulder: usize, dir: Direction, grid: &[Cell], key: Option<u64>, dims: Dims) -> Vec<(Vec<Cell>, Push, Option<u64>)> {
+  assert!(grid[boulder].has_boulder());
+  let mut next = vec![];
+  // A boulder resting on ice only stopped there because it couldn't slide on
+  if grid[boulder] == Cell::BoulderOnIce && step(boulder, dir.opposite(), grid, dims).is_some_and(|ahead| slides_into(grid[ahead], dir.opposite(), grid[boulder])) {
+    return next;
+  }
+  // Nor could it have come onto a one-way cell against its way
+  if !grid[boulder].allows(dir.opposite()) {
+    return next;
+  }
+  let directed = grid.iter().any(|cell| cell.is_directed());
+  let key = key.filter(|_| !directed).map(|key| key ^ zobrist::cell_key(boulder, grid[boulder]));
+  let mut origin = boulder;
+  while let Some(new_boulder) = step(origin, dir, grid, dims) {
+    origin = new_boulder;
+    let fits = matches!(grid[new_boulder], Cell::Reachable | Cell::Ice | Cell::OneWay(_)) && grid[new_boulder].accepts(grid[boulder]);
+    if !fits || !grid[new_boulder].allows(dir.opposite()) {
+      break;
+    }
+    let new_tractor = step(new_boulder, dir, grid, dims).filter(|idx| grid[*idx] == Cell::Reachable);
+    // After the push the tractor stood where the boulder came from, and has
+    // to be able to walk the whole region from there
+    if let Some(new_tractor) = new_tractor.filter(|_| !directed || fills_region(new_boulder, grid, dims)) {
+      let mut new_grid = grid.iter()
+        .map(|cell| if *cell == Cell::Reachable { Cell::Unreachable } else { *cell })
+        .collect::<Vec<Cell>>();
+      new_grid[boulder] = new_grid[boulder].without_boulder();
+      new_grid[new_boulder] = new_grid[new_boulder].with_boulder_like(grid[boulder]);
+      fill_reachable_cells_unchecked(new_tractor, &mut new_grid, dims);
+      if !grid[boulder].is_heavy() || has_run_up(new_tractor, dir.opposite(), &new_grid, dims) {
+        let key = key.map(|key| {
+          let tractor = new_grid.iter().position(|cell| *cell == Cell::Reachable).unwrap();
+          key ^ zobrist::tractor_key(tractor, 0) ^ zobrist::cell_key(new_boulder, grid[boulder])
+        });
+        next.push((new_grid, Push { boulder, dir, tractor: new_tractor }, key));
+      }
+    }
+    if grid[new_boulder] != Cell::Ice {
+      break;
+    }
+  }
+  next
+}
+
+// Undoes a pull that left the boulder at `boulder` and the tractor backed off
+// from it towards `dir.opposite()`: the boulder goes back towards `dir` and
+// the tractor stands where it was.
+fn undo_pull(boulder: usize, dir: Direction, grid: &[Cell], dims: Dims) -> Option<(Vec<Cell>, Push)> {
+  if !grid[boulder].is_loose_boulder() || grid[boulder].is_heavy() || grid[boulder].without_boulder() != Cell::Unreachable {
+    return None;
+  }
+  let from = step(boulder, dir.opposite(), grid, dims).filter(|idx| grid[*idx] == Cell::Reachable)?;
+  let new_boulder = step(boulder, dir, grid, dims)
+    .filter(|idx| grid[*idx] == Cell::Unreachable || grid[*idx] == Cell::Reachable)?;
+  let directed = grid.iter().any(|cell| cell.is_directed());
+  if directed && !fills_region(from, grid, dims) {
+    return None;
+  }
+  let mut new_grid = grid.iter()
+    .map(|cell| if *cell == Cell::Reachable { Cell::Unreachable } else { *cell })
+    .collect::<Vec<Cell>>();
+  new_grid[boulder] = Cell::Unreachable;
+  new_grid[new_boulder] = Cell::Unreachable.with_boulder_like(grid[boulder]);
+  fill_reachable_cells_unchecked(boulder, &mut new_grid, dims);
+  Some((new_grid, Push { boulder, dir, tractor: boulder }))
+}
+
+// Undoes the tractor crossing `cracked` towards `dir.opposite()` before it
+// caved in, the tractor going back over it towards `dir`
+fn undo_crossing(cracked: usize, dir: Direction, grid: &[Cell], dims: Dims) -> Option<(Vec<Cell>, Push)> {
+  let from = step(cracked, dir.opposite(), grid, dims).filter(|idx| grid[*idx] == Cell::Reachable)?;
+  let to = step(cracked, dir, grid, dims)
+    .filter(|idx| grid[*idx] == Cell::Unreachable || grid[*idx] == Cell::Reachable)?;
+  let directed = grid.iter().any(|cell| cell.is_directed());
+  if directed && !fills_region(from, grid, dims) {
+    return None;
+  }
+  let mut new_grid = grid.iter()
+    .map(|cell| if *cell == Cell::Reachable { Cell::Unreachable } else { *cell })
+    .collect::<Vec<Cell>>();
+  new_grid[cracked] = Cell::Cracked;
+  fill_reachable_cells_unchecked(to, &mut new_grid, dims);
+  Some((new_grid, Push { boulder: cracked, dir, tractor: to }))
+}
+
+// Cells `boulder` pushed towards `dir` moves on into rather than stopping in
+// front of
+fn slides_into(cell: Cell, dir: Direction, boulder: Cell) -> bool {
+  cell.accepts(boulder) && cell.allows(dir)
+}
+
+// Whether the tractor at `from` has `RUN_UP` cells of its region straight
+// behind it, pushing towards `dir`
+fn has_run_up(from: usize, dir: Direction, grid: &[Cell], dims: Dims) -> bool {
+  std::iter::successors(Some(from), |idx| step(*idx, dir.opposite(), grid, dims))
+    .skip(1)
+    .take_while(|idx| grid[*idx] == Cell::Reachable)
+    .take(RUN_UP)
+    .count() == RUN_UP
+}
+
+fn fills_region(from: usize, grid: &[Cell], dims: Dims) -> bool {
+  let floor = grid.iter()
+    .map(|cell| if *cell == Cell::Reachable { Cell::Unreachable } else { *cell })
+    .collect::<Vec<Cell>>();
+  let region = (0..grid.len()).filter(|idx| grid[*idx] == Cell::Reachable).collect::<HashSet<usize>>();
+  find_reachable_empty_cells_unchecked(from, &floor, dims) == region
+}
+
+// Exploration that can be stopped, checkpointed to disk, and picked up later
+#[derive(Deserialize, Serialize)]
+pub struct ExplorationSession {
+  graph: StateGraph,
+  dims: Dims,
+  queue: VecDeque<StateId>,
+  // Pruners can't be written to a checkpoint, re-add them after `load`
+  #[serde(skip)]
+  pruners: Vec<Arc<dyn StatePruner>>,
+  // Only `StandardRules` are written to a checkpoint, set any others again
+  // after `load`
+  #[serde(default = "default_rules", serialize_with = "save_rules", deserialize_with = "load_rules")]
+  rules: Arc<dyn Rules>,
+  #[serde(default)]
+  expanded: usize,
+  // Like the pruners, re-add after `load`
+  #[serde(skip)]
+  progress: Option<ProgressCallback>,
+  // Time spent exploring in earlier runs, and when this one began
+  #[serde(default)]
+  elapsed: Duration,
+  #[serde(skip)]
+  started: Option<Instant>,
+  #[serde(default)]
+  limits: Limits,
+  // The depth of the front of the queue, and how many states there are at
+  // that depth
+  #[serde(default)]
+  depth: usize,
+  #[serde(default)]
+  layer_left: usize,
+  #[serde(default)]
+  truncated: Option<Limit>,
+}
+
+impl ExplorationSession {
+  pub fn new(initial_state: Grid) -> Self {
+    Self::with_options(initial_state, &ExploreOptions::default())
+  }
+  pub fn with_options(initial_state: Grid, options: &ExploreOptions) -> Self {
+    Self::with_store(initial_state, &[], options, Box::new(vec![])).expect("a new graph starts empty")
+  }
+  // See `StateGraph::with_tractors`. Fails for more than one tractor unless
+  // the rules are the default ones.
+  pub fn with_tractors(initial_state: Grid, tractors: &[usize], options: &ExploreOptions) -> Result<Self, Error> {
+    Self::with_store(initial_state, tractors, options, Box::new(vec![]))
+  }
+  // See `StateGraph::with_store` and `with_tractors`
+  pub fn with_store(initial_state: Grid, tractors: &[usize], options: &ExploreOptions, store: Box<dyn StateStore>) -> Result<Self, Error> {
+    check_rules(options.rules.as_ref(), tractors)?;
+    let dims = initial_state.dims();
+    let mut graph = StateGraph::with_store(initial_state, tractors, options.symmetry, store)?;
+    graph.pulled = true;
+    let mut queue = VecDeque::new();
+    queue.push_back(graph.root());
+    Ok(ExplorationSession {
+      graph,
+      dims,
+      queue,
+      pruners: options.pruners.clone(),
+      rules: options.rules.clone(),
+      expanded: 0,
+      progress: options.progress.clone(),
+      elapsed: Duration::default(),
+      started: None,
+      limits: options.limits,
+      depth: 0,
+      layer_left: 1,
+      truncated: None,
+    })
+  }
+  pub fn add_pruner(&mut self, pruner: Arc<dyn StatePruner>) {
+    self.pruners.push(pruner);
+  }
+  pub fn set_rules(&mut self, rules: Arc<dyn Rules>) -> Result<(), Error> {
+    check_rules(rules.as_ref(), &self.graph.get_tractors(&self.graph.root()).unwrap_or_default())?;
+    self.rules = rules;
+    Ok(())
+  }
+  pub fn set_progress(&mut self, progress: ProgressCallback) {
+    self.progress = Some(progress);
+  }
+  pub fn progress(&self) -> Progress {
+    let elapsed = self.elapsed + self.started.map_or(Duration::default(), |started| started.elapsed());
+    Progress { expanded: self.expanded, frontier: self.queue.len(), states: self.graph.len(), elapsed }
+  }
+  fn report(&self) {
+    if let Some(progress) = &self.progress {
+      progress(&self.progress());
+    }
+  }
+  pub fn load(path: &Path) -> io::Result<Self> {
+    rmp_serde::decode::from_read_ref(&compression::read(path)?)
+      .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+  }
+  pub fn save(&self, path: &Path) -> io::Result<()> {
+    let buf = rmp_serde::encode::to_vec(self)
+      .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    let mut fout = File::create(path)?;
+    fout.write_all(&buf)?;
+    fout.flush()
+  }
+  pub fn graph(&self) -> &StateGraph {
+    &self.graph
+  }
+  pub fn pending(&self) -> usize {
+    self.queue.len()
+  }
+  pub fn is_done(&self) -> bool {
+    self.queue.is_empty()
+  }
+  // The limit that stopped exploring, if one did
+  pub fn truncated(&self) -> Option<Limit> {
+    self.truncated
+  }
+  // The limit that stops expanding the front of the queue. Running out of
+  // room for states only counts once a new one turns up.
+  fn reached_limit(&self) -> Option<Limit> {
+    if self.limits.max_depth.is_some_and(|max| self.depth >= max) {
+      Some(Limit::Depth)
+    } else if self.limits.timeout.is_some_and(|timeout| self.progress().elapsed >= timeout) {
+      Some(Limit::Timeout)
+    } else {
+      None
+    }
+  }
+  // Expands at most `max_states` queued states, returns whether exploration
+  // is complete
+  pub fn explore(&mut self, max_states: usize) -> bool {
+    let started = *self.started.get_or_insert_with(Instant::now);
+    for _ in 0..max_states {
+      if self.truncated.is_some() || self.queue.is_empty() {
+        break;
+      }
+      if self.layer_left == 0 {
+        self.depth += 1;
+        self.layer_left = self.queue.len();
+      }
+      self.truncated = self.reached_limit();
+      if self.truncated.is_some() {
+        break;
+      }
+      let id = self.queue.pop_front().unwrap();
+      self.layer_left -= 1;
+      self.expanded += 1;
+      if self.expanded.is_multiple_of(PROGRESS_INTERVAL) {
+        self.report();
+      }
+      let state = self.graph.get_state(&id).unwrap();
+      let tractors = self.graph.get_tractors(&id).unwrap();
+      let next = if tractors.len() > 1 {
+        next_pulled_states_multi(&state, &tractors, self.dims).into_iter()
+          .map(|(state, tractors, push)| (state, tractors, push, None))
+          .collect()
+      } else {
+        let key = self.graph.state_key(&id).map(|key| key ^ zobrist::tractor_key(tractors[0], 0));
+        self.rules.keyed_successors(&state, key, self.dims).into_iter()
+          .map(|(state, push, key)| (state, vec![], push, key))
+          .collect::<Vec<_>>()
+      };
+      for (new_state, new_tractors, push, key) in next {
+        let (found, transform) = match key {
+          Some(key) => (self.graph.find_keyed(&new_state, key), Transform::Identity),
+          None => self.graph.find_state_with(&new_state, &new_tractors),
+        };
+        let new_id = match found {
+          Some(new_id) => new_id,
+          None if self.pruners.iter().any(|p| p.should_prune(&new_state, self.dims)) => continue,
+          None if self.limits.max_states.is_some_and(|max| self.graph.len() >= max) => {
+            self.truncated = Some(Limit::States);
             continue;
+          },
+          None => {
+            let new_id = self.graph.insert_state_with_unchecked(new_state, &new_tractors);
+            self.queue.push_back(new_id);
+            new_id
           }
-          shortest.insert(&next, *neighbor);
-          queue.push_back(*neighbor);
-        }
+        };
+        self.graph.connect_transformed(&id, new_id, push, transform);
       }
     }
-    shortest
+    self.report();
+    self.elapsed += started.elapsed();
+    self.started = None;
+    self.is_done()
   }
-  // Graph builder methods
-  fn set_root(&mut self, state: Vec<Cell>) {
-    assert!(self.state_to_id.len() == 0);
-    self.insert_state(state);
-  }
-  pub fn insert_state(&mut self, state: Vec<Cell>) -> usize {
-    assert!(!self.state_to_id.contains_key(&state));
-    let id = self.state_to_id.len();
-    self.state_to_id.insert(state.clone(), id);
-    self.id_to_state.insert(id, state);
-    self.neighbors.insert(id, vec![]);
-    id
+  pub fn finish(mut self) -> StateGraph {
+    self.explore(usize::MAX);
+    self.graph
   }
-  // `to` state can be reached from `from` state
-  pub fn connect_states(&mut self, from: &Vec<Cell>, to: &Vec<Cell>) {
-    let from_id = self.state_to_id.get(from).cloned().unwrap();
-    let to_id = self.state_to_id.get(to).cloned().unwrap();
-    if let Some(from_neighbors) = self.neighbors.get_mut(&from_id) {
-      from_neighbors.push(to_id);
+  // Like `finish`, saying whether a limit stopped it
+  pub fn finish_within(mut self) -> Explored {
+    self.explore(usize::MAX);
+    match self.truncated {
+      Some(limit) => Explored::Truncated(self.graph, limit),
+      None => Explored::Complete(self.graph),
     }
   }
-  pub fn len(&self) -> usize {
-    self.state_to_id.len()
+}
+
+// Several tractors are only explored by the default rules, the pulls of
+// each worked out by `next_pulled_states_multi`
+fn check_rules(rules: &dyn Rules, tractors: &[usize]) -> Result<(), Error> {
+  if tractors.len() > 1 && rules.standard() != Some(StandardRules::default()) {
+    return Err(Error::RulesNeedOneTractor);
   }
+  Ok(())
 }
 
-pub fn find_solvable_states(tractor: usize, mut grid: Vec<Cell>, size: usize) -> StateGraph {
-  grid[tractor] = Cell::Unreachable;
-  fill_reachable_cells(tractor, &mut grid, size);
-  walk_states_graph_from(grid, size)
+fn save_rules<S: Serializer>(rules: &Arc<dyn Rules>, serializer: S) -> Result<S::Ok, S::Error> {
+  rules.standard().unwrap_or_default().serialize(serializer)
 }
 
-#[derive(Copy, Clone, Debug, PartialEq)]
-enum Direction {
-  Up,
-  Down,
-  Left,
-  Right,
-}
-
-static DIRECTIONS: &[Direction] = &[Direction::Up, Direction::Down, Direction::Left, Direction::Right];
-
-fn move_one(idx: usize, dir: Direction, board_size: usize) -> Option<usize> {
-  let row = idx / board_size;
-  let col = idx % board_size;
-  match dir {
-    Direction::Up => {
-      if row == 0 {
-        None
-      } else {
-        Some(to_index(row - 1, col, board_size))
-      }
+fn load_rules<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Arc<dyn Rules>, D::Error> {
+  Ok(Arc::new(StandardRules::deserialize(deserializer)?))
+}
+
+// Continues exploring from a checkpoint written by `ExplorationSession::save`
+pub fn resume(checkpoint: &Path) -> io::Result<StateGraph> {
+  Ok(ExplorationSession::load(checkpoint)?.finish())
+}
+
+// Like `next_pulled_states`, plus every state a move allowed by `rules` undoes.
+// An undone pull is recorded as the push that undoes it.
+pub fn next_pulled_states_with(state: &[Cell], rules: &StandardRules, dims: Dims) -> Vec<(Vec<Cell>, Push)> {
+  pulled_states(state, None, rules, dims).into_iter().map(|(state, push, _)| (state, push)).collect()
+}
+
+// Every state one pull away, each boulder dragged one cell towards the tractor,
+// or one crossing of collapsed floor undone
+pub fn next_pulled_states(state: &[Cell], dims: Dims) -> Vec<(Vec<Cell>, Push)> {
+  next_pulled_states_with(state, &StandardRules::default(), dims)
+}
+
+// Also the Zobrist key of each state where `extend_state` works it out from
+// `key`, that of `state` less its tractor's word
+fn pulled_states(state: &[Cell], key: Option<u64>, rules: &StandardRules, dims: Dims) -> Vec<(Vec<Cell>, Push, Option<u64>)> {
+  let mut next = vec![];
+  for (idx, cell) in state.iter().enumerate() {
+    if *cell == Cell::Collapsed {
+      next.extend(DIRECTIONS.iter().filter_map(|dir| undo_crossing(idx, *dir, state, dims)).map(|(state, push)| (state, push, None)));
     }
-    Direction::Down => {
-      if row >= board_size - 1 {
-        None
-      } else {
-        Some(to_index(row + 1, col, board_size))
-      }
+    if !cell.has_boulder() {
+      continue;
     }
-    Direction::Left => {
-      if col == 0 {
-        None
-      } else {
-        Some(to_index(row, col - 1, board_size))
-      }
+    for dir in DIRECTIONS {
+      next.extend(extend_state(idx, *dir, state, key, dims));
     }
-    Direction::Right => {
-      if col >= board_size - 1 {
-        None
-      } else {
-        Some(to_index(row, col + 1, board_size))
-      }
+  }
+  if rules.pulls {
+    for boulder in 0..state.len() {
+      next.extend(DIRECTIONS.iter().filter_map(|dir| undo_pull(boulder, *dir, state, dims)).map(|(state, push)| (state, push, None)));
     }
   }
+  next
 }
 
-fn extend_state(boulder: usize, dir: Direction, grid: &Vec<Cell>, size: usize) -> Option<Vec<Cell>> {
-  assert!(grid[boulder] == Cell::Boulder || grid[boulder] == Cell::BoulderInHole);
-  if let Some(new_boulder) = move_one(boulder, dir, size) {
-    if grid[new_boulder] != Cell::Reachable {
-      return None;
+// Every state one pull away when several tractors share the board, with the
+// smallest cell of each tractor's region after it. Any tractor can pull, and
+// one sharing a region with the puller may be left in any part of it the
+// pulled boulder cuts off.
+pub fn next_pulled_states_multi(state: &[Cell], tractors: &[usize], dims: Dims) -> Vec<(Vec<Cell>, Vec<usize>, Push)> {
+  let floor = state.iter()
+    .map(|cell| if *cell == Cell::Reachable { Cell::Unreachable } else { *cell })
+    .collect::<Vec<Cell>>();
+  let regions = tractors.iter()
+    .map(|tractor| find_reachable_empty_cells_unchecked(*tractor, &floor, dims))
+    .collect::<Vec<HashSet<usize>>>();
+  let mut next = vec![];
+  for (puller, region) in regions.iter().enumerate() {
+    // Tractors sharing a region pull the same way
+    if tractors[..puller].contains(&tractors[puller]) {
+      continue;
     }
-    if let Some(new_tractor) = move_one(new_boulder, dir, size) {
-      if grid[new_tractor] != Cell::Reachable {
-        return None;
+    for (boulder, cell) in state.iter().enumerate() {
+      if !cell.has_boulder() {
+        continue;
       }
-      let mut new_grid = grid.clone();
-      if new_grid[boulder] == Cell::Boulder {
-        new_grid[boulder] = Cell::Unreachable;
-      } else if new_grid[boulder] == Cell::BoulderInHole {
-        new_grid[boulder] = Cell::Hole;
-      }
-      new_grid[new_boulder] = Cell::Boulder;
-      for cell in &mut new_grid {
-        if *cell == Cell::Reachable {
-           *cell = Cell::Unreachable;
+      for dir in DIRECTIONS {
+        let new_boulder = match step(boulder, *dir, state, dims).filter(|idx| region.contains(idx)) {
+          Some(new_boulder) => new_boulder,
+          None => continue,
+        };
+        let new_tractor = match step(new_boulder, *dir, state, dims).filter(|idx| region.contains(idx)) {
+          Some(new_tractor) => new_tractor,
+          None => continue,
+        };
+        let run_up = std::iter::successors(Some(new_tractor), |idx| step(*idx, *dir, state, dims))
+          .skip(1)
+          .take_while(|idx| region.contains(idx))
+          .take(RUN_UP)
+          .count();
+        if cell.is_heavy() && run_up < RUN_UP {
+          continue;
+        }
+        let mut new_floor = floor.clone();
+        new_floor[boulder] = cell.without_boulder();
+        new_floor[new_boulder] = new_floor[new_boulder].with_boulder_like(*cell);
+        // Where each tractor may end up, the puller first
+        let mut choices = vec![vec![region_min(new_tractor, &new_floor, dims)]];
+        for (other, other_region) in regions.iter().enumerate() {
+          if other == puller {
+            continue;
+          }
+          if tractors[other] == tractors[puller] {
+            choices.push(split_region(other_region, new_boulder, &new_floor, dims));
+          } else {
+            choices.push(vec![region_min(tractors[other], &new_floor, dims)]);
+          }
+        }
+        let mut placements = vec![vec![]];
+        for options in &choices {
+          placements = placements.iter()
+            .flat_map(|placed| options.iter().map(move |option| {
+              let mut placed = placed.clone();
+              placed.push(*option);
+              placed
+            }))
+            .collect();
+        }
+        for placed in &mut placements {
+          placed.sort();
+        }
+        placements.sort();
+        placements.dedup();
+        for placed in placements {
+          let mut new_grid = new_floor.clone();
+          for tractor in &placed {
+            if new_grid[*tractor] != Cell::Reachable {
+              fill_reachable_cells_unchecked(*tractor, &mut new_grid, dims);
+            }
+          }
+          next.push((new_grid, placed, Push { boulder, dir: *dir, tractor: new_tractor }));
         }
       }
-      fill_reachable_cells(new_tractor, &mut new_grid, size);
-      return Some(new_grid);
     }
   }
-  None
+  next
+}
+
+fn region_min(from: usize, floor: &[Cell], dims: Dims) -> usize {
+  find_reachable_empty_cells_unchecked(from, floor, dims).into_iter().min().unwrap()
+}
+
+// The smallest cell of each part `region` falls into on `floor`, leaving out
+// `taken`
+fn split_region(region: &HashSet<usize>, taken: usize, floor: &[Cell], dims: Dims) -> Vec<usize> {
+  let mut covered = HashSet::new();
+  let mut parts = vec![];
+  for idx in region.iter().filter(|idx| **idx != taken) {
+    if covered.contains(idx) {
+      continue;
+    }
+    let part = find_reachable_empty_cells_unchecked(*idx, floor, dims);
+    parts.push(*part.iter().min().unwrap());
+    covered.extend(part);
+  }
+  parts
+}
+
+// Playing forwards undoes `extend_state`: the tractor pushes a loose boulder
+// ahead of it onto floor or into a hole, where it stays. Pushed onto ice it
+// slides on until it reaches floor, drops into a hole, or is stopped.
+fn push_state(boulder: usize, dir: Direction, grid: &[Cell], dims: Dims) -> Option<(Vec<Cell>, Push)> {
+  assert!(grid[boulder].is_loose_boulder());
+  let from = step(boulder, dir.opposite(), grid, dims)?;
+  if grid[from] != Cell::Reachable || !grid[boulder].allows(dir) {
+    return None;
+  }
+  if grid[boulder].is_heavy() && !has_run_up(from, dir, grid, dims) {
+    return None;
+  }
+  let moved = grid[boulder];
+  let mut new_boulder = step(boulder, dir, grid, dims).filter(|idx| slides_into(grid[*idx], dir, moved))?;
+  while grid[new_boulder] == Cell::Ice {
+    match step(new_boulder, dir, grid, dims).filter(|idx| slides_into(grid[*idx], dir, moved)) {
+      Some(next) => new_boulder = next,
+      None => break,
+    }
+  }
+  let mut new_grid = grid.to_vec();
+  new_grid[new_boulder] = grid[new_boulder].with_boulder_like(moved);
+  new_grid[boulder] = grid[boulder].without_boulder();
+  for cell in &mut new_grid {
+    if *cell == Cell::Reachable {
+       *cell = Cell::Unreachable;
+    }
+  }
+  fill_reachable_cells_unchecked(boulder, &mut new_grid, dims);
+  Some((new_grid, Push { boulder, dir, tractor: boulder }))
 }
 
-fn walk_states_graph_from(initial_state: Vec<Cell>, size: usize) -> StateGraph {
-  let mut found = StateGraph::new(initial_state.clone());
-  handle_next_state(initial_state, size, &mut found);
-  found
+// The tractor steps onto cracked floor next to it and straight off the far
+// side, which caves it in
+fn cross(cracked: usize, dir: Direction, grid: &[Cell], dims: Dims) -> Option<(Vec<Cell>, Push)> {
+  step(cracked, dir.opposite(), grid, dims).filter(|idx| grid[*idx] == Cell::Reachable)?;
+  let to = step(cracked, dir, grid, dims)
+    .filter(|idx| grid[*idx] == Cell::Unreachable || grid[*idx] == Cell::Reachable)?;
+  let mut new_grid = grid.iter()
+    .map(|cell| if *cell == Cell::Reachable { Cell::Unreachable } else { *cell })
+    .collect::<Vec<Cell>>();
+  new_grid[cracked] = Cell::Collapsed;
+  fill_reachable_cells_unchecked(to, &mut new_grid, dims);
+  Some((new_grid, Push { boulder: cracked, dir, tractor: to }))
 }
 
-// Assumes state is already in found
-fn handle_next_state(state: Vec<Cell>, size: usize, found: &mut StateGraph) {
+// Every state one push or crossing away
+pub fn next_pushed_states(state: &[Cell], dims: Dims) -> Vec<(Vec<Cell>, Push)> {
+  let mut next = vec![];
   for (idx, cell) in state.iter().enumerate() {
-    if cell != &Cell::Boulder && cell != &Cell::BoulderInHole {
+    if *cell == Cell::Cracked {
+      next.extend(DIRECTIONS.iter().filter_map(|dir| cross(idx, *dir, state, dims)));
+    }
+    if !cell.is_loose_boulder() {
       continue;
     }
     for dir in DIRECTIONS {
-      if let Some(new_state) = extend_state(idx, *dir, &state, size) {
-        if found.contains_state(&new_state) {
-          found.connect_states(&state, &new_state);
-          continue;
-        }
-        found.insert_state(new_state.clone());
-        found.connect_states(&state, &new_state);
-        handle_next_state(new_state, size, found);
+      if let Some(next_state) = push_state(idx, *dir, state, dims) {
+        next.push(next_state);
       }
     }
   }
+  next
 }
 
 #[cfg(test)]
 mod test {
   use super::*;
+  use crate::cell::Color;
+  use crate::storage::DiskStore;
 
   #[test]
   fn test_search() {
@@ -199,115 +1728,779 @@ mod test {
       Cell::Unreachable, Cell::Unreachable, Cell::Unreachable, Cell::Unreachable,
       Cell::BoulderInHole, Cell::Unreachable, Cell::Unreachable, Cell::BoulderInHole,
     ];
-    find_solvable_states(8, grid, 4);
+    find_solvable_states(&Level::new(grid, Dims::square(4), 8));
   }
 
   #[test]
-  fn test_walk_movement_graph() {
+  fn test_edge_pushes() {
     let grid = vec![
-      Cell::Hole, Cell::Unreachable, Cell::Unreachable, Cell::Hole,
-      Cell::Unreachable, Cell::Boulder, Cell::Unreachable, Cell::Boulder,
-      Cell::Boulder, Cell::Unreachable, Cell::Unreachable, Cell::Unreachable,
-      Cell::Hole, Cell::Unreachable, Cell::Boulder, Cell::Hole,
+      Cell::BoulderInHole, Cell::Unreachable, Cell::Unreachable,
+      Cell::Block, Cell::Block, Cell::Unreachable,
+      Cell::Block, Cell::Block, Cell::Unreachable,
     ];
-    let graph = grid_to_movement_graph(&grid, 4);
-    let reachable = walk_graph_from(1, &graph);
-    assert_eq!(reachable.len(), 7);
-    assert!(reachable.contains(&1));
-    assert!(reachable.contains(&2));
-    assert!(reachable.contains(&6));
-    assert!(reachable.contains(&9));
-    assert!(reachable.contains(&10));
-    assert!(reachable.contains(&11));
-    assert!(reachable.contains(&13));
+    let graph = find_solvable_states(&Level::new(grid, Dims::square(3), 8));
+    let root = graph.root();
+    let edges = graph.edges_from(&root).collect::<Vec<_>>();
+    assert_eq!(edges.len(), 1);
+    let (to, push) = edges[0];
+    assert_eq!(push, &Push { boulder: 0, dir: Direction::Right, tractor: 2 });
+    assert_eq!(graph.get_edge(&root, &to), Some(push));
+    assert_eq!(graph.get_state(&to).unwrap()[1], Cell::Boulder);
   }
 
   #[test]
-  fn test_walk_movement_graph_2() {
+  fn test_misuse_errors() {
+    let root = vec![Cell::Boulder, Cell::Reachable, Cell::Reachable];
+    let mut graph = StateGraph::new(Grid::new(root.clone(), Dims::new(1, 3)).unwrap());
+    assert_eq!(graph.insert_state(root.clone()), Err(Error::DuplicateState(graph.root())));
+    let other = vec![Cell::Reachable, Cell::Boulder, Cell::Reachable];
+    let push = Push { boulder: 0, dir: Direction::Right, tractor: 1 };
+    assert_eq!(graph.connect_states(&root, &other, push), Err(Error::UnknownState));
+    graph.insert_state(other.clone()).unwrap();
+    assert_eq!(graph.connect_states(&root, &other, push), Ok(()));
+    assert_eq!(graph.out_degree(&graph.root()), 1);
+    assert_eq!(graph.neighbors(&graph.root()).collect::<Vec<StateId>>(), vec![StateId::new(1)]);
+    assert_eq!(graph.neighbors(&StateId::new(2)).count(), 0);
+    assert_eq!(graph.out_degree(&StateId::new(2)), 0);
+  }
+
+  #[test]
+  fn test_rectangular_pulls() {
+    // Two rows of four: pulls mustn't wrap from the end of one row onto the
+    // start of the next
+    let dims = Dims::new(2, 4);
+    let grid = vec![
+      Cell::Unreachable, Cell::Unreachable, Cell::Unreachable, Cell::BoulderInHole,
+      Cell::Block, Cell::Block, Cell::Block, Cell::Unreachable,
+    ];
+    let graph = find_solvable_states(&Level::new(grid, dims, 0));
+    assert_eq!(graph.dims(), dims);
+    assert_eq!(graph.len(), 3);
+    let root = graph.root();
+    let deepest = *graph.build_shortest_path_from(&root).build_dist().last().unwrap().first().unwrap();
+    assert_eq!(graph.get_state(&deepest).unwrap()[1], Cell::Boulder);
+  }
+
+  #[test]
+  fn test_walls_survive_exploration() {
+    let grid = vec![
+      Cell::BoulderInHole, Cell::Unreachable, Cell::Unreachable,
+      Cell::Wall, Cell::Wall, Cell::Unreachable,
+      Cell::Wall, Cell::Wall, Cell::Unreachable,
+    ];
+    let graph = find_solvable_states(&Level::new(grid, Dims::square(3), 8));
+    assert_eq!(graph.len(), 2);
+    for (_, state) in graph.states() {
+      assert_eq!(state[3], Cell::Wall);
+      assert_eq!(state[4], Cell::Wall);
+    }
+  }
+
+  #[test]
+  fn test_multi_tractor_separate_rooms() {
+    // A wall down the middle, each tractor has its own boulder
+    let grid = vec![
+      Cell::BoulderInHole, Cell::Unreachable, Cell::Wall, Cell::Unreachable, Cell::BoulderInHole,
+      Cell::Unreachable, Cell::Unreachable, Cell::Wall, Cell::Unreachable, Cell::Unreachable,
+      Cell::Unreachable, Cell::Unreachable, Cell::Wall, Cell::Unreachable, Cell::Unreachable,
+    ];
+    let dims = Dims::new(3, 5);
+    let left = find_solvable_states(&Level::new(grid.clone(), dims, 6));
+    let right = find_solvable_states(&Level::new(grid.clone(), dims, 8));
+    let both = find_solvable_states_multi(&[6, 8], Grid::new(grid.clone(), dims).unwrap());
+    assert_eq!(both.len(), left.len() * right.len());
+    for (id, _) in both.states() {
+      assert_eq!(both.get_tractors(&id).unwrap().len(), 2);
+    }
+    // Other rules would be left unused
+    let grid = Grid::new(grid, dims).unwrap();
+    let options = ExploreOptions { rules: Arc::new(StandardRules { pulls: true }), ..Default::default() };
+    assert_eq!(ExplorationSession::with_tractors(grid.clone(), &[6, 8], &options).err(), Some(Error::RulesNeedOneTractor));
+    let mut session = ExplorationSession::with_tractors(grid, &[6, 8], &ExploreOptions::default()).unwrap();
+    assert_eq!(session.set_rules(options.rules), Err(Error::RulesNeedOneTractor));
+  }
+
+  #[test]
+  fn test_multi_tractor_split_region() {
+    // Pulling the boulder up cuts the shared region in three, and the tractor
+    // that didn't pull may be left in any of them
+    let grid = vec![
+      Cell::Wall, Cell::Unreachable, Cell::Wall,
+      Cell::Unreachable, Cell::Unreachable, Cell::Unreachable,
+      Cell::Wall, Cell::BoulderInHole, Cell::Wall,
+    ];
+    let dims = Dims::square(3);
+    assert_eq!(find_solvable_states(&Level::new(grid.clone(), dims, 1)).len(), 2);
+    let graph = find_solvable_states_multi(&[1, 3], Grid::new(grid, dims).unwrap());
+    assert_eq!(graph.len(), 4);
+    assert_eq!(graph.get_tractors(&graph.root()), Some(vec![1, 1]));
+    let mut placements = graph.states()
+      .filter(|(id, _)| *id != graph.root())
+      .map(|(id, _)| graph.get_tractors(&id).unwrap())
+      .collect::<Vec<Vec<usize>>>();
+    placements.sort();
+    assert_eq!(placements, vec![vec![1, 1], vec![1, 3], vec![1, 5]]);
+  }
+
+  #[test]
+  fn test_pulls_across_ice() {
+    // Pushed from 0 or 1 the boulder slides straight into the hole at 5
     let grid = vec![
-      Cell::Hole, Cell::Unreachable, Cell::Unreachable, Cell::BoulderInHole,
-      Cell::Boulder, Cell::Unreachable, Cell::Unreachable, Cell::Unreachable,
+      Cell::Unreachable, Cell::Unreachable, Cell::Ice, Cell::Ice, Cell::Ice, Cell::BoulderInHole,
+    ];
+    let graph = find_solvable_states(&Level::new(grid, Dims::new(1, 6), 0));
+    assert_eq!(graph.len(), 3);
+    let root = graph.root();
+    let mut origins = graph.edges_from(&root)
+      .map(|(to, push)| (push.tractor, graph.get_state(&to).unwrap()))
+      .collect::<Vec<_>>();
+    origins.sort_by_key(|(tractor, _)| *tractor);
+    assert_eq!(origins[0].0, 0);
+    assert_eq!(origins[0].1[1], Cell::Boulder);
+    assert_eq!(origins[1].0, 1);
+    assert_eq!(origins[1].1[2], Cell::BoulderOnIce);
+    for (state, push) in next_pushed_states(&origins[1].1, Dims::new(1, 6)) {
+      assert_eq!(push.boulder, 2);
+      assert!(is_goal_state(&state));
+    }
+  }
+
+  #[test]
+  fn test_one_way_pushes() {
+    let dims = Dims::new(1, 5);
+    let mut grid = vec![
+      Cell::Unreachable, Cell::Boulder, Cell::OneWay(Direction::Right), Cell::Unreachable, Cell::Hole,
+    ];
+    fill_reachable_cells_unchecked(0, &mut grid, dims);
+    let (next, _) = push_state(1, Direction::Right, &grid, dims).unwrap();
+    assert_eq!(next[2], Cell::BoulderOnOneWay(Direction::Right));
+    assert_eq!(push_state(2, Direction::Left, &next, dims), None);
+    let (next, _) = push_state(2, Direction::Right, &next, dims).unwrap();
+    assert_eq!(next[2], Cell::OneWay(Direction::Right));
+    grid[2] = Cell::OneWay(Direction::Left);
+    assert_eq!(push_state(1, Direction::Right, &grid, dims), None);
+  }
+
+  #[test]
+  fn test_zobrist_keys() {
+    let dims = Dims::square(4);
+    let mut grid = vec![
+      Cell::BoulderInHole, Cell::Unreachable, Cell::Unreachable, Cell::HeavyBoulderInHole,
+      Cell::Unreachable, Cell::Unreachable, Cell::Block, Cell::Unreachable,
       Cell::Unreachable, Cell::Unreachable, Cell::Unreachable, Cell::Unreachable,
+      Cell::Boulder, Cell::Unreachable, Cell::Unreachable, Cell::BoulderInHole,
+    ];
+    fill_reachable_cells_unchecked(5, &mut grid, dims);
+    let layout = Layout::from_state(&grid, dims);
+    let packed = layout.encode(&grid);
+    let key = packed.zobrist() ^ zobrist::tractor_key(packed.tractors()[0], 0);
+    let mut count = 0;
+    for boulder in (0..grid.len()).filter(|idx| grid[*idx].has_boulder()) {
+      for dir in DIRECTIONS {
+        for (next, _, next_key) in extend_state(boulder, *dir, &grid, Some(key), dims) {
+          assert_eq!(next_key, Some(layout.encode(&next).zobrist()));
+          count += 1;
+        }
+      }
+    }
+    assert!(count > 0);
+  }
+
+  #[test]
+  fn test_disk_store() {
+    let grid = vec![
       Cell::BoulderInHole, Cell::Unreachable, Cell::Unreachable, Cell::BoulderInHole,
+      Cell::Unreachable, Cell::Unreachable, Cell::Block, Cell::Unreachable,
+      Cell::Unreachable, Cell::Unreachable, Cell::Unreachable, Cell::Unreachable,
+      Cell::BoulderInHole, Cell::Unreachable, Cell::Unreachable, Cell::Unreachable,
+    ];
+    let store = DiskStore::in_dir(&std::env::temp_dir()).unwrap();
+    let path = store.path().to_path_buf();
+    let on_disk = find_solvable_states_in(&Level::new(grid.clone(), Dims::square(4), 9), &ExploreOptions::default(), Box::new(store)).unwrap();
+    let in_memory = find_solvable_states(&Level::new(grid, Dims::square(4), 9));
+    assert!(on_disk.len() > 1);
+    assert!(on_disk.to_bin() == in_memory.to_bin());
+    drop(on_disk);
+    assert!(!path.exists());
+  }
+
+  #[test]
+  fn test_one_way_pulls() {
+    let dims = Dims::new(1, 4);
+    let mut grid = vec![
+      Cell::Unreachable, Cell::Unreachable, Cell::BoulderOnOneWay(Direction::Right), Cell::Hole,
+    ];
+    fill_reachable_cells_unchecked(0, &mut grid, dims);
+    let pulls = extend_state(2, Direction::Left, &grid, None, dims);
+    assert_eq!(pulls.len(), 1);
+    assert_eq!(pulls[0].0[1], Cell::Boulder);
+    assert_eq!(pulls[0].0[2], Cell::OneWay(Direction::Right));
+    // It could never have been pushed onto the cell against its way
+    grid[2] = Cell::BoulderOnOneWay(Direction::Left);
+    assert!(extend_state(2, Direction::Left, &grid, None, dims).is_empty());
+  }
+
+  #[test]
+  fn test_colored_pushes() {
+    let dims = Dims::new(1, 4);
+    let mut grid = vec![
+      Cell::Unreachable, Cell::ColoredBoulder(Color::Red), Cell::ColoredHole(Color::Green), Cell::ColoredHole(Color::Red),
+    ];
+    fill_reachable_cells_unchecked(0, &mut grid, dims);
+    assert_eq!(push_state(1, Direction::Right, &grid, dims), None);
+    grid.swap(2, 3);
+    let (next, _) = push_state(1, Direction::Right, &grid, dims).unwrap();
+    assert_eq!(next[2], Cell::ColoredBoulderInHole(Color::Red));
+    assert!(!is_goal_state(&next));
+    let pulls = extend_state(2, Direction::Left, &next, None, dims);
+    assert_eq!(pulls.len(), 1);
+    assert_eq!(pulls[0].0[1], Cell::ColoredBoulder(Color::Red));
+  }
+
+  #[test]
+  fn test_pull_rule() {
+    // Boulders are never pushed into a corner off their hole, but one can be
+    // pulled back out of it
+    let mut grid = vec![Cell::Unreachable; 16];
+    grid[5] = Cell::BoulderInHole;
+    let pushes_only = find_solvable_states(&Level::new(grid.clone(), Dims::square(4), 0));
+    let options = ExploreOptions { rules: Arc::new(StandardRules { pulls: true }), ..Default::default() };
+    let with_pulls = find_solvable_states_with(&Level::new(grid, Dims::square(4), 0), &options);
+    assert!(with_pulls.len() > pushes_only.len());
+    let cornered = |state: &Grid| state[3] == Cell::Boulder;
+    assert!(!pushes_only.states().any(|(_, state)| cornered(&state)));
+    let (corner, _) = with_pulls.states().find(|(_, state)| cornered(state)).unwrap();
+    let undone = with_pulls.states()
+      .flat_map(|(id, _)| with_pulls.edges_from(&id).map(|(to, push)| (to, *push)).collect::<Vec<_>>())
+      .find(|(to, _)| *to == corner)
+      .unwrap();
+    assert_eq!(undone.1.tractor, undone.1.boulder);
+  }
+
+  #[test]
+  fn test_custom_rules() {
+    // The standard rules less any push upwards
+    struct NoPushingUp;
+    impl Rules for NoPushingUp {
+      fn successors(&self, state: &[Cell], dims: Dims) -> Vec<(Vec<Cell>, Push)> {
+        StandardRules::default().successors(state, dims).into_iter().filter(|(_, push)| push.dir != Direction::Up).collect()
+      }
+    }
+    let mut grid = vec![Cell::Unreachable; 16];
+    grid[5] = Cell::BoulderInHole;
+    let level = Level::new(grid, Dims::square(4), 0);
+    let full = find_solvable_states(&level);
+    let options = ExploreOptions { rules: Arc::new(NoPushingUp), ..Default::default() };
+    let limited = find_solvable_states_with(&level, &options);
+    assert!(limited.edges().count() < full.edges().count());
+    assert!(limited.states().all(|(id, _)| limited.edges_from(&id).all(|(_, push)| push.dir != Direction::Up)));
+    assert!(full.states().any(|(id, _)| full.edges_from(&id).any(|(_, push)| push.dir == Direction::Up)));
+  }
+
+  #[test]
+  fn test_pulls_through_portals() {
+    // The block splits the row, and only the portals get the boulder out
+    let mut grid = vec![
+      Cell::Unreachable, Cell::Unreachable, Cell::Portal(0), Cell::Block, Cell::Portal(0), Cell::Unreachable, Cell::BoulderInHole,
+    ];
+    let graph = find_solvable_states(&Level::new(grid.clone(), Dims::new(1, 7), 0));
+    assert_eq!(graph.len(), 3);
+    assert!(graph.states().any(|(_, state)| state[1] == Cell::Boulder));
+    grid[2] = Cell::Block;
+    grid[4] = Cell::Block;
+    assert_eq!(find_solvable_states(&Level::new(grid, Dims::new(1, 7), 0)).len(), 1);
+  }
+
+  #[test]
+  fn test_undo_crossing() {
+    // Once the floor at 1 has caved in, the tractor has to have come over it
+    let grid = vec![
+      Cell::Unreachable, Cell::Collapsed, Cell::Unreachable, Cell::Unreachable, Cell::BoulderInHole,
+    ];
+    let graph = find_solvable_states(&Level::new(grid, Dims::new(1, 5), 2));
+    let (_, start) = graph.states().find(|(_, state)| state[1] == Cell::Cracked && state[3] == Cell::Boulder).unwrap();
+    assert_eq!(start[0], Cell::Reachable);
+    let crossed = next_pushed_states(&start, Dims::new(1, 5));
+    assert_eq!(crossed.len(), 1);
+    assert!(crossed[0].1.is_crossing(&start));
+    assert_eq!(crossed[0].0[1], Cell::Collapsed);
+  }
+
+  #[test]
+  fn test_heavy_run_up() {
+    let dims = Dims::new(1, 6);
+    let mut grid = vec![
+      Cell::Unreachable, Cell::Unreachable, Cell::Unreachable, Cell::HeavyBoulder, Cell::Unreachable, Cell::Hole,
     ];
-    let graph = grid_to_movement_graph(&grid, 4);
-    let reachable = walk_graph_from(8, &graph);
-    assert_eq!(reachable.len(), 11);
-    assert!(reachable.contains(&1));
-    assert!(reachable.contains(&2));
-    assert!(reachable.contains(&5));
-    assert!(reachable.contains(&6));
-    assert!(reachable.contains(&7));
-    assert!(reachable.contains(&8));
-    assert!(reachable.contains(&9));
-    assert!(reachable.contains(&10));
-    assert!(reachable.contains(&11));
-    assert!(reachable.contains(&13));
-    assert!(reachable.contains(&14));
+    fill_reachable_cells_unchecked(0, &mut grid, dims);
+    let (next, _) = push_state(3, Direction::Right, &grid, dims).unwrap();
+    assert_eq!(next[4], Cell::HeavyBoulder);
+    let (next, _) = push_state(4, Direction::Right, &next, dims).unwrap();
+    assert_eq!(next[5], Cell::HeavyBoulderInHole);
+    // Two cells from the edge there's no room to get going
+    let mut short = vec![Cell::Unreachable, Cell::Unreachable, Cell::HeavyBoulder, Cell::Unreachable, Cell::Hole];
+    fill_reachable_cells_unchecked(0, &mut short, Dims::new(1, 5));
+    assert_eq!(push_state(2, Direction::Right, &short, Dims::new(1, 5)), None);
   }
 
   #[test]
-  fn test_build_movement_graph() {
+  fn test_heavy_pulls() {
+    let mut grid = vec![Cell::Unreachable; 5];
+    grid[4] = Cell::BoulderInHole;
+    assert_eq!(find_solvable_states(&Level::new(grid.clone(), Dims::new(1, 5), 0)).len(), 4);
+    grid[4] = Cell::HeavyBoulderInHole;
+    let graph = find_solvable_states(&Level::new(grid, Dims::new(1, 5), 0));
+    assert_eq!(graph.len(), 2);
+    assert!(graph.states().any(|(_, state)| state[3] == Cell::HeavyBoulder));
+  }
+
+  #[test]
+  fn test_shortest_path_to() {
     let grid = vec![
-      Cell::Hole, Cell::Unreachable, Cell::Unreachable, Cell::Hole,
-      Cell::Unreachable, Cell::Boulder, Cell::Unreachable, Cell::Boulder,
-      Cell::Boulder, Cell::Unreachable, Cell::Unreachable, Cell::Unreachable,
-      Cell::Hole, Cell::Unreachable, Cell::Boulder, Cell::Hole,
+      Cell::BoulderInHole, Cell::Unreachable, Cell::Unreachable, Cell::Unreachable,
+      Cell::Block, Cell::Block, Cell::Block, Cell::Unreachable,
+      Cell::Block, Cell::Block, Cell::Block, Cell::Unreachable,
+      Cell::Block, Cell::Block, Cell::Block, Cell::Unreachable,
     ];
+    let graph = find_solvable_states(&Level::new(grid, Dims::square(4), 15));
+    let root = graph.root();
+    let forward = graph.build_shortest_path_from(&root);
+    let deepest = *forward.build_dist().last().unwrap().first().unwrap();
+    let backward = graph.build_shortest_path_to(&deepest);
+    let mut path = backward.path(&root).unwrap();
+    assert_eq!(path.first(), Some(&root));
+    assert_eq!(path.last(), Some(&deepest));
+    path.reverse();
+    assert_eq!(Some(path), forward.path(&deepest));
+    for id in forward.path(&deepest).unwrap().windows(2) {
+      assert!(graph.get_predecessors(&id[0]).unwrap().contains(&id[1]));
+    }
+  }
 
-    let graph = grid_to_movement_graph(&grid, 4);
-    assert_eq!(graph.len(), 8);
+  #[test]
+  fn test_resume_session() {
+    let grid = vec![
+      Cell::BoulderInHole, Cell::Unreachable, Cell::Unreachable, Cell::BoulderInHole,
+      Cell::Unreachable, Cell::Unreachable, Cell::Unreachable, Cell::Unreachable,
+      Cell::Unreachable, Cell::Reachable, Cell::Unreachable, Cell::Unreachable,
+      Cell::BoulderInHole, Cell::Unreachable, Cell::Unreachable, Cell::BoulderInHole,
+    ];
+    let complete = find_solvable_states(&Level::new(grid.clone(), Dims::square(4), 9));
+    let mut session = ExplorationSession::new(Grid::new(grid.clone(), Dims::square(4)).unwrap());
+    assert!(!session.explore(10));
+    assert!(session.pending() > 0);
+    let checkpoint = std::env::temp_dir().join("lvlgen_test_resume_session");
+    session.save(&checkpoint).unwrap();
+    let resumed = resume(&checkpoint).unwrap();
+    assert_eq!(resumed.len(), complete.len());
+    // Standard rules are kept
+    let options = ExploreOptions { rules: Arc::new(StandardRules { pulls: true }), ..Default::default() };
+    let complete = find_solvable_states_with(&Level::new(grid.clone(), Dims::square(4), 9), &options);
+    let mut session = ExplorationSession::with_options(Grid::new(grid, Dims::square(4)).unwrap(), &options);
+    session.explore(10);
+    session.save(&checkpoint).unwrap();
+    let resumed = resume(&checkpoint).unwrap();
+    std::fs::remove_file(&checkpoint).unwrap();
+    assert_eq!(resumed.len(), complete.len());
+  }
 
-    assert!(!graph.contains_key(&0));
+  #[test]
+  fn test_progress() {
+    let grid = vec![
+      Cell::BoulderInHole, Cell::Unreachable, Cell::Unreachable, Cell::BoulderInHole,
+      Cell::Unreachable, Cell::Unreachable, Cell::Unreachable, Cell::Unreachable,
+      Cell::Unreachable, Cell::Reachable, Cell::Unreachable, Cell::Unreachable,
+      Cell::BoulderInHole, Cell::Unreachable, Cell::Unreachable, Cell::BoulderInHole,
+    ];
+    let reports = Arc::new(std::sync::Mutex::new(vec![]));
+    let sink = reports.clone();
+    let options = ExploreOptions {
+      progress: Some(Arc::new(move |progress: &Progress| sink.lock().unwrap().push(*progress))),
+      ..Default::default()
+    };
+    let mut session = ExplorationSession::with_options(Grid::new(grid, Dims::square(4)).unwrap(), &options);
+    session.explore(10);
+    let graph = session.finish();
+    let reports = reports.lock().unwrap();
+    assert_eq!(reports.len(), 2 + graph.len() / PROGRESS_INTERVAL);
+    assert_eq!(reports[0].expanded, 10);
+    let last = reports.last().unwrap();
+    assert_eq!((last.expanded, last.frontier, last.states), (graph.len(), 0, graph.len()));
+  }
 
-    assert!(graph.contains_key(&1));
-    assert_eq!(graph[&1].len(), 1);
-    assert_eq!(graph[&1][0], 2);
+  #[test]
+  fn test_limits() {
+    let grid = vec![
+      Cell::BoulderInHole, Cell::Unreachable, Cell::Unreachable, Cell::BoulderInHole,
+      Cell::Unreachable, Cell::Unreachable, Cell::Block, Cell::Unreachable,
+      Cell::Unreachable, Cell::Unreachable, Cell::Unreachable, Cell::Unreachable,
+      Cell::BoulderInHole, Cell::Unreachable, Cell::Unreachable, Cell::Unreachable,
+    ];
+    let within = |limits: Limits| {
+      find_solvable_states_within(&Level::new(grid.clone(), Dims::square(4), 9), &ExploreOptions { limits, ..Default::default() })
+    };
+    let full = find_solvable_states(&Level::new(grid.clone(), Dims::square(4), 9));
+    let explored = within(Limits { max_states: Some(full.len()), ..Default::default() });
+    assert!(explored.is_complete());
+    assert_eq!(explored.graph().len(), full.len());
+    let explored = within(Limits { max_states: Some(5), ..Default::default() });
+    assert!(matches!(explored, Explored::Truncated(_, Limit::States)));
+    assert_eq!(explored.graph().len(), 5);
+    let explored = within(Limits { max_depth: Some(1), ..Default::default() });
+    assert!(matches!(explored, Explored::Truncated(_, Limit::Depth)));
+    let graph = explored.into_graph();
+    assert_eq!(graph.len(), 1 + graph.out_degree(&graph.root()));
+    let explored = within(Limits { timeout: Some(Duration::default()), ..Default::default() });
+    assert!(matches!(explored, Explored::Truncated(_, Limit::Timeout)));
+    assert_eq!(explored.graph().len(), 1);
+  }
 
-    assert!(graph.contains_key(&2));
-    assert_eq!(graph[&2].len(), 2);
-    assert_eq!(graph[&2][0], 6);
-    assert_eq!(graph[&2][1], 1);
+  #[test]
+  fn test_find_pushed_states() {
+    let grid = vec![
+      Cell::Unreachable, Cell::Boulder, Cell::Unreachable, Cell::Hole,
+      Cell::Unreachable, Cell::Unreachable, Cell::Unreachable, Cell::Unreachable,
+    ];
+    let graph = find_pushed_states(&Level::new(grid, Dims::new(2, 4), 0));
+    let hint = graph.hint(&graph.root()).unwrap();
+    assert_eq!((hint.push.boulder, hint.push.dir), (1, Direction::Right));
+    let goal = graph.goal_states();
+    assert_eq!(goal.len(), 1);
+    assert_eq!(graph.shortest_path(&graph.root(), &goal[0]).unwrap().len(), 3);
+    assert_eq!(graph.out_degree(&goal[0]), 0);
+  }
 
-    assert!(!graph.contains_key(&3));
+  #[test]
+  fn test_explore_iter() {
+    let grid = vec![
+      Cell::Unreachable, Cell::Unreachable, Cell::Unreachable, Cell::Unreachable, Cell::Unreachable,
+      Cell::Unreachable, Cell::Boulder, Cell::Unreachable, Cell::Unreachable, Cell::Hole,
+      Cell::Unreachable, Cell::Unreachable, Cell::Unreachable, Cell::Unreachable, Cell::Unreachable,
+    ];
+    let level = Level::new(grid, Dims::new(3, 5), 0);
+    let mut states = explore_iter(&level);
+    let root = states.next().unwrap();
+    assert_eq!((root.parent, root.push), (None, None));
+    let goal = states.by_ref().find(|found| is_goal_state(&found.state)).unwrap();
+    assert_eq!(goal.push.unwrap().dir, Direction::Right);
+    assert!(states.graph().len() < find_pushed_states(&level).len());
+    let all = find_pushed_states(&level).len();
+    assert_eq!(find_pushed_states_up_to(&level, all).map(|graph| graph.len()), Some(all));
+    assert!(find_pushed_states_up_to(&level, all - 1).is_none());
+  }
 
-    assert!(graph.contains_key(&4));
-    assert_eq!(graph[&4].len(), 0);
+  #[test]
+  fn test_binary_round_trip() {
+    let grid = vec![
+      Cell::Hole, Cell::Unreachable, Cell::Unreachable, Cell::Unreachable,
+      Cell::Block, Cell::Block, Cell::Boulder, Cell::Unreachable,
+      Cell::Block, Cell::Block, Cell::Unreachable, Cell::Unreachable,
+      Cell::Block, Cell::Block, Cell::Unreachable, Cell::Unreachable,
+    ];
+    let level = Level::new(grid, Dims::square(4), 15);
+    // Pruning the dead state leaves a gap in the ids
+    let mut graph = find_pushed_states(&level);
+    graph.prune_unwinnable(&is_goal_state);
+    let buf = graph.to_bin();
+    assert!(buf.len() < rmp_serde::encode::to_vec(&graph).unwrap().len());
+    let loaded = StateGraph::from_bin(&buf).unwrap();
+    assert_eq!(loaded.len(), graph.len());
+    assert!(!loaded.is_pulled());
+    for (id, state) in graph.states() {
+      assert_eq!(loaded.get_id(&state), Some(id));
+      assert_eq!(loaded.edges_from(&id).collect::<Vec<_>>(), graph.edges_from(&id).collect::<Vec<_>>());
+      let mut predecessors = loaded.get_predecessors(&id).unwrap().clone();
+      predecessors.sort();
+      let mut expected = graph.get_predecessors(&id).unwrap().clone();
+      expected.sort();
+      assert_eq!(predecessors, expected);
+    }
+    assert!(StateGraph::from_bin(&buf[1..]).is_err());
+    let mut newer = buf.clone();
+    newer[BIN_MAGIC.len()] += 1;
+    assert!(StateGraph::from_bin(&newer).is_err());
+    let mut flagged = buf.clone();
+    flagged[BIN_MAGIC.len() + 1] = 2;
+    assert!(StateGraph::from_bin(&flagged).is_err());
+    assert!(StateGraph::from_bin(&buf[..buf.len() - 1]).is_err());
+    let pulled = find_solvable_states(&level);
+    assert!(StateGraph::from_bin(&pulled.to_bin()).unwrap().is_pulled());
+  }
 
-    assert!(!graph.contains_key(&5));
+  #[test]
+  fn test_binary_v1_loads() {
+    let grid = vec![
+      Cell::Boulder, Cell::Unreachable, Cell::Unreachable, Cell::Hole,
+      Cell::Unreachable, Cell::Ice, Cell::Unreachable, Cell::Unreachable,
+      Cell::Unreachable, Cell::Unreachable, Cell::Boulder, Cell::Hole,
+    ];
+    let graph = find_solvable_states(&Level::new(grid, Dims::new(3, 4), 6));
+    let mut v1 = BIN_MAGIC.to_vec();
+    v1.push(1);
+    v1.extend(rmp_serde::encode::to_vec(&graph.to_compact()).unwrap());
+    let loaded = StateGraph::from_bin(&v1).unwrap();
+    assert!(loaded.to_bin() == graph.to_bin());
+    assert!(StateGraph::from_bin(&loaded.to_bin()).unwrap().to_bin() == graph.to_bin());
+  }
 
-    assert!(graph.contains_key(&6));
-    assert_eq!(graph[&6].len(), 2);
-    assert_eq!(graph[&6][0], 2);
-    assert_eq!(graph[&6][1], 10);
+  #[test]
+  fn test_binary_malformed_header() {
+    // An empty one cell graph, with the given next id and count of states
+    let header = |next_id: usize, count: usize| {
+      let mut buf = BIN_MAGIC.to_vec();
+      buf.extend([BIN_VERSION, 0]);
+      write_varint(&mut buf, 1);
+      write_varint(&mut buf, 1);
+      buf.push(Cell::Unreachable.to_char() as u8);
+      write_varint(&mut buf, next_id);
+      write_varint(&mut buf, count);
+      buf.extend([0, 0, 0, 0]);
+      buf
+    };
+    assert!(StateGraph::from_bin(&header(0, 0)).unwrap().is_empty());
+    assert!(StateGraph::from_bin(&header(usize::MAX >> 1, 0)).is_err());
+    assert!(StateGraph::from_bin(&header(0, usize::MAX)).is_err());
+    let grid = vec![Cell::Boulder, Cell::Unreachable, Cell::Hole];
+    let mut compact = find_solvable_states(&Level::new(grid, Dims::new(1, 3), 1)).to_compact();
+    compact.next_id = 1 << 40;
+    let mut v1 = BIN_MAGIC.to_vec();
+    v1.push(1);
+    v1.extend(rmp_serde::encode::to_vec(&compact).unwrap());
+    assert!(StateGraph::from_bin(&v1).is_err());
+  }
 
-    assert!(!graph.contains_key(&7));
+  #[cfg(feature = "compression")]
+  #[test]
+  fn test_compressed_save() {
+    let grid = vec![
+      Cell::BoulderInHole, Cell::Unreachable, Cell::Unreachable,
+      Cell::Unreachable, Cell::Unreachable, Cell::Unreachable,
+      Cell::Unreachable, Cell::Unreachable, Cell::BoulderInHole,
+    ];
+    let graph = find_solvable_states(&Level::new(grid, Dims::square(3), 4));
+    let path = std::env::temp_dir().join("lvlgen_test_compressed_save");
+    graph.save_bin_compressed(&path).unwrap();
+    assert!(compression::is_compressed(&std::fs::read(&path).unwrap()));
+    let loaded = StateGraph::load_bin(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(loaded.len(), graph.len());
+  }
 
-    assert!(!graph.contains_key(&8));
+  #[test]
+  fn test_symmetric_paths_resolve() {
+    let grid = vec![
+      Cell::BoulderInHole, Cell::Unreachable, Cell::Unreachable, Cell::BoulderInHole,
+      Cell::Unreachable, Cell::Unreachable, Cell::Unreachable, Cell::Unreachable,
+      Cell::Unreachable, Cell::Unreachable, Cell::Unreachable, Cell::Unreachable,
+      Cell::BoulderInHole, Cell::Unreachable, Cell::Unreachable, Cell::BoulderInHole,
+    ];
+    let full = find_solvable_states(&Level::new(grid.clone(), Dims::square(4), 5));
+    let options = ExploreOptions { symmetry: true, ..Default::default() };
+    let reduced = find_solvable_states_with(&Level::new(grid, Dims::square(4), 5), &options);
+    assert!(reduced.len() < full.len());
+    let shortest = reduced.build_shortest_path_from(&reduced.root());
+    for idx in 0..reduced.len() {
+      let mut path = shortest.path(&StateId::new(idx)).unwrap();
+      path.reverse();
+      let (states, pushes) = reduced.resolve_path(&path).unwrap();
+      assert_eq!(states.len(), pushes.len() + 1);
+      assert_eq!(full.get_id(&states[0]), Some(full.root()));
+      for (step, push) in states.windows(2).zip(pushes) {
+        let from = full.get_id(&step[0]).unwrap();
+        let to = full.get_id(&step[1]).unwrap();
+        assert_eq!(full.get_edge(&from, &to), Some(&push));
+      }
+    }
+  }
 
-    assert!(graph.contains_key(&9));
-    assert_eq!(graph[&9].len(), 2);
-    assert_eq!(graph[&9][0], 13);
-    assert_eq!(graph[&9][1], 10);
+  #[test]
+  fn test_prune_unwinnable() {
+    let grid = vec![
+      Cell::BoulderInHole, Cell::Unreachable, Cell::Unreachable, Cell::BoulderInHole,
+      Cell::Unreachable, Cell::Unreachable, Cell::Unreachable, Cell::Unreachable,
+      Cell::Unreachable, Cell::Unreachable, Cell::Unreachable, Cell::Unreachable,
+      Cell::BoulderInHole, Cell::Unreachable, Cell::Unreachable, Cell::BoulderInHole,
+    ];
+    // Pulled back from the solved board, every state can be won
+    let mut graph = find_solvable_states(&Level::new(grid, Dims::square(4), 5));
+    let before = graph.len();
+    assert_eq!(graph.prune_unwinnable(&is_goal_state), 0);
+    assert_eq!(graph.len(), before);
+    let grid = vec![
+      Cell::Hole, Cell::Unreachable, Cell::Unreachable, Cell::Unreachable,
+      Cell::Block, Cell::Block, Cell::Boulder, Cell::Unreachable,
+      Cell::Block, Cell::Block, Cell::Unreachable, Cell::Unreachable,
+      Cell::Block, Cell::Block, Cell::Unreachable, Cell::Unreachable,
+    ];
+    // Pushed from the start, only the state with the boulder stranded on the
+    // bottom edge is lost
+    let mut graph = find_pushed_states(&Level::new(grid, Dims::square(4), 15));
+    let before = graph.len();
+    let dead = graph.states()
+      .map(|(id, _)| id)
+      .filter(|id| !graph.distances_to_goal().contains_key(id))
+      .collect::<Vec<StateId>>();
+    assert_eq!(dead.len(), 1);
+    assert_eq!(graph.prune_unwinnable(&is_goal_state), 1);
+    assert_eq!(graph.len(), before - 1);
+    assert!(!graph.contains_id(&dead[0]));
+    assert_eq!(graph.distances_to_goal().len(), graph.len());
+    for (id, _) in graph.states() {
+      for neighbor in graph.neighbors(&id) {
+        assert!(graph.contains_id(&neighbor));
+      }
+    }
+  }
 
-    assert!(graph.contains_key(&10));
-    assert_eq!(graph[&10].len(), 3);
-    assert_eq!(graph[&10][0], 6);
-    assert_eq!(graph[&10][1], 9);
-    assert_eq!(graph[&10][2], 11);
+  #[test]
+  fn test_compact() {
+    let grid = vec![
+      Cell::Hole, Cell::Unreachable, Cell::Unreachable, Cell::Unreachable,
+      Cell::Block, Cell::Block, Cell::Boulder, Cell::Unreachable,
+      Cell::Block, Cell::Block, Cell::Unreachable, Cell::Unreachable,
+      Cell::Block, Cell::Block, Cell::Unreachable, Cell::Unreachable,
+    ];
+    let mut graph = find_pushed_states(&Level::new(grid, Dims::square(4), 15));
+    graph.prune_unwinnable(&is_goal_state);
+    assert!((0..graph.len()).any(|idx| !graph.contains_id(&StateId::new(idx))));
+    let pruned = StateGraph::from_bin(&graph.to_bin()).unwrap();
+    let renumbered = graph.compact();
+    assert_eq!(graph.len(), pruned.len());
+    assert_eq!(renumbered.len(), pruned.len());
+    assert_eq!(renumbered[&pruned.root()], graph.root());
+    assert!((0..graph.len()).all(|idx| graph.contains_id(&StateId::new(idx))));
+    for (id, state) in pruned.states() {
+      let new = renumbered[&id];
+      assert_eq!(graph.get_id(&state), Some(new));
+      let edges = pruned.edges_from(&id).map(|(to, push)| (renumbered[&to], *push)).collect::<Vec<_>>();
+      assert_eq!(graph.edges_from(&new).map(|(to, push)| (to, *push)).collect::<Vec<_>>(), edges);
+      assert_eq!(graph.get_predecessors(&new).unwrap().len(), pruned.get_predecessors(&id).unwrap().len());
+    }
+    assert_eq!(graph.compact_into(Box::new(vec![None])), Err(Error::StoreNotEmpty));
+  }
 
-    assert!(graph.contains_key(&11));
-    assert_eq!(graph[&11].len(), 1);
-    assert_eq!(graph[&11][0], 10);
+  #[test]
+  fn test_goal_states() {
+    let grid = vec![
+      Cell::BoulderInHole, Cell::Unreachable, Cell::Unreachable, Cell::Unreachable,
+      Cell::Unreachable, Cell::Unreachable, Cell::Unreachable, Cell::Unreachable,
+      Cell::Unreachable, Cell::Unreachable, Cell::Unreachable, Cell::Unreachable,
+      Cell::Unreachable, Cell::Unreachable, Cell::Unreachable, Cell::BoulderInHole,
+    ];
+    let graph = find_solvable_states(&Level::new(grid, Dims::square(4), 5));
+    // Boulders are only ever moved out of holes, so the root is the only goal
+    assert_eq!(graph.goal_states(), vec![graph.root()]);
+    assert!(graph.is_goal(&graph.root()));
+    assert!(!graph.is_goal(&graph.neighbors(&graph.root()).next().unwrap()));
+    assert!(!graph.is_goal(&StateId::new(graph.len())));
+  }
 
-    assert!(!graph.contains_key(&12));
+  #[test]
+  fn test_distances_to_goal() {
+    let grid = vec![
+      Cell::BoulderInHole, Cell::Unreachable, Cell::Unreachable, Cell::Unreachable,
+      Cell::Block, Cell::Block, Cell::Block, Cell::Unreachable,
+      Cell::Block, Cell::Block, Cell::Block, Cell::Unreachable,
+      Cell::Block, Cell::Block, Cell::Block, Cell::Unreachable,
+    ];
+    // Pulled back from the solved board, each state is as far from a goal as
+    // from the root
+    let graph = find_solvable_states(&Level::new(grid, Dims::square(4), 15));
+    assert!(graph.is_pulled());
+    let dist = graph.distances_to_goal();
+    let shortest = graph.build_shortest_path_from(&graph.root());
+    assert_eq!(dist.len(), graph.len());
+    assert!(graph.states().all(|(id, _)| Some(dist[&id]) == shortest.depth(&id)));
+    assert!(dist.values().any(|remaining| *remaining > 1));
+    // Pushed from the start, the state with the boulder cornered can't reach
+    // the goal
+    let grid = vec![
+      Cell::Unreachable, Cell::Boulder, Cell::Unreachable, Cell::Hole,
+      Cell::Unreachable, Cell::Unreachable, Cell::Unreachable, Cell::Unreachable,
+    ];
+    let graph = find_pushed_states(&Level::new(grid, Dims::new(2, 4), 0));
+    assert!(!graph.is_pulled());
+    let dist = graph.distances_to_goal();
+    assert_eq!(dist[&graph.root()], 2);
+    assert_eq!(dist.len(), graph.len() - 1);
+  }
 
-    assert!(graph.contains_key(&13));
-    assert_eq!(graph[&13].len(), 1);
-    assert_eq!(graph[&13][0], 9);
+  #[test]
+  fn test_iterators() {
+    let grid = vec![
+      Cell::BoulderInHole, Cell::Unreachable, Cell::Unreachable, Cell::BoulderInHole,
+      Cell::Unreachable, Cell::Unreachable, Cell::Unreachable, Cell::Unreachable,
+      Cell::Unreachable, Cell::Unreachable, Cell::Unreachable, Cell::Unreachable,
+      Cell::BoulderInHole, Cell::Unreachable, Cell::Unreachable, Cell::BoulderInHole,
+    ];
+    let graph = find_solvable_states(&Level::new(grid, Dims::square(4), 5));
+    let mut count = 0;
+    for (id, state) in graph.states() {
+      assert_eq!(graph.get_state(&id), Some(state));
+      count += 1;
+    }
+    assert_eq!(count, graph.len());
+    let edges = graph.edges().collect::<Vec<(StateId, StateId)>>();
+    let total = (0..graph.len())
+      .map(|idx| graph.out_degree(&StateId::new(idx)))
+      .sum::<usize>();
+    assert_eq!(edges.len(), total);
+    for (from, to) in edges {
+      assert!(graph.get_edge(&from, &to).is_some());
+    }
+  }
 
-    assert!(!graph.contains_key(&14));
+  #[test]
+  fn test_shortest_path_between_states() {
+    let grid = vec![
+      Cell::BoulderInHole, Cell::Unreachable, Cell::Unreachable, Cell::BoulderInHole,
+      Cell::Unreachable, Cell::Unreachable, Cell::Unreachable, Cell::Unreachable,
+      Cell::Unreachable, Cell::Unreachable, Cell::Unreachable, Cell::Unreachable,
+      Cell::BoulderInHole, Cell::Unreachable, Cell::Unreachable, Cell::BoulderInHole,
+    ];
+    let graph = find_solvable_states(&Level::new(grid, Dims::square(4), 5));
+    let root = graph.root();
+    let shortest = graph.build_shortest_path_from(&root);
+    for idx in (0..graph.len()).step_by(17) {
+      let id = StateId::new(idx);
+      let path = graph.shortest_path(&root, &id).unwrap();
+      assert_eq!(path.len(), shortest.depth(&id).unwrap() + 1);
+      assert_eq!(path.first(), Some(&root));
+      assert_eq!(path.last(), Some(&id));
+      let pushes = graph.shortest_pushes(&root, &id).unwrap();
+      assert_eq!(pushes.len(), path.len() - 1);
+      for (step, push) in path.windows(2).zip(pushes) {
+        assert_eq!(graph.get_edge(&step[0], &step[1]), Some(&push));
+      }
+    }
+    // Boulders never go back into holes, so nothing leads back to the root
+    let leaf = StateId::new(graph.len() - 1);
+    assert_eq!(graph.shortest_path(&leaf, &root), None);
+    assert_eq!(graph.shortest_path(&root, &root), Some(vec![root]));
+  }
 
-    assert!(!graph.contains_key(&15));
+  #[test]
+  fn test_bidirectional_shortest_path() {
+    let grid = vec![
+      Cell::BoulderInHole, Cell::Unreachable, Cell::Unreachable, Cell::BoulderInHole,
+      Cell::Unreachable, Cell::Unreachable, Cell::Unreachable, Cell::Unreachable,
+      Cell::Unreachable, Cell::Unreachable, Cell::Unreachable, Cell::Unreachable,
+      Cell::BoulderInHole, Cell::Unreachable, Cell::Unreachable, Cell::BoulderInHole,
+    ];
+    let graph = find_solvable_states(&Level::new(grid, Dims::square(4), 5));
+    for from in (0..graph.len()).step_by(41).map(StateId::new) {
+      for to in (0..graph.len()).step_by(13).map(StateId::new) {
+        let expected = graph.shortest_path(&from, &to);
+        let actual = graph.bidirectional_shortest_path(&from, &to);
+        assert_eq!(actual.as_ref().map(|p| p.len()), expected.map(|p| p.len()));
+        if let Some(path) = actual {
+          assert_eq!(path.first(), Some(&from));
+          assert_eq!(path.last(), Some(&to));
+          for step in path.windows(2) {
+            assert!(graph.get_edge(&step[0], &step[1]).is_some());
+          }
+        }
+      }
+    }
   }
 }
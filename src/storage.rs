@@ -0,0 +1,130 @@
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::interner::Arena;
+use crate::packed::PackedState;
+
+// Where a `StateGraph` keeps its states. A plain `Vec` holds them in memory,
+// `DiskStore` in a file.
+pub trait StateStore: Arena<PackedState> + Send {}
+
+impl<A: Arena<PackedState> + Send> StateStore for A {}
+
+// Writes are batched up to this many bytes
+const BUFFER_SIZE: usize = 1 << 20;
+
+// Keeps states in a scratch file, appended in push order, so exploring isn't
+// bounded by memory for them. Only where each state starts is kept in memory,
+// along with unwritten ones. Removing a state forgets it without reclaiming
+// its bytes. The file is deleted once the store is dropped.
+//
+// The `Arena` methods can't fail, so I/O errors after creation panic.
+pub struct DiskStore {
+  path: PathBuf,
+  file: RefCell<File>,
+  // Offset and length of each state, `None` once removed
+  spans: Vec<Option<(u64, u32)>>,
+  // How much of the file is written, `buffer` coming after it
+  written: u64,
+  buffer: Vec<u8>,
+}
+
+static CREATED: AtomicUsize = AtomicUsize::new(0);
+
+impl DiskStore {
+  // Truncates `path` if it exists
+  pub fn create(path: &Path) -> io::Result<Self> {
+    let file = OpenOptions::new().read(true).write(true).create(true).truncate(true).open(path)?;
+    Ok(DiskStore { path: path.to_path_buf(), file: RefCell::new(file), spans: vec![], written: 0, buffer: vec![] })
+  }
+  // In a new file under `dir`
+  pub fn in_dir(dir: &Path) -> io::Result<Self> {
+    let name = format!("lvlgen-{}-{}.states", std::process::id(), CREATED.fetch_add(1, Ordering::Relaxed));
+    Self::create(&dir.join(name))
+  }
+  pub fn path(&self) -> &Path {
+    &self.path
+  }
+  fn flush(&mut self) {
+    self.file.get_mut().seek(SeekFrom::Start(self.written))
+      .and_then(|_| self.file.get_mut().write_all(&self.buffer))
+      .expect("writing to the state store failed");
+    self.written += self.buffer.len() as u64;
+    self.buffer.clear();
+  }
+  fn read(&self, offset: u64, len: u32) -> Vec<u8> {
+    if offset >= self.written {
+      let start = (offset - self.written) as usize;
+      return self.buffer[start..start + len as usize].to_vec();
+    }
+    let mut buf = vec![0; len as usize];
+    let mut file = self.file.borrow_mut();
+    file.seek(SeekFrom::Start(offset))
+      .and_then(|_| file.read_exact(&mut buf))
+      .expect("reading from the state store failed");
+    buf
+  }
+}
+
+impl Arena<PackedState> for DiskStore {
+  fn push(&mut self, value: PackedState) {
+    let bytes = rmp_serde::encode::to_vec(&value).unwrap();
+    let offset = self.written + self.buffer.len() as u64;
+    self.spans.push(Some((offset, bytes.len() as u32)));
+    self.buffer.extend(bytes);
+    if self.buffer.len() >= BUFFER_SIZE {
+      self.flush();
+    }
+  }
+  fn get(&self, handle: usize) -> Option<Cow<'_, PackedState>> {
+    let (offset, len) = (*self.spans.as_slice().get(handle)?)?;
+    let state = rmp_serde::decode::from_read_ref(&self.read(offset, len))
+      .expect("the state store is corrupt");
+    Some(Cow::Owned(state))
+  }
+  fn take(&mut self, handle: usize) -> Option<PackedState> {
+    let state = self.get(handle)?.into_owned();
+    self.spans[handle] = None;
+    Some(state)
+  }
+  fn len(&self) -> usize {
+    self.spans.len()
+  }
+}
+
+impl Drop for DiskStore {
+  fn drop(&mut self) {
+    let _ = fs::remove_file(&self.path);
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::cell::Cell;
+  use crate::grid::*;
+  use crate::packed::Layout;
+
+  #[test]
+  fn test_disk_store() {
+    let dims = Dims::new(1, 4);
+    let mut state = vec![Cell::Unreachable, Cell::Boulder, Cell::Unreachable, Cell::Hole];
+    fill_reachable_cells_unchecked(0, &mut state, dims);
+    let layout = Layout::from_state(&state, dims);
+    let mut store = DiskStore::in_dir(&std::env::temp_dir()).unwrap();
+    let path = store.path().to_path_buf();
+    store.push(layout.encode(&state));
+    store.flush();
+    store.push(layout.encode(&state));
+    assert_eq!(store.get(0).unwrap().into_owned(), layout.encode(&state));
+    assert_eq!(store.take(1), Some(layout.encode(&state)));
+    assert_eq!(store.get(1), None);
+    assert_eq!(store.len(), 2);
+    drop(store);
+    assert!(!path.exists());
+  }
+}
@@ -0,0 +1,250 @@
+use serde::{Deserialize, Serialize};
+
+use crate::cell::Cell;
+use crate::direction::Direction;
+use crate::grid::*;
+use crate::level::Level;
+use crate::state_graph::Push;
+
+// The symmetries of a square board
+#[derive(Copy, Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub enum Transform {
+  Identity,
+  Rotate90,
+  Rotate180,
+  Rotate270,
+  FlipHorizontal,
+  FlipVertical,
+  Transpose,
+  AntiTranspose,
+}
+
+pub static TRANSFORMS: &[Transform] = &[
+  Transform::Identity,
+  Transform::Rotate90,
+  Transform::Rotate180,
+  Transform::Rotate270,
+  Transform::FlipHorizontal,
+  Transform::FlipVertical,
+  Transform::Transpose,
+  Transform::AntiTranspose,
+];
+
+impl Transform {
+  // Rotating a quarter turn or transposing maps a board onto one with rows
+  // and columns swapped, so those only fit back on a square board
+  pub fn swaps_axes(self) -> bool {
+    matches!(self, Transform::Rotate90 | Transform::Rotate270 | Transform::Transpose | Transform::AntiTranspose)
+  }
+  pub fn apply_index(self, idx: usize, dims: Dims) -> usize {
+    let row = dims.row(idx);
+    let col = dims.col(idx);
+    let last_row = dims.rows - 1;
+    let last_col = dims.cols - 1;
+    let (row, col) = match self {
+      Transform::Identity => (row, col),
+      Transform::Rotate90 => (col, last_row - row),
+      Transform::Rotate180 => (last_row - row, last_col - col),
+      Transform::Rotate270 => (last_col - col, row),
+      Transform::FlipHorizontal => (row, last_col - col),
+      Transform::FlipVertical => (last_row - row, col),
+      Transform::Transpose => (col, row),
+      Transform::AntiTranspose => (last_col - col, last_row - row),
+    };
+    let cols = if self.swaps_axes() { dims.rows } else { dims.cols };
+    to_index(row, col, cols)
+  }
+  pub fn apply_direction(self, dir: Direction) -> Direction {
+    let (drow, dcol) = match dir {
+      Direction::Up => (-1, 0),
+      Direction::Down => (1, 0),
+      Direction::Left => (0, -1),
+      Direction::Right => (0, 1),
+    };
+    let (drow, dcol) = match self {
+      Transform::Identity => (drow, dcol),
+      Transform::Rotate90 => (dcol, -drow),
+      Transform::Rotate180 => (-drow, -dcol),
+      Transform::Rotate270 => (-dcol, drow),
+      Transform::FlipHorizontal => (drow, -dcol),
+      Transform::FlipVertical => (-drow, dcol),
+      Transform::Transpose => (dcol, drow),
+      Transform::AntiTranspose => (-dcol, -drow),
+    };
+    match (drow, dcol) {
+      (-1, 0) => Direction::Up,
+      (1, 0) => Direction::Down,
+      (0, -1) => Direction::Left,
+      _ => Direction::Right,
+    }
+  }
+  pub fn apply(self, state: &[Cell], dims: Dims) -> Vec<Cell> {
+    let mut transformed = state.to_vec();
+    for (idx, cell) in state.iter().enumerate() {
+      transformed[self.apply_index(idx, dims)] = *cell;
+    }
+    transformed
+  }
+  // The shape of a board once transformed
+  pub fn apply_dims(self, dims: Dims) -> Dims {
+    if self.swaps_axes() { Dims::new(dims.cols, dims.rows) } else { dims }
+  }
+  // One-way cells turn along with the board
+  pub fn apply_cell(self, cell: Cell) -> Cell {
+    match cell {
+      Cell::OneWay(dir) => Cell::OneWay(self.apply_direction(dir)),
+      Cell::BoulderOnOneWay(dir) => Cell::BoulderOnOneWay(self.apply_direction(dir)),
+      other => other,
+    }
+  }
+  // Unlike `apply`, works on any board shape and turns one-way cells too
+  pub fn apply_level(self, level: &Level) -> Level {
+    let mut grid = level.grid.clone();
+    for (idx, cell) in level.grid.iter().enumerate() {
+      grid[self.apply_index(idx, level.dims)] = self.apply_cell(*cell);
+    }
+    Level::new(grid, self.apply_dims(level.dims), self.apply_index(level.tractor, level.dims))
+      .with_metadata(level.metadata.clone())
+  }
+  pub fn apply_push(self, push: &Push, dims: Dims) -> Push {
+    Push {
+      boulder: self.apply_index(push.boulder, dims),
+      dir: self.apply_direction(push.dir),
+      tractor: self.apply_index(push.tractor, dims),
+    }
+  }
+  pub fn inverse(self) -> Self {
+    match self {
+      Transform::Rotate90 => Transform::Rotate270,
+      Transform::Rotate270 => Transform::Rotate90,
+      other => other,
+    }
+  }
+  // The transform applying `other` first, then `self`
+  pub fn compose(self, other: Transform) -> Self {
+    // The corners of a 2x2 board pin down every transform
+    let dims = Dims::square(2);
+    *TRANSFORMS.iter()
+      .find(|t| (0..4).all(|idx| t.apply_index(idx, dims) == self.apply_index(other.apply_index(idx, dims), dims)))
+      .unwrap()
+  }
+}
+
+// One representative of all the levels that play the same: solid rows and
+// columns along the edges are dropped, walls and blocks are told apart no
+// longer, the tractor stands on the first cell of its region, and of the
+// transformed boards the one that sorts first is kept
+pub fn canonical_level(level: &Level) -> Level {
+  let level = trim_solid_edges(level);
+  let mut state = level.initial_state();
+  for cell in &mut state {
+    if *cell == Cell::Block {
+      *cell = Cell::Wall;
+    }
+  }
+  let tractor = state.iter().position(|cell| *cell == Cell::Reachable).unwrap_or(level.tractor);
+  let level = Level::new(state, level.dims, tractor);
+  TRANSFORMS.iter()
+    .map(|t| {
+      let mut transformed = t.apply_level(&level);
+      transformed.tractor = transformed.grid.iter().position(|cell| *cell == Cell::Reachable)
+        .unwrap_or(transformed.tractor);
+      transformed
+    })
+    .min_by_key(|l| (l.dims.rows, l.dims.cols, l.grid.iter().map(|cell| cell.to_char()).collect::<String>()))
+    .unwrap()
+}
+
+fn trim_solid_edges(level: &Level) -> Level {
+  let dims = level.dims;
+  let is_solid = |row: usize, col: usize| matches!(level.grid[dims.index(row, col)], Cell::Wall | Cell::Block);
+  let (mut top, mut bottom, mut left, mut right) = (0, dims.rows, 0, dims.cols);
+  let (tractor_row, tractor_col) = (dims.row(level.tractor), dims.col(level.tractor));
+  while top < tractor_row && (left..right).all(|col| is_solid(top, col)) {
+    top += 1;
+  }
+  while bottom > tractor_row + 1 && (left..right).all(|col| is_solid(bottom - 1, col)) {
+    bottom -= 1;
+  }
+  while left < tractor_col && (top..bottom).all(|row| is_solid(row, left)) {
+    left += 1;
+  }
+  while right > tractor_col + 1 && (top..bottom).all(|row| is_solid(row, right - 1)) {
+    right -= 1;
+  }
+  let trimmed = Dims::new(bottom - top, right - left);
+  let grid = (top..bottom)
+    .flat_map(|row| (left..right).map(move |col| level.grid[dims.index(row, col)]))
+    .collect();
+  Level::new(grid, trimmed, trimmed.index(tractor_row - top, tractor_col - left))
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn test_inverse_and_compose() {
+    for t in TRANSFORMS {
+      assert_eq!(t.compose(t.inverse()), Transform::Identity);
+      for idx in 0..9 {
+        let dims = Dims::square(3);
+        assert_eq!(t.inverse().apply_index(t.apply_index(idx, dims), dims), idx);
+      }
+    }
+    assert_eq!(Transform::Rotate90.compose(Transform::Rotate90), Transform::Rotate180);
+    assert_eq!(Transform::FlipHorizontal.compose(Transform::FlipVertical), Transform::Rotate180);
+  }
+
+  #[test]
+  fn test_directions_follow_cells() {
+    for t in TRANSFORMS {
+      for dir in &[Direction::Up, Direction::Down, Direction::Left, Direction::Right] {
+        // From the center of a 3x3 board every direction stays in bounds
+        let moved = match dir {
+          Direction::Up => 1,
+          Direction::Down => 7,
+          Direction::Left => 3,
+          Direction::Right => 5,
+        };
+        let expected = t.apply_index(moved, Dims::square(3));
+        let actual = match t.apply_direction(*dir) {
+          Direction::Up => 1,
+          Direction::Down => 7,
+          Direction::Left => 3,
+          Direction::Right => 5,
+        };
+        assert_eq!(actual, expected);
+      }
+    }
+  }
+
+  #[test]
+  fn test_canonical_level() {
+    let row = vec![Cell::Unreachable, Cell::Unreachable, Cell::Boulder, Cell::Hole, Cell::OneWay(Direction::Right)];
+    let level = Level::new(row.clone(), Dims::new(1, 5), 0);
+    let canonical = canonical_level(&level);
+    for t in TRANSFORMS {
+      assert_eq!(canonical_level(&t.apply_level(&level)), canonical);
+    }
+    // Walled in and with the tractor a step over, it's still the same level
+    let mut grid = vec![Cell::Wall; 6];
+    grid.push(Cell::Block);
+    grid.extend(row);
+    let walled = Level::new(grid, Dims::new(2, 6), 8);
+    assert_eq!(canonical_level(&walled), canonical);
+    let mut turned = level.clone();
+    turned.grid[4] = Cell::OneWay(Direction::Left);
+    assert_ne!(canonical_level(&turned), canonical);
+  }
+
+  #[test]
+  fn test_flips_on_rectangular_board() {
+    let dims = Dims::new(2, 3);
+    assert_eq!(Transform::FlipHorizontal.apply_index(0, dims), 2);
+    assert_eq!(Transform::FlipVertical.apply_index(1, dims), 4);
+    assert_eq!(Transform::Rotate180.apply_index(3, dims), 2);
+    // Transposing a 2x3 board gives a 3x2 one
+    assert_eq!(Transform::Transpose.apply_index(2, dims), 4);
+  }
+}
@@ -0,0 +1,76 @@
+use crate::cell::Cell;
+use crate::grid::Dims;
+
+// A partial board in the usual text format, with `?` marking cells left for
+// the generator to fill. A `.` pins the tractor start.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Template {
+  pub cells: Vec<Option<Cell>>,
+  pub dims: Dims,
+  pub tractor: Option<usize>,
+}
+
+impl Template {
+  pub fn parse(text: &str) -> Result<Self, String> {
+    let mut cells = vec![];
+    let mut tractor = None;
+    let mut rows = 0;
+    let mut cols = None;
+    for line in text.lines().map(|line| line.trim_end()) {
+      let row = match line.strip_prefix('|').and_then(|rest| rest.strip_suffix('|')) {
+        Some(row) => row,
+        None if line.is_empty() || line.starts_with('+') => continue,
+        None => return Err(format!("expected `|` around row `{}`", line)),
+      };
+      let width = row.chars().count();
+      if *cols.get_or_insert(width) != width {
+        return Err(format!("row `{}` is not {} cells wide", line, cols.unwrap_or(0)));
+      }
+      for c in row.chars() {
+        match c {
+          '?' => cells.push(None),
+          '.' if tractor.is_some() => return Err("more than one tractor".into()),
+          '.' => {
+            tractor = Some(cells.len());
+            cells.push(Some(Cell::Unreachable));
+          },
+          c => match Cell::try_from_char(c) {
+            Some(cell) => cells.push(Some(cell)),
+            None => return Err(format!("unrecognized character `{}`", c)),
+          },
+        }
+      }
+      rows += 1;
+    }
+    let dims = match cols {
+      Some(cols) if cols > 0 => Dims::new(rows, cols),
+      _ => return Err("empty board".into()),
+    };
+    Ok(Template { cells, dims, tractor })
+  }
+  // Cells left for the generator
+  pub fn wildcards(&self) -> Vec<usize> {
+    (0..self.cells.len()).filter(|idx| self.cells[*idx].is_none()).collect()
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn test_parse() {
+    let template = Template::parse("+---+\n|#??|\n|?.?|\n|??O|\n+---+\n").unwrap();
+    assert_eq!(template.dims, Dims::square(3));
+    assert_eq!(template.tractor, Some(4));
+    assert_eq!(template.cells[0], Some(Cell::Block));
+    assert_eq!(template.cells[8], Some(Cell::Hole));
+    assert_eq!(template.wildcards(), vec![1, 2, 3, 5, 6, 7]);
+    assert!(Template::parse("|??|\n|?|\n").is_err());
+    assert!(Template::parse("|.?|\n|?.|\n").is_err());
+    assert!(Template::parse("|?x|\n|??|\n").is_err());
+    let wide = Template::parse("|?.??|\n|#??O|\n").unwrap();
+    assert_eq!(wide.dims, Dims::new(2, 4));
+    assert_eq!(wide.tractor, Some(1));
+  }
+}
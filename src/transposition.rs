@@ -0,0 +1,106 @@
+use std::collections::VecDeque;
+use std::hash::{Hash, Hasher};
+
+use rustc_hash::{FxHashMap, FxHasher};
+
+use crate::cell::Cell;
+use crate::grid::Dims;
+use crate::symmetry::TRANSFORMS;
+
+// What is known about a state, whichever board it came up on
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Outcome {
+  // No goal can be reached from it
+  Dead,
+  // The fewest pushes to a goal
+  Solved(usize),
+}
+
+// Outcomes of states met by earlier searches, so that searches over similar
+// boards don't work them out again. Boards are told apart by `state_key`
+// alone, a collision being unlikely enough to ignore. Holds at most
+// `capacity` states, forgetting the oldest first.
+#[derive(Clone, Debug)]
+pub struct TranspositionTable {
+  outcomes: FxHashMap<u64, Outcome>,
+  // Keys in the order they were added, oldest first
+  order: VecDeque<u64>,
+  capacity: usize,
+  hits: usize,
+}
+
+// Hashes the state under every rotation and reflection and keeps the least,
+// so boards that are turned copies of each other share their outcomes. The
+// state has to have its tractor region filled in.
+pub fn state_key(state: &[Cell], dims: Dims) -> u64 {
+  let mut cells = state.to_vec();
+  TRANSFORMS.iter().map(|transform| {
+    for (idx, cell) in state.iter().enumerate() {
+      cells[transform.apply_index(idx, dims)] = transform.apply_cell(*cell);
+    }
+    let mut hasher = FxHasher::default();
+    transform.apply_dims(dims).hash(&mut hasher);
+    cells.hash(&mut hasher);
+    hasher.finish()
+  }).min().unwrap()
+}
+
+impl TranspositionTable {
+  pub fn new(capacity: usize) -> Self {
+    TranspositionTable { outcomes: FxHashMap::default(), order: VecDeque::new(), capacity, hits: 0 }
+  }
+  pub fn get(&mut self, key: u64) -> Option<Outcome> {
+    let outcome = self.outcomes.get(&key).cloned();
+    if outcome.is_some() {
+      self.hits += 1;
+    }
+    outcome
+  }
+  pub fn insert(&mut self, key: u64, outcome: Outcome) {
+    if self.capacity == 0 {
+      return;
+    }
+    if self.outcomes.insert(key, outcome).is_none() {
+      self.order.push_back(key);
+    }
+    while self.order.len() > self.capacity {
+      let oldest = self.order.pop_front().unwrap();
+      self.outcomes.remove(&oldest);
+    }
+  }
+  pub fn len(&self) -> usize {
+    self.outcomes.len()
+  }
+  pub fn is_empty(&self) -> bool {
+    self.outcomes.is_empty()
+  }
+  // Lookups that found an outcome
+  pub fn hits(&self) -> usize {
+    self.hits
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::grid::fill_reachable_cells_unchecked;
+
+  #[test]
+  fn test_transposition_table() {
+    let dims = Dims::new(1, 3);
+    let mut state = vec![Cell::Boulder, Cell::Unreachable, Cell::Hole];
+    fill_reachable_cells_unchecked(1, &mut state, dims);
+    let mut turned = state.clone();
+    turned.reverse();
+    assert_eq!(state_key(&state, dims), state_key(&turned, dims));
+    let mut table = TranspositionTable::new(2);
+    table.insert(1, Outcome::Dead);
+    table.insert(2, Outcome::Solved(3));
+    table.insert(1, Outcome::Dead);
+    assert_eq!(table.get(2), Some(Outcome::Solved(3)));
+    table.insert(3, Outcome::Dead);
+    assert_eq!(table.get(1), None);
+    assert_eq!(table.len(), 2);
+    assert_eq!(table.hits(), 1);
+  }
+}
@@ -0,0 +1,160 @@
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::cell::{Cell, Color, COLORS};
+use crate::direction::DIRECTIONS;
+use crate::grid::{step, Dims};
+use crate::level::Level;
+
+// Something structurally wrong with a level, short of whether it can be
+// solved. Serializes tagged by `kind`, for tools reading the list.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Problem {
+  // The file couldn't be read as a level at all
+  Unparseable { message: String },
+  // There are more or fewer cells than the shape holds, so nothing else
+  // can be checked
+  DimsMismatch { cells: usize, rows: usize, cols: usize },
+  // The tractor's start isn't a cell of the board
+  TractorOffBoard { tractor: usize, cells: usize },
+  // Cells already marked as the tractor's region, which only exploring
+  // should mark, given by the first of them
+  MarkedReachable { row: usize, col: usize, cells: usize },
+  // Loose boulders of a color don't match the open holes of that color
+  CountMismatch { color: Option<Color>, boulders: usize, holes: usize },
+  // The tractor starts on a cell it can't stand on
+  TractorBlocked { row: usize, col: usize, cell: char },
+  // Floor the tractor could never walk to, even with every boulder gone,
+  // given by its first cell
+  DisconnectedFloor { row: usize, col: usize, cells: usize },
+}
+
+impl fmt::Display for Problem {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Problem::Unparseable { message } => write!(f, "unparseable: {}", message),
+      Problem::DimsMismatch { cells, rows, cols } => write!(f, "{} cells for a {}x{} board", cells, rows, cols),
+      Problem::TractorOffBoard { tractor, cells } => write!(f, "tractor starts at cell {} of {}", tractor, cells),
+      Problem::MarkedReachable { row, col, cells } => {
+        write!(f, "{} cells from ({}, {}) are marked reachable", cells, row, col)
+      },
+      Problem::CountMismatch { color: Some(color), boulders, holes } => {
+        write!(f, "{} {:?} boulders for {} {:?} holes", boulders, color, holes, color)
+      },
+      Problem::CountMismatch { color: None, boulders, holes } => {
+        write!(f, "{} boulders for {} holes", boulders, holes)
+      },
+      Problem::TractorBlocked { row, col, cell } => write!(f, "tractor starts on `{}` at ({}, {})", cell, row, col),
+      Problem::DisconnectedFloor { row, col, cells } => {
+        write!(f, "{} floor cells from ({}, {}) can't be reached", cells, row, col)
+      },
+    }
+  }
+}
+
+// Walls and blocks are the only cells that split the floor for good
+fn is_barrier(cell: Cell) -> bool {
+  matches!(cell, Cell::Wall | Cell::Block)
+}
+
+// Every problem found, empty for a sound level. A board that doesn't fit its
+// shape or a tractor off it is reported alone, as the other checks can't run.
+pub fn validate(level: &Level) -> Vec<Problem> {
+  let (dims, cells) = (level.dims, level.grid.len());
+  if cells != dims.len() {
+    return vec![Problem::DimsMismatch { cells, rows: dims.rows, cols: dims.cols }];
+  }
+  if level.tractor >= cells {
+    return vec![Problem::TractorOffBoard { tractor: level.tractor, cells }];
+  }
+  let mut problems = vec![];
+  for color in std::iter::once(None).chain(COLORS.iter().map(|color| Some(*color))) {
+    let boulders = level.grid.iter().filter(|cell| cell.is_loose_boulder() && cell.color() == color).count();
+    let holes = level.grid.iter().filter(|cell| cell.is_open_hole() && cell.color() == color).count();
+    if boulders != holes {
+      problems.push(Problem::CountMismatch { color, boulders, holes });
+    }
+  }
+  let marked = level.grid.iter().filter(|cell| **cell == Cell::Reachable).count();
+  if let Some(idx) = level.grid.iter().position(|cell| *cell == Cell::Reachable) {
+    problems.push(Problem::MarkedReachable { row: dims.row(idx), col: dims.col(idx), cells: marked });
+  }
+  let start = level.grid[level.tractor];
+  let stands = matches!(start, Cell::Unreachable | Cell::Reachable | Cell::Ice | Cell::OneWay(_)) || start.is_open_hole();
+  if !stands {
+    let (row, col) = (dims.row(level.tractor), dims.col(level.tractor));
+    problems.push(Problem::TractorBlocked { row, col, cell: start.to_char() });
+  }
+  let mut region = flood(level.tractor, &level.grid, dims);
+  for idx in 0..level.grid.len() {
+    if !region[idx] && !is_barrier(level.grid[idx]) && !matches!(level.grid[idx], Cell::Portal(_)) {
+      let cut_off = flood(idx, &level.grid, dims);
+      let cells = (0..cut_off.len()).filter(|idx| cut_off[*idx] && !region[*idx]).count();
+      problems.push(Problem::DisconnectedFloor { row: dims.row(idx), col: dims.col(idx), cells });
+      for (seen, cut_off) in region.iter_mut().zip(cut_off) {
+        *seen |= cut_off;
+      }
+    }
+  }
+  problems
+}
+
+// Cells joined to `from` by anything but barriers
+fn flood(from: usize, grid: &[Cell], dims: Dims) -> Vec<bool> {
+  let mut seen = vec![false; grid.len()];
+  seen[from] = true;
+  let mut stack = vec![from];
+  while let Some(idx) = stack.pop() {
+    for next in DIRECTIONS.iter().filter_map(|dir| step(idx, *dir, grid, dims)) {
+      if !seen[next] && !is_barrier(grid[next]) {
+        seen[next] = true;
+        stack.push(next);
+      }
+    }
+  }
+  seen
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn test_validate() {
+    let grid = vec![
+      Cell::Unreachable, Cell::Boulder, Cell::Hole, Cell::Unreachable,
+      Cell::Block, Cell::Block, Cell::Wall, Cell::Wall,
+      Cell::Unreachable, Cell::Unreachable, Cell::Wall, Cell::Unreachable,
+    ];
+    let dims = Dims::new(3, 4);
+    assert_eq!(validate(&Level::new(grid.clone(), dims, 0)), vec![
+      Problem::DisconnectedFloor { row: 2, col: 0, cells: 2 },
+      Problem::DisconnectedFloor { row: 2, col: 3, cells: 1 },
+    ]);
+    let mut extra = grid.clone();
+    extra[8] = Cell::ColoredBoulder(Color::Red);
+    assert_eq!(validate(&Level::new(extra, dims, 1))[..2], [
+      Problem::CountMismatch { color: Some(Color::Red), boulders: 1, holes: 0 },
+      Problem::TractorBlocked { row: 0, col: 1, cell: '*' },
+    ]);
+    assert_eq!(validate(&Level::new(grid.clone(), Dims::square(3), 0)), vec![
+      Problem::DimsMismatch { cells: 12, rows: 3, cols: 3 },
+    ]);
+    assert_eq!(validate(&Level::new(grid.clone(), dims, 12)), vec![
+      Problem::TractorOffBoard { tractor: 12, cells: 12 },
+    ]);
+    let mut marked = grid;
+    marked[0] = Cell::Reachable;
+    marked[3] = Cell::Reachable;
+    assert_eq!(Level::new(marked, dims, 0).validate()[0], Problem::MarkedReachable { row: 0, col: 0, cells: 2 });
+  }
+
+  #[test]
+  fn test_problems_serialize() {
+    let problem = Problem::CountMismatch { color: None, boulders: 2, holes: 1 };
+    let json = serde_json::to_string(&problem).unwrap();
+    assert_eq!(json, r#"{"kind":"count_mismatch","color":null,"boulders":2,"holes":1}"#);
+  }
+}
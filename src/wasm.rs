@@ -0,0 +1,57 @@
+use std::cell::RefCell;
+
+use js_sys::JSON;
+use serde_json::{json, Value};
+use wasm_bindgen::prelude::*;
+
+use crate::rpc::Server;
+
+// Bindings for web games. They take and give plain JS objects, shaped like
+// the params and results of the `rpc` methods they call: a level is a JSON
+// level object or text in any format `parse_level` reads, and errors are
+// thrown as their messages. Solves and hints share one server, so they keep
+// its transposition table and push graphs between calls.
+
+thread_local! {
+  static SERVER: RefCell<Server> = RefCell::new(Server::default());
+}
+
+#[wasm_bindgen(js_name = parseLevel)]
+pub fn parse_level(level: JsValue) -> Result<JsValue, JsValue> {
+  call("parse", json!({ "level": from_js(&level)? }))
+}
+
+// `{pushes, moves}`, or null when there's no solution
+#[wasm_bindgen]
+pub fn solve(level: JsValue) -> Result<JsValue, JsValue> {
+  call("solve", json!({ "level": from_js(&level)? }))
+}
+
+// The next push after `moves`, or null when nothing gets closer to solved
+#[wasm_bindgen]
+pub fn hint(level: JsValue, moves: Option<String>) -> Result<JsValue, JsValue> {
+  call("hint", json!({ "level": from_js(&level)?, "moves": moves.unwrap_or_default() }))
+}
+
+// Takes GenConfig fields, any left out taking their defaults
+#[wasm_bindgen]
+pub fn generate(config: JsValue) -> Result<JsValue, JsValue> {
+  call("generate", from_js(&config)?)
+}
+
+fn call(method: &str, params: Value) -> Result<JsValue, JsValue> {
+  let result = SERVER.with(|server| server.borrow_mut().call_method(method, params));
+  to_js(&result.map_err(|err| JsValue::from_str(&err))?)
+}
+
+fn from_js(value: &JsValue) -> Result<Value, JsValue> {
+  if value.is_undefined() || value.is_null() {
+    return Ok(Value::Null);
+  }
+  let text = String::from(JSON::stringify(value)?);
+  serde_json::from_str(&text).map_err(|err| JsValue::from_str(&err.to_string()))
+}
+
+fn to_js(value: &Value) -> Result<JsValue, JsValue> {
+  JSON::parse(&value.to_string())
+}
@@ -0,0 +1,68 @@
+use crate::cell::{Cell, Color};
+
+// Zobrist keys for states: every boulder, tractor region and caved-in cell
+// contributes a fixed random word, XORed together. Moving one boulder only
+// swaps two words, so a push's key follows from its parent's without looking
+// at the rest of the board. The words come from a mixing function rather
+// than a table, so keys don't depend on the board size.
+fn mix(mut z: u64) -> u64 {
+  z = z.wrapping_add(0x9e37_79b9_7f4a_7c15);
+  z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+  z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+  z ^ (z >> 31)
+}
+
+// Kinds of boulder told apart, and with them the other kinds of word
+const KINDS: u64 = 8;
+
+fn boulder_kind(heavy: bool, color: Option<Color>) -> u64 {
+  match (heavy, color) {
+    (true, _) => 1,
+    (false, None) => 0,
+    (false, Some(Color::Red)) => 2,
+    (false, Some(Color::Green)) => 3,
+    (false, Some(Color::Blue)) => 4,
+  }
+}
+
+pub fn boulder_key(idx: usize, heavy: bool, color: Option<Color>) -> u64 {
+  mix(idx as u64 * KINDS + boulder_kind(heavy, color))
+}
+
+// The word for whatever boulder `cell` holds
+pub fn cell_key(idx: usize, cell: Cell) -> u64 {
+  boulder_key(idx, cell.is_heavy(), cell.color())
+}
+
+// `nth` tells tractors sharing a region apart, whose words would otherwise
+// cancel out
+pub fn tractor_key(idx: usize, nth: usize) -> u64 {
+  mix((idx as u64 * KINDS + 5) ^ ((nth as u64) << 48))
+}
+
+pub fn collapsed_key(idx: usize) -> u64 {
+  mix(idx as u64 * KINDS + 6)
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn test_keys_differ() {
+    let mut keys = vec![];
+    for idx in 0..64 {
+      keys.push(boulder_key(idx, false, None));
+      keys.push(boulder_key(idx, true, None));
+      keys.push(cell_key(idx, Cell::ColoredBoulder(Color::Blue)));
+      keys.push(tractor_key(idx, 0));
+      keys.push(tractor_key(idx, 1));
+      keys.push(collapsed_key(idx));
+    }
+    let count = keys.len();
+    keys.sort();
+    keys.dedup();
+    assert_eq!(keys.len(), count);
+    assert_eq!(cell_key(3, Cell::BoulderInHole), cell_key(3, Cell::Boulder));
+  }
+}